@@ -1,8 +1,50 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// GLSL sources in shaders/ compiled into SPIR-V(OUT_DIR/<name>.spv) for the Vulkan backend: the name, the source and the macros defined.
+/// The precompiled ones in shaders/spv/ are used if glslangValidator is not available;
+/// regenerate them(`glslangValidator -V [-D<macro>] -o shaders/spv/<name>.spv shaders/<source>`) together with the sources
+const SHADERS: &'static [(&'static str, &'static str, &'static [&'static str])] = &[
+    ("sdf.vert", "sdf.vert", &[]), ("sdf.frag", "sdf.frag", &[]), ("msdf.frag", "sdf.frag", &["MULTI_CHANNEL"]),
+    ("vector.vert", "vector.vert", &[]), ("vector.frag", "vector.frag", &[])
+];
 
 fn main()
 {
     if cfg!(windows)
     {
-        println!(r"cargo:rustc-link-search={}\Lib", std::env::var("VULKAN_SDK").unwrap());
+        println!(r"cargo:rustc-link-search={}\Lib", env::var("VULKAN_SDK").unwrap());
+    }
+    compile_shaders();
+}
+
+fn compile_shaders()
+{
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    // glslangValidator bundled with the Vulkan SDK, or the one in PATH
+    let compiler = match env::var("VULKAN_SDK")
+    {
+        Ok(sdk) => PathBuf::from(sdk).join(if cfg!(windows) { "Bin" } else { "bin" }).join("glslangValidator"),
+        Err(_) => PathBuf::from("glslangValidator")
+    };
+    for &(name, source, defines) in SHADERS
+    {
+        let (source, output) = (PathBuf::from("shaders").join(source), out_dir.join(format!("{}.spv", name)));
+        let precompiled = PathBuf::from("shaders").join("spv").join(format!("{}.spv", name));
+        println!("cargo:rerun-if-changed={}", source.display());
+        println!("cargo:rerun-if-changed={}", precompiled.display());
+        let status = Command::new(&compiler).arg("-V").args(defines.iter().map(|d| format!("-D{}", d))).arg("-o").arg(&output).arg(&source).status();
+        match status
+        {
+            Ok(status) => if !status.success() { panic!("Failed to compile a shader: {}", source.display()); },
+            Err(e) =>
+            {
+                println!("cargo:warning=glslangValidator is not available({}): using the precompiled {}. \
+                    Install the Vulkan SDK or put glslangValidator in PATH if the shader sources are modified", e, precompiled.display());
+                fs::copy(&precompiled, &output).unwrap_or_else(|e| panic!("Failed to copy {}: {}", precompiled.display(), e));
+            }
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,50 @@
+#version 450
+
+// Per-draw parameters(must match render::SdfStyle).
+// Compiled with MULTI_CHANNEL defined for multi-channel fields(msdf.frag.spv): the distance is the median of RGB
+layout(push_constant) uniform SdfStyle
+{
+    vec4 placement, uv_placement, fill, outline_color, glow_color, shadow_color;
+    float threshold, smoothing, outline_width, glow_width;
+    vec2 shadow_offset;
+    float shadow_softness, opacity;
+} style;
+layout(set = 0, binding = 0) uniform sampler2D field;
+
+layout(location = 0) in vec2 uv;
+// the animated opacity(sdf.vert)
+layout(location = 1) flat in float opacity;
+layout(location = 0) out vec4 target;
+
+// Coverage of the region where the distance is above the edge, blurred over `width` on both sides
+float coverage(float d, float edge, float width) { return smoothstep(edge - width, edge + width, d); }
+vec4 premultiplied(vec4 c) { return vec4(c.rgb * c.a, c.a); }
+float median(vec3 v) { return max(min(v.r, v.g), min(max(v.r, v.g), v.b)); }
+#ifdef MULTI_CHANNEL
+float distance_at(vec2 p) { return median(texture(field, p).rgb); }
+#else
+float distance_at(vec2 p) { return texture(field, p).r; }
+#endif
+
+void main()
+{
+    float d = distance_at(uv);
+    // half of the change of the distance per screen pixel: the edge spans one pixel at any scale
+    float aa = max(0.5 * fwidth(d) * style.smoothing, 1.0e-4);
+    float outer_edge = style.threshold - style.outline_width;
+
+    float fill_a = coverage(d, style.threshold, aa);
+    float body_a = coverage(d, outer_edge, aa);
+    vec4 body = premultiplied(style.fill) * fill_a + premultiplied(style.outline_color) * (body_a - fill_a);
+
+    // glow fades out linearly(in the distance) from the outer edge
+    float glow_a = style.glow_width > 0.0 ? smoothstep(outer_edge - style.glow_width, outer_edge, d) * (1.0 - body_a) : 0.0;
+    vec4 glow = premultiplied(style.glow_color) * glow_a;
+
+    float ds = distance_at(uv - style.shadow_offset);
+    float shadow_a = style.shadow_color.a > 0.0 ? coverage(ds, outer_edge, max(aa, style.shadow_softness)) : 0.0;
+    vec4 shadow = premultiplied(style.shadow_color) * shadow_a;
+
+    vec4 under = glow + shadow * (1.0 - glow.a);
+    target = (body + under * (1.0 - body.a)) * style.opacity * opacity;
+}
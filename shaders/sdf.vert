@@ -0,0 +1,32 @@
+#version 450
+
+// Per-draw parameters(must match render::SdfStyle)
+layout(push_constant) uniform SdfStyle
+{
+    vec4 placement, uv_placement, fill, outline_color, glow_color, shadow_color;
+    float threshold, smoothing, outline_width, glow_width;
+    vec2 shadow_offset;
+    float shadow_softness, opacity;
+} style;
+// Values changed every frame without recording the commands: the layer([0]) and the element([1])(must match render::Animated)
+layout(set = 1, binding = 0) uniform Animated
+{
+    vec4 transform;
+    float opacity;
+} animated[2];
+
+layout(location = 0) in vec4 pos;
+layout(location = 1) in vec2 uv;
+layout(location = 0) out vec2 uv_out;
+layout(location = 1) flat out float opacity_out;
+out gl_PerVertex { vec4 gl_Position; };
+
+vec2 animate(vec2 p, vec4 t) { return p * t.xy + t.zw; }
+
+void main()
+{
+    vec2 p = pos.xy * style.placement.xy + style.placement.zw;
+    gl_Position = vec4(animate(animate(p, animated[1].transform), animated[0].transform), pos.zw);
+    uv_out = uv * style.uv_placement.xy + style.uv_placement.zw;
+    opacity_out = animated[0].opacity * animated[1].opacity;
+}
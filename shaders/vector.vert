@@ -0,0 +1,31 @@
+#version 450
+
+// Per-draw parameters(must match render::VectorStyle)
+layout(push_constant) uniform VectorStyle
+{
+    vec4 placement;
+    float opacity;
+} style;
+// Values changed every frame without recording the commands: the layer([0]) and the element([1])(must match render::Animated)
+layout(set = 0, binding = 0) uniform Animated
+{
+    vec4 transform;
+    float opacity;
+} animated[2];
+
+// vector::ColoredVertex: straight alpha colors
+layout(location = 0) in vec2 pos;
+layout(location = 1) in vec4 color;
+layout(location = 0) out vec4 color_out;
+out gl_PerVertex { vec4 gl_Position; };
+
+vec2 animate(vec2 p, vec4 t) { return p * t.xy + t.zw; }
+
+void main()
+{
+    vec2 p = pos * style.placement.xy + style.placement.zw;
+    gl_Position = vec4(animate(animate(p, animated[1].transform), animated[0].transform), 0.0, 1.0);
+    // premultiplied before the interpolation
+    float a = color.a * style.opacity * animated[0].opacity * animated[1].opacity;
+    color_out = vec4(color.rgb * a, a);
+}
@@ -0,0 +1,287 @@
+//! Time-based animations: easing curves, tweens, keyframes, springs, and sequential/parallel groups of them.
+//! Animations write their values into a target(e.g. the properties of a scene) through setter functions
+
+/// Maps the progress in [0, 1] to the eased progress
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing
+{
+    Linear, InQuad, OutQuad, InOutQuad, InCubic, OutCubic, InOutCubic,
+    /// Overshoots slightly before settling
+    OutBack,
+    /// CSS-like cubic bezier curve through (0, 0), (x1, y1), (x2, y2) and (1, 1)
+    CubicBezier(f32, f32, f32, f32)
+}
+impl Easing
+{
+    pub fn apply(&self, t: f32) -> f32
+    {
+        let t = t.max(0.0).min(1.0);
+        match *self
+        {
+            Easing::Linear => t,
+            Easing::InQuad => t * t,
+            Easing::OutQuad => t * (2.0 - t),
+            Easing::InOutQuad => if t < 0.5 { 2.0 * t * t } else { 1.0 - 2.0 * (1.0 - t) * (1.0 - t) },
+            Easing::InCubic => t * t * t,
+            Easing::OutCubic => 1.0 - (1.0 - t).powi(3),
+            Easing::InOutCubic => if t < 0.5 { 4.0 * t * t * t } else { 1.0 - 4.0 * (1.0 - t).powi(3) },
+            Easing::OutBack => { const C: f32 = 1.70158; let u = t - 1.0; 1.0 + (C + 1.0) * u * u * u + C * u * u },
+            Easing::CubicBezier(x1, y1, x2, y2) =>
+            {
+                let bezier = |a: f32, b: f32, s: f32| 3.0 * a * s * (1.0 - s) * (1.0 - s) + 3.0 * b * s * s * (1.0 - s) + s * s * s;
+                // x(s) is monotonic for x1, x2 in [0, 1]: bisection finds the parameter
+                let (mut lo, mut hi) = (0.0, 1.0);
+                for _ in 0 .. 24
+                {
+                    let mid = 0.5 * (lo + hi);
+                    if bezier(x1, x2, mid) < t { lo = mid; } else { hi = mid; }
+                }
+                bezier(y1, y2, 0.5 * (lo + hi))
+            }
+        }
+    }
+}
+
+/// Sets a property of the target
+pub type Setter<T> = fn(&mut T, f32);
+
+pub trait Animation<T>
+{
+    /// Advances by `dt` seconds and writes the values into the target.
+    /// Returns the time left over after the animation finished(0 while running)
+    fn advance(&mut self, dt: f32, target: &mut T) -> f32;
+    fn is_finished(&self) -> bool;
+}
+
+/// Interpolates between two values
+pub struct Tween<T> { from: f32, to: f32, duration: f32, easing: Easing, elapsed: f32, set: Setter<T> }
+impl<T> Tween<T>
+{
+    pub fn new(from: f32, to: f32, duration: f32, set: Setter<T>) -> Self
+    {
+        Tween { from, to, duration, easing: Easing::Linear, elapsed: 0.0, set }
+    }
+    pub fn easing(self, easing: Easing) -> Self { Tween { easing, .. self } }
+}
+impl<T> Animation<T> for Tween<T>
+{
+    fn advance(&mut self, dt: f32, target: &mut T) -> f32
+    {
+        let left = (self.elapsed + dt - self.duration).max(0.0);
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+        let t = if self.duration > 0.0 { self.elapsed / self.duration } else { 1.0 };
+        (self.set)(target, self.from + (self.to - self.from) * self.easing.apply(t));
+        left
+    }
+    fn is_finished(&self) -> bool { self.elapsed >= self.duration }
+}
+
+/// Values at points of time. Each easing applies to the segment ending at its frame
+pub struct Keyframes<T> { frames: Vec<(f32, f32, Easing)>, elapsed: f32, set: Setter<T> }
+impl<T> Keyframes<T>
+{
+    /// Starts at the value at time 0
+    pub fn new(value: f32, set: Setter<T>) -> Self { Keyframes { frames: vec![(0.0, value, Easing::Linear)], elapsed: 0.0, set } }
+    /// Adds a frame. Frames are added in the order of time
+    pub fn frame(mut self, time: f32, value: f32, easing: Easing) -> Self
+    {
+        assert!(self.frames.last().map(|f| f.0 <= time).unwrap_or(true), "Keyframes are not in the order of time");
+        self.frames.push((time, value, easing));
+        self
+    }
+    fn value_at(&self, time: f32) -> f32
+    {
+        match self.frames.iter().position(|f| f.0 > time)
+        {
+            Some(0) => self.frames[0].1,
+            Some(n) =>
+            {
+                let ((t0, v0, _), (t1, v1, e)) = (self.frames[n - 1], self.frames[n]);
+                v0 + (v1 - v0) * e.apply((time - t0) / (t1 - t0))
+            },
+            None => self.frames.last().unwrap().1
+        }
+    }
+}
+impl<T> Animation<T> for Keyframes<T>
+{
+    fn advance(&mut self, dt: f32, target: &mut T) -> f32
+    {
+        let end = self.frames.last().unwrap().0;
+        let left = (self.elapsed + dt - end).max(0.0);
+        self.elapsed = (self.elapsed + dt).min(end);
+        (self.set)(target, self.value_at(self.elapsed));
+        left
+    }
+    fn is_finished(&self) -> bool { self.elapsed >= self.frames.last().unwrap().0 }
+}
+
+/// A damped spring moving the value to the target. Finishes when it comes to rest
+pub struct Spring<T> { value: f32, velocity: f32, target: f32, stiffness: f32, damping: f32, set: Setter<T> }
+impl<T> Spring<T>
+{
+    /// Integration step(fixed, so that results do not depend on the frame rate)
+    const STEP: f32 = 1.0 / 240.0;
+    /// Distance and speed regarded as rest
+    const REST: f32 = 1.0e-3;
+
+    pub fn new(from: f32, to: f32, stiffness: f32, damping: f32, set: Setter<T>) -> Self
+    {
+        Spring { value: from, velocity: 0.0, target: to, stiffness, damping, set }
+    }
+    /// Moves the target keeping the current velocity
+    pub fn retarget(&mut self, to: f32) { self.target = to; }
+}
+impl<T> Animation<T> for Spring<T>
+{
+    fn advance(&mut self, dt: f32, target: &mut T) -> f32
+    {
+        let mut left = dt;
+        while left > 0.0 && !self.is_finished()
+        {
+            let step = left.min(Self::STEP);
+            self.velocity += (-self.stiffness * (self.value - self.target) - self.damping * self.velocity) * step;
+            self.value += self.velocity * step;
+            left -= step;
+        }
+        if self.is_finished() { self.value = self.target; self.velocity = 0.0; }
+        (self.set)(target, self.value);
+        if self.is_finished() { left } else { 0.0 }
+    }
+    fn is_finished(&self) -> bool { (self.value - self.target).abs() < Self::REST && self.velocity.abs() < Self::REST }
+}
+
+/// Waits
+pub struct Delay { duration: f32, elapsed: f32 }
+impl Delay { pub fn new(duration: f32) -> Self { Delay { duration, elapsed: 0.0 } } }
+impl<T> Animation<T> for Delay
+{
+    fn advance(&mut self, dt: f32, _target: &mut T) -> f32
+    {
+        let left = (self.elapsed + dt - self.duration).max(0.0);
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+        left
+    }
+    fn is_finished(&self) -> bool { self.elapsed >= self.duration }
+}
+
+/// Runs the animations one after another
+pub struct Sequence<T> { items: Vec<Box<Animation<T>>>, current: usize }
+impl<T> Sequence<T> { pub fn new(items: Vec<Box<Animation<T>>>) -> Self { Sequence { items, current: 0 } } }
+impl<T> Animation<T> for Sequence<T>
+{
+    fn advance(&mut self, dt: f32, target: &mut T) -> f32
+    {
+        let mut dt = dt;
+        while self.current < self.items.len()
+        {
+            dt = self.items[self.current].advance(dt, target);
+            if !self.items[self.current].is_finished() { return 0.0; }
+            self.current += 1;
+        }
+        dt
+    }
+    fn is_finished(&self) -> bool { self.current >= self.items.len() }
+}
+
+/// Runs the animations at the same time. Finishes with the last of them
+pub struct Parallel<T> { items: Vec<Box<Animation<T>>> }
+impl<T> Parallel<T> { pub fn new(items: Vec<Box<Animation<T>>>) -> Self { Parallel { items } } }
+impl<T> Animation<T> for Parallel<T>
+{
+    fn advance(&mut self, dt: f32, target: &mut T) -> f32
+    {
+        let mut left = dt;
+        for a in self.items.iter_mut().filter(|a| !a.is_finished()) { left = left.min(a.advance(dt, target)); }
+        if self.is_finished() { left } else { 0.0 }
+    }
+    fn is_finished(&self) -> bool { self.items.iter().all(|a| a.is_finished()) }
+}
+
+/// Animations running on a target
+pub struct Animator<T> { running: Vec<Box<Animation<T>>> }
+impl<T> Animator<T>
+{
+    pub fn new() -> Self { Animator { running: Vec::new() } }
+    pub fn start(&mut self, animation: Box<Animation<T>>) { self.running.push(animation); }
+    pub fn stop_all(&mut self) { self.running.clear(); }
+    /// Nothing is running: the frame loop does not have to render new frames for the target
+    pub fn is_idle(&self) -> bool { self.running.is_empty() }
+    /// Advances the running animations. Returns whether the target was changed
+    pub fn update(&mut self, dt: f32, target: &mut T) -> bool
+    {
+        if self.running.is_empty() { return false; }
+        for a in &mut self.running { a.advance(dt, target); }
+        self.running.retain(|a| !a.is_finished());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Props { opacity: f32, scale: f32 }
+    fn opacity(p: &mut Props, v: f32) { p.opacity = v; }
+    fn scale(p: &mut Props, v: f32) { p.scale = v; }
+
+    #[test] fn easing()
+    {
+        for e in &[Easing::Linear, Easing::InQuad, Easing::OutQuad, Easing::InOutQuad, Easing::InCubic, Easing::OutCubic,
+            Easing::InOutCubic, Easing::OutBack, Easing::CubicBezier(0.25, 0.1, 0.25, 1.0)]
+        {
+            assert!(e.apply(0.0).abs() < 1.0e-4 && (e.apply(1.0) - 1.0).abs() < 1.0e-4, "{:?}", e);
+        }
+        assert_eq!(Easing::InOutQuad.apply(0.5), 0.5);
+        assert!(Easing::OutBack.apply(0.8) > 1.0);
+        assert!((Easing::CubicBezier(0.0, 0.0, 1.0, 1.0).apply(0.3) - 0.3).abs() < 0.05);
+    }
+
+    #[test] fn tweens_and_keyframes()
+    {
+        let mut p = Props::default();
+        let mut t = Tween::new(0.0, 2.0, 1.0, opacity);
+        assert_eq!(t.advance(0.25, &mut p), 0.0);
+        assert_eq!(p.opacity, 0.5);
+        assert_eq!(t.advance(1.0, &mut p), 0.25);
+        assert!(t.is_finished() && p.opacity == 2.0);
+
+        let mut k = Keyframes::new(1.0, scale).frame(1.0, 2.0, Easing::Linear).frame(2.0, 0.0, Easing::InQuad);
+        k.advance(0.5, &mut p);
+        assert_eq!(p.scale, 1.5);
+        k.advance(1.0, &mut p);
+        assert_eq!(p.scale, 1.5);
+        k.advance(1.0, &mut p);
+        assert!(k.is_finished() && p.scale == 0.0);
+    }
+
+    #[test] fn groups()
+    {
+        let mut p = Props::default();
+        let mut s = Sequence::new(vec![box Delay::new(0.5), box Tween::new(0.0, 1.0, 1.0, opacity),
+            box Parallel::new(vec![box Tween::new(1.0, 0.0, 0.5, opacity), box Tween::new(1.0, 2.0, 1.0, scale)])]);
+        s.advance(0.75, &mut p);
+        assert_eq!(p, Props { opacity: 0.25, scale: 0.0 });
+        // the time left over by the tween goes to the parallel group
+        s.advance(1.0, &mut p);
+        assert_eq!(p, Props { opacity: 0.5, scale: 1.25 });
+        assert_eq!(s.advance(1.0, &mut p), 0.25);
+        assert!(s.is_finished());
+        assert_eq!(p, Props { opacity: 0.0, scale: 2.0 });
+    }
+
+    #[test] fn spring_and_animator()
+    {
+        let mut p = Props::default();
+        let mut a = Animator::new();
+        assert!(a.is_idle() && !a.update(0.016, &mut p));
+        a.start(box Spring::new(0.0, 1.0, 200.0, 20.0, scale));
+        assert!(a.update(0.05, &mut p));
+        assert!(p.scale > 0.0 && p.scale < 1.0);
+        let mut frames = 0;
+        while a.update(1.0 / 60.0, &mut p) { frames += 1; }
+        assert!(frames < 120 && p.scale == 1.0 && a.is_idle());
+    }
+}
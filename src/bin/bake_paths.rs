@@ -0,0 +1,29 @@
+//! Asset baker: extracts the paths of an SVG/SVGZ document with their fills and strokes into the baked vector image format(`vector::BakedImage`).
+//! Replaces the former `assets/strip_all_paths.d`
+//!
+//! usage> cargo run --bin bake_paths -- input.svg[z] [output.dcvp]
+
+extern crate dc2017;
+
+use dc2017::vector::{SvgImage, BakedImage, Paint};
+use std::path::PathBuf;
+use std::process::exit;
+
+fn main()
+{
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.len() > 2
+    {
+        println!("usage> bake_paths [input.svg/svgz] (output.dcvp)");
+        exit(1);
+    }
+    let input = PathBuf::from(&args[0]);
+    let output = args.get(1).map(PathBuf::from).unwrap_or_else(|| input.with_extension("dcvp"));
+
+    let image = SvgImage::load(&input).unwrap_or_else(|e| { println!("!! Failed to load {}: {}", input.display(), e); exit(2) });
+    let baked = BakedImage::from_svg(&image);
+    if let Err(e) = baked.save(&output) { println!("!! Failed to write {}: {}", output.display(), e); exit(2); }
+    let commands: usize = baked.shapes.iter().map(|s| s.path.commands.len()).sum();
+    let stroked = baked.shapes.iter().filter(|s| s.stroke != Paint::None).count();
+    println!("{} -> {}: {} shapes({} stroked), {} commands", input.display(), output.display(), baked.shapes.len(), stroked, commands);
+}
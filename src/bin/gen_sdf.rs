@@ -0,0 +1,69 @@
+//! Signed distance field generator for logos and icons(e.g. `assets/logo_ColoredLogo.sdf.png`)
+//!
+//! usage> cargo run --bin gen_sdf -- input.svg[z]/png output.png [--size WxH] [--spread px] [--msdf]
+//! Bitmaps are thresholded by the alpha channel(or the luminance if opaque) and should be several times larger than the output.
+
+extern crate metrics;
+extern crate image;
+extern crate dc2017;
+
+use metrics::*;
+use dc2017::vector::{SvgImage, SdfParams, sdf_from_bitmap};
+use std::path::Path;
+use std::process::exit;
+
+fn usage() -> !
+{
+    println!("usage> gen_sdf [input.svg/svgz/png] [output.png] (--size WxH) (--spread px) (--msdf)");
+    exit(1);
+}
+fn main()
+{
+    let (mut files, mut size, mut spread, mut multi_channel) = (Vec::new(), None, 4.0, false);
+    let mut args = std::env::args().skip(1);
+    while let Some(a) = args.next()
+    {
+        match &a as &str
+        {
+            "--size" =>
+            {
+                let s = args.next().unwrap_or_else(|| usage());
+                let mut wh = s.split('x').map(|v| v.parse::<u32>());
+                size = match (wh.next(), wh.next()) { (Some(Ok(w)), Some(Ok(h))) => Some(Size2U(w, h)), _ => usage() };
+            },
+            "--spread" => spread = args.next().and_then(|v| v.parse().ok()).unwrap_or_else(|| usage()),
+            "--msdf" => multi_channel = true,
+            _ => files.push(a)
+        }
+    }
+    if files.len() != 2 { usage(); }
+    let (input, output) = (Path::new(&files[0]), Path::new(&files[1]));
+    let is_svg = input.extension().map(|e| e == "svg" || e == "svgz").unwrap_or(false);
+
+    let field = if is_svg
+    {
+        let image = SvgImage::load(input).unwrap_or_else(|e| { println!("!! Failed to load {}: {}", input.display(), e); exit(2) });
+        let size = size.unwrap_or_else(|| Size2U(image.size.x().ceil() as _, image.size.y().ceil() as _));
+        image.distance_field(&SdfParams { size, spread }, multi_channel)
+    }
+    else
+    {
+        if multi_channel { println!("!! Multi-channel fields can only be generated from vector images"); exit(1); }
+        let source = image::open(input).unwrap_or_else(|e| { println!("!! Failed to load {}: {:?}", input.display(), e); exit(2) }).to_rgba();
+        let (w, h) = source.dimensions();
+        let opaque = source.pixels().all(|p| p.data[3] == 255);
+        let coverage: Vec<u8> = source.pixels().map(|p| if opaque
+        {
+            ((p.data[0] as u32 * 299 + p.data[1] as u32 * 587 + p.data[2] as u32 * 114) / 1000) as u8
+        }
+        else { p.data[3] }).collect();
+        sdf_from_bitmap(&coverage, Size2U(w, h), &SdfParams { size: size.unwrap_or(Size2U((w + 7) / 8, (h + 7) / 8)), spread })
+    };
+    let color = if field.channels == 3 { image::RGB(8) } else { image::Gray(8) };
+    if let Err(e) = image::save_buffer(output, &field.pixels, field.size.x(), field.size.y(), color)
+    {
+        println!("!! Failed to write {}: {:?}", output.display(), e); exit(2);
+    }
+    println!("{} -> {}: {}x{}, spread {}px{}", input.display(), output.display(), field.size.x(), field.size.y(), spread,
+        if multi_channel { ", multi-channel" } else { "" });
+}
@@ -0,0 +1,6 @@
+//! Colors
+
+/// RGBA color with components in [0, 1]
+#[repr(C)] #[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color(pub f32, pub f32, pub f32, pub f32);
+impl AsRef<[f32; 4]> for Color { fn as_ref(&self) -> &[f32; 4] { unsafe { ::std::mem::transmute(self) } } }
@@ -0,0 +1,344 @@
+//! Glyph atlas: glyphs rasterized on demand and packed into shelves of one texture.
+//! When the texture is full, the least recently used glyphs are evicted
+
+use metrics::*;
+use render::{RenderDevice, ResourceBlock, TextureParam, TextureUsage, ColorFormat};
+use super::{Font, GlyphBitmap, GlyphField};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{Result as IOResult, Error as IOError, ErrorKind};
+
+/// A glyph at a size. `font` identifies the font among the ones the caller uses with the atlas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlyphKey
+{
+    pub font: usize, pub glyph: u16,
+    /// Pixels per em in 1/64 pixels
+    pub size: u32
+}
+impl GlyphKey
+{
+    pub fn new(font: usize, glyph: u16, size: f32) -> Self { GlyphKey { font, glyph, size: (size * 64.0).round() as u32 } }
+    pub fn pixel_size(&self) -> f32 { self.size as f32 / 64.0 }
+}
+
+/// A glyph placed in the atlas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasEntry
+{
+    /// Top-left pixel in the atlas. Blank glyphs have zero size
+    pub position: (u32, u32), pub size: Size2U,
+    /// Position of the top-left pixel relative to the pen position on the baseline
+    pub offset: (i32, i32)
+}
+impl AtlasEntry
+{
+    /// Normalized texture coordinates: left, top, right, bottom
+    pub fn uv(&self, atlas_size: Size2U) -> [f32; 4]
+    {
+        let (w, h) = (atlas_size.x() as f32, atlas_size.y() as f32);
+        [self.position.0 as f32 / w, self.position.1 as f32 / h,
+            (self.position.0 + self.size.x()) as f32 / w, (self.position.1 + self.size.y()) as f32 / h]
+    }
+}
+
+/// What the atlas stores for each glyph
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GlyphMode
+{
+    /// Coverage rasterized for each size(grayscale)
+    Coverage,
+    /// Distance fields generated at `base_size` pixels per em and scaled when drawn with `SdfStyle`(grayscale).
+    /// `spread` is the distance in pixels of the base size encoded on each side of the edges
+    Sdf { base_size: f32, spread: f32 },
+    /// Multi-channel distance fields which keep the corners sharp when magnified(RGB)
+    Msdf { base_size: f32, spread: f32 }
+}
+impl GlyphMode
+{
+    pub fn channels(&self) -> usize { match *self { GlyphMode::Msdf { .. } => 3, _ => 1 } }
+}
+
+struct Slot { entry: AtlasEntry, shelf: Option<usize>, last_used: u64 }
+/// A row of glyphs of similar heights. Free spans are (x, width) in the order of x
+struct Shelf { y: u32, height: u32, free: Vec<(u32, u32)> }
+
+pub struct GlyphAtlas
+{
+    size: Size2U, mode: GlyphMode, pixels: Vec<u8>, shelves: Vec<Shelf>, slots: HashMap<GlyphKey, Slot>,
+    /// Incremented on every use; glyphs used since `frame_start` are never evicted
+    clock: u64, frame_start: u64,
+    /// Region modified since the last upload: min, max(exclusive)
+    dirty: Option<((u32, u32), (u32, u32))>
+}
+impl GlyphAtlas
+{
+    /// Gap between glyphs so that linear filtering does not bleed the neighbours in
+    const PADDING: u32 = 1;
+
+    pub fn new(size: Size2U) -> Self { Self::with_mode(size, GlyphMode::Coverage) }
+    pub fn with_mode(size: Size2U, mode: GlyphMode) -> Self
+    {
+        GlyphAtlas
+        {
+            size, mode, pixels: vec![0; size.x() as usize * size.y() as usize * mode.channels()], shelves: Vec::new(), slots: HashMap::new(),
+            clock: 0, frame_start: 0, dirty: None
+        }
+    }
+    pub fn size(&self) -> Size2U { self.size }
+    pub fn mode(&self) -> GlyphMode { self.mode }
+    pub fn pixels(&self) -> &[u8] { &self.pixels }
+    /// Number of glyphs in the atlas
+    pub fn len(&self) -> usize { self.slots.len() }
+    /// Parameters of the texture mirroring the atlas(`RenderDevice::create_resources`)
+    pub fn texture_param(&self) -> TextureParam<'static>
+    {
+        let color = if self.mode.channels() == 3 { ColorFormat::Default } else { ColorFormat::Grayscale };
+        TextureParam { size: self.size, color, usage: TextureUsage::FrequentlyUpdated, .. Default::default() }
+    }
+
+    /// Starts a new frame. Glyphs used in the current frame are never evicted
+    pub fn begin_frame(&mut self) { self.clock += 1; self.frame_start = self.clock; }
+
+    /// Factor from the pixels of the entries to the screen pixels of glyphs drawn at the size
+    pub fn draw_scale(&self, size: f32) -> f32
+    {
+        match self.mode
+        {
+            GlyphMode::Coverage => 1.0,
+            GlyphMode::Sdf { base_size, .. } | GlyphMode::Msdf { base_size, .. } => size / base_size
+        }
+    }
+    /// Distance fields of all sizes share the entry of the base size
+    fn stored_key(&self, key: &GlyphKey) -> GlyphKey
+    {
+        match self.mode
+        {
+            GlyphMode::Coverage => *key,
+            GlyphMode::Sdf { base_size, .. } | GlyphMode::Msdf { base_size, .. } => GlyphKey::new(key.font, key.glyph, base_size)
+        }
+    }
+
+    /// The glyph from the atlas, rasterized and inserted on a miss
+    pub fn get(&mut self, font: &Font, key: GlyphKey) -> IOResult<AtlasEntry>
+    {
+        if let Some(e) = self.lookup(&key) { return Ok(e); }
+        let key = self.stored_key(&key);
+        let entry = match self.mode
+        {
+            GlyphMode::Coverage => { let b = font.rasterize(key.glyph, key.pixel_size())?; self.insert(key, &b) },
+            GlyphMode::Sdf { base_size, spread } => { let f = font.distance_field(key.glyph, base_size, spread, false)?; self.insert_field(key, &f) },
+            GlyphMode::Msdf { base_size, spread } => { let f = font.distance_field(key.glyph, base_size, spread, true)?; self.insert_field(key, &f) }
+        };
+        entry.ok_or_else(|| IOError::new(ErrorKind::Other, "The glyph atlas is full"))
+    }
+    /// The entry of the glyph if present, marking it as used
+    pub fn lookup(&mut self, key: &GlyphKey) -> Option<AtlasEntry>
+    {
+        self.clock += 1;
+        let (clock, key) = (self.clock, self.stored_key(key));
+        self.slots.get_mut(&key).map(|s| { s.last_used = clock; s.entry })
+    }
+    /// Places the coverage bitmap(atlases in the coverage mode), evicting the least recently used glyphs if needed.
+    /// None if it does not fit even after evicting all the glyphs not used in the current frame
+    pub fn insert(&mut self, key: GlyphKey, bitmap: &GlyphBitmap) -> Option<AtlasEntry>
+    {
+        assert_eq!(self.mode, GlyphMode::Coverage, "Inserting a coverage bitmap into a distance field atlas");
+        self.place(key, bitmap.size, bitmap.offset, &bitmap.coverage)
+    }
+    /// Places the distance field(atlases in the distance field modes of the same channels)
+    pub fn insert_field(&mut self, key: GlyphKey, field: &GlyphField) -> Option<AtlasEntry>
+    {
+        assert!(self.mode != GlyphMode::Coverage && field.field.channels == self.mode.channels(), "Inserting a distance field of another mode");
+        let key = self.stored_key(&key);
+        self.place(key, field.field.size, field.offset, &field.field.pixels)
+    }
+    fn place(&mut self, key: GlyphKey, size: Size2U, offset: (i32, i32), pixels: &[u8]) -> Option<AtlasEntry>
+    {
+        if let Some(s) = self.slots.remove(&key) { self.release(&s); }
+        self.clock += 1;
+        let (w, h) = (size.x() + Self::PADDING, size.y() + Self::PADDING);
+        if size.x() == 0 || size.y() == 0
+        {
+            let entry = AtlasEntry { position: (0, 0), size: Size2U(0, 0), offset };
+            self.slots.insert(key, Slot { entry, shelf: None, last_used: self.clock });
+            return Some(entry);
+        }
+        if w > self.size.x() || h > self.size.y() { return None; }
+        let (shelf, x) = loop
+        {
+            if let Some(place) = self.allocate(w, h) { break place; }
+            if !self.evict_lru() { return None; }
+        };
+        let y = self.shelves[shelf].y;
+        self.blit(x, y, w, h, size, pixels);
+        let entry = AtlasEntry { position: (x, y), size, offset };
+        self.slots.insert(key, Slot { entry, shelf: Some(shelf), last_used: self.clock });
+        Some(entry)
+    }
+
+    /// A shelf of a close height first, then a new shelf, then any shelf tall enough
+    fn allocate(&mut self, w: u32, h: u32) -> Option<(usize, u32)>
+    {
+        let span = |s: &Shelf| s.free.iter().position(|&(_, fw)| fw >= w);
+        let close = self.shelves.iter().position(|s| s.height >= h && s.height <= h + h / 4 + 2 && span(s).is_some());
+        let found = close.or_else(||
+        {
+            let top = self.shelves.last().map(|s| s.y + s.height).unwrap_or(0);
+            if top + h > self.size.y() { return None; }
+            self.shelves.push(Shelf { y: top, height: h, free: vec![(0, self.size.x())] });
+            Some(self.shelves.len() - 1)
+        }).or_else(|| self.shelves.iter().position(|s| s.height >= h && span(s).is_some()));
+        found.map(|n|
+        {
+            let shelf = &mut self.shelves[n];
+            let i = span(shelf).unwrap();
+            let (x, fw) = shelf.free[i];
+            if fw == w { shelf.free.remove(i); } else { shelf.free[i] = (x + w, fw - w); }
+            (n, x)
+        })
+    }
+    fn release(&mut self, slot: &Slot)
+    {
+        let n = match slot.shelf { Some(n) => n, None => return };
+        {
+            let (x, w) = (slot.entry.position.0, slot.entry.size.x() + Self::PADDING);
+            let free = &mut self.shelves[n].free;
+            let i = free.iter().position(|&(fx, _)| fx > x).unwrap_or(free.len());
+            free.insert(i, (x, w));
+            // merge with the following span, then with the preceding one
+            if i + 1 < free.len() && free[i].0 + free[i].1 == free[i + 1].0 { free[i].1 += free[i + 1].1; free.remove(i + 1); }
+            if i > 0 && free[i - 1].0 + free[i - 1].1 == free[i].0 { free[i - 1].1 += free[i].1; free.remove(i); }
+        }
+        // emptied shelves at the bottom give their space back
+        let width = self.size.x();
+        while self.shelves.last().map(|s| s.free == [(0, width)]).unwrap_or(false) { self.shelves.pop(); }
+    }
+    /// false if all the glyphs are used in the current frame
+    fn evict_lru(&mut self) -> bool
+    {
+        let frame_start = self.frame_start;
+        let victim = self.slots.iter().filter(|&(_, s)| s.shelf.is_some() && s.last_used < frame_start)
+            .min_by_key(|&(_, s)| s.last_used).map(|(&k, _)| k);
+        match victim
+        {
+            Some(k) => { let s = self.slots.remove(&k).unwrap(); self.release(&s); true },
+            None => false
+        }
+    }
+    /// Copies the bitmap and clears the padding
+    fn blit(&mut self, x: u32, y: u32, w: u32, h: u32, size: Size2U, pixels: &[u8])
+    {
+        let c = self.mode.channels();
+        let (bw, stride) = (size.x() as usize * c, self.size.x() as usize * c);
+        for row in 0 .. h as usize
+        {
+            let o = (y as usize + row) * stride + x as usize * c;
+            let line = &mut self.pixels[o .. o + w as usize * c];
+            for p in line.iter_mut() { *p = 0; }
+            if row < size.y() as usize { line[.. bw].copy_from_slice(&pixels[row * bw .. (row + 1) * bw]); }
+        }
+        let (min, max) = self.dirty.unwrap_or(((x, y), (x + w, y + h)));
+        self.dirty = Some(((min.0.min(x), min.1.min(y)), (max.0.max(x + w), max.1.max(y + h))));
+    }
+
+    /// Pixels modified since the last upload: the position, the size and the tightly packed rows(in the format of `texture_param`)
+    pub fn dirty_pixels(&self) -> Option<((u32, u32), Size2U, Vec<u8>)>
+    {
+        self.dirty.map(|((x0, y0), (x1, y1))|
+        {
+            let c = self.mode.channels();
+            let (w, stride) = ((x1 - x0) as usize * c, self.size.x() as usize * c);
+            let rows = (y0 .. y1).flat_map(|y| { let o = y as usize * stride + x0 as usize * c; self.pixels[o .. o + w].iter().cloned() }).collect();
+            ((x0, y0), Size2U(x1 - x0, y1 - y0), rows)
+        })
+    }
+    /// Uploads the modified pixels into the texture created with `texture_param`. Nothing is done if no glyph was added
+    pub fn upload(&mut self, resources: &ResourceBlock, texture: usize) -> Result<(), Box<Error>>
+    {
+        if let Some((position, size, pixels)) = self.dirty_pixels()
+        {
+            RenderDevice::get().update_texture(resources, texture, position, size, &pixels)?;
+            self.dirty = None;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn bitmap(w: u32, h: u32, value: u8) -> GlyphBitmap
+    {
+        GlyphBitmap { size: Size2U(w, h), offset: (0, -(h as i32)), coverage: vec![value; (w * h) as usize] }
+    }
+    fn key(glyph: u16) -> GlyphKey { GlyphKey::new(0, glyph, 12.0) }
+    fn test_font() -> Font { Font::from_data(::std::rc::Rc::new(::font::sfnt::tests::test_font(0)), 0).unwrap() }
+
+    #[test] fn packing_and_upload_region()
+    {
+        let mut atlas = GlyphAtlas::new(Size2U(16, 8));
+        assert_eq!(atlas.insert(key(1), &bitmap(7, 3, 10)).unwrap().position, (0, 0));
+        assert_eq!(atlas.insert(key(2), &bitmap(7, 3, 20)).unwrap().position, (8, 0));
+        let (position, size, pixels) = atlas.dirty_pixels().unwrap();
+        assert_eq!((position, size), ((0, 0), Size2U(16, 4)));
+        // the padding column and row stay clear
+        assert_eq!(&pixels[.. 16], &[10, 10, 10, 10, 10, 10, 10, 0, 20, 20, 20, 20, 20, 20, 20, 0]);
+        assert!(pixels[48 ..].iter().all(|&p| p == 0));
+
+        let blank = atlas.insert(key(3), &GlyphBitmap { size: Size2U(0, 0), offset: (0, 0), coverage: Vec::new() }).unwrap();
+        assert_eq!(blank.size, Size2U(0, 0));
+        assert_eq!(atlas.insert(key(4), &bitmap(3, 3, 30)).unwrap().position, (0, 4));
+        assert_eq!(atlas.lookup(&key(4)).unwrap().uv(atlas.size()), [0.0, 0.5, 3.0 / 16.0, 7.0 / 8.0]);
+        assert!(atlas.insert(key(5), &bitmap(16, 3, 0)).is_none());
+    }
+
+    #[test] fn lru_eviction()
+    {
+        let mut atlas = GlyphAtlas::new(Size2U(16, 8));
+        atlas.begin_frame();
+        for g in 0 .. 4 { assert!(atlas.insert(key(g), &bitmap(7, 3, 1)).is_some()); }
+        atlas.begin_frame();
+        atlas.lookup(&key(0)); atlas.lookup(&key(2));
+        // 1 and 3 were not used in this frame; 1 is older
+        assert_eq!(atlas.insert(key(4), &bitmap(7, 3, 1)).unwrap().position, (8, 0));
+        assert!(atlas.lookup(&key(1)).is_none());
+        assert_eq!(atlas.insert(key(5), &bitmap(7, 3, 1)).unwrap().position, (8, 4));
+        // everything left is used in this frame
+        assert!(atlas.insert(key(6), &bitmap(7, 3, 1)).is_none());
+        assert_eq!(atlas.len(), 4);
+
+        atlas.begin_frame();
+        atlas.lookup(&key(5));
+        // a wide glyph takes the top shelf after evicting the glyphs until the shelf is emptied
+        assert_eq!(atlas.insert(key(7), &bitmap(15, 3, 1)).unwrap().position, (0, 0));
+        assert!([0, 2, 4].iter().all(|&g| atlas.lookup(&key(g)).is_none()));
+        assert_eq!(atlas.len(), 2);
+    }
+
+    #[test] fn distance_fields()
+    {
+        let font = test_font();
+        let mut atlas = GlyphAtlas::with_mode(Size2U(64, 64), GlyphMode::Sdf { base_size: 20.0, spread: 3.0 });
+        let square = atlas.get(&font, GlyphKey::new(0, 1, 20.0)).unwrap();
+        // 12x14 pixels(with the left bearing of 1) padded by the spread
+        assert_eq!((square.size, square.offset), (Size2U(18, 20), (-2, -17)));
+        // all sizes share the entry
+        assert_eq!(atlas.get(&font, GlyphKey::new(0, 1, 80.0)).unwrap(), square);
+        assert_eq!((atlas.len(), atlas.draw_scale(80.0)), (1, 4.0));
+        let pixel = |x: u32, y: u32| atlas.pixels()[(y * 64 + x) as usize];
+        assert!(pixel(9, 10) > 200 && pixel(0, 0) < 20);
+        assert!((pixel(3, 10) as i32 - 128).abs() <= 24);
+        assert_eq!(atlas.texture_param().color, ColorFormat::Grayscale);
+
+        let mut multi = GlyphAtlas::with_mode(Size2U(64, 64), GlyphMode::Msdf { base_size: 20.0, spread: 3.0 });
+        let square = multi.get(&font, GlyphKey::new(0, 1, 10.0)).unwrap();
+        let (_, size, pixels) = multi.dirty_pixels().unwrap();
+        assert_eq!((size, pixels.len()), (Size2U(19, 21), 19 * 21 * 3));
+        assert_eq!(square.size, Size2U(18, 20));
+        assert_eq!(multi.texture_param().color, ColorFormat::Default);
+        assert_eq!(multi.get(&font, GlyphKey::new(0, 2, 10.0)).unwrap().size, Size2U(0, 0));
+    }
+}
@@ -0,0 +1,384 @@
+//! Compact Font Format outlines(the `CFF ` table of OpenType fonts), including CID-keyed fonts as most CJK fonts are
+
+use metrics::*;
+use vector::Path;
+use super::sfnt::{Reader, invalid_data};
+use std::io::Result as IOResult;
+
+/// INDEX structure: offsets of the elements in the table
+#[derive(Debug, Clone, Copy)]
+struct Index { count: usize, off_size: u8, offsets: usize, data: usize }
+impl Index
+{
+    const EMPTY: Index = Index { count: 0, off_size: 1, offsets: 0, data: 0 };
+
+    /// Reads the header and skips the whole INDEX
+    fn read(r: &mut Reader) -> IOResult<Self>
+    {
+        let count = r.u16()? as usize;
+        if count == 0 { return Ok(Self::EMPTY); }
+        let off_size = r.u8()?;
+        if off_size == 0 || off_size > 4 { return Err(invalid_data("Broken INDEX in CFF")); }
+        let offsets = r.pos;
+        r.skip(count * off_size as usize)?;
+        let last = r.uint(off_size)? as usize;
+        // offsets are 1-based from the byte before the data
+        let data = r.pos - 1;
+        r.skip(last.saturating_sub(1))?;
+        Ok(Index { count, off_size, offsets, data })
+    }
+    fn get<'d>(&self, cff: &'d [u8], n: usize) -> IOResult<&'d [u8]>
+    {
+        if n >= self.count { return Err(invalid_data("CFF INDEX out of range")); }
+        let mut r = Reader::at(cff, self.offsets + n * self.off_size as usize);
+        let (start, end) = (r.uint(self.off_size)? as usize, r.uint(self.off_size)? as usize);
+        if start == 0 || end < start || self.data + end > cff.len() { return Err(invalid_data("Broken INDEX in CFF")); }
+        Ok(&cff[self.data + start .. self.data + end])
+    }
+}
+
+/// Operators(12 x are 1200 + x) and their operands
+struct Dict(Vec<(u16, Vec<f64>)>);
+impl Dict
+{
+    fn get(&self, op: u16) -> Option<&[f64]> { self.0.iter().find(|e| e.0 == op).map(|e| &e.1 as &[f64]) }
+    fn offset(&self, op: u16) -> Option<usize> { self.get(op).and_then(|v| v.first()).map(|&v| v as usize) }
+}
+fn parse_dict(bytes: &[u8]) -> IOResult<Dict>
+{
+    let (mut r, mut operands, mut entries) = (Reader::at(bytes, 0), Vec::new(), Vec::new());
+    while r.pos < bytes.len()
+    {
+        let b0 = r.u8()?;
+        if b0 <= 21
+        {
+            let op = if b0 == 12 { 1200 + r.u8()? as u16 } else { b0 as u16 };
+            entries.push((op, ::std::mem::replace(&mut operands, Vec::new())));
+        }
+        else if b0 == 28 { operands.push(r.i16()? as f64); }
+        else if b0 == 29 { operands.push(r.u32()? as i32 as f64); }
+        else if b0 == 30 { operands.push(real(&mut r)?); }
+        else if b0 >= 32 && b0 <= 246 { operands.push(b0 as f64 - 139.0); }
+        else if b0 >= 247 && b0 <= 250 { operands.push((b0 as f64 - 247.0) * 256.0 + r.u8()? as f64 + 108.0); }
+        else if b0 >= 251 && b0 <= 254 { operands.push(-(b0 as f64 - 251.0) * 256.0 - r.u8()? as f64 - 108.0); }
+        else { return Err(invalid_data("Broken DICT in CFF")); }
+    }
+    Ok(Dict(entries))
+}
+/// Real number operand in nibbles
+fn real(r: &mut Reader) -> IOResult<f64>
+{
+    let mut s = String::new();
+    loop
+    {
+        let b = r.u8()?;
+        for &n in &[b >> 4, b & 0x0f]
+        {
+            match n
+            {
+                0x0f => return s.parse().map_err(|_| invalid_data("Broken real number in CFF")),
+                0x0a => s.push('.'), 0x0b => s.push('E'), 0x0c => s.push_str("E-"), 0x0e => s.push('-'),
+                0x0d => return Err(invalid_data("Broken real number in CFF")),
+                d => s.push((b'0' + d) as char)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FdSelect { Single, Format0(usize), Format3(usize) }
+
+/// Charstrings and subroutines of a CFF table. Positions are relative to the table
+#[derive(Debug, Clone)]
+pub struct CffOutlines
+{
+    table: (usize, usize),
+    char_strings: Index, global_subrs: Index,
+    /// Local subroutines of each font DICT(one for non CID-keyed fonts)
+    local_subrs: Vec<Index>, fd_select: FdSelect
+}
+impl CffOutlines
+{
+    /// `table` is the offset and the length of the table in the font data
+    pub fn parse(data: &[u8], table: (usize, usize)) -> IOResult<Self>
+    {
+        let cff = &data[table.0 .. table.0 + table.1];
+        let header_size = Reader::at(cff, 2).u8()?;
+        let mut r = Reader::at(cff, header_size as usize);
+        let _names = Index::read(&mut r)?;
+        let top_dicts = Index::read(&mut r)?;
+        let _strings = Index::read(&mut r)?;
+        let global_subrs = Index::read(&mut r)?;
+        let top = parse_dict(top_dicts.get(cff, 0)?)?;
+        if top.get(1206).and_then(|v| v.first()).map(|&t| t != 2.0).unwrap_or(false)
+        {
+            return Err(invalid_data("Unsupported charstring type"));
+        }
+        let char_strings = Index::read(&mut Reader::at(cff, top.offset(17).ok_or_else(|| invalid_data("No CharStrings in CFF"))?))?;
+        // FontMatrix is assumed to be the reciprocal of unitsPerEm in the head table
+        let (local_subrs, fd_select) = match top.offset(1236)
+        {
+            Some(fd_array) =>
+            {
+                let fds = Index::read(&mut Reader::at(cff, fd_array))?;
+                let subrs = (0 .. fds.count).map(|n| fds.get(cff, n).and_then(parse_dict).and_then(|d| private_subrs(cff, &d)))
+                    .collect::<IOResult<Vec<_>>>()?;
+                let at = top.offset(1237).ok_or_else(|| invalid_data("No FDSelect in CID-keyed CFF"))?;
+                let fd_select = match Reader::at(cff, at).u8()?
+                {
+                    0 => FdSelect::Format0(at + 1), 3 => FdSelect::Format3(at + 1),
+                    _ => return Err(invalid_data("Unsupported FDSelect format"))
+                };
+                (subrs, fd_select)
+            },
+            None => (vec![private_subrs(cff, &top)?], FdSelect::Single)
+        };
+        Ok(CffOutlines { table, char_strings, global_subrs, local_subrs, fd_select })
+    }
+
+    pub fn outline(&self, data: &[u8], glyph: u16) -> IOResult<Path>
+    {
+        let cff = &data[self.table.0 .. self.table.0 + self.table.1];
+        let fd = match self.fd_select
+        {
+            FdSelect::Single => 0,
+            FdSelect::Format0(at) => Reader::at(cff, at + glyph as usize).u8()? as usize,
+            FdSelect::Format3(at) =>
+            {
+                let mut r = Reader::at(cff, at);
+                let (ranges, mut fd) = (r.u16()?, None);
+                for _ in 0 .. ranges
+                {
+                    let (first, n) = (r.u16()?, r.u8()?);
+                    if first > glyph { break; }
+                    fd = Some(n as usize);
+                }
+                fd.ok_or_else(|| invalid_data("The glyph is not in FDSelect"))?
+            }
+        };
+        let local_subrs = *self.local_subrs.get(fd).ok_or_else(|| invalid_data("FD index out of range"))?;
+        let mut interpreter = Interpreter::new(cff, self.global_subrs, local_subrs);
+        interpreter.run(self.char_strings.get(cff, glyph as usize)?, 0)?;
+        if interpreter.open { interpreter.path.close(); }
+        Ok(interpreter.path)
+    }
+}
+fn private_subrs(cff: &[u8], dict: &Dict) -> IOResult<Index>
+{
+    let (size, offset) = match dict.get(18) { Some(v) if v.len() == 2 => (v[0] as usize, v[1] as usize), _ => return Ok(Index::EMPTY) };
+    if offset + size > cff.len() { return Err(invalid_data("Private DICT is out of CFF")); }
+    match parse_dict(&cff[offset .. offset + size])?.offset(19)
+    {
+        // relative to the Private DICT
+        Some(subrs) => Index::read(&mut Reader::at(cff, offset + subrs)),
+        None => Ok(Index::EMPTY)
+    }
+}
+
+const MAX_SUBR_DEPTH: usize = 10;
+const MAX_STACK: usize = 48;
+/// Type 2 charstring interpreter. Hints are skipped
+struct Interpreter<'d>
+{
+    cff: &'d [u8], global_subrs: Index, local_subrs: Index,
+    path: Path, stack: Vec<f32>, x: f32, y: f32, stems: usize, width_parsed: bool, open: bool
+}
+impl<'d> Interpreter<'d>
+{
+    fn new(cff: &'d [u8], global_subrs: Index, local_subrs: Index) -> Self
+    {
+        Interpreter
+        {
+            cff, global_subrs, local_subrs, path: Path::new(), stack: Vec::with_capacity(MAX_STACK),
+            x: 0.0, y: 0.0, stems: 0, width_parsed: false, open: false
+        }
+    }
+    /// true if endchar is reached
+    fn run(&mut self, code: &[u8], depth: usize) -> IOResult<bool>
+    {
+        if depth > MAX_SUBR_DEPTH { return Err(invalid_data("Too deeply nested subroutines")); }
+        let mut r = Reader::at(code, 0);
+        while r.pos < code.len()
+        {
+            let b0 = r.u8()?;
+            if b0 == 28 || b0 >= 32
+            {
+                let v = if b0 == 28 { r.i16()? as f32 }
+                    else if b0 <= 246 { b0 as f32 - 139.0 }
+                    else if b0 <= 250 { (b0 as f32 - 247.0) * 256.0 + r.u8()? as f32 + 108.0 }
+                    else if b0 <= 254 { -(b0 as f32 - 251.0) * 256.0 - r.u8()? as f32 - 108.0 }
+                    else { r.u32()? as i32 as f32 / 65536.0 };
+                if self.stack.len() >= MAX_STACK { return Err(invalid_data("Charstring stack overflow")); }
+                self.stack.push(v);
+                continue;
+            }
+            match b0
+            {
+                10 | 29 =>
+                {
+                    let subrs = if b0 == 10 { self.local_subrs } else { self.global_subrs };
+                    let n = self.stack.pop().ok_or_else(|| invalid_data("Charstring stack underflow"))? as i32 + bias(subrs.count);
+                    if n < 0 { return Err(invalid_data("Subroutine index out of range")); }
+                    let cff = self.cff;
+                    if self.run(subrs.get(cff, n as usize)?, depth + 1)? { return Ok(true); }
+                    continue;
+                },
+                11 => return Ok(false),
+                _ => ()
+            }
+            let odd = self.stack.len() % 2 == 1;
+            let s = match b0
+            {
+                1 | 3 | 18 | 23 | 19 | 20 =>
+                {
+                    // hstem, vstem, hstemhm, vstemhm; hintmask and cntrmask may follow implicit vstems
+                    self.take_width(odd);
+                    self.stems += self.stack.len() / 2;
+                    self.stack.clear();
+                    if b0 == 19 || b0 == 20 { r.skip((self.stems + 7) / 8)?; }
+                    continue;
+                },
+                21 => { let n = self.stack.len(); self.take_width(n > 2); self.args() },
+                22 | 4 => { let n = self.stack.len(); self.take_width(n > 1); self.args() },
+                14 => { self.take_width(odd); self.stack.clear(); if self.open { self.path.close(); self.open = false; } return Ok(true); },
+                _ => self.args()
+            };
+            match b0
+            {
+                21 if s.len() >= 2 => self.move_by(s[0], s[1]),
+                22 if s.len() >= 1 => self.move_by(s[0], 0.0),
+                4 if s.len() >= 1 => self.move_by(0.0, s[0]),
+                5 => for p in s.chunks(2).filter(|p| p.len() == 2) { self.line_by(p[0], p[1]); },
+                6 | 7 =>
+                {
+                    let mut horizontal = b0 == 6;
+                    for &d in &s { if horizontal { self.line_by(d, 0.0) } else { self.line_by(0.0, d) } horizontal = !horizontal; }
+                },
+                8 => for c in s.chunks(6).filter(|c| c.len() == 6) { self.curve_by(c[0], c[1], c[2], c[3], c[4], c[5]); },
+                24 =>
+                {
+                    // rcurveline
+                    let n = s.len().saturating_sub(2) / 6 * 6;
+                    for c in s[.. n].chunks(6) { self.curve_by(c[0], c[1], c[2], c[3], c[4], c[5]); }
+                    if s.len() >= n + 2 { self.line_by(s[n], s[n + 1]); }
+                },
+                25 =>
+                {
+                    // rlinecurve
+                    let n = s.len().saturating_sub(6) / 2 * 2;
+                    for p in s[.. n].chunks(2) { self.line_by(p[0], p[1]); }
+                    if s.len() >= n + 6 { self.curve_by(s[n], s[n + 1], s[n + 2], s[n + 3], s[n + 4], s[n + 5]); }
+                },
+                26 =>
+                {
+                    // vvcurveto
+                    let (mut dx1, rest) = if s.len() % 2 == 1 { (s[0], &s[1 ..]) } else { (0.0, &s[..]) };
+                    for c in rest.chunks(4).filter(|c| c.len() == 4) { self.curve_by(dx1, c[0], c[1], c[2], 0.0, c[3]); dx1 = 0.0; }
+                },
+                27 =>
+                {
+                    // hhcurveto
+                    let (mut dy1, rest) = if s.len() % 2 == 1 { (s[0], &s[1 ..]) } else { (0.0, &s[..]) };
+                    for c in rest.chunks(4).filter(|c| c.len() == 4) { self.curve_by(c[0], dy1, c[1], c[2], c[3], 0.0); dy1 = 0.0; }
+                },
+                30 | 31 =>
+                {
+                    // vhcurveto, hvcurveto: the tangents alternate; the last curve may have an extra delta
+                    let (mut horizontal, mut i) = (b0 == 31, 0);
+                    while i + 4 <= s.len()
+                    {
+                        let last = if s.len() - i == 5 { s[i + 4] } else { 0.0 };
+                        if horizontal { self.curve_by(s[i], 0.0, s[i + 1], s[i + 2], last, s[i + 3]); }
+                        else { self.curve_by(0.0, s[i], s[i + 1], s[i + 2], s[i + 3], last); }
+                        i += 4; horizontal = !horizontal;
+                    }
+                },
+                12 => match r.u8()?
+                {
+                    35 if s.len() >= 12 => { self.curve_by(s[0], s[1], s[2], s[3], s[4], s[5]); self.curve_by(s[6], s[7], s[8], s[9], s[10], s[11]); },
+                    34 if s.len() >= 7 => { self.curve_by(s[0], 0.0, s[1], s[2], s[3], 0.0); self.curve_by(s[4], 0.0, s[5], -s[2], s[6], 0.0); },
+                    36 if s.len() >= 9 =>
+                    {
+                        self.curve_by(s[0], s[1], s[2], s[3], s[4], 0.0);
+                        self.curve_by(s[5], 0.0, s[6], s[7], s[8], -(s[1] + s[3] + s[7]));
+                    },
+                    37 if s.len() >= 11 =>
+                    {
+                        let (dx, dy) = (s[0] + s[2] + s[4] + s[6] + s[8], s[1] + s[3] + s[5] + s[7] + s[9]);
+                        let (dx6, dy6) = if dx.abs() > dy.abs() { (s[10], -dy) } else { (-dx, s[10]) };
+                        self.curve_by(s[0], s[1], s[2], s[3], s[4], s[5]);
+                        self.curve_by(s[6], s[7], s[8], s[9], dx6, dy6);
+                    },
+                    // arithmetic and storage operators(removed from the spec) are ignored
+                    _ => ()
+                },
+                21 | 22 | 4 => return Err(invalid_data("Charstring stack underflow")),
+                _ => return Err(invalid_data("Unknown charstring operator"))
+            }
+        }
+        Ok(false)
+    }
+
+    /// The first stack-clearing operator may have the advance width as an extra operand
+    fn take_width(&mut self, has_width: bool)
+    {
+        if !self.width_parsed && has_width { self.stack.remove(0); }
+        self.width_parsed = true;
+    }
+    fn args(&mut self) -> Vec<f32> { ::std::mem::replace(&mut self.stack, Vec::with_capacity(MAX_STACK)) }
+    fn move_by(&mut self, dx: f32, dy: f32)
+    {
+        if self.open { self.path.close(); }
+        self.x += dx; self.y += dy;
+        self.path.move_to(Point2F(self.x, self.y));
+        self.open = true;
+    }
+    fn line_by(&mut self, dx: f32, dy: f32)
+    {
+        self.x += dx; self.y += dy;
+        self.path.line_to(Point2F(self.x, self.y));
+    }
+    fn curve_by(&mut self, dx1: f32, dy1: f32, dx2: f32, dy2: f32, dx3: f32, dy3: f32)
+    {
+        let c1 = Point2F(self.x + dx1, self.y + dy1);
+        let c2 = Point2F(c1.0 + dx2, c1.1 + dy2);
+        self.x = c2.0 + dx3; self.y = c2.1 + dy3;
+        self.path.cubic_to(c1, c2, Point2F(self.x, self.y));
+    }
+}
+fn bias(count: usize) -> i32 { if count < 1240 { 107 } else if count < 33900 { 1131 } else { 32768 } }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use vector::PathCommand;
+
+    #[test] fn dict()
+    {
+        let d = parse_dict(&[250, 124, 17, 30, 0x1a, 0x5f, 12, 7, 28, 0x12, 0x34, 29, 0, 1, 0, 0, 139, 18]).unwrap();
+        assert_eq!(d.offset(17), Some(1000));
+        assert_eq!(d.get(1207), Some(&[1.5][..]));
+        assert_eq!(d.get(18), Some(&[4660.0, 65536.0, 0.0][..]));
+    }
+
+    #[test] fn charstrings()
+    {
+        // one local subroutine: vlineto 600, return
+        let subrs = [0, 1, 1, 1, 5, 248, 236, 7, 11];
+        let local = Index::read(&mut Reader::at(&subrs, 0)).unwrap();
+        assert_eq!(local.get(&subrs, 0).unwrap(), &[248, 236, 7, 11]);
+
+        // width 50, rmoveto 100 100, hlineto 500, callsubr -107(0 after the bias), hlineto -500, hvcurveto -50 -50 -50 -50, endchar
+        let code = [189, 239, 239, 21, 248, 136, 6, 32, 10, 252, 136, 6, 89, 89, 89, 89, 31, 14];
+        let mut i = Interpreter::new(&subrs, Index::EMPTY, local);
+        assert!(i.run(&code, 0).unwrap());
+        assert_eq!(i.path.commands, vec![PathCommand::MoveTo(Point2F(100.0, 100.0)), PathCommand::LineTo(Point2F(600.0, 100.0)),
+            PathCommand::LineTo(Point2F(600.0, 700.0)), PathCommand::LineTo(Point2F(100.0, 700.0)),
+            PathCommand::CubicTo(Point2F(50.0, 700.0), Point2F(0.0, 650.0), Point2F(0.0, 600.0)), PathCommand::Close]);
+
+        let mut i = Interpreter::new(&subrs, Index::EMPTY, local);
+        assert!(i.run(&[139, 10], 0).is_err());
+        assert!(i.run(&[21], 0).is_err());
+    }
+}
@@ -0,0 +1,346 @@
+//! Text layout: line breaking with Japanese kinsoku shori(line head/end prohibition rules), spacing between
+//! Japanese and Latin letters, font fallback, alignment and truncation with an ellipsis.
+//! Only the character mapping and the advances of the fonts are used, so layouts are computed without the GPU
+
+use metrics::*;
+use super::{Font, FontMetrics};
+use std::ops::Range;
+
+/// What the layout needs of a font
+pub trait LayoutFont
+{
+    fn glyph_index(&self, c: char) -> Option<u16>;
+    /// Advance width in pixels
+    fn advance(&self, glyph: u16, size: f32) -> f32;
+    fn metrics(&self, size: f32) -> FontMetrics;
+}
+impl LayoutFont for Font
+{
+    fn glyph_index(&self, c: char) -> Option<u16> { Font::glyph_index(self, c) }
+    fn advance(&self, glyph: u16, size: f32) -> f32 { Font::advance(self, glyph, size) }
+    fn metrics(&self, size: f32) -> FontMetrics { Font::metrics(self, size) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlign { Left, Center, Right }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle
+{
+    /// Pixels per em
+    pub size: f32,
+    /// Lines are broken to fit in the width. None lays out each paragraph in a line
+    pub max_width: Option<f32>, pub max_lines: Option<usize>, pub align: TextAlign,
+    /// Multiplier of the line height of the primary font
+    pub line_spacing: f32,
+    /// Space between Japanese and Latin letters or digits in em
+    pub latin_spacing: f32,
+    /// Truncated text ends with "…"
+    pub ellipsis: bool
+}
+impl TextStyle
+{
+    pub fn new(size: f32) -> Self
+    {
+        TextStyle { size, max_width: None, max_lines: None, align: TextAlign::Left, line_spacing: 1.0, latin_spacing: 0.25, ellipsis: true }
+    }
+    pub fn width(self, max_width: f32) -> Self { TextStyle { max_width: Some(max_width), .. self } }
+    pub fn max_lines(self, lines: usize) -> Self { TextStyle { max_lines: Some(lines), .. self } }
+    pub fn align(self, align: TextAlign) -> Self { TextStyle { align, .. self } }
+    pub fn line_spacing(self, line_spacing: f32) -> Self { TextStyle { line_spacing, .. self } }
+    pub fn latin_spacing(self, latin_spacing: f32) -> Self { TextStyle { latin_spacing, .. self } }
+    pub fn ellipsis(self, ellipsis: bool) -> Self { TextStyle { ellipsis, .. self } }
+}
+
+/// A glyph at its pen position on the baseline. y grows downwards from the top of the layout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph
+{
+    /// Index of the font in the fallback chain
+    pub font: usize, pub glyph: u16, pub position: Point2F,
+    /// Byte offset of the source character in the text
+    pub cluster: usize
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutLine
+{
+    pub glyphs: Range<usize>,
+    /// Byte range of the source text(excluding the ellipsis)
+    pub text: Range<usize>,
+    pub baseline: f32,
+    /// Left end and width of the line. Trailing spaces are not included
+    pub x: f32, pub width: f32
+}
+/// Consecutive glyphs of a line drawn with the same font
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphRun { pub font: usize, pub glyphs: Range<usize> }
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout
+{
+    pub glyphs: Vec<PositionedGlyph>, pub lines: Vec<LayoutLine>,
+    /// The maximum width(or the widest line) and the height from the top of the first line to the bottom of the last line
+    pub size: Size2F,
+    /// Lines over `max_lines` were dropped
+    pub truncated: bool
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Item { c: char, cluster: usize, font: usize, glyph: u16, advance: f32 }
+
+/// Characters not placed at the head of a line: closing brackets, punctuation, iteration marks and small kana
+const NOT_AT_LINE_HEAD: &'static str = ")]}｝〕〉》」』】〙〗〟’”｠»）］｡､、。，．・：；？！‼⁇⁈⁉ー‐゠–〜ゝゞヽヾ々〻\
+    ぁぃぅぇぉっゃゅょゎゕゖァィゥェォッャュョヮヵヶㇰㇱㇲㇳㇴㇵㇶㇷㇸㇹㇺㇻㇼㇽㇾㇿ…‥,.!?:;%";
+/// Characters not placed at the end of a line: opening brackets
+const NOT_AT_LINE_END: &'static str = "([{｛〔〈《「『【〘〖〝‘“｟«（［｢";
+/// Punctuation allowed to hang over the right end of a line(burasage)
+const HANGING: &'static str = "、。，．､｡,.";
+
+/// Full-width characters: CJK scripts, symbols and punctuation
+fn is_wide(c: char) -> bool
+{
+    let c = c as u32;
+    (0x1100 <= c && c <= 0x11ff) || (0x2e80 <= c && c <= 0x9fff) || (0xac00 <= c && c <= 0xd7af) || (0xf900 <= c && c <= 0xfaff)
+        || (0xfe30 <= c && c <= 0xfe4f) || (0xff00 <= c && c <= 0xff60) || (0xffe0 <= c && c <= 0xffe6) || (0x20000 <= c && c <= 0x3ffff)
+}
+fn is_wide_punctuation(c: char) -> bool
+{
+    let c = c as u32;
+    (0x3000 <= c && c <= 0x303f) || c == 0x30fb || (0xfe30 <= c && c <= 0xfe4f) || (0xff01 <= c && c <= 0xff0f)
+        || (0xff1a <= c && c <= 0xff20) || (0xff3b <= c && c <= 0xff40) || (0xff5b <= c && c <= 0xff60)
+}
+/// Between a Japanese letter and a Latin letter or digit
+fn takes_latin_spacing(a: char, b: char) -> bool
+{
+    let japanese = |c| is_wide(c) && !is_wide_punctuation(c);
+    let latin = |c: char| c.is_alphanumeric() && !is_wide(c);
+    (japanese(a) && latin(b)) || (latin(a) && japanese(b))
+}
+/// Whether a line can be broken between the characters
+fn can_break(a: char, b: char) -> bool
+{
+    if NOT_AT_LINE_HEAD.contains(b) || NOT_AT_LINE_END.contains(a) { return false; }
+    if a.is_whitespace() { return true; }
+    // spaces stay at the end of the line
+    if b.is_whitespace() { return false; }
+    is_wide(a) || is_wide(b) || (a == '-' && b.is_alphanumeric())
+}
+
+impl TextLayout
+{
+    /// Lays out the text with the fonts in the order of fallback.
+    /// Characters missing in all the fonts are drawn with the missing glyph of the first font. Line metrics come from the first font
+    pub fn new(text: &str, fonts: &[&LayoutFont], style: &TextStyle) -> Self
+    {
+        assert!(!fonts.is_empty(), "No fonts to lay out the text with");
+        let item = |c: char, cluster: usize|
+        {
+            let (font, glyph) = fonts.iter().enumerate().filter_map(|(n, f)| f.glyph_index(c).map(|g| (n, g))).next().unwrap_or((0, 0));
+            Item { c, cluster, font, glyph, advance: fonts[font].advance(glyph, style.size) }
+        };
+        let spacing = style.latin_spacing * style.size;
+        let gap = |items: &[Item], i: usize| if takes_latin_spacing(items[i - 1].c, items[i].c) { spacing } else { 0.0 };
+
+        // break into lines of items //
+        let mut lines: Vec<Vec<Item>> = Vec::new();
+        // byte offsets of the paragraphs of the lines: empty lines are placed there
+        let mut line_offsets = Vec::new();
+        let mut offset = 0;
+        for paragraph in if text.is_empty() { Vec::new() } else { text.split('\n').collect() }
+        {
+            let items: Vec<_> = paragraph.char_indices().filter(|&(_, c)| c != '\r').map(|(i, c)| item(c, offset + i)).collect();
+            let paragraph_offset = offset;
+            offset += paragraph.len() + 1;
+            let mut start = 0;
+            loop
+            {
+                let (mut x, mut end, mut last_break) = (0.0, items.len(), start);
+                for i in start .. items.len()
+                {
+                    let w = if i > start { gap(&items, i) } else { 0.0 } + items[i].advance;
+                    if i > start && can_break(items[i - 1].c, items[i].c) { last_break = i; }
+                    let fits = match style.max_width
+                    {
+                        Some(m) => x + w <= m + 1.0e-3 || items[i].c.is_whitespace() || (HANGING.contains(items[i].c) && x <= m),
+                        None => true
+                    };
+                    // breaking at the last opportunity pushes the prohibited characters to the next line(oidashi).
+                    // Words longer than the line are broken anywhere
+                    if !fits && i > start { end = if last_break > start { last_break } else { i }; break; }
+                    x += w;
+                }
+                lines.push(items[start .. end].to_vec());
+                line_offsets.push(paragraph_offset);
+                if end >= items.len() { break; }
+                start = end;
+            }
+        }
+
+        // truncate //
+        let truncated = style.max_lines.map(|m| lines.len() > m).unwrap_or(false);
+        if truncated { lines.truncate(style.max_lines.unwrap()); }
+        let mut ranges: Vec<_> = lines.iter().zip(&line_offsets).map(|(items, &offset)| match (items.first(), items.last())
+        {
+            (Some(a), Some(b)) => a.cluster .. b.cluster + b.c.len_utf8(),
+            _ => offset .. offset
+        }).collect();
+        if truncated
+        {
+            if style.ellipsis
+            {
+                let dots = if fonts.iter().any(|f| f.glyph_index('…').is_some()) { vec![item('…', 0)] } else { vec![item('.', 0); 3] };
+                let dots_width = dots.iter().map(|d| d.advance).sum::<f32>();
+                if let Some(last) = lines.last_mut()
+                {
+                    loop
+                    {
+                        let trailing = last.last().map(|i| i.c.is_whitespace() || NOT_AT_LINE_END.contains(i.c)).unwrap_or(false);
+                        let width = (0 .. last.len()).map(|i| if i > 0 { gap(last, i) } else { 0.0 } + last[i].advance).sum::<f32>();
+                        if !trailing && style.max_width.map(|m| width + dots_width <= m + 1.0e-3).unwrap_or(true) { break; }
+                        if last.pop().is_none() { break; }
+                    }
+                    let range = ranges.last_mut().unwrap();
+                    let cluster = last.last().map(|i| i.cluster + i.c.len_utf8()).unwrap_or(range.start);
+                    range.end = cluster.max(range.start);
+                    last.extend(dots.into_iter().map(|d| Item { cluster, .. d }));
+                }
+            }
+        }
+
+        // place //
+        let metrics = fonts[0].metrics(style.size);
+        let line_height = metrics.line_height() * style.line_spacing;
+        let (mut glyphs, mut placed) = (Vec::new(), Vec::with_capacity(lines.len()));
+        for (n, items) in lines.iter().enumerate()
+        {
+            let baseline = metrics.ascent + n as f32 * line_height;
+            let (first, mut x, mut width) = (glyphs.len(), 0.0, 0.0);
+            for i in 0 .. items.len()
+            {
+                if i > 0 { x += gap(items, i); }
+                glyphs.push(PositionedGlyph { font: items[i].font, glyph: items[i].glyph, position: Point2F(x, baseline), cluster: items[i].cluster });
+                x += items[i].advance;
+                if !items[i].c.is_whitespace() { width = x; }
+            }
+            placed.push(LayoutLine { glyphs: first .. glyphs.len(), text: ranges[n].clone(), baseline, x: 0.0, width });
+        }
+        let width = style.max_width.unwrap_or_else(|| placed.iter().map(|l| l.width).fold(0.0, f32::max));
+        let k = match style.align { TextAlign::Left => 0.0, TextAlign::Center => 0.5, TextAlign::Right => 1.0 };
+        for l in &mut placed
+        {
+            // hanging punctuation may make the line wider than the box
+            l.x = ((width - l.width) * k).max(0.0);
+            for g in &mut glyphs[l.glyphs.clone()] { g.position.0 += l.x; }
+        }
+        let height = if placed.is_empty() { 0.0 } else { (placed.len() - 1) as f32 * line_height + metrics.ascent + metrics.descent };
+        TextLayout { glyphs, lines: placed, size: Size2F(width, height), truncated }
+    }
+
+    /// Glyphs split into runs of the same font in each line
+    pub fn runs(&self) -> Vec<GlyphRun>
+    {
+        let mut runs: Vec<GlyphRun> = Vec::new();
+        for l in &self.lines
+        {
+            for n in l.glyphs.clone()
+            {
+                let font = self.glyphs[n].font;
+                if n != l.glyphs.start && runs.last().map(|r| r.font == font).unwrap_or(false) { runs.last_mut().unwrap().glyphs.end += 1; }
+                else { runs.push(GlyphRun { font, glyphs: n .. n + 1 }); }
+            }
+        }
+        runs
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use font::sfnt::tests::test_font;
+    use std::rc::Rc;
+
+    /// Full-width characters are 1em wide, the others are 0.5em. Characters in the string are missing
+    struct Mono(&'static str);
+    impl LayoutFont for Mono
+    {
+        fn glyph_index(&self, c: char) -> Option<u16> { if self.0.contains(c) { None } else { Some(c as u32 as u16) } }
+        fn advance(&self, glyph: u16, size: f32) -> f32
+        {
+            if glyph == 0 || is_wide(::std::char::from_u32(glyph as u32).unwrap_or('a')) { size } else { size * 0.5 }
+        }
+        fn metrics(&self, size: f32) -> FontMetrics { FontMetrics { ascent: size * 0.8, descent: size * 0.2, line_gap: 0.0 } }
+    }
+
+    fn lines(text: &str, style: &TextStyle) -> Vec<String>
+    {
+        let layout = TextLayout::new(text, &[&Mono("")], style);
+        layout.lines.iter().map(|l| text[l.text.clone()].to_owned()).collect()
+    }
+
+    #[test] fn kinsoku()
+    {
+        let style = TextStyle::new(10.0).width(50.0);
+        // 。 hangs over the end, then the line is broken before か
+        assert_eq!(lines("あいうえお。かきく", &style), vec!["あいうえお。", "かきく"]);
+        assert_eq!(lines("あいうえお。かき", &style.width(45.0)), vec!["あいうえ", "お。かき"]);
+        // no small kana at the head, no opening bracket at the end
+        assert_eq!(lines("あいうえおっか", &style), vec!["あいうえ", "おっか"]);
+        assert_eq!(lines("あいうえ「お」か", &style), vec!["あいうえ", "「お」か"]);
+        assert_eq!(lines("あいうえ……お", &style.width(45.0)), vec!["あいう", "え……お"]);
+    }
+
+    #[test] fn latin_words_and_spacing()
+    {
+        let style = TextStyle::new(10.0).width(40.0);
+        assert_eq!(lines("hello world", &style), vec!["hello ", "world"]);
+        assert_eq!(lines("abcdefghij", &style.width(20.0)), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(lines("a-b-c-de", &style.width(30.0)), vec!["a-b-c-", "de"]);
+        let layout = TextLayout::new("日本語text 2017年", &[&Mono("")], &TextStyle::new(10.0));
+        let x: Vec<_> = layout.glyphs.iter().map(|g| g.position.0).collect();
+        assert_eq!(x, vec![0.0, 10.0, 20.0, 32.5, 37.5, 42.5, 47.5, 52.5, 57.5, 62.5, 67.5, 72.5, 80.0]);
+        assert_eq!(layout.lines[0].width, 90.0);
+        // trailing spaces are not a part of the width
+        assert_eq!(TextLayout::new("ab  ", &[&Mono("")], &TextStyle::new(10.0)).lines[0].width, 10.0);
+    }
+
+    #[test] fn fallback()
+    {
+        let font = Font::from_data(Rc::new(test_font(0)), 0).unwrap();
+        let layout = TextLayout::new("Aあbx", &[&font, &Mono("x")], &TextStyle::new(10.0).latin_spacing(0.0));
+        assert_eq!(layout.glyphs.iter().map(|g| (g.font, g.glyph)).collect::<Vec<_>>(), vec![(0, 1), (0, 3), (1, 'b' as u16), (0, 0)]);
+        assert_eq!(layout.glyphs[1].position, Point2F(7.0, 8.0));
+        assert_eq!(layout.glyphs[3].position.0, 14.5);
+        assert_eq!(layout.runs(), vec![GlyphRun { font: 0, glyphs: 0 .. 2 }, GlyphRun { font: 1, glyphs: 2 .. 3 }, GlyphRun { font: 0, glyphs: 3 .. 4 }]);
+    }
+
+    #[test] fn truncation_and_alignment()
+    {
+        let text = "あいうえおかきくけこさしす";
+        let style = TextStyle::new(10.0).width(50.0).max_lines(2).align(TextAlign::Right);
+        let layout = TextLayout::new(text, &[&Mono("")], &style);
+        assert!(layout.truncated);
+        assert_eq!(layout.lines.len(), 2);
+        assert_eq!(&text[layout.lines[1].text.clone()], "かきくけ");
+        let last = &layout.glyphs[layout.lines[1].glyphs.clone()];
+        assert_eq!(last.last().unwrap().glyph, '…' as u16);
+        assert_eq!((layout.lines[1].x, layout.lines[1].width), (5.0, 45.0));
+        assert_eq!(last[0].position, Point2F(5.0, 18.0));
+        assert_eq!(layout.size, Size2F(50.0, 20.0));
+
+        let centered = TextLayout::new(text, &[&Mono("…")], &style.align(TextAlign::Center).max_lines(1));
+        let dots = &centered.glyphs[centered.lines[0].glyphs.clone()];
+        assert_eq!(dots.iter().filter(|g| g.glyph == '.' as u16).count(), 3);
+        assert_eq!((centered.lines[0].x, centered.lines[0].width), (2.5, 45.0));
+        assert!(!TextLayout::new("あい", &[&Mono("")], &style).truncated);
+    }
+
+    #[test] fn paragraphs()
+    {
+        let layout = TextLayout::new("a\r\n\nbc", &[&Mono("")], &TextStyle::new(10.0).line_spacing(1.5));
+        assert_eq!(layout.lines.iter().map(|l| l.baseline).collect::<Vec<_>>(), vec![8.0, 23.0, 38.0]);
+        assert_eq!(layout.lines.iter().map(|l| l.text.clone()).collect::<Vec<_>>(), vec![0 .. 1, 3 .. 3, 4 .. 6]);
+        assert_eq!(layout.size, Size2F(10.0, 40.0));
+        assert!(TextLayout::new("", &[&Mono("")], &TextStyle::new(10.0)).lines.is_empty());
+    }
+}
@@ -0,0 +1,9 @@
+//! Fonts: TrueType/OpenType(CFF) loading, glyph rasterization, the glyph atlas and text layout
+
+mod sfnt;
+mod cff;
+mod atlas;
+mod layout;
+pub use self::sfnt::*;
+pub use self::atlas::*;
+pub use self::layout::*;
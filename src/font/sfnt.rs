@@ -0,0 +1,541 @@
+//! sfnt containers(TrueType/OpenType fonts and collections) and the tables used for rendering
+
+use metrics::*;
+use vector::{Path, Transform2D, FillRule, SdfParams, DistanceField, coverage_mask, sdf_from_shapes, msdf_from_shapes};
+use super::cff::CffOutlines;
+use std::io::prelude::*;
+use std::io::{Result as IOResult, Error as IOError, ErrorKind};
+use std::path::Path as FilePath;
+use std::fs::File;
+use std::rc::Rc;
+
+/// Big endian reader with bounds checks
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Reader<'d> { data: &'d [u8], pub pos: usize }
+impl<'d> Reader<'d>
+{
+    pub fn at(data: &'d [u8], pos: usize) -> Self { Reader { data, pos } }
+    pub fn bytes(&mut self, n: usize) -> IOResult<&'d [u8]>
+    {
+        if self.pos + n > self.data.len() { return Err(invalid_data("Unexpected end of font data")); }
+        self.pos += n;
+        Ok(&self.data[self.pos - n .. self.pos])
+    }
+    pub fn skip(&mut self, n: usize) -> IOResult<()> { self.bytes(n).map(drop) }
+    pub fn u8(&mut self) -> IOResult<u8> { self.bytes(1).map(|b| b[0]) }
+    pub fn u16(&mut self) -> IOResult<u16> { self.bytes(2).map(|b| (b[0] as u16) << 8 | b[1] as u16) }
+    pub fn i16(&mut self) -> IOResult<i16> { self.u16().map(|v| v as i16) }
+    pub fn u32(&mut self) -> IOResult<u32> { self.bytes(4).map(|b| (b[0] as u32) << 24 | (b[1] as u32) << 16 | (b[2] as u32) << 8 | b[3] as u32) }
+    /// Unsigned integer of 1 to 4 bytes(offsets in CFF)
+    pub fn uint(&mut self, size: u8) -> IOResult<u32> { self.bytes(size as usize).map(|b| b.iter().fold(0, |v, &x| v << 8 | x as u32)) }
+}
+pub(super) fn invalid_data(msg: &str) -> IOError { IOError::new(ErrorKind::InvalidData, msg) }
+
+fn tag(t: &[u8; 4]) -> u32 { (t[0] as u32) << 24 | (t[1] as u32) << 16 | (t[2] as u32) << 8 | t[3] as u32 }
+
+/// Character to glyph mapping subtable
+#[derive(Debug, Clone, Copy)]
+enum CharMap { None, SegmentMapping(usize), SegmentedCoverage(usize) }
+#[derive(Debug, Clone)]
+enum Outlines { TrueType { loca: usize, glyf: (usize, usize), long_offsets: bool }, Cff(CffOutlines) }
+
+/// Font-wide metrics in pixels. Descent is positive below the baseline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontMetrics { pub ascent: f32, pub descent: f32, pub line_gap: f32 }
+impl FontMetrics { pub fn line_height(&self) -> f32 { self.ascent + self.descent + self.line_gap } }
+/// Horizontal metrics of a glyph in pixels, y grows downwards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphMetrics
+{
+    pub advance: f32,
+    /// Ink bounds(min, max) relative to the pen position on the baseline. None for blank glyphs
+    pub bounds: Option<(Point2F, Point2F)>
+}
+/// Rasterized coverage of a glyph
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphBitmap
+{
+    pub size: Size2U,
+    /// Position of the top-left pixel relative to the pen position on the baseline
+    pub offset: (i32, i32),
+    /// One byte per pixel, rows from the top
+    pub coverage: Vec<u8>
+}
+/// Distance field of a glyph, padded by the spread on each side
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphField
+{
+    /// Position of the top-left pixel relative to the pen position on the baseline
+    pub offset: (i32, i32),
+    pub field: DistanceField
+}
+
+/// A face of a TrueType or OpenType(CFF outlines) font. Faces of a collection share the file data
+#[derive(Clone)]
+pub struct Font
+{
+    data: Rc<Vec<u8>>,
+    /// Family name from the `name` table(empty if missing)
+    pub family: String,
+    pub units_per_em: u16, pub ascender: i16, pub descender: i16, pub line_gap: i16,
+    glyph_count: u16, cmap: CharMap, hmtx: usize, h_metric_count: u16, outlines: Outlines
+}
+impl ::std::fmt::Debug for Font
+{
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result
+    {
+        write!(f, "Font {{ family: {:?}, units_per_em: {}, glyphs: {} }}", self.family, self.units_per_em, self.glyph_count)
+    }
+}
+impl Font
+{
+    /// Loads a face of a font file. `index` selects the face of a collection(.ttc/.otc) and is 0 for other files
+    pub fn load<P: AsRef<FilePath>>(path: P, index: u32) -> IOResult<Self> { Self::from_data(Rc::new(read_file(path)?), index) }
+    /// Loads all the faces in a font file
+    pub fn load_all<P: AsRef<FilePath>>(path: P) -> IOResult<Vec<Self>>
+    {
+        let data = Rc::new(read_file(path)?);
+        (0 .. Self::face_count(&data)?).map(|n| Self::from_data(data.clone(), n)).collect()
+    }
+    pub fn face_count(data: &[u8]) -> IOResult<u32>
+    {
+        let mut r = Reader::at(data, 0);
+        if r.u32()? == tag(b"ttcf") { r.skip(4)?; r.u32() } else { Ok(1) }
+    }
+    pub fn from_data(data: Rc<Vec<u8>>, index: u32) -> IOResult<Self>
+    {
+        let mut r = Reader::at(&data, 0);
+        let face = if r.u32()? == tag(b"ttcf")
+        {
+            r.skip(4)?;
+            if index >= r.u32()? { return Err(invalid_data("Face index out of range")); }
+            r.skip(index as usize * 4)?;
+            r.u32()? as usize
+        }
+        else if index == 0 { 0 } else { return Err(invalid_data("Face index out of range")); };
+
+        let mut r = Reader::at(&data, face);
+        match r.u32()?
+        {
+            0x0001_0000 | 0x7472_7565 /* true */ | 0x4f54_544f /* OTTO */ => (),
+            _ => return Err(invalid_data("Not a TrueType/OpenType font"))
+        }
+        let table_count = r.u16()?;
+        r.skip(6)?;
+        let mut tables = Vec::with_capacity(table_count as usize);
+        for _ in 0 .. table_count
+        {
+            let (t, _, offset, length) = (r.u32()?, r.u32()?, r.u32()? as usize, r.u32()? as usize);
+            if offset + length > data.len() { return Err(invalid_data("A table is out of the file")); }
+            tables.push((t, offset, length));
+        }
+        let find = |t: &[u8; 4]| tables.iter().find(|e| e.0 == tag(t)).map(|&(_, o, l)| (o, l));
+        let require = |t: &[u8; 4]| find(t).ok_or_else(|| invalid_data(&format!("Missing table: {}", String::from_utf8_lossy(t))));
+
+        let head = require(b"head")?.0;
+        let units_per_em = Reader::at(&data, head + 18).u16()?;
+        if units_per_em == 0 { return Err(invalid_data("Zero unitsPerEm")); }
+        let long_offsets = Reader::at(&data, head + 50).i16()? != 0;
+        let mut r = Reader::at(&data, require(b"hhea")?.0 + 4);
+        let (ascender, descender, line_gap) = (r.i16()?, r.i16()?, r.i16()?);
+        let h_metric_count = Reader::at(&data, require(b"hhea")?.0 + 34).u16()?;
+        if h_metric_count == 0 { return Err(invalid_data("No horizontal metrics")); }
+        let glyph_count = Reader::at(&data, require(b"maxp")?.0 + 4).u16()?;
+        let hmtx = require(b"hmtx")?.0;
+        let cmap = select_cmap(&data, require(b"cmap")?.0)?;
+        let outlines = match (find(b"glyf"), find(b"loca"), find(b"CFF "))
+        {
+            (Some(glyf), Some((loca, _)), _) => Outlines::TrueType { loca, glyf, long_offsets },
+            (_, _, Some((cff, length))) => Outlines::Cff(CffOutlines::parse(&data, (cff, length))?),
+            _ => return Err(invalid_data("No glyph outlines"))
+        };
+        let family = find(b"name").and_then(|(o, _)| family_name(&data, o).ok()).unwrap_or_default();
+
+        Ok(Font { data: data.clone(), family, units_per_em, ascender, descender, line_gap, glyph_count, cmap, hmtx, h_metric_count, outlines })
+    }
+
+    pub fn glyph_count(&self) -> u16 { self.glyph_count }
+    /// Scale from the font units to pixels at the size(pixels per em)
+    pub fn scale(&self, size: f32) -> f32 { size / self.units_per_em as f32 }
+    pub fn metrics(&self, size: f32) -> FontMetrics
+    {
+        let s = self.scale(size);
+        FontMetrics { ascent: self.ascender as f32 * s, descent: -(self.descender as f32) * s, line_gap: self.line_gap as f32 * s }
+    }
+
+    /// The glyph of the character. None if the font does not cover it(mapped to .notdef)
+    pub fn glyph_index(&self, c: char) -> Option<u16>
+    {
+        let g = match self.cmap
+        {
+            CharMap::None => None,
+            CharMap::SegmentMapping(o) => segment_mapping(&self.data, o, c as u32).ok().and_then(|g| g),
+            CharMap::SegmentedCoverage(o) => segmented_coverage(&self.data, o, c as u32).ok().and_then(|g| g)
+        };
+        match g { Some(0) | None => None, Some(g) if g >= self.glyph_count => None, g => g }
+    }
+    pub fn has_glyph(&self, c: char) -> bool { self.glyph_index(c).is_some() }
+
+    /// Advance width in the font units
+    pub fn advance_units(&self, glyph: u16) -> u16
+    {
+        let n = ::std::cmp::min(glyph, self.h_metric_count - 1);
+        Reader::at(&self.data, self.hmtx + n as usize * 4).u16().unwrap_or(0)
+    }
+    pub fn advance(&self, glyph: u16, size: f32) -> f32 { self.advance_units(glyph) as f32 * self.scale(size) }
+    pub fn glyph_metrics(&self, glyph: u16, size: f32) -> GlyphMetrics
+    {
+        let bounds = self.outline(glyph).ok().and_then(|p| p.transformed(&self.pixel_transform(size)).bounds());
+        GlyphMetrics { advance: self.advance(glyph, size), bounds }
+    }
+
+    /// Outline in the font units(y grows upwards). Broken glyphs are errors
+    pub fn outline(&self, glyph: u16) -> IOResult<Path>
+    {
+        if glyph >= self.glyph_count { return Err(invalid_data("Glyph index out of range")); }
+        match self.outlines
+        {
+            Outlines::TrueType { loca, glyf, long_offsets } =>
+            {
+                let mut path = Path::new();
+                glyf_outline(&self.data, loca, glyf, long_offsets, glyph, &Transform2D::IDENTITY, 0, &mut path)?;
+                Ok(path)
+            },
+            Outlines::Cff(ref cff) => cff.outline(&self.data, glyph)
+        }
+    }
+    /// From the font units into pixels at the size, y flipped
+    pub fn pixel_transform(&self, size: f32) -> Transform2D { let s = self.scale(size); Transform2D::scale(s, -s) }
+
+    /// Antialiased coverage of the glyph at the size(pixels per em). Blank glyphs have zero size
+    pub fn rasterize(&self, glyph: u16, size: f32) -> IOResult<GlyphBitmap>
+    {
+        let path = self.outline(glyph)?.transformed(&self.pixel_transform(size));
+        let (min, max) = match path.bounds()
+        {
+            Some(b) => b,
+            None => return Ok(GlyphBitmap { size: Size2U(0, 0), offset: (0, 0), coverage: Vec::new() })
+        };
+        let (x0, y0) = (min.0.floor() as i32, min.1.floor() as i32);
+        let size = Size2U((max.0.ceil() as i32 - x0).max(1) as u32, (max.1.ceil() as i32 - y0).max(1) as u32);
+        let figures = path.transformed(&Transform2D::translate(-x0 as f32, -y0 as f32)).flatten(0.1);
+        Ok(GlyphBitmap { size, offset: (x0, y0), coverage: coverage_mask(&figures, size) })
+    }
+    /// Single or multi-channel distance field of the glyph at the size. Blank glyphs give an empty field
+    pub fn distance_field(&self, glyph: u16, size: f32, spread: f32, multi_channel: bool) -> IOResult<GlyphField>
+    {
+        let path = self.outline(glyph)?.transformed(&self.pixel_transform(size));
+        let channels = if multi_channel { 3 } else { 1 };
+        let (min, max) = match path.bounds()
+        {
+            Some(b) => b,
+            None => return Ok(GlyphField { offset: (0, 0), field: DistanceField { size: Size2U(0, 0), channels, pixels: Vec::new() } })
+        };
+        let pad = spread.ceil() as i32;
+        let (x0, y0) = (min.0.floor() as i32 - pad, min.1.floor() as i32 - pad);
+        let params = SdfParams { size: Size2U((max.0.ceil() as i32 + pad - x0) as u32, (max.1.ceil() as i32 + pad - y0) as u32), spread };
+        let figures = path.transformed(&Transform2D::translate(-x0 as f32, -y0 as f32)).flatten(0.05);
+        let shapes = [(&figures[..], FillRule::NonZero)];
+        let field = if multi_channel { msdf_from_shapes(&shapes, &params) } else { sdf_from_shapes(&shapes, &params) };
+        Ok(GlyphField { offset: (x0, y0), field })
+    }
+}
+
+fn read_file<P: AsRef<FilePath>>(path: P) -> IOResult<Vec<u8>>
+{
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Prefers the full Unicode subtable(format 12) to the BMP one(format 4)
+fn select_cmap(data: &[u8], cmap: usize) -> IOResult<CharMap>
+{
+    let mut r = Reader::at(data, cmap + 2);
+    let mut selected = CharMap::None;
+    for _ in 0 .. r.u16()?
+    {
+        let (platform, encoding, offset) = (r.u16()?, r.u16()?, r.u32()? as usize);
+        let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+        if !unicode { continue; }
+        match Reader::at(data, cmap + offset).u16()?
+        {
+            12 => return Ok(CharMap::SegmentedCoverage(cmap + offset)),
+            4 => selected = CharMap::SegmentMapping(cmap + offset),
+            _ => ()
+        }
+    }
+    Ok(selected)
+}
+fn segment_mapping(data: &[u8], table: usize, c: u32) -> IOResult<Option<u16>>
+{
+    if c > 0xffff { return Ok(None); }
+    let seg_count = Reader::at(data, table + 6).u16()? as usize / 2;
+    let (ends, starts) = (table + 14, table + 16 + seg_count * 2);
+    let (deltas, range_offsets) = (starts + seg_count * 2, starts + seg_count * 4);
+    // the first segment whose end is not less than the character
+    let (mut lo, mut hi) = (0, seg_count);
+    while lo < hi
+    {
+        let mid = (lo + hi) / 2;
+        if (Reader::at(data, ends + mid * 2).u16()? as u32) < c { lo = mid + 1; } else { hi = mid; }
+    }
+    if lo >= seg_count { return Ok(None); }
+    let start = Reader::at(data, starts + lo * 2).u16()? as u32;
+    if c < start { return Ok(None); }
+    let delta = Reader::at(data, deltas + lo * 2).u16()?;
+    let range_offset_at = range_offsets + lo * 2;
+    let range_offset = Reader::at(data, range_offset_at).u16()? as usize;
+    if range_offset == 0 { return Ok(Some((c as u16).wrapping_add(delta))); }
+    let g = Reader::at(data, range_offset_at + range_offset + (c - start) as usize * 2).u16()?;
+    Ok(if g == 0 { None } else { Some(g.wrapping_add(delta)) })
+}
+fn segmented_coverage(data: &[u8], table: usize, c: u32) -> IOResult<Option<u16>>
+{
+    let count = Reader::at(data, table + 12).u32()? as usize;
+    let (mut lo, mut hi) = (0, count);
+    while lo < hi
+    {
+        let mid = (lo + hi) / 2;
+        let mut r = Reader::at(data, table + 16 + mid * 12);
+        let (start, end, glyph) = (r.u32()?, r.u32()?, r.u32()?);
+        if c < start { hi = mid; } else if c > end { lo = mid + 1; } else { return Ok(Some((glyph + c - start) as u16)); }
+    }
+    Ok(None)
+}
+
+/// English(or the first Unicode) family name
+fn family_name(data: &[u8], name: usize) -> IOResult<String>
+{
+    let mut r = Reader::at(data, name + 2);
+    let (count, strings) = (r.u16()?, name + r.u16()? as usize);
+    let mut found = None;
+    for _ in 0 .. count
+    {
+        let (platform, encoding, language, id, length, offset) = (r.u16()?, r.u16()?, r.u16()?, r.u16()?, r.u16()? as usize, r.u16()? as usize);
+        if id != 1 || !(platform == 0 || (platform == 3 && encoding == 1)) { continue; }
+        let utf16: Vec<u16> = Reader::at(data, strings + offset).bytes(length)?.chunks(2).filter(|c| c.len() == 2)
+            .map(|c| (c[0] as u16) << 8 | c[1] as u16).collect();
+        found = Some(String::from_utf16_lossy(&utf16));
+        if platform == 3 && language == 0x0409 { break; }
+    }
+    found.ok_or_else(|| invalid_data("No family name"))
+}
+
+const MAX_COMPONENT_DEPTH: usize = 8;
+/// Appends the TrueType glyph transformed by `t`. Composite glyphs are expanded recursively
+fn glyf_outline(data: &[u8], loca: usize, glyf: (usize, usize), long_offsets: bool, glyph: u16, t: &Transform2D, depth: usize, sink: &mut Path) -> IOResult<()>
+{
+    if depth > MAX_COMPONENT_DEPTH { return Err(invalid_data("Too deeply nested composite glyph")); }
+    let (start, end) = if long_offsets
+    {
+        let mut r = Reader::at(data, loca + glyph as usize * 4);
+        (r.u32()? as usize, r.u32()? as usize)
+    }
+    else
+    {
+        let mut r = Reader::at(data, loca + glyph as usize * 2);
+        (r.u16()? as usize * 2, r.u16()? as usize * 2)
+    };
+    if start >= end { return Ok(()); }
+    if end > glyf.1 { return Err(invalid_data("A glyph is out of the glyf table")); }
+    let glyph_data = &data[glyf.0 + start .. glyf.0 + end];
+    let mut r = Reader::at(glyph_data, 0);
+    let contours = r.i16()?;
+    r.skip(8)?;
+    if contours >= 0
+    {
+        let mut path = Path::new();
+        simple_glyph(&mut r, contours as usize, &mut path)?;
+        sink.commands.extend(path.transformed(t).commands);
+        return Ok(());
+    }
+    loop
+    {
+        let (flags, component) = (r.u16()?, r.u16()?);
+        let (dx, dy) = if flags & 0x0001 != 0 { (r.i16()? as f32, r.i16()? as f32) } else { (r.u8()? as i8 as f32, r.u8()? as i8 as f32) };
+        let f2dot14 = |r: &mut Reader| r.i16().map(|v| v as f32 / 16384.0);
+        let (a, b, c, d) = if flags & 0x0008 != 0 { let s = f2dot14(&mut r)?; (s, 0.0, 0.0, s) }
+            else if flags & 0x0040 != 0 { (f2dot14(&mut r)?, 0.0, 0.0, f2dot14(&mut r)?) }
+            else if flags & 0x0080 != 0 { (f2dot14(&mut r)?, f2dot14(&mut r)?, f2dot14(&mut r)?, f2dot14(&mut r)?) }
+            else { (1.0, 0.0, 0.0, 1.0) };
+        // matching points(without ARGS_ARE_XY_VALUES) are rare in practice and placed without offsets
+        let (e, f) = if flags & 0x0002 != 0 { (dx, dy) } else { (0.0, 0.0) };
+        let local = Transform2D { a, b, c, d, e, f };
+        glyf_outline(data, loca, glyf, long_offsets, component, &local.then(t), depth + 1, sink)?;
+        if flags & 0x0020 == 0 { return Ok(()); }
+    }
+}
+fn simple_glyph(r: &mut Reader, contours: usize, path: &mut Path) -> IOResult<()>
+{
+    let mut ends = Vec::with_capacity(contours);
+    for _ in 0 .. contours { ends.push(r.u16()? as usize); }
+    let point_count = ends.last().map(|&e| e + 1).unwrap_or(0);
+    let instructions = r.u16()? as usize;
+    r.skip(instructions)?;
+    let mut flags = Vec::with_capacity(point_count);
+    while flags.len() < point_count
+    {
+        let f = r.u8()?;
+        flags.push(f);
+        if f & 0x08 != 0 { for _ in 0 .. r.u8()? { flags.push(f); } }
+    }
+    flags.truncate(point_count);
+    let mut coords = |short: u8, same_or_positive: u8| -> IOResult<Vec<f32>>
+    {
+        let mut v = 0i32;
+        flags.iter().map(|&f|
+        {
+            if f & short != 0 { let d = r.u8()? as i32; v += if f & same_or_positive != 0 { d } else { -d }; }
+            else if f & same_or_positive == 0 { v += r.i16()? as i32; }
+            Ok(v as f32)
+        }).collect()
+    };
+    let xs = coords(0x02, 0x10)?;
+    let ys = coords(0x04, 0x20)?;
+
+    let mut begin = 0;
+    for &end in &ends
+    {
+        if end < begin || end >= point_count { return Err(invalid_data("Broken contour end points")); }
+        let points: Vec<(Point2F, bool)> = (begin .. end + 1).map(|i| (Point2F(xs[i], ys[i]), flags[i] & 0x01 != 0)).collect();
+        begin = end + 1;
+        quadratic_contour(&points, path);
+    }
+    Ok(())
+}
+/// Contour of on-curve and off-curve points. Consecutive off-curve points imply on-curve points at their midpoints
+fn quadratic_contour(points: &[(Point2F, bool)], path: &mut Path)
+{
+    if points.is_empty() { return; }
+    let mid = |a: Point2F, b: Point2F| Point2F((a.0 + b.0) * 0.5, (a.1 + b.1) * 0.5);
+    // start on an on-curve point(implied if there is none)
+    let first_on = points.iter().position(|p| p.1);
+    let (start, rotated): (Point2F, Vec<(Point2F, bool)>) = match first_on
+    {
+        Some(n) => (points[n].0, points[n + 1 ..].iter().chain(&points[.. n + 1]).cloned().collect()),
+        None => (mid(points[points.len() - 1].0, points[0].0), points.iter().cloned().chain(Some((mid(points[points.len() - 1].0, points[0].0), true))).collect())
+    };
+    path.move_to(start);
+    let mut control: Option<Point2F> = None;
+    for &(p, on) in &rotated
+    {
+        match (control, on)
+        {
+            (None, true) => { path.line_to(p); },
+            (None, false) => control = Some(p),
+            (Some(c), true) => { path.quad_to(c, p); control = None; },
+            (Some(c), false) => { path.quad_to(c, mid(c, p)); control = Some(p); }
+        }
+    }
+    path.close();
+}
+
+#[cfg(test)]
+pub(super) mod tests
+{
+    use super::*;
+
+    fn put16(v: &mut Vec<u8>, x: u16) { v.extend_from_slice(&[(x >> 8) as u8, x as u8]); }
+    fn put32(v: &mut Vec<u8>, x: u32) { put16(v, (x >> 16) as u16); put16(v, x as u16); }
+
+    /// A TrueType font with 1000 units per em: .notdef, a 600x700 square mapped to 'A', a blank glyph for ' ',
+    /// and a composite of the square scaled by half for 'あ'. Table offsets start at `base`
+    pub fn test_font(base: usize) -> Vec<u8>
+    {
+        let mut head = vec![0; 54];
+        head[18] = (1000 >> 8) as u8; head[19] = (1000 & 0xff) as u8;
+        let mut hhea = vec![0; 36];
+        hhea[4 .. 10].copy_from_slice(&[0x03, 0x20, 0xff, 0x38, 0x00, 0x64]); // 800, -200, 100
+        hhea[35] = 3;
+        let mut maxp = vec![0; 6];
+        maxp[5] = 4;
+        let mut hmtx = Vec::new();
+        for &(adv, lsb) in &[(500, 0), (700, 50), (250, 0)] { put16(&mut hmtx, adv); put16(&mut hmtx, lsb); }
+        put16(&mut hmtx, 0);
+
+        // format 4 with deltas only: ' ' -> 2, 'A' -> 1, 'あ'(U+3042) -> 3
+        let mut cmap = Vec::new();
+        put16(&mut cmap, 0); put16(&mut cmap, 1);
+        put16(&mut cmap, 3); put16(&mut cmap, 1); put32(&mut cmap, 12);
+        let segs: [(u16, u16, u16); 4] = [(0x20, 0x20, 2u16.wrapping_sub(0x20)), (0x41, 0x41, 1u16.wrapping_sub(0x41)),
+            (0x3042, 0x3042, 3u16.wrapping_sub(0x3042)), (0xffff, 0xffff, 1)];
+        put16(&mut cmap, 4); put16(&mut cmap, 16 + 8 * 4); put16(&mut cmap, 0);
+        put16(&mut cmap, 8); put16(&mut cmap, 8); put16(&mut cmap, 2); put16(&mut cmap, 0);
+        for s in &segs { put16(&mut cmap, s.1); }
+        put16(&mut cmap, 0);
+        for s in &segs { put16(&mut cmap, s.0); }
+        for s in &segs { put16(&mut cmap, s.2); }
+        for _ in &segs { put16(&mut cmap, 0); }
+
+        let mut glyf = Vec::new();
+        // the square: contours 1, bbox, end point 3, no instructions, all on-curve, x/y as words
+        put16(&mut glyf, 1); for &v in &[50, 0, 650, 700] { put16(&mut glyf, v); }
+        put16(&mut glyf, 3); put16(&mut glyf, 0);
+        glyf.extend_from_slice(&[0x01, 0x01, 0x01, 0x01]);
+        for &x in &[50i16, 600, 0, -600] { put16(&mut glyf, x as u16); }
+        for &y in &[0i16, 0, 700, 0] { put16(&mut glyf, y as u16); }
+        let composite = glyf.len();
+        put16(&mut glyf, 0xffff); for &v in &[0, 0, 0, 0] { put16(&mut glyf, v); }
+        // ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES | WE_HAVE_A_SCALE, glyph 1, offset (100, 0), scale 0.5
+        put16(&mut glyf, 0x000b); put16(&mut glyf, 1); put16(&mut glyf, 100); put16(&mut glyf, 0); put16(&mut glyf, 0x2000);
+        let mut loca = Vec::new();
+        for &o in &[0, 0, composite, composite, glyf.len()] { put32(&mut loca, o as u32); }
+        head[51] = 1;
+
+        let tables: Vec<(&[u8; 4], Vec<u8>)> = vec![(b"cmap", cmap), (b"glyf", glyf), (b"head", head), (b"hhea", hhea),
+            (b"hmtx", hmtx), (b"loca", loca), (b"maxp", maxp)];
+        let mut font = Vec::new();
+        put32(&mut font, 0x0001_0000); put16(&mut font, tables.len() as u16); put16(&mut font, 0); put16(&mut font, 0); put16(&mut font, 0);
+        let mut offset = base + 12 + 16 * tables.len();
+        for &(t, ref data) in &tables
+        {
+            put32(&mut font, tag(t)); put32(&mut font, 0); put32(&mut font, offset as u32); put32(&mut font, data.len() as u32);
+            offset += (data.len() + 3) & !3;
+        }
+        for &(_, ref data) in &tables { font.extend_from_slice(data); while font.len() % 4 != 0 { font.push(0); } }
+        font
+    }
+
+    #[test] fn truetype()
+    {
+        let font = Font::from_data(Rc::new(test_font(0)), 0).unwrap();
+        assert_eq!(font.glyph_count(), 4);
+        assert_eq!((font.glyph_index('A'), font.glyph_index(' '), font.glyph_index('あ'), font.glyph_index('B')), (Some(1), Some(2), Some(3), None));
+        assert_eq!(font.metrics(20.0), FontMetrics { ascent: 16.0, descent: 4.0, line_gap: 2.0 });
+        // glyphs after the last long metric share its advance
+        assert_eq!((font.advance(1, 20.0), font.advance(3, 20.0)), (14.0, 5.0));
+
+        let m = font.glyph_metrics(1, 20.0);
+        assert_eq!(m.bounds, Some((Point2F(1.0, -14.0), Point2F(13.0, 0.0))));
+        assert_eq!(font.glyph_metrics(2, 20.0).bounds, None);
+        let composite = font.outline(3).unwrap().bounds().unwrap();
+        assert_eq!(composite, (Point2F(125.0, 0.0), Point2F(425.0, 350.0)));
+
+        let b = font.rasterize(1, 20.0).unwrap();
+        assert_eq!((b.size, b.offset), (Size2U(12, 14), (1, -14)));
+        assert!(b.coverage.iter().all(|&c| c == 255));
+        assert_eq!(font.rasterize(2, 20.0).unwrap().size, Size2U(0, 0));
+    }
+
+    #[test] fn collection()
+    {
+        let mut ttc = Vec::new();
+        put32(&mut ttc, tag(b"ttcf")); put32(&mut ttc, 0x0001_0000); put32(&mut ttc, 2); put32(&mut ttc, 20); put32(&mut ttc, 20);
+        ttc.extend(test_font(20));
+        let data = Rc::new(ttc);
+        assert_eq!(Font::face_count(&data).unwrap(), 2);
+        assert_eq!(Font::from_data(data.clone(), 1).unwrap().glyph_index('A'), Some(1));
+        assert!(Font::from_data(data.clone(), 2).is_err());
+        assert!(Font::from_data(Rc::new(b"not a font".to_vec()), 0).is_err());
+    }
+
+    #[test] fn implied_on_curve_points()
+    {
+        let mut path = Path::new();
+        quadratic_contour(&[(Point2F(0.0, 0.0), false), (Point2F(10.0, 0.0), false), (Point2F(10.0, 10.0), true)], &mut path);
+        assert_eq!(path.commands[0], ::vector::PathCommand::MoveTo(Point2F(10.0, 10.0)));
+        assert_eq!(path.commands[1], ::vector::PathCommand::QuadTo(Point2F(0.0, 0.0), Point2F(5.0, 0.0)));
+        assert_eq!(path.commands[2], ::vector::PathCommand::QuadTo(Point2F(10.0, 0.0), Point2F(10.0, 10.0)));
+    }
+}
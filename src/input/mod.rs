@@ -0,0 +1,103 @@
+//! Platform-neutral input events and the queue carrying them from the window server thread to the render loop
+
+pub mod x11;
+#[cfg(feature = "target_x11")] pub mod xlib;
+
+use metrics::*;
+use textedit::TextInputEvent;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCode
+{
+    Escape, Enter, Tab, Backspace, Delete, Insert, Space,
+    Left, Right, Up, Down, Home, End, PageUp, PageDown,
+    /// Function keys(F1 = `F(1)`)
+    F(u8),
+    /// Letters(lowercase), digits and symbols of the key without the modifiers
+    Char(char),
+    /// Platform key code of keys not listed above
+    Other(u32)
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Modifiers { pub shift: bool, pub control: bool, pub alt: bool, pub meta: bool }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerButton { Primary, Secondary, Middle }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent
+{
+    /// Pointer events in the client area(pixels)
+    PointerMove(Point2F), PointerDown(Point2F, PointerButton), PointerUp(Point2F, PointerButton),
+    /// Scrolling at the pointer in pixels. Positive values scroll the contents towards the right/bottom ends
+    Wheel { position: Point2F, delta: (f32, f32) },
+    KeyDown { key: KeyCode, modifiers: Modifiers, repeat: bool },
+    KeyUp { key: KeyCode, modifiers: Modifiers },
+    /// Editing of the focused text field
+    Text(TextInputEvent),
+    /// The window got(true) or lost(false) the keyboard focus
+    Focus(bool),
+    /// New size of the client area
+    Resize(Size2U),
+    /// The window is requested to be closed
+    Close
+}
+impl InputEvent
+{
+    /// Where the pointer events happened
+    pub fn position(&self) -> Option<Point2F>
+    {
+        match *self
+        {
+            InputEvent::PointerMove(p) | InputEvent::PointerDown(p, _) | InputEvent::PointerUp(p, _) | InputEvent::Wheel { position: p, .. } => Some(p),
+            _ => None
+        }
+    }
+    /// The escape key(going back to the previous scene or closing dialogs)
+    pub fn is_back(&self) -> bool { match *self { InputEvent::KeyDown { key: KeyCode::Escape, .. } => true, _ => false } }
+}
+
+/// Events posted from the window server thread and taken by the render loop once per frame
+pub struct InputQueue(Mutex<Vec<InputEvent>>);
+impl InputQueue
+{
+    pub fn new() -> Self { InputQueue(Mutex::new(Vec::new())) }
+    /// Consecutive moves of the pointer and resizes are merged into the last one
+    pub fn post(&self, event: InputEvent)
+    {
+        let mut q = self.0.lock().unwrap();
+        let merged = match (q.last(), &event)
+        {
+            (Some(&InputEvent::PointerMove(_)), &InputEvent::PointerMove(_)) | (Some(&InputEvent::Resize(_)), &InputEvent::Resize(_)) => true,
+            _ => false
+        };
+        if merged { q.pop(); }
+        q.push(event);
+    }
+    /// The events posted since the last call, in the order of posting
+    pub fn take(&self) -> Vec<InputEvent> { ::std::mem::replace(&mut *self.0.lock().unwrap(), Vec::new()) }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test] fn queue()
+    {
+        let q = InputQueue::new();
+        q.post(InputEvent::PointerMove(Point2F(1.0, 1.0)));
+        q.post(InputEvent::PointerMove(Point2F(2.0, 1.0)));
+        q.post(InputEvent::PointerDown(Point2F(2.0, 1.0), PointerButton::Primary));
+        q.post(InputEvent::PointerMove(Point2F(3.0, 1.0)));
+        q.post(InputEvent::Resize(Size2U(640, 480)));
+        q.post(InputEvent::Resize(Size2U(800, 600)));
+        assert_eq!(q.take(), vec![InputEvent::PointerMove(Point2F(2.0, 1.0)), InputEvent::PointerDown(Point2F(2.0, 1.0), PointerButton::Primary),
+            InputEvent::PointerMove(Point2F(3.0, 1.0)), InputEvent::Resize(Size2U(800, 600))]);
+        assert!(q.take().is_empty());
+
+        let escape = InputEvent::KeyDown { key: KeyCode::Escape, modifiers: Modifiers::default(), repeat: false };
+        assert!(escape.is_back() && escape.position().is_none());
+        assert_eq!(InputEvent::Wheel { position: Point2F(4.0, 5.0), delta: (0.0, 48.0) }.position(), Some(Point2F(4.0, 5.0)));
+    }
+}
@@ -0,0 +1,239 @@
+//! Translation of X events into input events. The fields are taken from the decoded events
+//! (KeyPress/KeyRelease with the keysym looked up, ButtonPress/ButtonRelease, MotionNotify, FocusIn/FocusOut, ConfigureNotify).
+//! `Translator` posts them as the event source(`input::xlib`) decodes them
+
+use metrics::*;
+use textedit::TextInputEvent;
+use textedit::x11::{translate_key, SHIFT_MASK, CONTROL_MASK};
+use super::{InputEvent, InputQueue, KeyCode, Modifiers, PointerButton};
+
+pub const MOD1_MASK: u16 = 1 << 3;
+pub const MOD4_MASK: u16 = 1 << 6;
+/// Pixels scrolled by a notch of the wheel
+pub const WHEEL_STEP: f32 = 48.0;
+
+pub fn modifiers(state: u16) -> Modifiers
+{
+    Modifiers
+    {
+        shift: state & SHIFT_MASK != 0, control: state & CONTROL_MASK != 0, alt: state & MOD1_MASK != 0, meta: state & MOD4_MASK != 0
+    }
+}
+pub fn key_code(keysym: u32) -> KeyCode
+{
+    match keysym
+    {
+        0xff1b => KeyCode::Escape,
+        0xff0d | 0xff8d => KeyCode::Enter,
+        0xff09 => KeyCode::Tab,
+        0xff08 => KeyCode::Backspace,
+        0xffff => KeyCode::Delete,
+        0xff63 => KeyCode::Insert,
+        0x20 => KeyCode::Space,
+        0xff51 => KeyCode::Left,
+        0xff52 => KeyCode::Up,
+        0xff53 => KeyCode::Right,
+        0xff54 => KeyCode::Down,
+        0xff50 => KeyCode::Home,
+        0xff57 => KeyCode::End,
+        0xff55 => KeyCode::PageUp,
+        0xff56 => KeyCode::PageDown,
+        0xffbe ... 0xffc9 => KeyCode::F((keysym - 0xffbe + 1) as u8),
+        0x41 ... 0x5a => KeyCode::Char((keysym as u8 + 0x20) as char),
+        0x21 ... 0x7e => KeyCode::Char(keysym as u8 as char),
+        _ => KeyCode::Other(keysym)
+    }
+}
+
+/// KeyPress(with the text input for the focused text field) or KeyRelease.
+/// `repeat` is known from a release followed by a press of the same key at the same time
+pub fn key(keysym: u32, state: u16, pressed: bool, repeat: bool) -> Vec<InputEvent>
+{
+    let (key, modifiers) = (key_code(keysym), modifiers(state));
+    if !pressed { return vec![InputEvent::KeyUp { key, modifiers }]; }
+    let mut events = vec![InputEvent::KeyDown { key, modifiers, repeat }];
+    if !modifiers.alt && !modifiers.meta { events.extend(translate_key(keysym, state).map(InputEvent::Text)); }
+    events
+}
+/// ButtonPress/ButtonRelease. Buttons 4-7 are the wheel(only the presses scroll); None for the other buttons
+pub fn button(detail: u8, x: i16, y: i16, pressed: bool) -> Option<InputEvent>
+{
+    let position = Point2F(x as f32, y as f32);
+    let pointer = |b| Some(if pressed { InputEvent::PointerDown(position, b) } else { InputEvent::PointerUp(position, b) });
+    let wheel = |dx, dy| if pressed { Some(InputEvent::Wheel { position, delta: (dx * WHEEL_STEP, dy * WHEEL_STEP) }) } else { None };
+    match detail
+    {
+        1 => pointer(PointerButton::Primary),
+        2 => pointer(PointerButton::Middle),
+        3 => pointer(PointerButton::Secondary),
+        4 => wheel(0.0, -1.0),
+        5 => wheel(0.0, 1.0),
+        6 => wheel(-1.0, 0.0),
+        7 => wheel(1.0, 0.0),
+        _ => None
+    }
+}
+/// MotionNotify
+pub fn motion(x: i16, y: i16) -> InputEvent { InputEvent::PointerMove(Point2F(x as f32, y as f32)) }
+/// ConfigureNotify. None if the size is not changed
+pub fn configure(width: u16, height: u16, current: Size2U) -> Option<InputEvent>
+{
+    let size = Size2U(width as u32, height as u32);
+    if size == current { None } else { Some(InputEvent::Resize(size)) }
+}
+
+/// X events decoded by the event source
+#[derive(Debug, Clone, PartialEq)]
+pub enum XInput
+{
+    /// KeyPress/KeyRelease not filtered by the input method: the keysym(0 for NoSymbol) and the text looked up.
+    /// Text committed by the input method comes with NoSymbol
+    Key { keycode: u8, keysym: u32, state: u16, time: u32, pressed: bool, text: Option<String> },
+    /// From the preedit callbacks of the input method(`textedit::x11::Preedit`)
+    Text(TextInputEvent),
+    /// ButtonPress/ButtonRelease and MotionNotify in the window coordinates
+    Button { detail: u8, x: i16, y: i16, pressed: bool }, Motion { x: i16, y: i16 },
+    /// ConfigureNotify(also sent when the window is moved)
+    Configure { width: u16, height: u16 },
+    /// FocusIn(true)/FocusOut(false)
+    Focus(bool)
+}
+
+/// Posts the input events of the X events into the queue.
+/// A KeyRelease is held until the next event: a KeyPress of the same key at the same time follows it when the key repeats
+pub struct Translator
+{
+    held: Option<(u8, u32, u32, u16)>,
+    /// The last posted size of the window(the first ConfigureNotify is always posted)
+    size: Size2U
+}
+impl Translator
+{
+    pub fn new() -> Self { Translator { held: None, size: Size2U(0, 0) } }
+    pub fn post(&mut self, queue: &InputQueue, event: XInput)
+    {
+        let repeat = match (self.held, &event)
+        {
+            (Some((keycode, time, ..)), &XInput::Key { keycode: k, time: t, pressed: true, .. }) => keycode == k && time == t,
+            _ => false
+        };
+        if repeat { self.held = None; } else { self.flush(queue); }
+        match event
+        {
+            XInput::Key { keycode, keysym, state, time, pressed: false, .. } => self.held = Some((keycode, time, keysym, state)),
+            XInput::Key { keysym: 0, text: Some(text), .. } => if !text.is_empty() { queue.post(InputEvent::Text(TextInputEvent::Commit(text))); },
+            XInput::Key { keysym: 0, .. } => (),
+            XInput::Key { keysym, state, text, .. } =>
+            {
+                let modifiers = modifiers(state);
+                let typed = text.and_then(|t| if !t.is_empty() && !t.chars().any(char::is_control) { Some(t) } else { None });
+                match typed
+                {
+                    // the text looked up with the layout(e.g. kana keysyms): editing keys and shortcuts are translated from the keysym
+                    Some(t) if !modifiers.control && !modifiers.alt && !modifiers.meta =>
+                    {
+                        queue.post(InputEvent::KeyDown { key: key_code(keysym), modifiers, repeat });
+                        queue.post(InputEvent::Text(TextInputEvent::Text(t)));
+                    },
+                    _ => for e in key(keysym, state, true, repeat) { queue.post(e); }
+                }
+            },
+            XInput::Text(e) => queue.post(InputEvent::Text(e)),
+            XInput::Button { detail, x, y, pressed } => if let Some(e) = button(detail, x, y, pressed) { queue.post(e); },
+            XInput::Motion { x, y } => queue.post(motion(x, y)),
+            XInput::Configure { width, height } => if let Some(e) = configure(width, height, self.size)
+            {
+                self.size = Size2U(width as _, height as _);
+                queue.post(e);
+            },
+            XInput::Focus(f) => queue.post(InputEvent::Focus(f))
+        }
+    }
+    /// Posts the held KeyRelease. Called when no events are left to read
+    pub fn flush(&mut self, queue: &InputQueue)
+    {
+        if let Some((_, _, keysym, state)) = self.held.take() { for e in key(keysym, state, false, false) { queue.post(e); } }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use textedit::{EditKey, Composition};
+
+    #[test] fn keys()
+    {
+        let plain = Modifiers::default();
+        assert_eq!(key(0x41, SHIFT_MASK, true, false), vec![
+            InputEvent::KeyDown { key: KeyCode::Char('a'), modifiers: Modifiers { shift: true, .. plain }, repeat: false },
+            InputEvent::Text(TextInputEvent::Text("A".to_owned()))
+        ]);
+        assert_eq!(key(0xff51, CONTROL_MASK, true, true)[1], InputEvent::Text(TextInputEvent::Key { key: EditKey::Left, extend: false, word: true }));
+        assert_eq!(key(0xffc9, 0, true, false), vec![InputEvent::KeyDown { key: KeyCode::F(12), modifiers: plain, repeat: false }]);
+        assert_eq!(key(0x61, MOD1_MASK, true, false).len(), 1);
+        assert_eq!(key(0xff1b, 0, false, false), vec![InputEvent::KeyUp { key: KeyCode::Escape, modifiers: plain }]);
+    }
+    #[test] fn pointer()
+    {
+        assert_eq!(button(1, 10, 20, true), Some(InputEvent::PointerDown(Point2F(10.0, 20.0), PointerButton::Primary)));
+        assert_eq!(button(3, 10, 20, false), Some(InputEvent::PointerUp(Point2F(10.0, 20.0), PointerButton::Secondary)));
+        assert_eq!(button(5, 0, 0, true), Some(InputEvent::Wheel { position: Point2F(0.0, 0.0), delta: (0.0, WHEEL_STEP) }));
+        assert_eq!(button(5, 0, 0, false), None);
+        assert_eq!(button(9, 0, 0, true), None);
+        assert_eq!(configure(640, 480, Size2U(640, 480)), None);
+    }
+    #[test] fn posting()
+    {
+        let (q, mut t) = (InputQueue::new(), Translator::new());
+        let press = |keycode, keysym, time| XInput::Key { keycode, keysym, state: 0, time, pressed: true, text: None };
+        let release = |keycode, keysym, time| XInput::Key { keycode, keysym, state: 0, time, pressed: false, text: None };
+        let (down, up) = (|key, repeat| InputEvent::KeyDown { key, modifiers: Modifiers::default(), repeat },
+            |key| InputEvent::KeyUp { key, modifiers: Modifiers::default() });
+        t.post(&q, press(38, 0x61, 10));
+        // auto-repeat: the release at the time of the next press is dropped
+        t.post(&q, release(38, 0x61, 50));
+        t.post(&q, press(38, 0x61, 50));
+        t.post(&q, release(38, 0x61, 60));
+        assert_eq!(q.take(), vec![down(KeyCode::Char('a'), false), InputEvent::Text(TextInputEvent::Text("a".to_owned())),
+            down(KeyCode::Char('a'), true), InputEvent::Text(TextInputEvent::Text("a".to_owned()))]);
+        t.flush(&q);
+        assert_eq!(q.take(), vec![up(KeyCode::Char('a'))]);
+
+        // the input method: the composition, then the committed text with NoSymbol
+        let composing = TextInputEvent::Preedit(Composition { text: "がく".to_owned(), cursor: 6 });
+        t.post(&q, XInput::Text(composing.clone()));
+        t.post(&q, XInput::Text(TextInputEvent::Preedit(Composition::default())));
+        t.post(&q, XInput::Key { keycode: 0, keysym: 0, state: 0, time: 70, pressed: true, text: Some("学籍".to_owned()) });
+        t.post(&q, release(36, 0xff0d, 80));
+        t.post(&q, press(38, 0x61, 90));
+        assert_eq!(q.take(), vec![InputEvent::Text(composing), InputEvent::Text(TextInputEvent::Preedit(Composition::default())),
+            InputEvent::Text(TextInputEvent::Commit("学籍".to_owned())), up(KeyCode::Enter),
+            down(KeyCode::Char('a'), false), InputEvent::Text(TextInputEvent::Text("a".to_owned()))]);
+
+        // the looked up text is typed(the kana layout), but editing keys and shortcuts come from the keysyms
+        let typed = |keycode, keysym, state, text: &str| XInput::Key { keycode, keysym, state, time: 95, pressed: true, text: Some(text.to_owned()) };
+        t.post(&q, typed(40, 0x4c1, 0, "チ"));
+        t.post(&q, typed(22, 0xff08, 0, "\u{8}"));
+        t.post(&q, typed(38, 0x61, CONTROL_MASK, "\u{1}"));
+        assert_eq!(q.take(), vec![down(KeyCode::Other(0x4c1), false), InputEvent::Text(TextInputEvent::Text("チ".to_owned())),
+            down(KeyCode::Backspace, false), InputEvent::Text(TextInputEvent::Key { key: EditKey::Backspace, extend: false, word: false }),
+            InputEvent::KeyDown { key: KeyCode::Char('a'), modifiers: Modifiers { control: true, .. Modifiers::default() }, repeat: false },
+            InputEvent::Text(TextInputEvent::Key { key: EditKey::SelectAll, extend: false, word: false })]);
+
+        // the pointer, the window structure and the focus: a held release is posted before them
+        t.post(&q, release(38, 0x61, 100));
+        t.post(&q, XInput::Motion { x: 10, y: 20 });
+        t.post(&q, XInput::Motion { x: 12, y: 20 });
+        t.post(&q, XInput::Button { detail: 1, x: 12, y: 20, pressed: true });
+        t.post(&q, XInput::Button { detail: 4, x: 12, y: 20, pressed: false });
+        t.post(&q, XInput::Configure { width: 960, height: 540 });
+        t.post(&q, XInput::Focus(false));
+        // moves of the window keep the size
+        t.post(&q, XInput::Configure { width: 960, height: 540 });
+        t.post(&q, XInput::Focus(true));
+        assert_eq!(q.take(), vec![up(KeyCode::Char('a')), InputEvent::PointerMove(Point2F(12.0, 20.0)),
+            InputEvent::PointerDown(Point2F(12.0, 20.0), PointerButton::Primary), InputEvent::Resize(Size2U(960, 540)),
+            InputEvent::Focus(false), InputEvent::Focus(true)]);
+    }
+}
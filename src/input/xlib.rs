@@ -0,0 +1,352 @@
+//! The X11 event source: a second connection to the server selecting the input of the main window(created and owned by `ws_common`),
+//! with an input method context whose preedit callbacks drive the composition. The decoded events are posted by `Translator`.
+//! Only one client can select the button presses of a window: the pointer buttons are not read if another client does.
+//! `init` is called on the main thread before any other thread starts
+
+use libc::{self, c_char, c_int, c_uint, c_long, c_ulong, c_ushort, c_void};
+use textedit::TextInputEvent;
+use textedit::x11::Preedit;
+use super::InputQueue;
+use super::x11::{Translator, XInput};
+use std::ffi::CStr;
+use std::ptr::{null, null_mut};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::thread;
+use std::time::Duration;
+
+enum Display {}
+enum XIMRec {}
+enum XICRec {}
+type Window = c_ulong;
+type Atom = c_ulong;
+type KeySym = c_ulong;
+type XPointer = *mut c_char;
+type XIM = *mut XIMRec;
+type XIC = *mut XICRec;
+type XErrorHandler = extern "C" fn(*mut Display, *mut XErrorEvent) -> c_int;
+
+/// Large enough for every event(`XEvent` is a union of 24 longs). Fields are read from the structures of the event types
+#[repr(C)] struct XEvent { pad: [c_long; 24] }
+#[repr(C)] #[allow(dead_code)] struct XAnyEvent { type_: c_int, serial: c_ulong, send_event: c_int, display: *mut Display, window: Window }
+#[repr(C)] #[allow(dead_code)] struct XKeyEvent
+{
+    type_: c_int, serial: c_ulong, send_event: c_int, display: *mut Display, window: Window, root: Window, subwindow: Window,
+    time: c_ulong, x: c_int, y: c_int, x_root: c_int, y_root: c_int, state: c_uint, keycode: c_uint, same_screen: c_int
+}
+#[repr(C)] #[allow(dead_code)] struct XButtonEvent
+{
+    type_: c_int, serial: c_ulong, send_event: c_int, display: *mut Display, window: Window, root: Window, subwindow: Window,
+    time: c_ulong, x: c_int, y: c_int, x_root: c_int, y_root: c_int, state: c_uint, button: c_uint, same_screen: c_int
+}
+#[repr(C)] #[allow(dead_code)] struct XMotionEvent
+{
+    type_: c_int, serial: c_ulong, send_event: c_int, display: *mut Display, window: Window, root: Window, subwindow: Window,
+    time: c_ulong, x: c_int, y: c_int, x_root: c_int, y_root: c_int, state: c_uint, is_hint: c_char, same_screen: c_int
+}
+#[repr(C)] #[allow(dead_code)] struct XConfigureEvent
+{
+    type_: c_int, serial: c_ulong, send_event: c_int, display: *mut Display, event: Window, window: Window,
+    x: c_int, y: c_int, width: c_int, height: c_int, border_width: c_int, above: Window, override_redirect: c_int
+}
+#[repr(C)] #[allow(dead_code)] struct XErrorEvent
+{
+    type_: c_int, display: *mut Display, resourceid: c_ulong, serial: c_ulong, error_code: u8, request_code: u8, minor_code: u8
+}
+/// The preedit start callback returns the maximum length of the composition; the others return nothing(the value is ignored)
+#[repr(C)] #[allow(dead_code)] struct XIMCallback { client_data: XPointer, callback: extern "C" fn(XIC, XPointer, XPointer) -> c_int }
+#[repr(C)] #[allow(dead_code)] struct XIMText { length: c_ushort, feedback: *mut c_ulong, encoding_is_wchar: c_int, string: *mut c_char }
+#[repr(C)] struct XIMPreeditDrawCallbackStruct { caret: c_int, chg_first: c_int, chg_length: c_int, text: *mut XIMText }
+#[repr(C)] #[allow(dead_code)] struct XIMPreeditCaretCallbackStruct { position: c_int, direction: c_int, style: c_int }
+
+const KEY_PRESS: c_int = 2;
+const KEY_RELEASE: c_int = 3;
+const BUTTON_PRESS: c_int = 4;
+const BUTTON_RELEASE: c_int = 5;
+const MOTION_NOTIFY: c_int = 6;
+const FOCUS_IN: c_int = 9;
+const FOCUS_OUT: c_int = 10;
+const CONFIGURE_NOTIFY: c_int = 22;
+const KEY_PRESS_MASK: c_long = 1 << 0;
+const KEY_RELEASE_MASK: c_long = 1 << 1;
+const BUTTON_PRESS_MASK: c_long = 1 << 2;
+const BUTTON_RELEASE_MASK: c_long = 1 << 3;
+const POINTER_MOTION_MASK: c_long = 1 << 6;
+const STRUCTURE_NOTIFY_MASK: c_long = 1 << 17;
+const FOCUS_CHANGE_MASK: c_long = 1 << 21;
+const BAD_ACCESS: u8 = 10;
+const XA_CARDINAL: Atom = 6;
+const X_BUFFER_OVERFLOW: c_int = -1;
+const X_LOOKUP_CHARS: c_int = 2;
+const X_LOOKUP_KEYSYM: c_int = 3;
+const X_LOOKUP_BOTH: c_int = 4;
+const XIM_PREEDIT_CALLBACKS: c_ulong = 0x0002;
+const XIM_PREEDIT_NOTHING: c_ulong = 0x0008;
+const XIM_STATUS_NOTHING: c_ulong = 0x0400;
+const XIM_ABSOLUTE_POSITION: c_int = 10;
+const XN_INPUT_STYLE: &'static [u8] = b"inputStyle\0";
+const XN_CLIENT_WINDOW: &'static [u8] = b"clientWindow\0";
+const XN_FOCUS_WINDOW: &'static [u8] = b"focusWindow\0";
+const XN_PREEDIT_ATTRIBUTES: &'static [u8] = b"preeditAttributes\0";
+const XN_PREEDIT_START_CALLBACK: &'static [u8] = b"preeditStartCallback\0";
+const XN_PREEDIT_DONE_CALLBACK: &'static [u8] = b"preeditDoneCallback\0";
+const XN_PREEDIT_DRAW_CALLBACK: &'static [u8] = b"preeditDrawCallback\0";
+const XN_PREEDIT_CARET_CALLBACK: &'static [u8] = b"preeditCaretCallback\0";
+
+#[link(name = "X11")] extern "C"
+{
+    fn XOpenDisplay(name: *const c_char) -> *mut Display;
+    fn XDefaultRootWindow(display: *mut Display) -> Window;
+    fn XQueryTree(display: *mut Display, w: Window, root: *mut Window, parent: *mut Window, children: *mut *mut Window, count: *mut c_uint) -> c_int;
+    fn XFetchName(display: *mut Display, w: Window, name: *mut *mut c_char) -> c_int;
+    fn XInternAtom(display: *mut Display, name: *const c_char, only_if_exists: c_int) -> Atom;
+    fn XGetWindowProperty(display: *mut Display, w: Window, property: Atom, offset: c_long, length: c_long, delete: c_int, req_type: Atom,
+        actual_type: *mut Atom, actual_format: *mut c_int, count: *mut c_ulong, bytes_after: *mut c_ulong, data: *mut *mut u8) -> c_int;
+    fn XFree(data: *mut c_void) -> c_int;
+    fn XSelectInput(display: *mut Display, w: Window, mask: c_long) -> c_int;
+    fn XSync(display: *mut Display, discard: c_int) -> c_int;
+    fn XSetErrorHandler(handler: Option<XErrorHandler>) -> Option<XErrorHandler>;
+    fn XInitThreads() -> c_int;
+    fn XNextEvent(display: *mut Display, event: *mut XEvent) -> c_int;
+    fn XPending(display: *mut Display) -> c_int;
+    fn XFilterEvent(event: *mut XEvent, w: Window) -> c_int;
+    fn XLookupString(event: *mut XKeyEvent, buffer: *mut c_char, bytes: c_int, keysym: *mut KeySym, status: *mut c_void) -> c_int;
+    fn XSetLocaleModifiers(modifiers: *const c_char) -> *mut c_char;
+    fn XOpenIM(display: *mut Display, db: *mut c_void, res_name: *mut c_char, res_class: *mut c_char) -> XIM;
+    fn XCreateIC(im: XIM, ...) -> XIC;
+    fn XVaCreateNestedList(unused: c_int, ...) -> *mut c_void;
+    fn XSetICFocus(ic: XIC);
+    fn XUnsetICFocus(ic: XIC);
+    fn Xutf8LookupString(ic: XIC, event: *mut XKeyEvent, buffer: *mut c_char, bytes: c_int, keysym: *mut KeySym, status: *mut c_int) -> c_int;
+}
+
+/// Makes Xlib usable from multiple threads and sets the locale of the input methods.
+/// Must be called before any other call of Xlib and before any other thread starts(`setlocale` is not thread-safe)
+pub fn init()
+{
+    unsafe
+    {
+        if XInitThreads() == 0 { println!("!! Xlib does not support threads: the input may be corrupted"); }
+        libc::setlocale(libc::LC_CTYPE, b"\0".as_ptr() as _);
+        XSetLocaleModifiers(b"\0".as_ptr() as _);
+    }
+}
+/// Starts reading the input of the main window(found by the title) in a thread. Nothing is posted without the X server or the window
+pub fn spawn(title: &'static str, queue: &'static InputQueue)
+{
+    let spawned = thread::Builder::new().name("Input Source".into()).spawn(move || unsafe { run(title, queue) });
+    if let Err(e) = spawned { println!("!! Failed to spawn the input thread: {}", e); }
+}
+
+/// The composition updated by the preedit callbacks, and the events they made
+struct Composing { preedit: Preedit, events: Vec<TextInputEvent> }
+
+unsafe fn run(title: &str, queue: &InputQueue)
+{
+    let display = XOpenDisplay(null());
+    if display.is_null() { println!("!! Input is not available: failed to connect to the X server"); return; }
+    // the window is created by the main thread: looked up until it appears(for `WINDOW_WAIT_COUNT` * 50 ms)
+    const WINDOW_WAIT_COUNT: usize = 100;
+    let mut window = None;
+    for _ in 0 .. WINDOW_WAIT_COUNT
+    {
+        window = find_window(display, title);
+        if window.is_some() { break; }
+        thread::sleep(Duration::from_millis(50));
+    }
+    let window = match window { Some(w) => w, None => { println!("!! Input is not available: the main window({}) is not found", title); return; } };
+    select_input(display, window);
+    // the callbacks refer the composition while the context lives(until the process ends)
+    let mut composing = box Composing { preedit: Preedit::default(), events: Vec::new() };
+    let ic = create_ic(display, window, &mut *composing);
+
+    let mut translator = Translator::new();
+    let mut event = XEvent { pad: [0; 24] };
+    loop
+    {
+        XNextEvent(display, &mut event);
+        // keys composing the text are taken by the input method
+        let filtered = XFilterEvent(&mut event, 0) != 0;
+        for e in composing.events.drain(..) { translator.post(queue, XInput::Text(e)); }
+        if !filtered
+        {
+            match (*(&event as *const _ as *const XAnyEvent)).type_
+            {
+                KEY_PRESS | KEY_RELEASE => translator.post(queue, decode_key(&mut *(&mut event as *mut _ as *mut XKeyEvent), ic)),
+                BUTTON_PRESS | BUTTON_RELEASE =>
+                {
+                    let b = &*(&event as *const _ as *const XButtonEvent);
+                    translator.post(queue, XInput::Button { detail: b.button as u8, x: b.x as i16, y: b.y as i16, pressed: b.type_ == BUTTON_PRESS });
+                },
+                MOTION_NOTIFY =>
+                {
+                    let m = &*(&event as *const _ as *const XMotionEvent);
+                    translator.post(queue, XInput::Motion { x: m.x as i16, y: m.y as i16 });
+                },
+                CONFIGURE_NOTIFY =>
+                {
+                    let c = &*(&event as *const _ as *const XConfigureEvent);
+                    translator.post(queue, XInput::Configure { width: c.width as u16, height: c.height as u16 });
+                },
+                t @ FOCUS_IN | t @ FOCUS_OUT =>
+                {
+                    if !ic.is_null() { if t == FOCUS_IN { XSetICFocus(ic); } else { XUnsetICFocus(ic); } }
+                    translator.post(queue, XInput::Focus(t == FOCUS_IN));
+                },
+                _ => ()
+            }
+        }
+        if XPending(display) == 0 { translator.flush(queue); }
+    }
+}
+
+/// The error code of the last failed request of the connection(0 if none)
+static LAST_ERROR: AtomicUsize = ATOMIC_USIZE_INIT;
+extern "C" fn record_error(_display: *mut Display, event: *mut XErrorEvent) -> c_int
+{
+    LAST_ERROR.store(unsafe { (*event).error_code } as usize, Ordering::Release);
+    0
+}
+/// Selects the keys, the pointer, the focus and the structure of the window.
+/// The selection fails(BadAccess) without the pointer buttons if the window server already selected them
+unsafe fn select_input(display: *mut Display, window: Window)
+{
+    const BASE_MASK: c_long = KEY_PRESS_MASK | KEY_RELEASE_MASK | POINTER_MOTION_MASK | STRUCTURE_NOTIFY_MASK | FOCUS_CHANGE_MASK;
+    // the default handler exits the process: replaced only while the selection is made
+    LAST_ERROR.store(0, Ordering::Release);
+    let previous = XSetErrorHandler(Some(record_error));
+    XSelectInput(display, window, BASE_MASK | BUTTON_PRESS_MASK | BUTTON_RELEASE_MASK);
+    XSync(display, 0);
+    if LAST_ERROR.load(Ordering::Acquire) == BAD_ACCESS as usize
+    {
+        println!("!! The pointer buttons are not read: they are selected by another client");
+        XSelectInput(display, window, BASE_MASK);
+        XSync(display, 0);
+    }
+    XSetErrorHandler(previous);
+}
+
+/// The top-level window of this process(`_NET_WM_PID`) with the title, or any window with the title if the pid is not set
+unsafe fn find_window(display: *mut Display, title: &str) -> Option<Window>
+{
+    let pid_atom = XInternAtom(display, b"_NET_WM_PID\0".as_ptr() as _, 1);
+    let (mut stack, mut named) = (vec![XDefaultRootWindow(display)], None);
+    while let Some(w) = stack.pop()
+    {
+        let mut name = null_mut();
+        if XFetchName(display, w, &mut name) != 0 && !name.is_null()
+        {
+            let matches = CStr::from_ptr(name).to_bytes() == title.as_bytes();
+            XFree(name as _);
+            if matches
+            {
+                match window_pid(display, w, pid_atom)
+                {
+                    Some(pid) if pid == libc::getpid() as c_ulong => return Some(w),
+                    Some(_) => (),
+                    None => if named.is_none() { named = Some(w); }
+                }
+            }
+        }
+        let (mut root, mut parent, mut children, mut count) = (0, 0, null_mut(), 0);
+        if XQueryTree(display, w, &mut root, &mut parent, &mut children, &mut count) != 0 && !children.is_null()
+        {
+            stack.extend_from_slice(::std::slice::from_raw_parts(children, count as usize));
+            XFree(children as _);
+        }
+    }
+    named
+}
+unsafe fn window_pid(display: *mut Display, w: Window, pid_atom: Atom) -> Option<c_ulong>
+{
+    if pid_atom == 0 { return None; }
+    let (mut actual_type, mut format, mut count, mut remaining, mut data) = (0, 0, 0, 0, null_mut());
+    if XGetWindowProperty(display, w, pid_atom, 0, 1, 0, XA_CARDINAL, &mut actual_type, &mut format, &mut count, &mut remaining, &mut data) != 0
+        || data.is_null()
+    {
+        return None;
+    }
+    // 32-bit properties are returned as longs
+    let pid = if format == 32 && count == 1 { Some(*(data as *const c_ulong)) } else { None };
+    XFree(data as _);
+    pid
+}
+
+/// The context with the preedit callbacks, or the one drawing the composition by itself if the input method does not call back.
+/// Null without input methods(keys are looked up without them)
+unsafe fn create_ic(display: *mut Display, window: Window, composing: *mut Composing) -> XIC
+{
+    // the locale is set by `init`
+    let im = XOpenIM(display, null_mut(), null_mut(), null_mut());
+    if im.is_null() { println!("!! No input method is available"); return null_mut(); }
+    let data = composing as XPointer;
+    let c = &*Box::into_raw(box [
+        XIMCallback { client_data: data, callback: preedit_start }, XIMCallback { client_data: data, callback: preedit_done },
+        XIMCallback { client_data: data, callback: preedit_draw }, XIMCallback { client_data: data, callback: preedit_caret }
+    ]);
+    let attributes = XVaCreateNestedList(0,
+        XN_PREEDIT_START_CALLBACK.as_ptr(), &c[0] as *const XIMCallback, XN_PREEDIT_DONE_CALLBACK.as_ptr(), &c[1] as *const XIMCallback,
+        XN_PREEDIT_DRAW_CALLBACK.as_ptr(), &c[2] as *const XIMCallback, XN_PREEDIT_CARET_CALLBACK.as_ptr(), &c[3] as *const XIMCallback,
+        null::<c_char>());
+    let ic = XCreateIC(im, XN_INPUT_STYLE.as_ptr(), XIM_PREEDIT_CALLBACKS | XIM_STATUS_NOTHING,
+        XN_CLIENT_WINDOW.as_ptr(), window, XN_FOCUS_WINDOW.as_ptr(), window, XN_PREEDIT_ATTRIBUTES.as_ptr(), attributes, null::<c_char>());
+    XFree(attributes);
+    if !ic.is_null() { return ic; }
+    XCreateIC(im, XN_INPUT_STYLE.as_ptr(), XIM_PREEDIT_NOTHING | XIM_STATUS_NOTHING,
+        XN_CLIENT_WINDOW.as_ptr(), window, XN_FOCUS_WINDOW.as_ptr(), window, null::<c_char>())
+}
+
+/// The keysym(NoSymbol if only text is committed) and the text looked up through the input method context if any
+unsafe fn decode_key(key: &mut XKeyEvent, ic: XIC) -> XInput
+{
+    let pressed = key.type_ == KEY_PRESS;
+    let mut keysym = 0;
+    let text = if pressed && !ic.is_null()
+    {
+        let (mut buffer, mut status) = (vec![0u8; 64], 0);
+        let mut length = Xutf8LookupString(ic, key, buffer.as_mut_ptr() as _, buffer.len() as _, &mut keysym, &mut status);
+        if status == X_BUFFER_OVERFLOW
+        {
+            buffer = vec![0u8; length as usize];
+            length = Xutf8LookupString(ic, key, buffer.as_mut_ptr() as _, buffer.len() as _, &mut keysym, &mut status);
+        }
+        if status != X_LOOKUP_KEYSYM && status != X_LOOKUP_BOTH { keysym = 0; }
+        if status == X_LOOKUP_CHARS || status == X_LOOKUP_BOTH { Some(String::from_utf8_lossy(&buffer[.. length.max(0) as usize]).into_owned()) }
+        else { None }
+    }
+    else { XLookupString(key, null_mut(), 0, &mut keysym, null_mut()); None };
+    XInput::Key { keycode: key.keycode as u8, keysym: keysym as u32, state: key.state as u16, time: key.time as u32, pressed, text }
+}
+
+extern "C" fn preedit_start(_ic: XIC, _data: XPointer, _call: XPointer) -> c_int { -1 }
+extern "C" fn preedit_done(_ic: XIC, data: XPointer, _call: XPointer) -> c_int
+{
+    let c = unsafe { &mut *(data as *mut Composing) };
+    let e = c.preedit.done();
+    c.events.push(e);
+    0
+}
+extern "C" fn preedit_draw(_ic: XIC, data: XPointer, call: XPointer) -> c_int
+{
+    let (c, d) = unsafe { (&mut *(data as *mut Composing), &*(call as *const XIMPreeditDrawCallbackStruct)) };
+    let text = unsafe { xim_text(d.text) };
+    let e = c.preedit.draw(d.caret.max(0) as usize, d.chg_first.max(0) as usize, d.chg_length.max(0) as usize, &text);
+    c.events.push(e);
+    0
+}
+extern "C" fn preedit_caret(_ic: XIC, data: XPointer, call: XPointer) -> c_int
+{
+    let (c, d) = unsafe { (&mut *(data as *mut Composing), &*(call as *const XIMPreeditCaretCallbackStruct)) };
+    if d.direction == XIM_ABSOLUTE_POSITION { let e = c.preedit.caret(d.position.max(0) as usize); c.events.push(e); }
+    0
+}
+/// Multi-byte strings are in the encoding of the locale(UTF-8 in the supported environments)
+unsafe fn xim_text(text: *const XIMText) -> String
+{
+    if text.is_null() || (*text).string.is_null() { return String::new(); }
+    let t = &*text;
+    if t.encoding_is_wchar != 0
+    {
+        ::std::slice::from_raw_parts(t.string as *const u32, t.length as usize).iter().filter_map(|&c| ::std::char::from_u32(c)).collect()
+    }
+    else { CStr::from_ptr(t.string).to_string_lossy().into_owned() }
+}
@@ -0,0 +1,8 @@
+//! Renderer-independent parts shared by the client and the asset tools(`src/bin`)
+
+extern crate metrics;
+extern crate svgparser;
+extern crate flate2;
+
+pub mod color;
+pub mod vector;
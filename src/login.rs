@@ -0,0 +1,105 @@
+//! The login scene: the student ID and the password of the portal in a form built with the widget toolkit
+
+use render::{RenderCommandsBasic, Color};
+use font::{Font, LayoutFont, TextAlign};
+use input::InputEvent;
+use textedit::TextField;
+use ui::{WidgetTree, WidgetId, UiRenderer, DisplayList, Theme, Notice, Notification};
+use ui::{Panel, Label, TextInput, Button, FlexStyle, Align, Justify, Edges};
+use scene::{Scene, SceneRequest, SceneUpdate, Transition};
+use metrics::*;
+use std::error::Error;
+use std::rc::Rc;
+use Application;
+
+pub struct LoginScene
+{
+    fonts: Vec<Rc<Font>>, tree: WidgetTree, fields: Fields,
+    /// Created with the GPU resources
+    renderer: Option<UiRenderer>,
+    /// The last painted list of the tree and the size of the client area it was painted for
+    list: DisplayList, viewport: Size2U
+}
+struct Fields { student_id: WidgetId, password: WidgetId, submit: WidgetId, message: WidgetId }
+impl LoginScene
+{
+    /// Fonts in the order of fallback
+    const FONTS: &'static [&'static str] = &["assets/fonts/NotoSansCJKjp-Regular.otf"];
+    const FORM_WIDTH: f32 = 320.0;
+
+    /// Loads the fonts: the GPU resources are created when the scene enters the stack
+    pub fn new() -> Result<Self, Box<Error>>
+    {
+        let fonts = Self::FONTS.iter().map(|p| Font::load(p, 0).map(Rc::new)).collect::<Result<Vec<_>, _>>()?;
+        let mut tree = WidgetTree::new(fonts.iter().map(|f| f.clone() as Rc<LayoutFont>).collect(), Theme::default());
+        let fields = Self::build(&mut tree);
+        Ok(LoginScene { fonts, tree, fields, renderer: None, list: DisplayList::new(), viewport: Size2U(1, 1) })
+    }
+    fn build(tree: &mut WidgetTree) -> Fields
+    {
+        let surface = tree.theme().surface;
+        let root = tree.set_root(box Panel::new(FlexStyle::column().justify(Justify::Center).align_items(Align::Center).gap(16.0)
+            .padding(Edges::all(24.0)), Some(surface)));
+        tree.add(root, box Label::new("DigitalCampus 2017").size(28.0));
+        let student_id = tree.add(root, box TextInput::new(TextField::new(), "学籍番号").width(Self::FORM_WIDTH));
+        let password = tree.add(root, box TextInput::new(TextField::password(), "パスワード").width(Self::FORM_WIDTH));
+        let submit = tree.add(root, box Button::new("ログイン"));
+        let message = tree.add(root, box Label::new("").width(Self::FORM_WIDTH).align(TextAlign::Center));
+        tree.set_focus(Some(student_id));
+        Fields { student_id, password, submit, message }
+    }
+
+    fn submit(&mut self)
+    {
+        let filled = [self.fields.student_id, self.fields.password].iter()
+            .all(|&f| self.tree.get::<TextInput>(f).map(|t| !t.field().text().is_empty()).unwrap_or(false));
+        let message = if filled { "ログインしています…" } else { "学籍番号とパスワードを入力してください" };
+        if let Some(l) = self.tree.get_mut::<Label>(self.fields.message) { l.set_text(message); }
+    }
+    /// Paints the tree and batches the list for the renderer
+    fn repaint(&mut self) -> Result<(), Box<Error>>
+    {
+        self.list = self.tree.paint();
+        match self.renderer { Some(ref mut r) => r.prepare(&self.list, self.viewport), None => Ok(()) }
+    }
+}
+impl Scene for LoginScene
+{
+    fn init_resources(&mut self) -> Result<(), Box<Error>>
+    {
+        let (w, h) = Application::get().main_window.client_size();
+        self.viewport = Size2U(w as _, h as _);
+        self.tree.resize(self.viewport);
+        self.renderer = Some(UiRenderer::new(self.fonts.clone())?);
+        self.repaint()
+    }
+    fn release_resources(&mut self) { self.renderer = None; }
+    fn handle_input(&mut self, event: &InputEvent) -> Option<SceneRequest>
+    {
+        if let InputEvent::Resize(size) = *event { self.viewport = size; }
+        let result = self.tree.dispatch(event);
+        if !result.handled && event.is_back() { return Some(SceneRequest::Pop(Transition::Slide(0.4))); }
+        for Notification { widget, notice } in result.notifications
+        {
+            match notice
+            {
+                Notice::Submitted if widget == self.fields.student_id => self.tree.set_focus(Some(self.fields.password)),
+                Notice::Submitted => self.submit(),
+                Notice::Clicked if widget == self.fields.submit => self.submit(),
+                _ => ()
+            }
+        }
+        None
+    }
+    fn update(&mut self, dt: f32) -> SceneUpdate
+    {
+        if !self.tree.update(dt) { return SceneUpdate::Idle; }
+        if let Err(e) = self.repaint() { println!("!! Failed to prepare the login form: {}", e); }
+        SceneUpdate::Redraw
+    }
+    fn background(&self) -> Color { self.tree.theme().surface }
+    fn record(&self, rec: &mut RenderCommandsBasic)
+    {
+        if let Some(ref r) = self.renderer { r.record(rec, &self.list); }
+    }
+}
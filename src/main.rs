@@ -12,14 +12,28 @@ extern crate metrics;
 #[cfg(windows)] extern crate widestring;
 #[cfg(windows)] use comdrive::ResultCarrier;
 extern crate num;
-extern crate mio;
+#[cfg(windows)] extern crate svgparser;
+extern crate dc2017;
 
 use ws_common::{NativeWindow, WindowServer};
 
 mod render;
-use render::{RenderDevice, TextureParam, ColorFormat, TextureUsage};
+use render::{RenderDevice, TextureParam, ColorFormat, TextureUsage, Color, RenderCommandsBasic};
+use render::{ResourceBlock, TextureBinding, BuiltinResourceKey, SdfStyle, VectorMesh, VectorStyle, Animated, AnimatedSlot};
 use metrics::*;
-mod event;
+mod overlay;
+use dc2017::vector;
+mod font;
+mod textedit;
+mod input;
+use input::{InputEvent, InputQueue, KeyCode};
+mod ui;
+mod scene;
+use scene::{Scene, SceneManager, SceneRequest, SceneUpdate, FrameUpdate, Transition};
+mod login;
+mod animation;
+use animation::{Animator, Tween, Easing, Parallel, Sequence, Delay};
+mod screenshot;
 
 #[cfg(windows)] mod imaging;
 #[cfg(not(windows))] extern crate image;
@@ -28,37 +42,130 @@ use image::GenericImage;
 
 pub struct WelcomeSceneRender
 {
-    
+    /// Width / height of the logo texture
+    logo_aspect: f32,
+    /// The binding is dropped before the resource block
+    logo: Option<(Box<TextureBinding>, Box<ResourceBlock>)>,
+    /// The brand header(optional: drawn if the baked image is available)
+    header: Option<VectorMesh>,
+    logo_props: LogoProps, animator: Animator<LogoProps>,
+    /// The properties are fed into the slot every frame instead of recording the commands again
+    logo_animation: Option<AnimatedSlot>
 }
+/// Animated properties of the logo
+pub struct LogoProps { opacity: f32, scale: f32 }
 impl WelcomeSceneRender
 {
-    pub fn init() -> Self
+    /// The university brand background
+    const BACKGROUND: Color = Color(0.0, 0.24, 0.47, 1.0);
+    /// Half of the logo height in the normalized device coordinates(0.5 = half of the screen height)
+    const LOGO_HEIGHT: f32 = 0.5;
+    /// Baked vector image of the header(`src/bin/bake_paths.rs`) and its tessellation cache
+    const HEADER_IMAGE: &'static str = "assets/welcome_header.dcvp";
+    const HEADER_CACHE: &'static str = "assets/welcome_header.dcmc";
+    /// Flattening tolerance of the header in the image units
+    const HEADER_TOLERANCE: f32 = 0.05;
+
+    pub fn new() -> Self
+    {
+        WelcomeSceneRender
+        {
+            logo_aspect: 1.0, logo: None, header: None, logo_props: LogoProps { opacity: 0.0, scale: 0.85 }, animator: Animator::new(),
+            logo_animation: None
+        }
+    }
+
+    /// White logo with a soft glow and a drop shadow at the center of the screen(scaled around it by the animation)
+    pub fn logo_style(&self) -> SdfStyle
+    {
+        let (w, h) = Application::get().main_window.client_size();
+        SdfStyle::new(Color(1.0, 1.0, 1.0, 1.0)).place((Self::LOGO_HEIGHT * self.logo_aspect * h as f32 / w as f32, Self::LOGO_HEIGHT), (0.0, 0.0))
+            .glow(0.15, Color(1.0, 1.0, 1.0, 0.35)).shadow([0.01, 0.015], 0.1, Color(0.0, 0.0, 0.0, 0.4))
+    }
+    /// The header spanning the width of the screen at the top, keeping the aspect ratio
+    pub fn header_style(&self, header: &VectorMesh) -> VectorStyle
     {
-        let p_logo_encoded = image::open("assets/logo_ColoredLogo.sdf.png").expect("Failed to load the university logo");
+        let (w, h) = Application::get().main_window.client_size();
+        let scale = 2.0 / header.size.x();
+        VectorStyle::default().place((scale, scale * w as f32 / h as f32), (-1.0, -1.0))
+    }
+    fn animate_logo(&self)
+    {
+        let LogoProps { opacity, scale } = self.logo_props;
+        if let Some(ref s) = self.logo_animation { RenderDevice::get().set_animated(s, Animated { scale: [scale; 2], opacity, .. Animated::IDENTITY }); }
+    }
+}
+impl Scene for WelcomeSceneRender
+{
+    fn init_resources(&mut self) -> Result<(), Box<std::error::Error>>
+    {
+        let p_logo_encoded = image::open("assets/logo_ColoredLogo.sdf.png")?;
         let (w, h) = p_logo_encoded.dimensions();
         println!("The university logo loaded: size = {}x{} estimatedSize = {} bytes", w, h, w * h);
         let res = RenderDevice::get().create_resources(&[], &[
-            TextureParam { size: Size2U(w, h), color: ColorFormat::Grayscale, usage: TextureUsage::Immutable(p_logo_encoded.as_luma8().unwrap()), .. Default::default() }
-        ]).expect("Failed to create some resources");
-        RenderDevice::get().update_render_commands(|rec, n|
+            TextureParam
+            {
+                size: Size2U(w, h), color: ColorFormat::Grayscale, usage: TextureUsage::Immutable(p_logo_encoded.as_luma8().unwrap()),
+                mipmaps: true, .. Default::default()
+            }
+        ])?;
+        let logo = RenderDevice::get().bind_texture(&*res, 0)?;
+        self.logo_animation = Some(RenderDevice::get().new_animated_slot()?);
+        self.logo_aspect = w as f32 / h as f32;
+        self.logo = Some((logo, res));
+        self.header = match vector::MeshCache::load_or_tessellate(Self::HEADER_IMAGE, Self::HEADER_CACHE, Self::HEADER_TOLERANCE)
         {
-            rec.prepare_render_targets(&[&*RenderDevice::get().get_primary_render_target(n)]);
-            rec.set_render_target(&*RenderDevice::get().get_primary_render_target(n));
-        }).expect("Failed to initialize render commands");
-        RenderDevice::get().do_render().expect("Failed to kick initial render");
-        /*let primary_cmds = RenderDevice::get().new_render_command_buffer(RenderDevice::get().swapchain_buffer_count())
-            .expect("Failed to generate render command buffers");
-        for n in 0 .. RenderDevice::get().swapchain_buffer_count()
+            Ok(c) => VectorMesh::new(&c)?,
+            Err(e) => { println!("!! The header image is not loaded({}): {}", Self::HEADER_IMAGE, e); None }
+        };
+        // fade/scale-in of the logo
+        self.logo_props = LogoProps { opacity: 0.0, scale: 0.85 };
+        self.animator.stop_all();
+        self.animator.start(box Sequence::new(vec![box Delay::new(0.2), box Parallel::new(vec![
+            box Tween::new(0.0, 1.0, 0.6, |p: &mut LogoProps, v| p.opacity = v).easing(Easing::OutCubic),
+            box Tween::new(0.85, 1.0, 0.8, |p: &mut LogoProps, v| p.scale = v).easing(Easing::OutBack)
+        ])]));
+        self.animate_logo();
+        Ok(())
+    }
+    fn release_resources(&mut self) { self.logo = None; self.header = None; self.logo_animation = None; }
+    /// A click or the enter key opens the login form
+    fn handle_input(&mut self, event: &InputEvent) -> Option<SceneRequest>
+    {
+        match *event
         {
-            primary_cmds.begin_recording(n).expect("Failed to begin recording a command buffer")
-                .set_primary_render_target(n);
-        }*/
-        // RenderDevice::get().do_render(|| ()).unwrap();
-        WelcomeSceneRender {}
+            InputEvent::PointerUp(..) | InputEvent::KeyDown { key: KeyCode::Enter, .. } => match login::LoginScene::new()
+            {
+                Ok(s) => Some(SceneRequest::Push(box s, Transition::Slide(0.4))),
+                Err(e) => { println!("!! The login form is not available: {}", e); None }
+            },
+            _ => None
+        }
+    }
+    fn update(&mut self, dt: f32) -> SceneUpdate
+    {
+        if !self.animator.update(dt, &mut self.logo_props) { return SceneUpdate::Idle; }
+        self.animate_logo();
+        SceneUpdate::Animate
+    }
+    fn background(&self) -> Color { Self::BACKGROUND }
+    fn record(&self, rec: &mut RenderCommandsBasic)
+    {
+        if let Some(ref h) = self.header { rec.draw_vector(&*h.vertices(), &self.header_style(h)); }
+        let logo = match self.logo { Some((ref b, _)) => b, None => return };
+        let unit_rect = RenderDevice::get().get_builtin_vertex_array(BuiltinResourceKey::UnitRect).expect("Failed to get the unit rect");
+        rec.set_animation(self.logo_animation.as_ref());
+        rec.draw_sdf(&*unit_rect, &**logo, &self.logo_style());
+        rec.set_animation(None);
     }
 }
 
-pub struct Application { pub main_window: NativeWindow }
+pub struct Application
+{
+    pub main_window: NativeWindow,
+    /// Input from the window server, taken by the render loop every frame
+    pub input: InputQueue
+}
 impl Application
 {
     AppInstance!(pub static instance: Application = Application::new());
@@ -66,15 +173,20 @@ impl Application
     pub fn get<'a>() -> &'a Self { Self::instance() }
 
     const INITIAL_SIZE: (u16, u16) = (960, 960 * 9 / 16);
+    /// Also finds the window to read the input of(`input::xlib`)
+    const TITLE: &'static str = "DigitalCampus 2017";
     fn new() -> Self
     {
-        let main_window = NativeWindow::new(Self::INITIAL_SIZE, "DigitalCampus 2017", true);
+        let main_window = NativeWindow::new(Self::INITIAL_SIZE, Self::TITLE, true);
         main_window.show();
-        Application { main_window }
+        Application { main_window, input: InputQueue::new() }
     }
+    /// Returns when the window is closed: the render loop ends with the posted `InputEvent::Close`
     fn process_events(&self)
     {
+        #[cfg(feature = "target_x11")] input::xlib::spawn(Self::TITLE, &Application::get().input);
         WindowServer::instance().process_events();
+        self.input.post(InputEvent::Close);
     }
 }
 
@@ -87,10 +199,51 @@ fn main()
         libc::atexit(uninit);
     }
     println!("=== DIGITAL CAMPUS 2017 ===");
+    #[cfg(feature = "target_x11")] input::xlib::init();
+    let render_thread = std::thread::Builder::new().name("Render Loop".into()).spawn(render_loop).expect("Failed to spawn the render thread");
+    Application::instance().process_events();
+    render_thread.join().unwrap();
+}
+
+/// Polling interval of the render loop while no scene is animating
+const IDLE_INTERVAL_MS: u64 = 16;
+/// Owns the render device: every use of it is in this thread. Runs until `InputEvent::Close` is taken
+fn render_loop()
+{
     RenderDevice::init();
     println!("RenderAgent: {}", RenderDevice::get().agent());
-    let scene = WelcomeSceneRender::init();
-    Application::instance().process_events();
+    let mut screenshots = screenshot::Screenshots::from_args(std::env::args());
+    let mut stats_overlay = overlay::StatsOverlay::new(std::env::args().any(|a| a == "--render-stats"));
+    let mut scenes = SceneManager::new();
+    scenes.push(box WelcomeSceneRender::new(), Transition::None).expect("Failed to initialize the welcome scene");
+    let mut frame = 0;
+    let mut last_frame = std::time::Instant::now();
+    'frames: loop
+    {
+        for e in Application::get().input.take()
+        {
+            match e
+            {
+                InputEvent::Close => break 'frames,
+                InputEvent::KeyDown { key: KeyCode::F(12), .. } => screenshot::request(),
+                InputEvent::KeyDown { key: KeyCode::F(3), .. } => stats_overlay.toggle(),
+                e => if let Err(e) = scenes.handle_input(&e) { println!("!! Failed to handle the input: {}", e); }
+            }
+        }
+        let now = std::time::Instant::now();
+        let dt = now - last_frame;
+        last_frame = now;
+        let update = scenes.update(dt.as_secs() as f32 + dt.subsec_nanos() as f32 * 1.0e-9).expect("Failed to update the scenes");
+        // animated values are read when the frame is rendered: the commands are recorded only when the draws change
+        if update == FrameUpdate::Record { scenes.record_commands().expect("Failed to record render commands"); }
+        // nothing changes on the screen while the scenes are idle: the last presented frame stays
+        if update == FrameUpdate::Idle && !screenshots.is_pending() { std::thread::sleep(std::time::Duration::from_millis(IDLE_INTERVAL_MS)); continue; }
+        screenshots.render_frame(frame);
+        stats_overlay.update();
+        frame += 1;
+    }
+    RenderDevice::get().wait_render_ready().expect("Failed to wait the last frames");
+    drop(scenes);
     RenderDevice::uninit();
 }
 
@@ -0,0 +1,39 @@
+//! Debug overlay showing render statistics
+
+use render::{RenderDevice, RenderStatistics};
+use std::time::{Duration, Instant};
+
+pub struct StatsOverlay { visible: bool, last_report: Option<Instant> }
+impl StatsOverlay
+{
+    const REPORT_INTERVAL_SECS: u64 = 1;
+
+    pub fn new(visible: bool) -> Self { StatsOverlay { visible, last_report: None } }
+    pub fn toggle(&mut self) { self.visible = !self.visible; self.last_report = None; }
+    pub fn is_visible(&self) -> bool { self.visible }
+
+    pub fn lines(stats: &RenderStatistics) -> Vec<String>
+    {
+        let mut lines = vec![
+            format!("frames: {}", stats.frames_rendered),
+            format!("draw calls: {} instances: {}", stats.last_frame.draw_calls, stats.last_frame.instances),
+            format!("pipeline binds: {} descriptor binds: {}", stats.last_frame.pipeline_binds, stats.last_frame.descriptor_binds),
+            format!("uploaded: {} bytes", stats.bytes_uploaded),
+            format!("resource blocks: {}", stats.live_resource_blocks)
+        ];
+        for (t, b) in &stats.memory_allocated { lines.push(format!("memory type #{}: {} bytes", t, b)); }
+        lines
+    }
+
+    /// Called after each frame is rendered.
+    /// The overlay is printed to the console at fixed intervals until text rendering is available
+    pub fn update(&mut self)
+    {
+        if !self.visible { return; }
+        let now = Instant::now();
+        if self.last_report.map(|t| now - t < Duration::from_secs(Self::REPORT_INTERVAL_SECS)).unwrap_or(false) { return; }
+        self.last_report = Some(now);
+        println!("--- Render Statistics ---");
+        for l in Self::lines(&RenderDevice::get().statistics()) { println!("  {}", l); }
+    }
+}
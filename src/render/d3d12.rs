@@ -12,9 +12,17 @@ use winapi::um::winbase::INFINITE;
 use winapi::um::handleapi::CloseHandle;
 use winapi::um::synchapi::{CreateEventA, WaitForSingleObject};
 use std::cell::RefCell;
+use super::{Color, LoadOp};
+use vector::{Path, PathCommand};
 
-pub struct PathImage { inner: d2::PathGeometry }
-impl super::VectorImage for PathImage {}
+/// The geometry realized for Direct2D and its source for the queries
+pub struct PathImage { inner: d2::PathGeometry, source: Path }
+impl super::VectorImage for PathImage
+{
+    fn bounds(&self) -> Option<(Point2F, Point2F)> { super::VectorImage::bounds(&self.source) }
+    fn fill_contains(&self, p: Point2F) -> bool { super::VectorImage::fill_contains(&self.source, p) }
+    fn stroke_distance(&self, p: Point2F) -> Option<f32> { super::VectorImage::stroke_distance(&self.source, p) }
+}
 
 const BACKBUFFER_COUNT: usize = 2;
 
@@ -95,7 +103,7 @@ impl RenderDevice
         self.agent_str.as_ref().unwrap()
     }
 
-    pub fn begin_render(&self) -> IOResult<u32>
+    pub fn begin_render(&self, load: LoadOp) -> IOResult<u32>
     {
         self.render_control.borrow().wait()?;
         let findex = self.swapchain.current_back_buffer_index();
@@ -103,8 +111,12 @@ impl RenderDevice
         // let xs: dxgi::Surface = self.scbuffers[findex as usize].1.query_interface()?;
         let xbmp = self.dc2.new_bitmap_for_render_target(d2::RenderableBitmapSource::FromDxgiSurface(&self.scbuffers[findex as usize].1),
             ::winapi::shared::dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM, dxgi::AlphaMode::Ignored)?;
-        self.dc2.set_target(&xbmp)
-            .begin_draw().clear(&d2::ColorF { r: 1.0, g: 1.0, b: 1.0, a: 0.5 }).end_draw()?;
+        // Load and DontCare leave the previous contents of the back buffer as is
+        if let LoadOp::Clear(Color(r, g, b, a)) = load
+        {
+            self.dc2.set_target(&xbmp).begin_draw().clear(&d2::ColorF { r, g, b, a }).end_draw()?;
+        }
+        else { self.dc2.set_target(&xbmp); }
         Ok(findex)
     }
     pub fn end_render(&self, findex: u32) -> IOResult<()>
@@ -120,79 +132,32 @@ impl RenderDevice
     {
         let p = self.dev2.factory().new_path_geometry()?;
         let sink = p.open()?;
+        let mut source = Path::new();
         for figure in provider
         {
-            let mut prev = Point2F(0.0, 0.0);
-            let mut last_curve_pvec = Point2F(0.0, 0.0);
             let mut closed = true;
-            for segment in figure
+            let figure = Path::from_svg_tokens(figure).arcs_to_cubics();
+            source.commands.extend_from_slice(&figure.commands);
+            for command in figure.commands
             {
-                match *segment
+                match command
                 {
-                    Segment::MoveTo { abs, x, y } =>
-                    {
-                        prev = if abs { Point2F(x as _, y as _) } else { Point2F(x as _, y as _) + &prev };
-                        if !closed { sink.end_figure(false); } closed = false; sink.begin_figure(prev, true);
-                    },
-                    Segment::LineTo { abs, x, y } =>
-                    {
-                        prev = if abs { Point2F(x as _, y as _) } else { Point2F(x as _, y as _) + &prev };
-                        sink.add(&prev);
-                    },
-                    Segment::HorizontalLineTo { abs, x } => { prev.0 = if abs { x as _ } else { x as f32 + prev.0 }; sink.add(&prev); },
-                    Segment::VerticalLineTo   { abs, y } => { prev.1 = if abs { y as _ } else { y as f32 + prev.1 }; sink.add(&prev); },
-                    Segment::CurveTo { abs, x1, y1, x2, y2, x, y } =>
-                    {
-                        let p1 = Point2F(x1 as _, y1 as _) + &if abs { Point2F::ZERO } else { prev };
-                        let p2 = Point2F(x2 as _, y2 as _) + &if abs { Point2F::ZERO } else { prev };
-                        prev = Point2F(x as _, y as _) + &if abs { Point2F::ZERO } else { prev };
-                        last_curve_pvec = prev - &p2;
-                        sink.add(&d2::BezierSegment { point1: *transmute_safe(&p1), point2: *transmute_safe(&p2), point3: *transmute_safe(&prev) });
-                    },
-                    Segment::Quadratic { abs, x1, y1, x, y } =>
-                    {
-                        let p1 = Point2F(x1 as _, y1 as _) + &if abs { Point2F::ZERO } else { prev };
-                        prev = Point2F(x as _, y as _) + &if abs { Point2F::ZERO } else { prev };
-                        last_curve_pvec = prev - &p1;
-                        sink.add(&d2::QuadraticBezierSegment { point1: *transmute_safe(&p1), point2: *transmute_safe(&prev) });
-                    },
-                    Segment::SmoothCurveTo { abs, x2, y2, x, y } =>
-                    {
-                        let p1 = prev + &last_curve_pvec;
-                        let p2 = Point2F(x2 as _, y2 as _) + &if abs { Point2F::ZERO } else { prev };
-                        prev = Point2F(x as _, y as _) + &if abs { Point2F::ZERO } else { prev };
-                        last_curve_pvec = prev - &p2;
-                        sink.add(&d2::BezierSegment { point1: *transmute_safe(&p1), point2: *transmute_safe(&p2), point3: *transmute_safe(&prev) });
-                    },
-                    Segment::SmoothQuadratic { abs, x, y } =>
-                    {
-                        let p1 = prev + &last_curve_pvec;
-                        prev = Point2F(x as _, y as _) + &if abs { Point2F::ZERO } else { prev };
-                        last_curve_pvec = prev - &p1;
-                        sink.add(&d2::QuadraticBezierSegment { point1: *transmute_safe(&p1), point2: *transmute_safe(&prev) });
-                    },
-                    Segment::EllipticalArc { abs, rx, ry, x_axis_rotation, large_arc, sweep, x, y } =>
-                    {
-                        prev = Point2F(x as _, y as _) + &if abs { Point2F::ZERO } else { prev };
-                        sink.add(&d2::ArcSegment
-                        {
-                            point: *transmute_safe(&prev), size: d2::SizeF { width: rx as _, height: ry as _ },
-                            rotationAngle: x_axis_rotation as _, arcSize: if large_arc { d2::ArcSize::Large } else { d2::ArcSize::Small } as _,
-                            sweepDirection: if sweep { d2::SweepDirection::CW } else { d2::SweepDirection::CCW } as _
-                        });
-                    },
-                    Segment::ClosePath { .. } =>
+                    PathCommand::MoveTo(p) => { if !closed { sink.end_figure(false); } closed = false; sink.begin_figure(p, true); },
+                    PathCommand::LineTo(p) => sink.add(&p),
+                    PathCommand::QuadTo(c, p) => sink.add(&d2::QuadraticBezierSegment { point1: *transmute_safe(&c), point2: *transmute_safe(&p) }),
+                    PathCommand::CubicTo(c1, c2, p) => sink.add(&d2::BezierSegment
                     {
-                        assert!(!closed); closed = true;
-                        sink.end_figure(true);
-                    }
+                        point1: *transmute_safe(&c1), point2: *transmute_safe(&c2), point3: *transmute_safe(&p)
+                    }),
+                    PathCommand::ArcTo { .. } => unreachable!("arcs are converted to cubic curves"),
+                    PathCommand::Close => if !closed { closed = true; sink.end_figure(true); }
                 }
             }
             if !closed { sink.end_figure(false); }
         }
         sink.close()?;
 
-        Ok(PathImage { inner: p })
+        Ok(PathImage { inner: p, source })
     }
 }
 
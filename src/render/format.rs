@@ -0,0 +1,197 @@
+//! Texture color formats
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorFormat
+{
+    /// R8
+    Grayscale,
+    /// R8G8: e.g. multi-channel distance fields
+    TwoChannels,
+    /// R8G8B8
+    Default,
+    /// R8G8B8A8
+    WithAlpha,
+    DefaultSRGB, WithAlphaSRGB,
+    /// B8G8R8A8
+    BGRA, BGRASRGB,
+    /// R16 float
+    GrayscaleF16,
+    /// R16G16B16A16 float
+    WithAlphaF16,
+    /// Block compressed formats(4x4 texels per block)
+    BC1, BC1SRGB, BC3, BC3SRGB, BC4, BC5, BC7, BC7SRGB, ETC2, ETC2SRGB, ETC2WithAlpha, ETC2WithAlphaSRGB
+}
+impl ColorFormat
+{
+    pub fn is_compressed(self) -> bool { self.block_bytes().is_some() }
+    /// Bytes per pixel of uncompressed formats
+    pub fn bytes_per_pixel(self) -> Option<usize>
+    {
+        match self
+        {
+            ColorFormat::Grayscale => Some(1),
+            ColorFormat::TwoChannels | ColorFormat::GrayscaleF16 => Some(2),
+            ColorFormat::Default | ColorFormat::DefaultSRGB => Some(3),
+            ColorFormat::WithAlpha | ColorFormat::WithAlphaSRGB | ColorFormat::BGRA | ColorFormat::BGRASRGB => Some(4),
+            ColorFormat::WithAlphaF16 => Some(8),
+            _ => None
+        }
+    }
+    /// Bytes per 4x4 block of compressed formats
+    pub fn block_bytes(self) -> Option<usize>
+    {
+        match self
+        {
+            ColorFormat::BC1 | ColorFormat::BC1SRGB | ColorFormat::BC4 | ColorFormat::ETC2 | ColorFormat::ETC2SRGB => Some(8),
+            ColorFormat::BC3 | ColorFormat::BC3SRGB | ColorFormat::BC5 | ColorFormat::BC7 | ColorFormat::BC7SRGB
+                | ColorFormat::ETC2WithAlpha | ColorFormat::ETC2WithAlphaSRGB => Some(16),
+            _ => None
+        }
+    }
+    /// Alignment of the offsets of buffer to image copies: multiples of the texel(block) size and of 4
+    pub fn copy_alignment(self) -> usize
+    {
+        let texel = self.block_bytes().or(self.bytes_per_pixel()).unwrap();
+        let gcd = if texel % 4 == 0 { 4 } else if texel % 2 == 0 { 2 } else { 1 };
+        texel * 4 / gcd
+    }
+    /// Bytes of the pixels of an image
+    pub fn image_bytes(self, width: u32, height: u32) -> usize
+    {
+        match self.block_bytes()
+        {
+            Some(b) => ((width as usize + 3) / 4) * ((height as usize + 3) / 4) * b,
+            None => width as usize * height as usize * self.bytes_per_pixel().unwrap()
+        }
+    }
+
+    /// Formats which can be used instead of this format, in order of preference.
+    /// Pixels can be converted into any of them by `convert_pixels`
+    pub fn fallbacks(self) -> &'static [ColorFormat]
+    {
+        match self
+        {
+            ColorFormat::Default => &[ColorFormat::WithAlpha, ColorFormat::BGRA],
+            ColorFormat::DefaultSRGB => &[ColorFormat::WithAlphaSRGB, ColorFormat::BGRASRGB],
+            ColorFormat::WithAlpha => &[ColorFormat::BGRA],
+            ColorFormat::WithAlphaSRGB => &[ColorFormat::BGRASRGB],
+            ColorFormat::BGRA => &[ColorFormat::WithAlpha],
+            ColorFormat::BGRASRGB => &[ColorFormat::WithAlphaSRGB],
+            ColorFormat::TwoChannels => &[ColorFormat::WithAlpha],
+            _ => &[]
+        }
+    }
+
+    /// Converts pixels in this format into the other format on the CPU.
+    /// Fails if the conversion is not supported(compressed or float formats) or the pixels are cut in the middle
+    pub fn convert_pixels(self, to: ColorFormat, pixels: &[u8]) -> Result<Vec<u8>, PixelError>
+    {
+        if self == to { return Ok(pixels.to_vec()); }
+        let unsupported = Err(PixelError::Unsupported(self, to));
+        let convertible = |f: ColorFormat| !f.is_compressed() && f != ColorFormat::GrayscaleF16 && f != ColorFormat::WithAlphaF16;
+        if !convertible(self) || !convertible(to) { return unsupported; }
+        let bpp = self.bytes_per_pixel().unwrap();
+        if pixels.len() % bpp != 0 { return Err(PixelError::Length { expected: (pixels.len() / bpp + 1) * bpp, actual: pixels.len() }); }
+        // extracts pixels as RGBA
+        let rgba: Vec<[u8; 4]> = match self
+        {
+            ColorFormat::Grayscale => pixels.iter().map(|&l| [l, l, l, 255]).collect(),
+            ColorFormat::TwoChannels => pixels.chunks(2).map(|p| [p[0], p[1], 0, 255]).collect(),
+            ColorFormat::Default | ColorFormat::DefaultSRGB => pixels.chunks(3).map(|p| [p[0], p[1], p[2], 255]).collect(),
+            ColorFormat::WithAlpha | ColorFormat::WithAlphaSRGB => pixels.chunks(4).map(|p| [p[0], p[1], p[2], p[3]]).collect(),
+            ColorFormat::BGRA | ColorFormat::BGRASRGB => pixels.chunks(4).map(|p| [p[2], p[1], p[0], p[3]]).collect(),
+            _ => return unsupported
+        };
+        let mut out = Vec::with_capacity(rgba.len() * to.bytes_per_pixel().unwrap_or(0));
+        for p in rgba
+        {
+            match to
+            {
+                ColorFormat::Grayscale => out.push(p[0]),
+                ColorFormat::TwoChannels => out.extend_from_slice(&p[.. 2]),
+                ColorFormat::Default | ColorFormat::DefaultSRGB => out.extend_from_slice(&p[.. 3]),
+                ColorFormat::WithAlpha | ColorFormat::WithAlphaSRGB => out.extend_from_slice(&p),
+                ColorFormat::BGRA | ColorFormat::BGRASRGB => out.extend_from_slice(&[p[2], p[1], p[0], p[3]]),
+                _ => return unsupported
+            }
+        }
+        Ok(out)
+    }
+    /// Checks that the pixels fill a `width` x `height` x `layers` image
+    pub fn check_length(self, width: u32, height: u32, layers: u32, pixels: &[u8]) -> Result<(), PixelError>
+    {
+        let expected = self.image_bytes(width, height) * layers as usize;
+        if pixels.len() == expected { Ok(()) } else { Err(PixelError::Length { expected, actual: pixels.len() }) }
+    }
+}
+
+/// Pixels which cannot be uploaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelError
+{
+    Unsupported(ColorFormat, ColorFormat),
+    /// Bytes of the pixels
+    Length { expected: usize, actual: usize }
+}
+impl Display for PixelError
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult
+    {
+        match *self
+        {
+            PixelError::Unsupported(from, to) => write!(fmt, "Pixels in {:?} cannot be converted into {:?}", from, to),
+            PixelError::Length { expected, actual } => write!(fmt, "{} bytes of pixels are given for {} bytes", actual, expected)
+        }
+    }
+}
+impl Error for PixelError
+{
+    fn description(&self) -> &str
+    {
+        match *self { PixelError::Unsupported(..) => "Unsupported pixel conversion", PixelError::Length { .. } => "Pixels of a wrong length" }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test] fn conversion()
+    {
+        let rgb = [10, 20, 30, 40, 50, 60];
+        assert_eq!(ColorFormat::Default.convert_pixels(ColorFormat::WithAlpha, &rgb), Ok(vec![10, 20, 30, 255, 40, 50, 60, 255]));
+        assert_eq!(ColorFormat::Default.convert_pixels(ColorFormat::BGRA, &rgb), Ok(vec![30, 20, 10, 255, 60, 50, 40, 255]));
+        assert_eq!(ColorFormat::BGRA.convert_pixels(ColorFormat::WithAlpha, &[1, 2, 3, 4]), Ok(vec![3, 2, 1, 4]));
+        assert_eq!(ColorFormat::TwoChannels.convert_pixels(ColorFormat::WithAlpha, &[7, 8]), Ok(vec![7, 8, 0, 255]));
+        assert_eq!(ColorFormat::Grayscale.convert_pixels(ColorFormat::WithAlphaSRGB, &[9]), Ok(vec![9, 9, 9, 255]));
+        assert_eq!(ColorFormat::Default.convert_pixels(ColorFormat::WithAlpha, &rgb[.. 5]), Err(PixelError::Length { expected: 6, actual: 5 }));
+        assert_eq!(ColorFormat::BC1.convert_pixels(ColorFormat::WithAlpha, &[0; 8]), Err(PixelError::Unsupported(ColorFormat::BC1, ColorFormat::WithAlpha)));
+        assert!(ColorFormat::WithAlphaF16.convert_pixels(ColorFormat::WithAlpha, &[0; 8]).is_err());
+        assert!(ColorFormat::WithAlpha.convert_pixels(ColorFormat::BC7, &[0; 4]).is_err());
+    }
+    #[test] fn fallbacks_and_sizes()
+    {
+        // every fallback can be converted into
+        let formats = [ColorFormat::Grayscale, ColorFormat::TwoChannels, ColorFormat::Default, ColorFormat::WithAlpha, ColorFormat::DefaultSRGB,
+            ColorFormat::WithAlphaSRGB, ColorFormat::BGRA, ColorFormat::BGRASRGB, ColorFormat::BC1, ColorFormat::ETC2WithAlpha];
+        for &f in &formats
+        {
+            let pixels = vec![0; f.image_bytes(4, 4)];
+            for &to in f.fallbacks() { assert!(f.convert_pixels(to, &pixels).is_ok(), "{:?} -> {:?}", f, to); }
+        }
+        assert_eq!(ColorFormat::Default.image_bytes(3, 2), 18);
+        assert_eq!(ColorFormat::BC1.image_bytes(5, 4), 16);
+        assert_eq!(ColorFormat::BC7.image_bytes(1, 1), 16);
+        assert_eq!(ColorFormat::Default.copy_alignment(), 12);
+        assert_eq!(ColorFormat::Grayscale.copy_alignment(), 4);
+        assert_eq!(ColorFormat::TwoChannels.copy_alignment(), 4);
+        assert_eq!(ColorFormat::WithAlphaF16.copy_alignment(), 8);
+        assert_eq!(ColorFormat::BC1.copy_alignment(), 8);
+        assert!(ColorFormat::WithAlpha.check_length(2, 2, 2, &[0; 32]).is_ok());
+        assert_eq!(ColorFormat::WithAlpha.check_length(2, 2, 1, &[0; 15]), Err(PixelError::Length { expected: 16, actual: 15 }));
+    }
+}
@@ -1,26 +1,45 @@
 
 mod vk;
 #[cfg(windows)] mod d3d12;
+mod stats;
+pub use self::stats::{RenderStatistics, CommandStatistics};
+pub use dc2017::color::Color;
+mod format;
+pub use self::format::{ColorFormat, PixelError};
 use std::error::Error;
+use std::cell::Cell;
 use metrics::*;
 
-pub trait VectorImage {}
+pub use vector::VectorImage;
+use vector::MeshCache;
 
 pub enum RenderDevice
 {
     Vulkan(vk::RenderDevice), #[cfg(windows)] DirectX12(d3d12::RenderDevice)
 }
+/// The device is not Sync(command recording state is kept in Cells): it is created, used and dropped in one thread(the render loop)
 static mut RD: *const RenderDevice = 0 as *const _;
+thread_local!(static OWNER: Cell<bool> = Cell::new(false));
 impl RenderDevice
 {
-    /// Helping RLS completion
-    pub fn get<'a>() -> &'a Self { unsafe { &*RD } }
+    /// Helping RLS completion. Panics in threads other than the one which called `init`
+    pub fn get<'a>() -> &'a Self
+    {
+        assert!(OWNER.with(Cell::get), "RenderDevice is used outside the render thread");
+        unsafe { &*RD }
+    }
 
     pub fn init()
     {
         unsafe { RD = Box::into_raw(box Self::new()); }
+        OWNER.with(|o| o.set(true));
+    }
+    pub fn uninit()
+    {
+        assert!(OWNER.with(Cell::get), "RenderDevice is dropped outside the render thread");
+        unsafe { drop(Box::from_raw(RD as *mut Self)); RD = 0 as *const _; }
+        OWNER.with(|o| o.set(false));
     }
-    pub fn uninit() { unsafe { drop(Box::from_raw(RD as *mut Self)); RD = 0 as *const _; } }
 
     #[cfg(windows)]
     fn new() -> Self
@@ -103,7 +122,7 @@ impl RenderDevice
         }
     }
 
-    pub fn do_render(&self) -> Result<bool, Box<Error>>
+    pub fn do_render(&self) -> Result<(), Box<Error>>
     {
         match *self
         {
@@ -112,6 +131,15 @@ impl RenderDevice
             RenderDevice::DirectX12(ref d) => unimplemented!("need to change")
         }
     }
+    pub fn do_render_and_capture(&self) -> Result<CapturedImage, Box<Error>>
+    {
+        match *self
+        {
+            RenderDevice::Vulkan(ref d) => d.do_render_and_capture().map_err(From::from),
+            #[cfg(windows)]
+            RenderDevice::DirectX12(_) => unimplemented!()
+        }
+    }
     pub fn wait_render_ready(&self) -> Result<(), Box<Error>>
     {
         match *self
@@ -121,6 +149,67 @@ impl RenderDevice
             RenderDevice::DirectX12(ref d) => unimplemented!()
         }
     }
+    /// A slot of the animated values, initialized with the identity. Fails when `ANIMATED_SLOTS` slots are used
+    pub fn new_animated_slot(&self) -> Result<AnimatedSlot, Box<Error>>
+    {
+        match *self
+        {
+            RenderDevice::Vulkan(ref d) => d.new_animated_slot().map(AnimatedSlot).ok_or_else(|| From::from("All the animated slots are used")),
+            #[cfg(windows)]
+            RenderDevice::DirectX12(_) => unimplemented!()
+        }
+    }
+    /// The values read by the draws bound to the slot, from the next rendered frame
+    pub fn set_animated(&self, slot: &AnimatedSlot, values: Animated)
+    {
+        match *self
+        {
+            RenderDevice::Vulkan(ref d) => d.set_animated(slot.0, values),
+            #[cfg(windows)]
+            RenderDevice::DirectX12(_) => unimplemented!()
+        }
+    }
+    /// A vertex buffer rewritten without waiting for the frames in flight: a region of `capacity` bytes per swapchain image
+    pub fn new_dynamic_vertices(&self, capacity: usize) -> Result<Box<DynamicVertices>, Box<Error>>
+    {
+        match *self
+        {
+            RenderDevice::Vulkan(ref d) => d.new_dynamic_vertices(capacity).map(|x| box x as _).map_err(From::from),
+            #[cfg(windows)]
+            RenderDevice::DirectX12(_) => unimplemented!()
+        }
+    }
+    /// The vertices read by the draws from the next rendered frame: each image gets them right before its commands are submitted.
+    /// Fails if they are longer than the capacity
+    pub fn set_dynamic_vertices(&self, vertices: &DynamicVertices, data: &[u8]) -> Result<(), Box<Error>>
+    {
+        match *self
+        {
+            RenderDevice::Vulkan(ref d) => d.set_dynamic_vertices(vertices, data),
+            #[cfg(windows)]
+            RenderDevice::DirectX12(_) => unimplemented!()
+        }
+    }
+    /// The format which is actually used for textures requested in the format: the format itself or one of its fallbacks.
+    /// None if neither of them can be sampled on the device
+    pub fn supported_color_format(&self, format: ColorFormat, mipmaps: bool) -> Option<ColorFormat>
+    {
+        match *self
+        {
+            RenderDevice::Vulkan(ref d) => d.resolve_color_format(format, mipmaps),
+            #[cfg(windows)]
+            RenderDevice::DirectX12(_) => unimplemented!()
+        }
+    }
+    pub fn statistics(&self) -> RenderStatistics
+    {
+        match *self
+        {
+            RenderDevice::Vulkan(ref d) => d.statistics(),
+            #[cfg(windows)]
+            RenderDevice::DirectX12(_) => unimplemented!()
+        }
+    }
     pub fn get_builtin_vertex_array<'d>(&'d self, key: BuiltinResourceKey) -> Result<Box<VertexArray + 'd>, Box<Error>>
     {
         match *self
@@ -131,6 +220,49 @@ impl RenderDevice
         }
     }
 
+    /// Vertices in a buffer of the resource block, drawn with 32-bit indices in another buffer if any.
+    /// Buffers are specified by their indices in the contents passed to `create_resources`
+    pub fn get_vertex_array<'d>(&self, resources: &'d ResourceBlock, vertex_buffer: usize, vertex_count: usize,
+        index_buffer: Option<(usize, usize)>) -> Box<VertexArray + 'd>
+    {
+        match *self
+        {
+            RenderDevice::Vulkan(ref d) => box d.get_vertex_array(resources, vertex_buffer, vertex_count, index_buffer) as _,
+            #[cfg(windows)]
+            RenderDevice::DirectX12(_) => unimplemented!()
+        }
+    }
+    /// Vertices from `offset` bytes in the dynamic vertex buffer(in the region of the image which the commands are recorded for)
+    pub fn get_dynamic_vertex_array<'d>(&self, vertices: &'d DynamicVertices, offset: usize, vertex_count: usize) -> Box<VertexArray + 'd>
+    {
+        match *self
+        {
+            RenderDevice::Vulkan(ref d) => box d.get_dynamic_vertex_array(vertices, offset, vertex_count) as _,
+            #[cfg(windows)]
+            RenderDevice::DirectX12(_) => unimplemented!()
+        }
+    }
+    /// Replaces a region of a FrequentlyUpdated texture in the resource block with the tightly packed pixels
+    pub fn update_texture(&self, resources: &ResourceBlock, index: usize, offset: (u32, u32), size: Size2U, pixels: &[u8]) -> Result<(), Box<Error>>
+    {
+        match *self
+        {
+            RenderDevice::Vulkan(ref d) => d.update_texture(resources, index, offset, size, pixels).map_err(From::from),
+            #[cfg(windows)]
+            RenderDevice::DirectX12(_) => unimplemented!()
+        }
+    }
+    /// Makes a texture in the resource block usable in draws(e.g. `draw_sdf`)
+    pub fn bind_texture(&self, resources: &ResourceBlock, index: usize) -> Result<Box<TextureBinding>, Box<Error>>
+    {
+        match *self
+        {
+            RenderDevice::Vulkan(ref d) => d.bind_texture(resources, index).map(|x| box x as _).map_err(From::from),
+            #[cfg(windows)]
+            RenderDevice::DirectX12(_) => unimplemented!()
+        }
+    }
+
     pub(self) fn ensure_vk(&self) -> &vk::RenderDevice
     {
         match self { &RenderDevice::Vulkan(ref v) => v, _ => panic!("unexpected") }
@@ -144,7 +276,17 @@ pub enum BuiltinResourceKey
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct BufferContent { pub kind: BufferKind, pub bytesize: usize }
+pub struct BufferContent<'p>
+{
+    pub kind: BufferKind, pub bytesize: usize,
+    /// Uploaded into the buffer on creation(at most `bytesize` bytes)
+    pub initial_data: Option<&'p [u8]>
+}
+impl<'p> BufferContent<'p>
+{
+    pub fn new(kind: BufferKind, bytesize: usize) -> Self { BufferContent { kind, bytesize, initial_data: None } }
+    pub fn with_data(kind: BufferKind, data: &'p [u8]) -> Self { BufferContent { kind, bytesize: data.len(), initial_data: Some(data) } }
+}
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BufferKind { Vertex, Index, Constant }
 #[derive(Debug, PartialEq, Eq)]
@@ -160,18 +302,200 @@ impl<'p> TextureUsage<'p>
 #[derive(Debug, PartialEq, Eq)]
 pub struct TextureParam<'p>
 {
-    pub size: Size2U, pub layers: u32, pub color: ColorFormat, pub usage: TextureUsage<'p>
+    pub size: Size2U, pub layers: u32, pub color: ColorFormat, pub usage: TextureUsage<'p>,
+    pub sampler: SamplerParam,
+    /// Generate a full mipmap chain from the initial pixels(only for immutable textures)
+    pub mipmaps: bool
 }
 impl<'p> Default for TextureParam<'p>
 {
     fn default() -> Self
     {
-        TextureParam { size: Size2U(1, 1), layers: 1, color: ColorFormat::WithAlpha, usage: TextureUsage::FrequentlyUpdated }
+        TextureParam
+        {
+            size: Size2U(1, 1), layers: 1, color: ColorFormat::WithAlpha, usage: TextureUsage::FrequentlyUpdated,
+            sampler: SamplerParam::default(), mipmaps: false
+        }
+    }
+}
+impl<'p> TextureParam<'p>
+{
+    pub(self) fn generates_mipmaps(&self) -> bool { self.mipmaps && self.usage.is_immutable() }
+    /// Levels down to 1x1: floor(log2(the longer side)) + 1
+    pub(self) fn mip_levels(&self) -> u32
+    {
+        if self.generates_mipmaps() { 32 - ::std::cmp::max(self.size.x(), self.size.y()).leading_zeros() } else { 1 }
     }
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ColorFormat { Grayscale, Default, WithAlpha }
+pub enum Filter { Nearest, Linear }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressMode { ClampToEdge, Repeat, MirroredRepeat }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplerParam
+{
+    pub mag_filter: Filter, pub min_filter: Filter, pub mipmap_filter: Filter,
+    pub address_u: AddressMode, pub address_v: AddressMode,
+    /// Maximum anisotropy; None disables anisotropic filtering
+    pub anisotropy: Option<u32>
+}
+impl Default for SamplerParam
+{
+    fn default() -> Self
+    {
+        SamplerParam
+        {
+            mag_filter: Filter::Linear, min_filter: Filter::Linear, mipmap_filter: Filter::Linear,
+            address_u: AddressMode::ClampToEdge, address_v: AddressMode::ClampToEdge, anisotropy: None
+        }
+    }
+}
+impl SamplerParam
+{
+    pub fn nearest() -> Self
+    {
+        SamplerParam { mag_filter: Filter::Nearest, min_filter: Filter::Nearest, mipmap_filter: Filter::Nearest, .. Default::default() }
+    }
+    pub fn address(self, mode: AddressMode) -> Self { SamplerParam { address_u: mode, address_v: mode, .. self } }
+    pub fn anisotropy(self, max: u32) -> Self { SamplerParam { anisotropy: Some(max), .. self } }
+}
+
+/// What happens to the previous contents of a render target when rendering into it begins
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadOp
+{
+    /// Fill the whole target with the color
+    Clear(Color),
+    /// Preserve the previous contents(e.g. for overlays)
+    Load,
+    /// Previous contents are undefined; use when every pixel will be overwritten
+    DontCare
+}
+/// Per-draw parameters of the signed distance field mode.
+/// Distances are in the encoded values of the field(0.5 + d / (2 * spread) for fields generated by `vector::SdfParams`)
+#[repr(C)] #[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SdfStyle
+{
+    /// Scale(xy) and offset(zw) applied to the vertex positions in the normalized device coordinates
+    pub placement: [f32; 4],
+    /// Scale(xy) and offset(zw) applied to the texture coordinates(e.g. to draw an entry of a glyph atlas)
+    pub uv_placement: [f32; 4],
+    pub fill: Color, pub outline_color: Color, pub glow_color: Color, pub shadow_color: Color,
+    /// The value at the edge of the shape
+    pub threshold: f32,
+    /// Width of the antialiased edge in screen pixels
+    pub smoothing: f32,
+    /// Width of the outline outside the edge
+    pub outline_width: f32,
+    /// Distance over which the glow fades out from the outer edge(including the outline)
+    pub glow_width: f32,
+    /// Offset of the shadow in texture coordinates
+    pub shadow_offset: [f32; 2],
+    /// Blur width of the shadow edge
+    pub shadow_softness: f32,
+    pub opacity: f32
+}
+/// Push constants up to 128 bytes are available on every device(`maxPushConstantsSize`): fails to compile if the style is larger
+#[allow(dead_code)] const SDF_STYLE_FITS_PUSH_CONSTANTS: [(); 0] = [(); (::std::mem::size_of::<SdfStyle>() > 128) as usize];
+impl SdfStyle
+{
+    const NONE: Color = Color(0.0, 0.0, 0.0, 0.0);
+
+    pub fn new(fill: Color) -> Self
+    {
+        SdfStyle
+        {
+            placement: [1.0, 1.0, 0.0, 0.0], uv_placement: [1.0, 1.0, 0.0, 0.0], fill, outline_color: Self::NONE, glow_color: Self::NONE, shadow_color: Self::NONE,
+            threshold: 0.5, smoothing: 1.0, outline_width: 0.0, glow_width: 0.0, shadow_offset: [0.0; 2], shadow_softness: 0.0, opacity: 1.0
+        }
+    }
+    pub fn place(self, scale: (f32, f32), offset: (f32, f32)) -> Self { SdfStyle { placement: [scale.0, scale.1, offset.0, offset.1], .. self } }
+    /// Samples the region of the texture(left, top, right, bottom in texture coordinates; `font::AtlasEntry::uv`)
+    pub fn uv_rect(self, rect: [f32; 4]) -> Self { SdfStyle { uv_placement: [rect[2] - rect[0], rect[3] - rect[1], rect[0], rect[1]], .. self } }
+    pub fn threshold(self, threshold: f32) -> Self { SdfStyle { threshold, .. self } }
+    pub fn smoothing(self, smoothing: f32) -> Self { SdfStyle { smoothing, .. self } }
+    pub fn outline(self, width: f32, color: Color) -> Self { SdfStyle { outline_width: width, outline_color: color, .. self } }
+    pub fn glow(self, width: f32, color: Color) -> Self { SdfStyle { glow_width: width, glow_color: color, .. self } }
+    pub fn shadow(self, offset: [f32; 2], softness: f32, color: Color) -> Self
+    {
+        SdfStyle { shadow_offset: offset, shadow_softness: softness, shadow_color: color, .. self }
+    }
+    pub fn opacity(self, opacity: f32) -> Self { SdfStyle { opacity, .. self } }
+}
+
+/// Per-draw parameters of the vector mode: colored triangles(`vector::ColoredVertex`, e.g. a `vector::MeshCache`)
+#[repr(C)] #[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VectorStyle
+{
+    /// Scale(xy) and offset(zw) mapping the vertex positions into the normalized device coordinates
+    pub placement: [f32; 4],
+    pub opacity: f32
+}
+impl Default for VectorStyle { fn default() -> Self { VectorStyle { placement: [1.0, 1.0, 0.0, 0.0], opacity: 1.0 } } }
+impl VectorStyle
+{
+    pub fn place(self, scale: (f32, f32), offset: (f32, f32)) -> Self { VectorStyle { placement: [scale.0, scale.1, offset.0, offset.1], .. self } }
+    pub fn opacity(self, opacity: f32) -> Self { VectorStyle { opacity, .. self } }
+}
+
+/// Values read by the draws when a frame is rendered: changing them(`RenderDevice::set_animated`) does not need the commands to be recorded again.
+/// Applied after the placement of the styles: positions in the normalized device coordinates are scaled and offset, and colors are multiplied by the opacity
+#[repr(C)] #[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Animated { pub scale: [f32; 2], pub offset: [f32; 2], pub opacity: f32 }
+impl Animated
+{
+    pub const IDENTITY: Animated = Animated { scale: [1.0, 1.0], offset: [0.0, 0.0], opacity: 1.0 };
+}
+/// Slots of the animated values including the identity, which is always bound to the draws without slots
+pub const ANIMATED_SLOTS: usize = 32;
+/// A slot of the animated values(`RenderDevice::new_animated_slot`), freed on drop
+pub struct AnimatedSlot(usize);
+impl AnimatedSlot
+{
+    pub(self) fn index(&self) -> usize { self.0 }
+}
+impl Drop for AnimatedSlot
+{
+    fn drop(&mut self)
+    {
+        match *RenderDevice::get()
+        {
+            RenderDevice::Vulkan(ref d) => d.free_animated_slot(self.0),
+            #[cfg(windows)]
+            RenderDevice::DirectX12(_) => unimplemented!()
+        }
+    }
+}
+
+/// Colored triangles of a mesh cache in a resource block(a vertex and an index buffer), drawn by `draw_vector`
+pub struct VectorMesh
+{
+    resources: Box<ResourceBlock>, vertex_count: usize, index_count: usize,
+    /// Size of the image in the coordinates of the vertices
+    pub size: Size2F
+}
+impl VectorMesh
+{
+    /// None for empty meshes
+    pub fn new(cache: &MeshCache) -> Result<Option<Self>, Box<Error>>
+    {
+        if cache.index_count() == 0 { return Ok(None); }
+        let resources = RenderDevice::get().create_resources(&[
+            BufferContent::with_data(BufferKind::Vertex, cache.vertex_bytes()), BufferContent::with_data(BufferKind::Index, cache.index_bytes())
+        ], &[])?;
+        Ok(Some(VectorMesh { resources, vertex_count: cache.vertex_count(), index_count: cache.index_count(), size: cache.size }))
+    }
+    pub fn vertices<'d>(&'d self) -> Box<VertexArray + 'd>
+    {
+        RenderDevice::get().get_vertex_array(&*self.resources, 0, self.vertex_count, Some((1, self.index_count)))
+    }
+}
+
+/// Contents of a rendered frame: RGBA8, top to bottom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedImage { pub size: Size2U, pub pixels: Vec<u8> }
 pub trait ResourceBlock {}
+pub trait DynamicVertices {}
 
 pub trait RenderCommands
 {
@@ -180,10 +504,43 @@ pub trait RenderCommands
 pub trait RenderCommandsBasic
 {
     fn prepare_render_targets(&mut self, targets: &[&RenderTarget]);
-    fn set_render_target(&mut self, target: &RenderTarget);
-    fn execute_subcommands_into(&mut self, target: &RenderTarget, subcommands: &[&CommandBuffer]);
+    fn set_render_target(&mut self, target: &RenderTarget, load: LoadOp);
+    fn execute_subcommands_into(&mut self, target: &RenderTarget, load: LoadOp, subcommands: &[&CommandBuffer]);
     fn draw(&mut self, vertices: &VertexArray, instance_count: usize);
+    /// Draws the vertices sampling the texture as a signed distance field
+    fn draw_sdf(&mut self, vertices: &VertexArray, field: &TextureBinding, style: &SdfStyle);
+    /// `draw_sdf` for multi-channel fields(the distance is the median of RGB; `font::GlyphMode::Msdf`)
+    fn draw_msdf(&mut self, vertices: &VertexArray, field: &TextureBinding, style: &SdfStyle);
+    /// Draws the triangles of colored vertices(`vector::ColoredVertex`)
+    fn draw_vector(&mut self, vertices: &VertexArray, style: &VectorStyle);
+    /// Animated values applied to the following draws after their styles; None for the identity
+    fn set_animation(&mut self, slot: Option<&AnimatedSlot>);
+    /// Animated values applied after those of `set_animation`(e.g. to the whole scene during a transition); None for the identity
+    fn set_layer_animation(&mut self, slot: Option<&AnimatedSlot>);
+    /// Restricts the following draws to the rectangle(pixels: left, top, right, bottom) until the render target is changed.
+    /// None draws into the whole target
+    fn set_clip(&mut self, rect: Option<[u32; 4]>);
 }
 pub trait RenderTarget {}
 pub trait CommandBuffer {}
 pub trait VertexArray {}
+pub trait TextureBinding {}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test] fn mip_levels()
+    {
+        let pixels = [0u8; 4];
+        let levels = |w, h| TextureParam { size: Size2U(w, h), usage: TextureUsage::Immutable(&pixels), mipmaps: true, .. Default::default() }.mip_levels();
+        assert_eq!(levels(1, 1), 1);
+        assert_eq!(levels(256, 64), 9);
+        assert_eq!(levels(300, 200), 9);
+        assert_eq!(levels(512, 1024), 11);
+        // only immutable textures have mipmaps
+        assert_eq!(TextureParam { size: Size2U(256, 256), mipmaps: true, .. Default::default() }.mip_levels(), 1);
+        assert_eq!(TextureParam { size: Size2U(256, 256), usage: TextureUsage::Immutable(&pixels), .. Default::default() }.mip_levels(), 1);
+    }
+}
@@ -0,0 +1,132 @@
+//! Render statistics
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Counters of commands recorded into a command buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CommandStatistics
+{
+    pub draw_calls: usize, pub instances: usize, pub pipeline_binds: usize, pub descriptor_binds: usize
+}
+
+/// State bound while recording a command buffer. Binds of the bound objects are skipped; the others are counted.
+/// Objects are identified by their addresses, which are stable while the recorder borrows them
+#[derive(Debug, Clone, Default)]
+pub struct BindTracker { pipeline: Option<usize>, descriptor_set: Option<usize>, animation: Option<[usize; 2]>, pub stats: CommandStatistics }
+impl BindTracker
+{
+    /// Whether the pipeline must be bound
+    pub fn pipeline<T>(&mut self, pipeline: &T) -> bool
+    {
+        let key = pipeline as *const T as usize;
+        if self.pipeline == Some(key) { return false; }
+        self.pipeline = Some(key);
+        // sets bound with another pipeline layout may be disturbed
+        self.descriptor_set = None;
+        self.animation = None;
+        self.stats.pipeline_binds += 1;
+        true
+    }
+    /// Whether the descriptor set must be bound
+    pub fn descriptor_set<T>(&mut self, set: &T) -> bool
+    {
+        let key = set as *const T as usize;
+        if self.descriptor_set == Some(key) { return false; }
+        self.descriptor_set = Some(key);
+        self.stats.descriptor_binds += 1;
+        true
+    }
+    /// Whether the animated values must be bound with the slots of the layer and the element
+    pub fn animation(&mut self, slots: [usize; 2]) -> bool
+    {
+        if self.animation == Some(slots) { return false; }
+        self.animation = Some(slots);
+        self.stats.descriptor_binds += 1;
+        true
+    }
+    pub fn draw(&mut self, instances: usize) { self.stats.draw_calls += 1; self.stats.instances += instances; }
+}
+
+/// A snapshot of the counters in the render layer
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RenderStatistics
+{
+    /// Commands executed in the last rendered frame(commands in secondary command buffers are not counted)
+    pub last_frame: CommandStatistics,
+    pub frames_rendered: u64,
+    /// Total bytes transferred from the host since startup
+    pub bytes_uploaded: u64,
+    /// Device memory currently allocated, keyed by memory type index
+    pub memory_allocated: BTreeMap<u32, u64>,
+    pub live_resource_blocks: usize
+}
+
+/// Collects statistics from the backend
+pub struct StatisticsCollector(Mutex<RenderStatistics>);
+impl StatisticsCollector
+{
+    pub fn new() -> Self { StatisticsCollector(Mutex::new(RenderStatistics::default())) }
+    pub fn snapshot(&self) -> RenderStatistics { self.0.lock().unwrap().clone() }
+
+    pub fn frame_rendered(&self, commands: CommandStatistics)
+    {
+        let mut s = self.0.lock().unwrap();
+        s.last_frame = commands; s.frames_rendered += 1;
+    }
+    pub fn uploaded(&self, bytes: u64) { self.0.lock().unwrap().bytes_uploaded += bytes; }
+    pub fn allocated(&self, memory_type: u32, bytes: u64)
+    {
+        *self.0.lock().unwrap().memory_allocated.entry(memory_type).or_insert(0) += bytes;
+    }
+    pub fn freed(&self, memory_type: u32, bytes: u64)
+    {
+        if let Some(b) = self.0.lock().unwrap().memory_allocated.get_mut(&memory_type) { *b -= bytes; }
+    }
+    pub fn resource_block_created(&self) { self.0.lock().unwrap().live_resource_blocks += 1; }
+    pub fn resource_block_dropped(&self) { self.0.lock().unwrap().live_resource_blocks -= 1; }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test] fn binds_and_draws()
+    {
+        let (pipeline, sets) = (1u8, [2u8, 3u8]);
+        let mut t = BindTracker::default();
+        for n in 0 .. 4
+        {
+            t.pipeline(&pipeline);
+            t.descriptor_set(&sets[n / 2]);
+            t.draw(1);
+        }
+        assert_eq!(t.stats, CommandStatistics { draw_calls: 4, instances: 4, pipeline_binds: 1, descriptor_binds: 2 });
+        assert!(!t.pipeline(&pipeline));
+        assert!(t.descriptor_set(&sets[0]));
+        assert!(t.animation([0, 0]) && !t.animation([0, 0]) && t.animation([1, 0]));
+        // rebound after the pipeline changes
+        t.pipeline(&sets[0]);
+        assert!(t.animation([1, 0]));
+    }
+    #[test] fn collector()
+    {
+        let c = StatisticsCollector::new();
+        c.allocated(0, 4096);
+        c.allocated(1, 1024);
+        c.uploaded(1024);
+        c.resource_block_created();
+        c.frame_rendered(CommandStatistics { draw_calls: 3, instances: 3, pipeline_binds: 1, descriptor_binds: 2 });
+        // nothing is uploaded while the frames are rendered without changes
+        c.frame_rendered(CommandStatistics { draw_calls: 3, instances: 3, pipeline_binds: 1, descriptor_binds: 2 });
+        let s = c.snapshot();
+        assert_eq!((s.frames_rendered, s.bytes_uploaded, s.last_frame.draw_calls, s.live_resource_blocks), (2, 1024, 3, 1));
+        c.freed(0, 4096);
+        c.resource_block_dropped();
+        let s = c.snapshot();
+        assert_eq!(s.memory_allocated.get(&0), Some(&0));
+        assert_eq!(s.memory_allocated.get(&1), Some(&1024));
+        assert_eq!(s.live_resource_blocks, 0);
+    }
+}
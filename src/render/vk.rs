@@ -5,12 +5,13 @@ use ws_common::WindowServer;
 use ferrite::traits::*;
 #[cfg(feature = "debug")] use libc;
 use metrics::*;
-use event::*;
-use std::sync::Arc;
-use std::mem::replace;
 use std::error::Error;
 use std::borrow::Cow;
 use std::ops::Deref;
+use super::LoadOp;
+use super::stats::*;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
 
 const APPNAME: &'static str = "dc2017";
 
@@ -66,17 +67,18 @@ const UNIT_RECT_VERTICES: &'static [PosUV; 4] = &[
     PosUV::new([ 1.0,  1.0, 0.0, 1.0], [1.0, 1.0])
 ];
 
-pub struct VertexBufferSlice<'p> { buf: &'p fe::Buffer, offset: usize, count: usize }
+/// `image_stride` bytes are added to the offset per swapchain image the commands are recorded for(the regions of `DynamicVertices`)
+pub struct VertexBufferSlice<'p> { buf: &'p fe::Buffer, offset: usize, count: usize, image_stride: usize }
 pub struct IndexBufferSlice<'p> { buf: &'p fe::Buffer, format: fe::IndexType, offset: usize, count: usize }
 
 pub struct RenderDeviceCore
 {
     instance: fe::Instance, adapter: fe::PhysicalDevice, device: fe::Device,
     #[cfg(feature = "debug")] debug_report: fe::DebugReportCallback,
-    graphics_queue: (u32, fe::Queue), transfer_queue: (u32, fe::Queue),
+    graphics_queue: (u32, fe::Queue), #[allow(dead_code)] transfer_queue: (u32, fe::Queue),
 
     agent_str: LazyData<String>, devprops: LazyData<fe::vk::VkPhysicalDeviceProperties>, memindices: MemoryIndices,
-    builtin_data: LazyData<(fe::DeviceMemory, fe::Buffer)>
+    builtin_data: LazyData<(fe::DeviceMemory, fe::Buffer)>, stats: StatisticsCollector, sampler_anisotropy: bool
 }
 impl RenderDeviceCore
 {
@@ -106,10 +108,12 @@ impl RenderDeviceCore
                 let c = ::std::cmp::min(2, queue_families.queue_count(graphics_qf));
                 (c, vec![fe::DeviceQueueCreateInfo(graphics_qf, vec![0.0; c as usize])])
             };
+        let sampler_anisotropy = adapter.features().samplerAnisotropy != fe::vk::VK_FALSE;
         let device =
         {
             let devbuilder = fe::DeviceBuilder::new(&adapter)
-                .add_extension("VK_KHR_swapchain");
+                .add_extension("VK_KHR_swapchain")
+                .enable_feature(&fe::vk::VkPhysicalDeviceFeatures { samplerAnisotropy: sampler_anisotropy as _, .. Default::default() });
             #[cfg(feature = "debug")]
             let devbuilder = devbuilder.add_layer("VK_LAYER_LUNARG_standard_validation");
             devbuilder.add_queues(queues).create().expect("Failed to create device")
@@ -128,14 +132,16 @@ impl RenderDeviceCore
             Ok(RenderDeviceCore
             {
                 instance, adapter, device, debug_report, graphics_queue: gq, transfer_queue: tq, agent_str: LazyData::INIT,
-                devprops: LazyData::INIT, memindices, builtin_data: LazyData::INIT
+                devprops: LazyData::INIT, memindices, builtin_data: LazyData::INIT, stats: StatisticsCollector::new(),
+                sampler_anisotropy
             })
         }
         #[cfg(not(feature = "debug"))] {
             Ok(RenderDeviceCore
             {
                 instance, adapter, device, graphics_queue: gq, transfer_queue: tq, agent_str: LazyData::INIT,
-                devprops: LazyData::INIT, memindices, builtin_data: LazyData::INIT
+                devprops: LazyData::INIT, memindices, builtin_data: LazyData::INIT, stats: StatisticsCollector::new(),
+                sampler_anisotropy
             })
         }
     }
@@ -160,6 +166,7 @@ impl RenderDeviceCore
             let breq = buf.requirements();
             let mem = fe::DeviceMemory::allocate(&self.device, breq.size as _, self.memindices.devlocal)
                 .expect("Failed to allocate a device memory");
+            self.stats.allocated(self.memindices.devlocal, breq.size as _);
             buf.bind(&mem, 0).expect("Failed to bind a device memory with a buffer");
             let sbuf = fe::BufferDesc::new(bsize, fe::BufferUsage::TRANSFER_SRC).create(&self.device)
                 .expect("Failed to create abuilt-in buffer for staging");
@@ -174,7 +181,8 @@ impl RenderDeviceCore
                 mv.unit_rect.clone_from_slice(UNIT_RECT_VERTICES);
                 mi.unit_rect.copy_from_slice(&[0, 1, 2, 2, 1, 3]);
             }).expect("Failed to initialize a built-in buffer");
-            RenderDevice::imm_transferring(|mut rec|
+            // the buffer is used on the graphics queue: no ownership transfer is needed
+            RenderDevice::imm_submission(|mut rec|
             {
                 rec.pipeline_barrier(fe::PipelineStageFlags::ALL_COMMANDS, fe::PipelineStageFlags::TRANSFER, false, &[], &[fe::vk::VkBufferMemoryBarrier
                 {
@@ -186,12 +194,13 @@ impl RenderDeviceCore
                     .. Default::default()
                 }], &[]);
                 rec.copy_buffer(&sbuf, &buf, &[fe::vk::VkBufferCopy { srcOffset: 0, dstOffset: 0, size: bsize as _ }]);
-                rec.pipeline_barrier(fe::PipelineStageFlags::ALL_COMMANDS, fe::PipelineStageFlags::TRANSFER, false, &[], &[fe::vk::VkBufferMemoryBarrier
+                rec.pipeline_barrier(fe::PipelineStageFlags::TRANSFER, fe::PipelineStageFlags::VERTEX_INPUT, false, &[], &[fe::vk::VkBufferMemoryBarrier
                 {
                     srcAccessMask: fe::vk::VK_ACCESS_TRANSFER_WRITE_BIT, dstAccessMask: fe::vk::VK_ACCESS_VERTEX_ATTRIBUTE_READ_BIT | fe::vk::VK_ACCESS_INDEX_READ_BIT,
                     buffer: buf.native_ptr(), offset: 0, size: bsize as _, .. Default::default()
                 }], &[]);
             }).expect("Failure while transferring data in a built-in buffer");
+            self.stats.uploaded(bsize as _);
             (mem, buf)
         }).1
     }
@@ -204,9 +213,42 @@ impl Drop for RenderDevice
 pub struct MemoryIndices { devlocal: u32, host: u32 }
 pub struct RenderDevice
 {
-    surface: fe::Surface, swapchain: fe::Swapchain, rt_views: Vec<fe::ImageView>,
-    render_control: RenderControl, primary_rt_pass: fe::RenderPass, rtsc: Vec<fe::Framebuffer>,
-    rtcp: fe::CommandPool, rtcmds: Vec<fe::CommandBuffer>, buffer_ready: fe::Semaphore, present_ready: fe::Semaphore
+    surface: fe::Surface, swapchain: fe::Swapchain, sc_format: fe::vk::VkFormat, rt_views: Vec<fe::ImageView>,
+    render_control: RenderControl, primary_rt_pass: RenderPassSet, rtsc: Vec<fe::Framebuffer>,
+    texture_layout: fe::DescriptorSetLayout, animated: AnimatedValues, sdf: SdfPipeline, msdf: SdfPipeline, vector: VectorPipeline,
+    /// Written when the images are submitted; the dropped ones are forgotten then
+    dynamic_vertices: RefCell<Vec<Weak<DynamicVertexBuffer>>>,
+    rtcp: fe::CommandPool, rtcmds: Vec<fe::CommandBuffer>, rtcmd_stats: Vec<Cell<CommandStatistics>>,
+    /// Created at the first capture and reused
+    capture: RefCell<Option<CaptureResources>>
+}
+/// Readback buffer of the screenshots and the commands copying into it
+struct CaptureResources
+{
+    // the command buffer is freed with the pool; the buffer is destroyed before the memory
+    cmd: Vec<fe::CommandBuffer>, pool: fe::CommandPool, buffer: fe::Buffer, memory: fe::DeviceMemory, bytesize: usize
+}
+impl CaptureResources
+{
+    fn new(bytesize: usize) -> fe::Result<Self>
+    {
+        let core = RenderDeviceCore::get();
+        let buffer = fe::BufferDesc::new(bytesize, fe::BufferUsage::TRANSFER_DEST).create(&core.device)?;
+        let memory_size = buffer.requirements().size;
+        let memory = fe::DeviceMemory::allocate(&core.device, memory_size as _, core.memindices.host)?;
+        buffer.bind(&memory, 0)?;
+        core.stats.allocated(core.memindices.host, memory_size as _);
+        let pool = fe::CommandPool::new(&core.device, core.graphics_queue.0, true, false)?;
+        Ok(CaptureResources { cmd: pool.alloc(1, true)?, pool, buffer, memory, bytesize })
+    }
+}
+impl Drop for CaptureResources
+{
+    fn drop(&mut self)
+    {
+        let core = RenderDeviceCore::get();
+        core.stats.freed(core.memindices.host, self.buffer.requirements().size as _);
+    }
 }
 impl RenderDevice
 {
@@ -236,7 +278,7 @@ impl RenderDevice
         let fmt = format.format;
         let (width, height) = target.client_size();
         let swapchain = fe::SwapchainBuilder::new(&surface, ::std::cmp::max(2, caps.minImageCount), format,
-            fe::Extent2D(width as _, height as _), fe::ImageUsage::COLOR_ATTACHMENT)
+            fe::Extent2D(width as _, height as _), fe::ImageUsage::COLOR_ATTACHMENT.transfer_src())
             .present_mode(present_mode).enable_clip().composite_alpha(fe::CompositeAlpha::Opaque)
             .pre_transform(fe::SurfaceTransform::Identity).create(&core.device).expect("Failed to create a Swapchain");
         let images = swapchain.get_images().expect("Failed to get swapchain buffers");
@@ -244,16 +286,19 @@ impl RenderDevice
         {
             aspect_mask: fe::AspectMask::COLOR, mip_levels: 0 .. 1, array_layers: 0 .. 1
         })).collect::<Result<Vec<_>, _>>().expect("Failed to create views to each swapchain buffers");
-        let primary_rt_pass = fe::RenderPassBuilder::new()
-            .add_attachment(fe::vk::VkAttachmentDescription
-            {
-                loadOp: fe::vk::VK_ATTACHMENT_LOAD_OP_CLEAR, storeOp: fe::vk::VK_ATTACHMENT_STORE_OP_STORE,
-                format: fmt, initialLayout: fe::ImageLayout::ColorAttachmentOpt as _, finalLayout: fe::ImageLayout::PresentSrc as _,
-                samples: 1, flags: 0, stencilLoadOp: fe::vk::VK_ATTACHMENT_LOAD_OP_DONT_CARE, stencilStoreOp: fe::vk::VK_ATTACHMENT_STORE_OP_DONT_CARE
-            })
-            .add_subpass(fe::SubpassDescription::new().add_color_output(0, fe::ImageLayout::ColorAttachmentOpt, None))
-            .create(&core.device).expect("Failed to create a render pass object for primary render targets");
-        let rtsc: Vec<_> = views.iter().map(|v| fe::Framebuffer::new(&primary_rt_pass, &[v], v.size(), 1))
+        let primary_rt_pass = RenderPassSet::new(&core.device, fmt, ResourceAfterUsage::Displayed)
+            .expect("Failed to create render pass objects for primary render targets");
+        let texture_layout = fe::DescriptorSetLayout::new(&core.device, &fe::DSLBindings
+        {
+            combined_image_sampler: Some((0, 1, fe::ShaderStage::FRAGMENT, vec![])), .. fe::DSLBindings::empty()
+        }).expect("Failed to create a descriptor set layout for textures");
+        let animated = AnimatedValues::new(views.len()).expect("Failed to create the buffer of the animated values");
+        let sdf = SdfPipeline::new(&core.device, &texture_layout, &animated.layout, &primary_rt_pass.clear, false)
+            .expect("Failed to create the pipeline for signed distance fields");
+        let msdf = SdfPipeline::new(&core.device, &texture_layout, &animated.layout, &primary_rt_pass.clear, true)
+            .expect("Failed to create the pipeline for multi-channel signed distance fields");
+        let vector = VectorPipeline::new(&core.device, &animated.layout, &primary_rt_pass.clear).expect("Failed to create the pipeline for vector meshes");
+        let rtsc: Vec<_> = views.iter().map(|v| fe::Framebuffer::new(&primary_rt_pass.clear, &[v], v.size(), 1))
             .collect::<Result<_, _>>().expect("Failed to create render targets of each swapchain buffers");
         let rtcp = fe::CommandPool::new(&core.device, core.graphics_queue.0, false, false).expect("Failed to create a CommandPool");
         let rtcmds = rtcp.alloc(rtsc.len() as _, true).expect("Failed to allocate command buffers for rendering to swapchain buffers");
@@ -278,9 +323,10 @@ impl RenderDevice
         #[cfg(feature = "debug")]
         Ok(RenderDevice
         {
-            render_control: RenderControl::init(&core.device, &swapchain), surface, swapchain, rt_views: views, primary_rt_pass, rtsc,
-            rtcp, rtcmds, buffer_ready: fe::Semaphore::new(&core.device).expect("Failed to create a semaphore(buffer_ready)"),
-            present_ready: fe::Semaphore::new(&core.device).expect("Failed to create a semaphore(present_ready)")
+            render_control: RenderControl::init(&core.device, rtsc.len()).expect("Failed to initialize frame synchronization objects"),
+            rtcmd_stats: vec![Cell::new(CommandStatistics::default()); rtcmds.len()],
+            surface, swapchain, sc_format: fmt, rt_views: views, primary_rt_pass, rtsc, rtcp, rtcmds, texture_layout, animated, sdf, msdf, vector,
+            dynamic_vertices: RefCell::new(Vec::new()), capture: RefCell::new(None)
         })
     }
 
@@ -295,17 +341,6 @@ impl RenderDevice
         }], None)?;
         core.device.wait()
     }
-    fn imm_transferring<F: FnOnce(fe::CmdRecord)>(recorder: F) -> fe::Result<()>
-    {
-        let core = RenderDeviceCore::get();
-        let cpt = fe::CommandPool::new(&core.device, core.transfer_queue.0, true, false)?;
-        let init_c = cpt.alloc(1, true)?; recorder(init_c[0].begin()?);
-        core.transfer_queue.1.submit(&[fe::SubmissionBatch
-        {
-            command_buffers: Cow::Borrowed(&[&init_c[0]]), .. Default::default()
-        }], None)?;
-        core.device.wait()
-    }
 }
 
 impl RenderDevice
@@ -325,13 +360,45 @@ impl RenderDevice
         RenderDeviceCore::get().devprops.load(|| RenderDeviceCore::get().adapter.properties()).limits.minUniformBufferOffsetAlignment
     }
 
-    pub fn create_resources(&self, buffer_data: &[super::BufferContent], texture_data: &[super::TextureParam]) -> fe::Result<ResourceBlock>
+    /// The first of the format and its fallbacks which can be sampled(and blitted for mipmap generation) with optimal tiling
+    pub fn resolve_color_format(&self, format: super::ColorFormat, mipmaps: bool) -> Option<super::ColorFormat>
+    {
+        let mut required = fe::vk::VK_FORMAT_FEATURE_SAMPLED_IMAGE_BIT | fe::vk::VK_FORMAT_FEATURE_SAMPLED_IMAGE_FILTER_LINEAR_BIT;
+        if mipmaps { required |= fe::vk::VK_FORMAT_FEATURE_BLIT_SRC_BIT | fe::vk::VK_FORMAT_FEATURE_BLIT_DST_BIT; }
+        ::std::iter::once(&format).chain(format.fallbacks()).find(|f|
+        {
+            let props = RenderDeviceCore::get().adapter.format_properties(f.translate_vk());
+            (props.optimalTilingFeatures & required) == required
+        }).cloned()
+    }
+    /// The format of a texture: FrequentlyUpdated textures also require the staging image in the format(linear tiling)
+    fn resolve_texture_format(&self, param: &super::TextureParam) -> Option<super::ColorFormat>
+    {
+        if param.usage != super::TextureUsage::FrequentlyUpdated { return self.resolve_color_format(param.color, param.generates_mipmaps()); }
+        ::std::iter::once(&param.color).chain(param.color.fallbacks()).find(|&&f|
+        {
+            self.resolve_color_format(f, false) == Some(f) && Self::supports_linear_staging(f, param.size, param.layers)
+        }).cloned()
+    }
+    /// Linear tiled images in the format can be created with the size and copied from
+    fn supports_linear_staging(format: super::ColorFormat, size: Size2U, layers: u32) -> bool
+    {
+        let mut props: fe::vk::VkImageFormatProperties = unsafe { ::std::mem::zeroed() };
+        let r = unsafe
+        {
+            fe::vk::vkGetPhysicalDeviceImageFormatProperties(RenderDeviceCore::get().adapter.native_ptr(), format.translate_vk(),
+                fe::vk::VK_IMAGE_TYPE_2D, fe::vk::VK_IMAGE_TILING_LINEAR, fe::ImageUsage::TRANSFER_SRC.0, 0, &mut props)
+        };
+        r == fe::vk::VK_SUCCESS && props.maxExtent.width >= size.x() && props.maxExtent.height >= size.y() && props.maxArrayLayers >= layers
+    }
+
+    pub fn create_resources(&self, buffer_data: &[super::BufferContent], texture_data: &[super::TextureParam]) -> Result<ResourceBlock, Box<Error>>
     {
         #[derive(Debug)]
         struct BufferDataPlacement { offset: fe::vk::VkDeviceSize, bytesize: fe::vk::VkDeviceSize, flags: fe::vk::VkBufferUsageFlags }
         fn alignment(p: fe::vk::VkDeviceSize, a: fe::vk::VkDeviceSize) -> fe::vk::VkDeviceSize { (p / a + 1) * a }
         let uf_alignment = |p| alignment(p, self.minimum_uniform_alignment());
-        let bdp: Vec<_> = buffer_data.into_iter().scan(0, |current_offset, &super::BufferContent { kind, bytesize }|
+        let bdp: Vec<_> = buffer_data.into_iter().scan(0, |current_offset, &super::BufferContent { kind, bytesize, .. }|
         {
             let offset = if kind == super::BufferKind::Constant { uf_alignment(*current_offset) } else { *current_offset };
             *current_offset = offset + bytesize as fe::vk::VkDeviceSize;
@@ -341,22 +408,26 @@ impl RenderDevice
         let (buffer, sbuffer) = if buffer_size > 0
         {
             let buffer_usage = fe::BufferUsage(bdp.iter().fold(0, |bits, b| bits | b.flags));
-            let buffer = fe::BufferDesc::new(buffer_size as _, buffer_usage).create(&RenderDeviceCore::get().device)?;
-            let sbuffer = fe::BufferDesc::new(buffer_size as _, buffer_usage).create(&RenderDeviceCore::get().device)?;
+            let buffer = fe::BufferDesc::new(buffer_size as _, buffer_usage.transfer_dest()).create(&RenderDeviceCore::get().device)?;
+            let sbuffer = fe::BufferDesc::new(buffer_size as _, buffer_usage.transfer_src()).create(&RenderDeviceCore::get().device)?;
             (Some(buffer), Some(sbuffer))
         }
         else { (None, None) };
         let (bufalloc, sbufalloc) = (buffer.as_ref().map(MemoryBound::requirements), sbuffer.as_ref().map(MemoryBound::requirements));
 
+        let formats = texture_data.iter().map(|p| self.resolve_texture_format(p)
+            .ok_or(fe::VkResultBox(fe::vk::VK_ERROR_FORMAT_NOT_SUPPORTED))).collect::<fe::Result<Vec<_>>>()?;
+
         // collect textures //
         let (mut initial_barriers, mut final_barriers) = (Vec::with_capacity(texture_data.len() * 3), Vec::with_capacity(texture_data.len()));
         let mut tdp = Vec::with_capacity(texture_data.len());
         let mut current_offset = 0;
-        for param in texture_data
+        for (n, param) in texture_data.iter().enumerate()
         {
-            let object = fe::ImageDesc::new(fe::Extent2D(param.size.x(), param.size.y()), param.color.translate_vk(),
-                param.usage.translate_vk(), fe::ImageLayout::Preinitialized)
-                .array_layers(param.layers).create(&RenderDeviceCore::get().device)?;
+            let usage = if param.generates_mipmaps() { param.usage.translate_vk().transfer_src() } else { param.usage.translate_vk() };
+            let object = fe::ImageDesc::new(fe::Extent2D(param.size.x(), param.size.y()), formats[n].translate_vk(),
+                usage, fe::ImageLayout::Preinitialized)
+                .mip_levels(param.mip_levels()).array_layers(param.layers).create(&RenderDeviceCore::get().device)?;
             let req = object.requirements();
             let offset = alignment(current_offset, req.alignment);
             current_offset = offset + req.size;
@@ -372,168 +443,490 @@ impl RenderDevice
                     super::TextureUsage::RenderTarget => fe::vk::VK_ACCESS_SHADER_READ_BIT,
                     super::TextureUsage::FrequentlyUpdated | super::TextureUsage::Immutable(_) => fe::vk::VK_ACCESS_TRANSFER_WRITE_BIT
                 },
-                image: object.native_ptr(), subresourceRange: fe::vk::VkImageSubresourceRange { aspectMask: fe::AspectMask::COLOR.0, .. Default::default() },
+                image: object.native_ptr(), subresourceRange: fe::vk::VkImageSubresourceRange
+                {
+                    aspectMask: fe::AspectMask::COLOR.0, levelCount: param.mip_levels(), .. Default::default()
+                },
                 .. Default::default()
             };
+            if param.usage == super::TextureUsage::FrequentlyUpdated
+            {
+                final_barriers.place_back() <- fe::vk::VkImageMemoryBarrier
+                {
+                    oldLayout: fe::ImageLayout::TransferDestOpt as _, newLayout: fe::ImageLayout::ShaderReadOnlyOpt as _,
+                    srcAccessMask: fe::vk::VK_ACCESS_TRANSFER_WRITE_BIT, dstAccessMask: fe::vk::VK_ACCESS_SHADER_READ_BIT,
+                    image: object.native_ptr(), subresourceRange: fe::vk::VkImageSubresourceRange
+                    {
+                        aspectMask: fe::AspectMask::COLOR.0, levelCount: 1, layerCount: param.layers, .. Default::default()
+                    },
+                    .. Default::default()
+                };
+            }
             tdp.place_back() <- TexturePlacement { offset, object };
         }
         let texture_bytes = current_offset;
         let buffer_base = alignment(texture_bytes, bufalloc.map(|x| x.alignment).unwrap_or(1));
-        let memory = fe::DeviceMemory::allocate(&RenderDeviceCore::get().device, (buffer_base + buffer_size) as _, RenderDeviceCore::get().memindices.devlocal)?;
+        let memory_size = buffer_base + buffer_size;
+        let memory = fe::DeviceMemory::allocate(&RenderDeviceCore::get().device, memory_size as _, RenderDeviceCore::get().memindices.devlocal)?;
         if let Some(ref b) = buffer.as_ref() { b.bind(&memory, buffer_base as _)?; }
         let mut image = Vec::with_capacity(tdp.len());
         for TexturePlacement { object, offset } in tdp
         {
             object.bind(&memory, offset as _)?; image.push(object);
         }
+        let views = image.iter().zip(texture_data).map(|(i, p)| i.create_view(None, None, &fe::ComponentMapping::default(), &fe::ImageSubresourceRange
+        {
+            aspect_mask: fe::AspectMask::COLOR, mip_levels: 0 .. p.mip_levels(), array_layers: 0 .. p.layers
+        })).collect::<fe::Result<Vec<_>>>()?;
 
         // collect staging textures //
         let mut current_offset = 0;
         let mut tdps = Vec::with_capacity(texture_data.len());
-        for param in texture_data.iter().filter(|p| p.usage == super::TextureUsage::FrequentlyUpdated)
+        let mut simage_index = vec![None; texture_data.len()];
+        for (n, param) in texture_data.iter().enumerate().filter(|&(_, ref p)| p.usage == super::TextureUsage::FrequentlyUpdated)
         {
-            let object = fe::ImageDesc::new(fe::Extent2D(param.size.x(), param.size.y()), param.color.translate_vk(),
+            let object = fe::ImageDesc::new(fe::Extent2D(param.size.x(), param.size.y()), formats[n].translate_vk(),
                 fe::ImageUsage::TRANSFER_SRC, fe::ImageLayout::Preinitialized)
                 .use_linear_tiling().array_layers(param.layers).create(&RenderDeviceCore::get().device)?;
             let req = object.requirements();
             let offset = alignment(current_offset, req.alignment);
             current_offset = offset + req.size;
+            // host writes require the general layout
             initial_barriers.place_back() <- fe::vk::VkImageMemoryBarrier
             {
-                oldLayout: fe::ImageLayout::Preinitialized as _, newLayout: fe::ImageLayout::TransferSrcOpt as _,
+                oldLayout: fe::ImageLayout::Preinitialized as _, newLayout: fe::ImageLayout::General as _,
                 dstAccessMask: fe::vk::VK_ACCESS_TRANSFER_READ_BIT, image: object.native_ptr(),
                 subresourceRange: fe::vk::VkImageSubresourceRange { aspectMask: fe::AspectMask::COLOR.0, .. Default::default() },
                 .. Default::default()
             };
+            simage_index[n] = Some((tdps.len(), param.color, formats[n]));
             tdps.place_back() <- TexturePlacement { offset, object };
         }
         let stexture_bytes = current_offset;
         let sbuffer_base = alignment(stexture_bytes, sbufalloc.map(|x| x.alignment).unwrap_or(1));
-        let smemory = fe::DeviceMemory::allocate(&RenderDeviceCore::get().device, (sbuffer_base + buffer_size) as _, RenderDeviceCore::get().memindices.host)?;
+        let smemory_size = sbuffer_base + buffer_size;
+        let smemory = fe::DeviceMemory::allocate(&RenderDeviceCore::get().device, smemory_size as _, RenderDeviceCore::get().memindices.host)?;
         if let Some(ref b) = sbuffer.as_ref() { b.bind(&smemory, sbuffer_base as _)?; }
         for &TexturePlacement { ref object, offset } in &tdps { object.bind(&smemory, offset as _)?; }
 
-        // process temporary staging textures //
+        // initial buffer data is staged in the host-visible buffer then copied //
+        let initial_data: Vec<_> = buffer_data.iter().zip(&bdp).filter_map(|(c, p)| c.initial_data.map(|d|
+        {
+            assert!(d.len() <= c.bytesize, "Initial data is larger than the buffer");
+            (p, d)
+        })).collect();
+        if !initial_data.is_empty()
+        {
+            smemory.map(sbuffer_base as usize .. (sbuffer_base + buffer_size) as usize).map(|mmap| for &(p, d) in &initial_data
+            {
+                unsafe { mmap.slice_mut::<u8>(p.offset as _, d.len()).copy_from_slice(d); }
+            })?;
+        }
+        let buffer_copies: Vec<_> = initial_data.iter().map(|&(p, d)| fe::vk::VkBufferCopy { srcOffset: p.offset, dstOffset: p.offset, size: d.len() as _ })
+            .collect();
+        let buffer_barriers = |src, dst| buffer.as_ref().map(|b| initial_data.iter().map(|&(p, d)| fe::vk::VkBufferMemoryBarrier
+        {
+            srcAccessMask: src, dstAccessMask: dst,
+            buffer: b.native_ptr(), offset: p.offset, size: d.len() as _, .. Default::default()
+        }).collect::<Vec<_>>()).unwrap_or_default();
+        let initial_buffer_barriers = buffer_barriers(0, fe::vk::VK_ACCESS_TRANSFER_WRITE_BIT);
+        let final_buffer_barriers = buffer_barriers(fe::vk::VK_ACCESS_TRANSFER_WRITE_BIT,
+            fe::vk::VK_ACCESS_VERTEX_ATTRIBUTE_READ_BIT | fe::vk::VK_ACCESS_INDEX_READ_BIT | fe::vk::VK_ACCESS_UNIFORM_READ_BIT);
+        let initial_bytes = initial_data.iter().map(|&(_, d)| d.len()).sum::<usize>();
+
+        // process temporary staging buffer //
         let mut current_offset = 0;
-        let mut tdpts = Vec::with_capacity(texture_data.len());
-        let mut copies = Vec::with_capacity(texture_data.len());
+        let mut staged = Vec::with_capacity(texture_data.len());
         for (n, param) in texture_data.iter().enumerate().filter(|&(_, ref p)| p.usage.is_immutable())
         {
-            let object = fe::ImageDesc::new(fe::Extent2D(param.size.x(), param.size.y()), param.color.translate_vk(),
-                fe::ImageUsage::TRANSFER_SRC, fe::ImageLayout::Preinitialized)
-                .use_linear_tiling().array_layers(param.layers).create(&RenderDeviceCore::get().device)?;
-            let req = object.requirements();
-            let offset = alignment(current_offset, req.alignment);
-            current_offset = offset + req.size;
-            initial_barriers.place_back() <- fe::vk::VkImageMemoryBarrier
-            {
-                oldLayout: fe::ImageLayout::Preinitialized as _, newLayout: fe::ImageLayout::TransferSrcOpt as _,
-                dstAccessMask: fe::vk::VK_ACCESS_TRANSFER_READ_BIT, image: object.native_ptr(),
-                subresourceRange: fe::vk::VkImageSubresourceRange { aspectMask: fe::AspectMask::COLOR.0, .. Default::default() },
-                .. Default::default()
-            };
+            let pixels = param.usage.initial_pixels().unwrap();
+            param.color.check_length(param.size.x(), param.size.y(), param.layers, pixels)?;
+            let pixels = if formats[n] == param.color { Cow::Borrowed(pixels) } else { Cow::Owned(param.color.convert_pixels(formats[n], pixels)?) };
+            let offset = alignment(current_offset, formats[n].copy_alignment() as _);
+            current_offset = offset + pixels.len() as fe::vk::VkDeviceSize;
+            // the base level of a mipmapped texture becomes the source of the first blit
+            let (final_layout, final_access) = if param.generates_mipmaps() { (fe::ImageLayout::TransferSrcOpt, fe::vk::VK_ACCESS_TRANSFER_READ_BIT) }
+                else { (fe::ImageLayout::ShaderReadOnlyOpt, fe::vk::VK_ACCESS_SHADER_READ_BIT) };
             final_barriers.place_back() <- fe::vk::VkImageMemoryBarrier
             {
-                oldLayout: fe::ImageLayout::TransferDestOpt as _, newLayout: fe::ImageLayout::ShaderReadOnlyOpt as _,
-                srcAccessMask: fe::vk::VK_ACCESS_TRANSFER_WRITE_BIT, dstAccessMask: fe::vk::VK_ACCESS_SHADER_READ_BIT,
-                image: image[n].native_ptr(), subresourceRange: fe::vk::VkImageSubresourceRange { aspectMask: fe::AspectMask::COLOR.0, .. Default::default() },
+                oldLayout: fe::ImageLayout::TransferDestOpt as _, newLayout: final_layout as _,
+                srcAccessMask: fe::vk::VK_ACCESS_TRANSFER_WRITE_BIT, dstAccessMask: final_access,
+                image: image[n].native_ptr(), subresourceRange: fe::vk::VkImageSubresourceRange
+                {
+                    aspectMask: fe::AspectMask::COLOR.0, levelCount: 1, layerCount: param.layers, .. Default::default()
+                },
                 .. Default::default()
             };
-            copies.place_back() <- (n, fe::vk::VkImageCopy
+            staged.place_back() <- (n, fe::vk::VkBufferImageCopy
             {
-                srcSubresource: fe::vk::VkImageSubresourceLayers { aspectMask: fe::AspectMask::COLOR.0, .. Default::default() },
-                dstSubresource: fe::vk::VkImageSubresourceLayers { aspectMask: fe::AspectMask::COLOR.0, .. Default::default() },
-                extent: AsRef::<fe::vk::VkExtent3D>::as_ref(object.size()).clone(), .. unsafe { ::std::mem::zeroed() }
-            });
-            tdpts.place_back() <- (TexturePlacement { offset, object }, param.usage.initial_pixels().unwrap());
-        }
-        let tstexture_bytes = current_offset;
-        let tsmemory = fe::DeviceMemory::allocate(&RenderDeviceCore::get().device, tstexture_bytes as _, RenderDeviceCore::get().memindices.host)?;
-        for &(TexturePlacement { ref object, offset }, _) in &tdpts { object.bind(&tsmemory, offset as _)?; }
-        tsmemory.map(0 .. tstexture_bytes as _).map(|mmap|
-        {
-            for &(TexturePlacement { offset, .. }, buf) in &tdpts
+                bufferOffset: offset, imageSubresource: fe::vk::VkImageSubresourceLayers
+                {
+                    aspectMask: fe::AspectMask::COLOR.0, layerCount: param.layers, .. Default::default()
+                },
+                imageExtent: fe::vk::VkExtent3D { width: param.size.x(), height: param.size.y(), depth: 1 }, .. unsafe { ::std::mem::zeroed() }
+            }, pixels);
+        }
+        let staging_bytes = current_offset;
+        let staging = if staging_bytes > 0
+        {
+            let buf = fe::BufferDesc::new(staging_bytes as _, fe::BufferUsage::TRANSFER_SRC).create(&RenderDeviceCore::get().device)?;
+            let mem = fe::DeviceMemory::allocate(&RenderDeviceCore::get().device, buf.requirements().size as _, RenderDeviceCore::get().memindices.host)?;
+            buf.bind(&mem, 0)?;
+            mem.map(0 .. staging_bytes as _).map(|mmap|
             {
-                unsafe { mmap.slice_mut::<u8>(offset as _, buf.len()).copy_from_slice(buf); }
-            }
-        })?;
-        Self::imm_transferring(|mut rec|
+                for &(_, ref cp, ref pixels) in &staged
+                {
+                    unsafe { mmap.slice_mut::<u8>(cp.bufferOffset as _, pixels.len()).copy_from_slice(pixels); }
+                }
+            })?;
+            Some((mem, buf))
+        }
+        else { None };
+        // recorded on the graphics queue which uses the resources: no ownership transfers between queue families are needed,
+        // and blitting(mipmap generation) is not supported on transfer-only queues
+        Self::imm_submission(|mut rec|
         {
-            rec.pipeline_barrier(fe::PipelineStageFlags::ALL_COMMANDS, fe::PipelineStageFlags::TRANSFER, false, &[], &[], &initial_barriers);
-            for (n, (nd, cp)) in copies.into_iter().enumerate()
+            rec.pipeline_barrier(fe::PipelineStageFlags::ALL_COMMANDS, fe::PipelineStageFlags::TRANSFER, false, &[], &initial_buffer_barriers, &initial_barriers);
+            if !buffer_copies.is_empty()
+            {
+                rec.copy_buffer(sbuffer.as_ref().unwrap(), buffer.as_ref().unwrap(), &buffer_copies);
+            }
+            if let Some((_, ref sb)) = staging
+            {
+                for &(nd, ref cp, _) in &staged
+                {
+                    rec.copy_buffer_to_image(sb, &image[nd], fe::ImageLayout::TransferDestOpt, &[cp.clone()]);
+                }
+            }
+            rec.pipeline_barrier(fe::PipelineStageFlags::TRANSFER, fe::PipelineStageFlags::ALL_COMMANDS, false, &[], &final_buffer_barriers, &final_barriers);
+            for (n, param) in texture_data.iter().enumerate().filter(|&(_, ref p)| p.generates_mipmaps())
             {
-                rec.copy_image(&tdpts[n].0.object, fe::ImageLayout::TransferSrcOpt, &image[nd], fe::ImageLayout::TransferDestOpt, &[cp]);
+                Self::record_mipmap_generation(&mut rec, &image[n], param.size, param.layers, param.mip_levels());
             }
-            rec.pipeline_barrier(fe::PipelineStageFlags::TRANSFER, fe::PipelineStageFlags::ALL_COMMANDS, false, &[], &[], &final_barriers);
         })?;
+        let samplers = texture_data.iter().map(|p| p.sampler.create_vk()).collect::<fe::Result<Vec<_>>>()?;
 
-        Ok(ResourceBlock { memory, smemory, buffer, sbuffer, image, simage: tdps })
+        let stats = &RenderDeviceCore::get().stats;
+        stats.allocated(RenderDeviceCore::get().memindices.devlocal, memory_size as _);
+        stats.allocated(RenderDeviceCore::get().memindices.host, smemory_size as _);
+        stats.uploaded(staging_bytes + initial_bytes as fe::vk::VkDeviceSize);
+        stats.resource_block_created();
+        let buffer_offsets = bdp.iter().map(|p| p.offset).collect();
+        Ok(ResourceBlock { memory, smemory, buffer, sbuffer, buffer_offsets, image, views, samplers, simage: tdps, simage_index, memory_size, smemory_size })
+    }
+    /// Fills the levels after the base level by blitting downscaled images; all levels are left in ShaderReadOnlyOpt.
+    /// Expects the base level in TransferSrcOpt and the others in TransferDestOpt
+    fn record_mipmap_generation(rec: &mut fe::CmdRecord, image: &fe::Image, size: Size2U, layers: u32, levels: u32)
+    {
+        let level_range = |level, count| fe::vk::VkImageSubresourceRange
+        {
+            aspectMask: fe::AspectMask::COLOR.0, baseMipLevel: level, levelCount: count, baseArrayLayer: 0, layerCount: layers
+        };
+        let level_layers = |level| fe::vk::VkImageSubresourceLayers
+        {
+            aspectMask: fe::AspectMask::COLOR.0, mipLevel: level, baseArrayLayer: 0, layerCount: layers
+        };
+        let level_extent = |level: u32| fe::vk::VkOffset3D
+        {
+            x: ::std::cmp::max(1, size.x() >> level) as _, y: ::std::cmp::max(1, size.y() >> level) as _, z: 1
+        };
+        for level in 1 .. levels
+        {
+            rec.blit_image(image, fe::ImageLayout::TransferSrcOpt, image, fe::ImageLayout::TransferDestOpt, &[fe::vk::VkImageBlit
+            {
+                srcSubresource: level_layers(level - 1), srcOffsets: [fe::vk::VkOffset3D { x: 0, y: 0, z: 0 }, level_extent(level - 1)],
+                dstSubresource: level_layers(level), dstOffsets: [fe::vk::VkOffset3D { x: 0, y: 0, z: 0 }, level_extent(level)]
+            }], fe::FilterMode::Linear);
+            rec.pipeline_barrier(fe::PipelineStageFlags::TRANSFER, fe::PipelineStageFlags::TRANSFER, false, &[], &[], &[fe::vk::VkImageMemoryBarrier
+            {
+                oldLayout: fe::ImageLayout::TransferDestOpt as _, newLayout: fe::ImageLayout::TransferSrcOpt as _,
+                srcAccessMask: fe::vk::VK_ACCESS_TRANSFER_WRITE_BIT, dstAccessMask: fe::vk::VK_ACCESS_TRANSFER_READ_BIT,
+                image: image.native_ptr(), subresourceRange: level_range(level, 1), .. Default::default()
+            }]);
+        }
+        rec.pipeline_barrier(fe::PipelineStageFlags::TRANSFER, fe::PipelineStageFlags::ALL_COMMANDS, false, &[], &[], &[fe::vk::VkImageMemoryBarrier
+        {
+            oldLayout: fe::ImageLayout::TransferSrcOpt as _, newLayout: fe::ImageLayout::ShaderReadOnlyOpt as _,
+            srcAccessMask: fe::vk::VK_ACCESS_TRANSFER_READ_BIT, dstAccessMask: fe::vk::VK_ACCESS_SHADER_READ_BIT,
+            image: image.native_ptr(), subresourceRange: level_range(0, levels), .. Default::default()
+        }]);
     }
 
     pub fn update_render_commands<F: FnMut(&mut super::RenderCommandsBasic, usize)>(&self, mut updater: F) -> fe::Result<()>
     {
+        self.render_control.wait_all()?;
         self.rtcp.reset(true)?;
         for (n, c) in self.rtcmds.iter().enumerate()
         {
-            let mut rec = CommandRecorder { rec: c.begin()?, in_render_pass: false };
+            let mut rec = CommandRecorder::new(c.begin()?, &self.rtcmd_stats[n], n);
             updater(&mut rec, n);
         }
         Ok(())
     }
     pub fn get_primary_render_target(&self, index: usize) -> RenderTarget { RenderTarget::PrimaryRT(index) }
 
-    /*pub fn new_render_target(&self, res: &fe::ImageView, optimized_clear: Option<Color>, after_usage: ResourceAfterUsage) -> fe::Result<RenderTarget>
+    /*pub fn new_render_target(&self, res: &fe::ImageView, after_usage: ResourceAfterUsage) -> fe::Result<RenderTarget>
     {
-        let rp = fe::RenderPassBuilder::new()
-            .add_attachment(fe::vk::VkAttachmentDescription
-            {
-                loadOp: if optimized_clear.is_some() { fe::vk::VK_ATTACHMENT_LOAD_OP_CLEAR } else { fe::vk::VK_ATTACHMENT_LOAD_OP_LOAD },
-                storeOp: fe::vk::VK_ATTACHMENT_STORE_OP_STORE,
-                format: res.format(), initialLayout: fe::ImageLayout::ColorAttachmentOpt as _, finalLayout: after_usage.translate_vk() as _,
-                samples: 1, flags: 0, stencilLoadOp: fe::vk::VK_ATTACHMENT_LOAD_OP_DONT_CARE, stencilStoreOp: fe::vk::VK_ATTACHMENT_STORE_OP_DONT_CARE
-            })
-            .add_subpass(fe::SubpassDescription::new().add_color_output(0, fe::ImageLayout::ColorAttachmentOpt, None))
-            .create(&self.device)?;
-        let fb = fe::Framebuffer::new(&rp, &[res], res.size(), 1)?;
-        Ok(RenderTarget(rp, fb, optimized_clear))
+        let rp = RenderPassSet::new(&RenderDeviceCore::get().device, res.format(), after_usage)?;
+        let fb = fe::Framebuffer::new(&rp.clear, &[res], res.size(), 1)?;
+        Ok(RenderTarget::Owned(rp, fb))
     }*/
 
     // pub fn swapchain_buffer_count(&self) -> usize { self.rtsc.len() }
-    pub fn do_render(&self) -> fe::Result<bool>
+    /// Renders a frame. Blocks while `FRAMES_IN_FLIGHT` frames are already queued on the GPU
+    pub fn do_render(&self) -> fe::Result<()>
     {
-        if let Some(next) = self.render_control.check_ready_next()?
+        self.render_frame(None).map(drop)
+    }
+    /// Renders a frame and reads back its contents. Blocks until the frame is presented
+    pub fn do_render_and_capture(&self) -> fe::Result<super::CapturedImage>
+    {
+        let size = self.rt_views[0].size().clone();
+        let bytesize = (size.0 * size.1 * 4) as usize;
+        let mut capture = self.capture.borrow_mut();
+        if capture.as_ref().map(|c| c.bytesize != bytesize).unwrap_or(true) { *capture = None; *capture = Some(CaptureResources::new(bytesize)?); }
+        let c = capture.as_ref().unwrap();
+        // the last capture is completed: the command buffer can be recorded again
+        c.pool.reset(false)?;
+
+        let completion = self.render_frame(Some((&c.cmd[0], &c.buffer)))?;
+        completion.wait()?;
+        let mut pixels = c.memory.map(0 .. bytesize).map(|mm| unsafe { mm.slice::<u8>(0, bytesize).to_vec() })?;
+        if self.sc_format == fe::vk::VK_FORMAT_B8G8R8A8_UNORM
         {
-            RenderDeviceCore::get().graphics_queue.1.submit(&[fe::SubmissionBatch
-            {
-                command_buffers: Cow::Borrowed(&[&self.rtcmds[next as usize]]),
-                signal_semaphores: Cow::Borrowed(&[(&self.present_ready)]),
-                .. Default::default()
-            }], None)?;
-            RenderDeviceCore::get().graphics_queue.1.present(&[(&self.swapchain, next)], &[&self.present_ready])?;
-            self.render_control.begin_acquire_next();
-            Ok(true)
+            for px in pixels.chunks_mut(4) { px.swap(0, 2); }
         }
-        else
+        Ok(super::CapturedImage { size: Size2U(size.0, size.1), pixels })
+    }
+    /// Submits the commands and presents the image, optionally copying the rendered image into the buffer before presentation.
+    /// Returns the fence to be signaled when the frame is completed
+    fn render_frame(&self, capture: Option<(&fe::CommandBuffer, &fe::Buffer)>) -> fe::Result<&fe::Fence>
+    {
+        let (frame, next) = self.render_control.begin_frame(&self.swapchain)?;
+        // the frame which last read the region of the image is completed
+        self.animated.write(next as usize)?;
+        self.write_dynamic_vertices(next as usize)?;
+        let mut command_buffers = vec![&self.rtcmds[next as usize]];
+        // the acquired image is presented even if the capture fails: the semaphore and the fence must be consumed/signaled
+        let captured = match capture
         {
-            Ok(false)
-        }
+            Some((cmd, buf)) => self.record_capture(cmd, &self.rt_views[next as usize], buf).map(|_| command_buffers.push(cmd)),
+            None => Ok(())
+        };
+        frame.completion.reset()?;
+        RenderDeviceCore::get().graphics_queue.1.submit(&[fe::SubmissionBatch
+        {
+            wait_semaphores: Cow::Borrowed(&[(&frame.acquired, fe::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)]),
+            command_buffers: Cow::Owned(command_buffers),
+            signal_semaphores: Cow::Borrowed(&[&frame.rendered])
+        }], Some(&frame.completion))?;
+        RenderDeviceCore::get().graphics_queue.1.present(&[(&self.swapchain, next)], &[&frame.rendered])?;
+        RenderDeviceCore::get().stats.frame_rendered(self.rtcmd_stats[next as usize].get());
+        self.render_control.end_frame();
+        captured.map(|_| &frame.completion)
+    }
+    fn record_capture(&self, cmd: &fe::CommandBuffer, target: &fe::ImageView, dest: &fe::Buffer) -> fe::Result<()>
+    {
+        let image = target.deref();
+        let range = fe::vk::VkImageSubresourceRange { aspectMask: fe::AspectMask::COLOR.0, levelCount: 1, layerCount: 1, .. Default::default() };
+        let mut rec = cmd.begin()?;
+        rec.pipeline_barrier(fe::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT, fe::PipelineStageFlags::TRANSFER, false, &[], &[], &[fe::vk::VkImageMemoryBarrier
+        {
+            srcAccessMask: fe::vk::VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT, dstAccessMask: fe::vk::VK_ACCESS_TRANSFER_READ_BIT,
+            oldLayout: fe::ImageLayout::PresentSrc as _, newLayout: fe::ImageLayout::TransferSrcOpt as _,
+            image: image.native_ptr(), subresourceRange: range.clone(), .. Default::default()
+        }]);
+        rec.copy_image_to_buffer(image, fe::ImageLayout::TransferSrcOpt, dest, &[fe::vk::VkBufferImageCopy
+        {
+            imageSubresource: fe::vk::VkImageSubresourceLayers { aspectMask: fe::AspectMask::COLOR.0, layerCount: 1, .. Default::default() },
+            imageExtent: AsRef::<fe::vk::VkExtent3D>::as_ref(target.size()).clone(), .. unsafe { ::std::mem::zeroed() }
+        }]);
+        rec.pipeline_barrier(fe::PipelineStageFlags::TRANSFER, fe::PipelineStageFlags::BOTTOM_OF_PIPE, false, &[], &[fe::vk::VkBufferMemoryBarrier
+        {
+            srcAccessMask: fe::vk::VK_ACCESS_TRANSFER_WRITE_BIT, dstAccessMask: fe::vk::VK_ACCESS_HOST_READ_BIT,
+            buffer: dest.native_ptr(), offset: 0, size: fe::vk::VK_WHOLE_SIZE, .. Default::default()
+        }], &[fe::vk::VkImageMemoryBarrier
+        {
+            srcAccessMask: fe::vk::VK_ACCESS_TRANSFER_READ_BIT, dstAccessMask: fe::vk::VK_ACCESS_MEMORY_READ_BIT,
+            oldLayout: fe::ImageLayout::TransferSrcOpt as _, newLayout: fe::ImageLayout::PresentSrc as _,
+            image: image.native_ptr(), subresourceRange: range, .. Default::default()
+        }]);
+        Ok(())
     }
+    /// Waits until all frames in flight have been rendered
     pub fn wait_render_ready(&self) -> fe::Result<()>
     {
-        self.render_control.wait_last_render_completion().map(drop)
+        self.render_control.wait_all()
+    }
+    /// None if all the slots are used
+    pub fn new_animated_slot(&self) -> Option<usize>
+    {
+        let used = self.animated.used.get();
+        let index = (!used).trailing_zeros() as usize;
+        if index >= super::ANIMATED_SLOTS { return None; }
+        self.animated.used.set(used | 1 << index);
+        self.animated.values.borrow_mut()[index] = super::Animated::IDENTITY;
+        Some(index)
+    }
+    pub fn free_animated_slot(&self, index: usize) { self.animated.used.set(self.animated.used.get() & !(1 << index)); }
+    pub fn set_animated(&self, index: usize, values: super::Animated) { self.animated.values.borrow_mut()[index] = values; }
+
+    /// A host visible vertex buffer with a region of `capacity` bytes per swapchain image
+    pub fn new_dynamic_vertices(&self, capacity: usize) -> fe::Result<DynamicVertices>
+    {
+        let core = RenderDeviceCore::get();
+        let buffer = fe::BufferDesc::new(capacity * self.rt_views.len(), fe::BufferUsage::VERTEX_BUFFER).create(&core.device)?;
+        let memory_size = buffer.requirements().size;
+        let memory = fe::DeviceMemory::allocate(&core.device, memory_size as _, core.memindices.host)?;
+        buffer.bind(&memory, 0)?;
+        core.stats.allocated(core.memindices.host, memory_size as _);
+        let b = Rc::new(DynamicVertexBuffer { buffer, memory, memory_size, capacity, data: RefCell::new(Vec::new()), stale: Cell::new(0) });
+        self.dynamic_vertices.borrow_mut().push(Rc::downgrade(&b));
+        Ok(DynamicVertices(b))
+    }
+    /// Fails if the vertices are longer than the capacity
+    pub fn set_dynamic_vertices(&self, vertices: &super::DynamicVertices, data: &[u8]) -> Result<(), Box<Error>>
+    {
+        let b = &unsafe { &*(vertices as *const _ as *const DynamicVertices) }.0;
+        if data.len() > b.capacity
+        {
+            return Err(From::from(format!("{} bytes of vertices are given for a dynamic vertex buffer of {} bytes", data.len(), b.capacity)));
+        }
+        let mut current = b.data.borrow_mut();
+        current.clear();
+        current.extend_from_slice(data);
+        b.stale.set((1 << self.rt_views.len()) - 1);
+        Ok(())
+    }
+    /// Vertices from `offset` bytes in the region of the image which the commands are recorded for
+    pub fn get_dynamic_vertex_array<'d>(&self, vertices: &'d super::DynamicVertices, offset: usize, vertex_count: usize) -> VertexArray<'d>
+    {
+        let b = &unsafe { &*(vertices as *const _ as *const DynamicVertices) }.0;
+        VertexArray { vb_desc: VertexBufferSlice { buf: &b.buffer, offset, count: vertex_count, image_stride: b.capacity }, ib_desc: None }
+    }
+    /// Copies the vertices replaced since the image was submitted last into its regions
+    fn write_dynamic_vertices(&self, image: usize) -> fe::Result<()>
+    {
+        let mut buffers = self.dynamic_vertices.borrow_mut();
+        buffers.retain(|b| b.upgrade().is_some());
+        for b in buffers.iter().filter_map(Weak::upgrade)
+        {
+            if b.stale.get() & (1 << image) == 0 { continue; }
+            let data = b.data.borrow();
+            if !data.is_empty()
+            {
+                let base = image * b.capacity;
+                b.memory.map(base .. base + data.len()).map(|mm| unsafe { mm.slice_mut::<u8>(0, data.len()).copy_from_slice(&data); })?;
+            }
+            b.stale.set(b.stale.get() & !(1 << image));
+        }
+        Ok(())
     }
 
     pub fn new_render_command_buffer(&self, count: usize) -> fe::Result<RenderCommands>
     {
         let cp = fe::CommandPool::new(&RenderDeviceCore::get().device, RenderDeviceCore::get().graphics_queue.0, false, false)?;
         let commands = cp.alloc(count as _, true)?;
-        Ok(RenderCommands(cp, commands))
+        Ok(RenderCommands(cp, commands, vec![Cell::new(CommandStatistics::default()); count]))
     }
     pub fn new_render_subcommand_buffer(&self, count: usize) -> fe::Result<RenderCommands>
     {
         let cp = fe::CommandPool::new(&RenderDeviceCore::get().device, RenderDeviceCore::get().graphics_queue.0, false, false)?;
         let commands = cp.alloc(count as _, false)?;
-        Ok(RenderCommands(cp, commands))
+        Ok(RenderCommands(cp, commands, vec![Cell::new(CommandStatistics::default()); count]))
+    }
+    pub fn statistics(&self) -> RenderStatistics { RenderDeviceCore::get().stats.snapshot() }
+    /// Vertices in a buffer of the resource block, drawn with 32-bit indices in another buffer if any.
+    /// Buffers are specified by their indices in the contents passed to `create_resources`
+    pub fn get_vertex_array<'d>(&self, resources: &'d super::ResourceBlock, vertex_buffer: usize, vertex_count: usize,
+        index_buffer: Option<(usize, usize)>) -> VertexArray<'d>
+    {
+        let res = unsafe { &*(resources as *const _ as *const ResourceBlock) };
+        let buf = res.buffer.as_ref().expect("The resource block has no buffers");
+        VertexArray
+        {
+            vb_desc: VertexBufferSlice { buf, offset: res.buffer_offsets[vertex_buffer] as _, count: vertex_count, image_stride: 0 },
+            ib_desc: index_buffer.map(|(n, count)| IndexBufferSlice { buf, offset: res.buffer_offsets[n] as _, format: fe::IndexType::U32, count })
+        }
+    }
+    /// Writes the pixels(in the format of the texture parameter) into the staging image of a FrequentlyUpdated texture
+    /// then copies the region into the texture on the graphics queue.
+    /// Waits for the frames in flight first: they may still sample the texture, and the staging image is not multi-buffered
+    pub fn update_texture(&self, resources: &super::ResourceBlock, index: usize, offset: (u32, u32), size: Size2U, pixels: &[u8]) -> Result<(), Box<Error>>
+    {
+        let res = unsafe { &*(resources as *const _ as *const ResourceBlock) };
+        let (sindex, color, format) = match res.simage_index.get(index).and_then(|x| *x)
+        {
+            Some(x) => x, None => return Err(From::from("Updating a texture that is not FrequentlyUpdated"))
+        };
+        color.check_length(size.x(), size.y(), 1, pixels)?;
+        let pixels = if format == color { Cow::Borrowed(pixels) } else { Cow::Owned(color.convert_pixels(format, pixels)?) };
+        let bpp = match format.bytes_per_pixel()
+        {
+            Some(b) => b as fe::vk::VkDeviceSize, None => return Err(From::from("Updating a compressed texture"))
+        };
+        self.wait_render_ready()?;
+        let TexturePlacement { offset: base, object: ref simage } = res.simage[sindex];
+        let mut layout: fe::vk::VkSubresourceLayout = unsafe { ::std::mem::zeroed() };
+        unsafe
+        {
+            fe::vk::vkGetImageSubresourceLayout(RenderDeviceCore::get().device.native_ptr(), simage.native_ptr(),
+                &fe::vk::VkImageSubresource { aspectMask: fe::AspectMask::COLOR.0, mipLevel: 0, arrayLayer: 0 }, &mut layout);
+        }
+        let row_bytes = size.x() as usize * bpp as usize;
+        let first = layout.offset + offset.1 as fe::vk::VkDeviceSize * layout.rowPitch + offset.0 as fe::vk::VkDeviceSize * bpp;
+        let last = first + (size.y().max(1) - 1) as fe::vk::VkDeviceSize * layout.rowPitch + row_bytes as fe::vk::VkDeviceSize;
+        res.smemory.map((base + first) as usize .. (base + last) as usize).map(|mmap| for (y, row) in pixels.chunks(row_bytes).take(size.y() as _).enumerate()
+        {
+            unsafe { mmap.slice_mut::<u8>(y * layout.rowPitch as usize, row_bytes).copy_from_slice(row); }
+        })?;
+
+        let range = fe::vk::VkImageSubresourceRange { aspectMask: fe::AspectMask::COLOR.0, levelCount: 1, layerCount: 1, .. Default::default() };
+        let layers = fe::vk::VkImageSubresourceLayers { aspectMask: fe::AspectMask::COLOR.0, layerCount: 1, .. Default::default() };
+        let region = fe::vk::VkOffset3D { x: offset.0 as _, y: offset.1 as _, z: 0 };
+        let image = &res.image[index];
+        // the same queue family as the frames sampling the texture: no ownership transfer is needed
+        Self::imm_submission(|mut rec|
+        {
+            rec.pipeline_barrier(fe::PipelineStageFlags::HOST | fe::PipelineStageFlags::FRAGMENT_SHADER, fe::PipelineStageFlags::TRANSFER, false, &[], &[], &[
+                fe::vk::VkImageMemoryBarrier
+                {
+                    srcAccessMask: fe::vk::VK_ACCESS_HOST_WRITE_BIT, dstAccessMask: fe::vk::VK_ACCESS_TRANSFER_READ_BIT,
+                    oldLayout: fe::ImageLayout::General as _, newLayout: fe::ImageLayout::General as _,
+                    image: simage.native_ptr(), subresourceRange: range.clone(), .. Default::default()
+                },
+                fe::vk::VkImageMemoryBarrier
+                {
+                    srcAccessMask: fe::vk::VK_ACCESS_SHADER_READ_BIT, dstAccessMask: fe::vk::VK_ACCESS_TRANSFER_WRITE_BIT,
+                    oldLayout: fe::ImageLayout::ShaderReadOnlyOpt as _, newLayout: fe::ImageLayout::TransferDestOpt as _,
+                    image: image.native_ptr(), subresourceRange: range.clone(), .. Default::default()
+                }]);
+            rec.copy_image(simage, fe::ImageLayout::General, image, fe::ImageLayout::TransferDestOpt, &[fe::vk::VkImageCopy
+            {
+                srcSubresource: layers.clone(), srcOffset: region.clone(), dstSubresource: layers, dstOffset: region,
+                extent: fe::vk::VkExtent3D { width: size.x(), height: size.y(), depth: 1 }
+            }]);
+            rec.pipeline_barrier(fe::PipelineStageFlags::TRANSFER, fe::PipelineStageFlags::FRAGMENT_SHADER, false, &[], &[], &[fe::vk::VkImageMemoryBarrier
+            {
+                srcAccessMask: fe::vk::VK_ACCESS_TRANSFER_WRITE_BIT, dstAccessMask: fe::vk::VK_ACCESS_SHADER_READ_BIT,
+                oldLayout: fe::ImageLayout::TransferDestOpt as _, newLayout: fe::ImageLayout::ShaderReadOnlyOpt as _,
+                image: image.native_ptr(), subresourceRange: range, .. Default::default()
+            }]);
+        })?;
+        RenderDeviceCore::get().stats.uploaded((size.x() as usize * size.y() as usize) as fe::vk::VkDeviceSize * bpp);
+        Ok(())
+    }
+    /// A descriptor set sampling the texture(in ShaderReadOnlyOpt layout)
+    pub fn bind_texture(&self, resources: &super::ResourceBlock, index: usize) -> fe::Result<TextureBinding>
+    {
+        let res = unsafe { &*(resources as *const _ as *const ResourceBlock) };
+        let (view, sampler) = (res.views[index].clone(), res.samplers[index].clone());
+        let pool = fe::DescriptorPool::new(&RenderDeviceCore::get().device, 1,
+            &[fe::DescriptorPoolSize(fe::DescriptorType::CombinedImageSampler, 1)], false)?;
+        let set = pool.alloc(&[&self.texture_layout])?[0];
+        RenderDeviceCore::get().device.update_descriptor_sets(&[fe::DescriptorSetWriteInfo(set, 0, 0,
+            fe::DescriptorUpdateInfo::CombinedImageSampler(vec![(Some(sampler.native_ptr()), view.native_ptr(), fe::ImageLayout::ShaderReadOnlyOpt)]))], &[]);
+        Ok(TextureBinding { _pool: pool, set, _view: view, _sampler: sampler })
     }
     pub fn get_builtin_vertex_array(&self, key: super::BuiltinResourceKey) -> fe::Result<VertexArray>
     {
@@ -544,7 +937,7 @@ impl RenderDevice
                 let bb = RenderDeviceCore::get().query_builtin_buffer();
                 Ok(VertexArray
                 {
-                    vb_desc: VertexBufferSlice { buf: bb, offset: BuiltinVertices::offset_unit_rect(), count: 4 },
+                    vb_desc: VertexBufferSlice { buf: bb, offset: BuiltinVertices::offset_unit_rect(), count: 4, image_stride: 0 },
                     ib_desc: Some(IndexBufferSlice { buf: bb, offset: BuiltinIndices::offset_unit_rect(), format: fe::IndexType::U16, count: 6 })
                 })
             }
@@ -556,9 +949,6 @@ impl RenderDevice
 {
     Displayed, ShaderRead, TargetedForRender, CopySource
 }
-#[repr(C)] #[derive(Debug, Clone, PartialEq)]
-pub struct Color(pub f32, pub f32, pub f32, pub f32);
-impl AsRef<[f32; 4]> for Color { fn as_ref(&self) -> &[f32; 4] { unsafe { ::std::mem::transmute(self) } } }
 impl ResourceAfterUsage
 {
     fn translate_vk(self) -> fe::ImageLayout
@@ -585,6 +975,42 @@ impl<'p> super::TextureUsage<'p>
         }
     }
 }
+impl super::SamplerParam
+{
+    fn create_vk(&self) -> fe::Result<fe::Sampler>
+    {
+        let mut b = fe::SamplerBuilder::default();
+        b.mag_filter(self.mag_filter.translate_vk()).min_filter(self.min_filter.translate_vk())
+            .mip_filter(self.mipmap_filter == super::Filter::Linear)
+            .addressing(self.address_u.translate_vk(), self.address_v.translate_vk(), fe::AddressingMode::ClampToEdge)
+            .lod_range(0.0 .. fe::vk::VK_LOD_CLAMP_NONE);
+        if let Some(a) = self.anisotropy.and_then(|a| if RenderDeviceCore::get().sampler_anisotropy { Some(a) } else { None })
+        {
+            let limit = RenderDeviceCore::get().devprops.load(|| RenderDeviceCore::get().adapter.properties()).limits.maxSamplerAnisotropy;
+            b.max_anisotropy(limit.min(a as f32));
+        }
+        b.create(&RenderDeviceCore::get().device)
+    }
+}
+impl super::Filter
+{
+    fn translate_vk(self) -> fe::FilterMode
+    {
+        match self { super::Filter::Nearest => fe::FilterMode::Nearest, super::Filter::Linear => fe::FilterMode::Linear }
+    }
+}
+impl super::AddressMode
+{
+    fn translate_vk(self) -> fe::AddressingMode
+    {
+        match self
+        {
+            super::AddressMode::ClampToEdge => fe::AddressingMode::ClampToEdge,
+            super::AddressMode::Repeat => fe::AddressingMode::Repeat,
+            super::AddressMode::MirroredRepeat => fe::AddressingMode::MirroredRepeat
+        }
+    }
+}
 impl super::BufferKind
 {
     fn translate_vk(self) -> fe::BufferUsage
@@ -604,8 +1030,27 @@ impl super::ColorFormat
         match self
         {
             super::ColorFormat::Grayscale => fe::vk::VK_FORMAT_R8_UNORM,
+            super::ColorFormat::TwoChannels => fe::vk::VK_FORMAT_R8G8_UNORM,
             super::ColorFormat::Default => fe::vk::VK_FORMAT_R8G8B8_UNORM,
-            super::ColorFormat::WithAlpha => fe::vk::VK_FORMAT_R8G8B8A8_UNORM
+            super::ColorFormat::WithAlpha => fe::vk::VK_FORMAT_R8G8B8A8_UNORM,
+            super::ColorFormat::DefaultSRGB => fe::vk::VK_FORMAT_R8G8B8_SRGB,
+            super::ColorFormat::WithAlphaSRGB => fe::vk::VK_FORMAT_R8G8B8A8_SRGB,
+            super::ColorFormat::BGRA => fe::vk::VK_FORMAT_B8G8R8A8_UNORM,
+            super::ColorFormat::BGRASRGB => fe::vk::VK_FORMAT_B8G8R8A8_SRGB,
+            super::ColorFormat::GrayscaleF16 => fe::vk::VK_FORMAT_R16_SFLOAT,
+            super::ColorFormat::WithAlphaF16 => fe::vk::VK_FORMAT_R16G16B16A16_SFLOAT,
+            super::ColorFormat::BC1 => fe::vk::VK_FORMAT_BC1_RGBA_UNORM_BLOCK,
+            super::ColorFormat::BC1SRGB => fe::vk::VK_FORMAT_BC1_RGBA_SRGB_BLOCK,
+            super::ColorFormat::BC3 => fe::vk::VK_FORMAT_BC3_UNORM_BLOCK,
+            super::ColorFormat::BC3SRGB => fe::vk::VK_FORMAT_BC3_SRGB_BLOCK,
+            super::ColorFormat::BC4 => fe::vk::VK_FORMAT_BC4_UNORM_BLOCK,
+            super::ColorFormat::BC5 => fe::vk::VK_FORMAT_BC5_UNORM_BLOCK,
+            super::ColorFormat::BC7 => fe::vk::VK_FORMAT_BC7_UNORM_BLOCK,
+            super::ColorFormat::BC7SRGB => fe::vk::VK_FORMAT_BC7_SRGB_BLOCK,
+            super::ColorFormat::ETC2 => fe::vk::VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK,
+            super::ColorFormat::ETC2SRGB => fe::vk::VK_FORMAT_ETC2_R8G8B8_SRGB_BLOCK,
+            super::ColorFormat::ETC2WithAlpha => fe::vk::VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK,
+            super::ColorFormat::ETC2WithAlphaSRGB => fe::vk::VK_FORMAT_ETC2_R8G8B8A8_SRGB_BLOCK
         }
     }
 }
@@ -613,23 +1058,221 @@ pub struct TexturePlacement { offset: fe::vk::VkDeviceSize, object: fe::Image }
 pub struct ResourceBlock
 {
     memory: fe::DeviceMemory, smemory: fe::DeviceMemory, buffer: Option<fe::Buffer>, sbuffer: Option<fe::Buffer>,
-    image: Vec<fe::Image>, simage: Vec<TexturePlacement>
+    /// Offset of each buffer content in `buffer`(and `sbuffer`)
+    buffer_offsets: Vec<fe::vk::VkDeviceSize>,
+    image: Vec<fe::Image>, views: Vec<fe::ImageView>, samplers: Vec<fe::Sampler>, simage: Vec<TexturePlacement>,
+    /// Index in `simage`, the requested and the actual format of each FrequentlyUpdated texture
+    simage_index: Vec<Option<(usize, super::ColorFormat, super::ColorFormat)>>, memory_size: fe::vk::VkDeviceSize, smemory_size: fe::vk::VkDeviceSize
 }
 impl super::ResourceBlock for ResourceBlock {}
+impl Drop for ResourceBlock
+{
+    fn drop(&mut self)
+    {
+        let core = RenderDeviceCore::get();
+        core.stats.freed(core.memindices.devlocal, self.memory_size as _);
+        core.stats.freed(core.memindices.host, self.smemory_size as _);
+        core.stats.resource_block_dropped();
+    }
+}
+
+/// Keeps the view and the sampler alive while the descriptor set is used
+pub struct TextureBinding { _pool: fe::DescriptorPool, set: fe::vk::VkDescriptorSet, _view: fe::ImageView, _sampler: fe::Sampler }
+impl super::TextureBinding for TextureBinding {}
+
+/// The animated values(`super::Animated`) of every swapchain image in a host visible uniform buffer: `ANIMATED_SLOTS` slots per image.
+/// Draws read two slots(the layer and the element) through the dynamic offsets of one descriptor set.
+/// The values of an image are written right before submitting its commands, so the recorded commands stay valid while they change
+struct AnimatedValues
+{
+    layout: fe::DescriptorSetLayout, _pool: fe::DescriptorPool, set: fe::vk::VkDescriptorSet,
+    // the buffer is destroyed before the memory
+    buffer: fe::Buffer, memory: fe::DeviceMemory, memory_size: fe::vk::VkDeviceSize,
+    /// Distance between the slots(aligned to `minUniformBufferOffsetAlignment`)
+    stride: usize,
+    values: RefCell<Vec<super::Animated>>,
+    /// A bit per slot; the identity(slot 0) is always used
+    used: Cell<u32>
+}
+impl AnimatedValues
+{
+    fn new(image_count: usize) -> fe::Result<Self>
+    {
+        use std::mem::size_of;
+        let core = RenderDeviceCore::get();
+        let align = core.devprops.load(|| core.adapter.properties()).limits.minUniformBufferOffsetAlignment as usize;
+        let stride = (size_of::<super::Animated>() + align - 1) / align * align;
+        let layout = fe::DescriptorSetLayout::new(&core.device, &fe::DSLBindings
+        {
+            uniform_buffer_dynamic: Some((0, 2, fe::ShaderStage::VERTEX)), .. fe::DSLBindings::empty()
+        })?;
+        let buffer = fe::BufferDesc::new(stride * super::ANIMATED_SLOTS * image_count, fe::BufferUsage::UNIFORM_BUFFER).create(&core.device)?;
+        let memory_size = buffer.requirements().size;
+        let memory = fe::DeviceMemory::allocate(&core.device, memory_size as _, core.memindices.host)?;
+        buffer.bind(&memory, 0)?;
+        core.stats.allocated(core.memindices.host, memory_size as _);
+        let pool = fe::DescriptorPool::new(&core.device, 1, &[fe::DescriptorPoolSize(fe::DescriptorType::UniformBufferDynamic, 2)], false)?;
+        let set = pool.alloc(&[&layout])?[0];
+        let slot = (buffer.native_ptr(), 0 .. size_of::<super::Animated>());
+        core.device.update_descriptor_sets(&[fe::DescriptorSetWriteInfo(set, 0, 0, fe::DescriptorUpdateInfo::UniformBufferDynamic(vec![slot.clone(), slot]))], &[]);
+        Ok(AnimatedValues
+        {
+            layout, _pool: pool, set, buffer, memory, memory_size, stride,
+            values: RefCell::new(vec![super::Animated::IDENTITY; super::ANIMATED_SLOTS]), used: Cell::new(1)
+        })
+    }
+    /// Dynamic offset of the slot in the region of the image
+    fn offset(&self, image: usize, slot: usize) -> u32 { ((image * super::ANIMATED_SLOTS + slot) * self.stride) as _ }
+    /// Copies the current values into the region of the image
+    fn write(&self, image: usize) -> fe::Result<()>
+    {
+        let base = self.offset(image, 0) as usize;
+        let values = self.values.borrow();
+        self.memory.map(base .. base + self.stride * super::ANIMATED_SLOTS).map(|mm| for (n, v) in values.iter().enumerate()
+        {
+            *unsafe { mm.get_mut::<super::Animated>(n * self.stride) } = *v;
+        })
+    }
+}
+impl Drop for AnimatedValues
+{
+    fn drop(&mut self)
+    {
+        let core = RenderDeviceCore::get();
+        core.stats.freed(core.memindices.host, self.memory_size as _);
+    }
+}
+
+/// Vertices rewritten while the recorded commands stay valid: a host visible vertex buffer with a region per swapchain image.
+/// The current vertices are copied into the region of an image right before submitting its commands, as `AnimatedValues` are
+pub struct DynamicVertices(Rc<DynamicVertexBuffer>);
+impl super::DynamicVertices for DynamicVertices {}
+struct DynamicVertexBuffer
+{
+    // the buffer is destroyed before the memory
+    buffer: fe::Buffer, memory: fe::DeviceMemory, memory_size: fe::vk::VkDeviceSize,
+    /// Bytes of a region
+    capacity: usize,
+    data: RefCell<Vec<u8>>,
+    /// A bit per swapchain image whose region holds older vertices
+    stale: Cell<u32>
+}
+impl Drop for DynamicVertexBuffer
+{
+    fn drop(&mut self)
+    {
+        let core = RenderDeviceCore::get();
+        core.stats.freed(core.memindices.host, self.memory_size as _);
+    }
+}
+
+/// The signed distance field mode: a textured quad with per-draw `SdfStyle` in push constants(shaders/sdf.*)
+/// and the animated values in set 1. Multi-channel fields are sampled by another fragment shader(msdf.frag.spv).
+/// Created for the primary render targets; usable with any render pass of the same attachment format
+pub struct SdfPipeline { layout: fe::PipelineLayout, pipeline: fe::Pipeline }
+impl SdfPipeline
+{
+    fn new(device: &fe::Device, texture_layout: &fe::DescriptorSetLayout, animated_layout: &fe::DescriptorSetLayout, pass: &fe::RenderPass,
+        multi_channel: bool) -> fe::Result<Self>
+    {
+        use std::mem::size_of;
+        let fragment = if multi_channel { &include_bytes!(concat!(env!("OUT_DIR"), "/msdf.frag.spv"))[..] }
+            else { &include_bytes!(concat!(env!("OUT_DIR"), "/sdf.frag.spv"))[..] };
+        let vsh = fe::ShaderModule::from_memory(device, &include_bytes!(concat!(env!("OUT_DIR"), "/sdf.vert.spv"))[..])?;
+        let fsh = fe::ShaderModule::from_memory(device, fragment)?;
+        let layout = fe::PipelineLayout::new(device, &[texture_layout, animated_layout],
+            &[(fe::ShaderStage::VERTEX.fragment(), 0 .. size_of::<super::SdfStyle>() as u32)])?;
+        let vbind = [fe::vk::VkVertexInputBindingDescription { binding: 0, stride: size_of::<PosUV>() as _, inputRate: fe::vk::VK_VERTEX_INPUT_RATE_VERTEX }];
+        let vattr = [
+            fe::vk::VkVertexInputAttributeDescription { location: 0, binding: 0, format: fe::vk::VK_FORMAT_R32G32B32A32_SFLOAT, offset: 0 },
+            fe::vk::VkVertexInputAttributeDescription { location: 1, binding: 0, format: fe::vk::VK_FORMAT_R32G32_SFLOAT, offset: 16 }
+        ];
+        let mut vps = fe::VertexProcessingStages::new(fe::PipelineShader::new(&vsh, "main", None), &vbind, &vattr,
+            fe::vk::VK_PRIMITIVE_TOPOLOGY_TRIANGLE_LIST);
+        vps.fragment_shader(fe::PipelineShader::new(&fsh, "main", None));
+        // the fragment shader outputs premultiplied colors
+        let pipeline = fe::GraphicsPipelineBuilder::new(&layout, (pass, 0)).vertex_processing(vps)
+            .viewport_scissors(fe::DynamicArrayState::Dynamic(1), fe::DynamicArrayState::Dynamic(1))
+            .add_attachment_blend(fe::AttachmentColorBlendState::premultiplied()).create(device, None)?;
+        Ok(SdfPipeline { layout, pipeline })
+    }
+}
+
+/// The vector mode: colored triangles(`vector::ColoredVertex`) with per-draw `VectorStyle` in push constants(shaders/vector.*)
+/// and the animated values in set 0.
+/// Created for the primary render targets as `SdfPipeline` is
+pub struct VectorPipeline { layout: fe::PipelineLayout, pipeline: fe::Pipeline }
+impl VectorPipeline
+{
+    fn new(device: &fe::Device, animated_layout: &fe::DescriptorSetLayout, pass: &fe::RenderPass) -> fe::Result<Self>
+    {
+        use std::mem::size_of;
+        let vsh = fe::ShaderModule::from_memory(device, &include_bytes!(concat!(env!("OUT_DIR"), "/vector.vert.spv"))[..])?;
+        let fsh = fe::ShaderModule::from_memory(device, &include_bytes!(concat!(env!("OUT_DIR"), "/vector.frag.spv"))[..])?;
+        let layout = fe::PipelineLayout::new(device, &[animated_layout], &[(fe::ShaderStage::VERTEX, 0 .. size_of::<super::VectorStyle>() as u32)])?;
+        let vbind = [fe::vk::VkVertexInputBindingDescription
+        {
+            binding: 0, stride: size_of::<::vector::ColoredVertex>() as _, inputRate: fe::vk::VK_VERTEX_INPUT_RATE_VERTEX
+        }];
+        let vattr = [
+            fe::vk::VkVertexInputAttributeDescription { location: 0, binding: 0, format: fe::vk::VK_FORMAT_R32G32_SFLOAT, offset: 0 },
+            fe::vk::VkVertexInputAttributeDescription { location: 1, binding: 0, format: fe::vk::VK_FORMAT_R8G8B8A8_UNORM, offset: 8 }
+        ];
+        let mut vps = fe::VertexProcessingStages::new(fe::PipelineShader::new(&vsh, "main", None), &vbind, &vattr,
+            fe::vk::VK_PRIMITIVE_TOPOLOGY_TRIANGLE_LIST);
+        vps.fragment_shader(fe::PipelineShader::new(&fsh, "main", None));
+        // the vertex shader premultiplies the colors
+        let pipeline = fe::GraphicsPipelineBuilder::new(&layout, (pass, 0)).vertex_processing(vps)
+            .viewport_scissors(fe::DynamicArrayState::Dynamic(1), fe::DynamicArrayState::Dynamic(1))
+            .add_attachment_blend(fe::AttachmentColorBlendState::premultiplied()).create(device, None)?;
+        Ok(VectorPipeline { layout, pipeline })
+    }
+}
+
+/// Render passes which differ only in the load operation of the color attachment.
+/// All of them are compatible with each other, so one framebuffer can be used with any of them
+pub struct RenderPassSet { clear: fe::RenderPass, load: fe::RenderPass, dont_care: fe::RenderPass }
+impl RenderPassSet
+{
+    fn new(device: &fe::Device, format: fe::vk::VkFormat, after_usage: ResourceAfterUsage) -> fe::Result<Self>
+    {
+        let create = |load_op| fe::RenderPassBuilder::new()
+            .add_attachment(fe::vk::VkAttachmentDescription
+            {
+                loadOp: load_op, storeOp: fe::vk::VK_ATTACHMENT_STORE_OP_STORE,
+                format, initialLayout: fe::ImageLayout::ColorAttachmentOpt as _, finalLayout: after_usage.translate_vk() as _,
+                samples: 1, flags: 0, stencilLoadOp: fe::vk::VK_ATTACHMENT_LOAD_OP_DONT_CARE, stencilStoreOp: fe::vk::VK_ATTACHMENT_STORE_OP_DONT_CARE
+            })
+            .add_subpass(fe::SubpassDescription::new().add_color_output(0, fe::ImageLayout::ColorAttachmentOpt, None))
+            .create(device);
+        Ok(RenderPassSet
+        {
+            clear: create(fe::vk::VK_ATTACHMENT_LOAD_OP_CLEAR)?, load: create(fe::vk::VK_ATTACHMENT_LOAD_OP_LOAD)?,
+            dont_care: create(fe::vk::VK_ATTACHMENT_LOAD_OP_DONT_CARE)?
+        })
+    }
+    fn select(&self, load: &LoadOp) -> &fe::RenderPass
+    {
+        match *load
+        {
+            LoadOp::Clear(_) => &self.clear, LoadOp::Load => &self.load, LoadOp::DontCare => &self.dont_care
+        }
+    }
+}
 
 pub enum RenderTarget
 {
-    Owned(fe::RenderPass, fe::Framebuffer, Option<Color>),
+    Owned(RenderPassSet, fe::Framebuffer),
     PrimaryRT(usize)
 }
 impl super::RenderTarget for RenderTarget {}
 impl RenderTarget
 {
-    fn pass(&self) -> &fe::RenderPass
+    fn passes(&self) -> &RenderPassSet
     {
         match *self
         {
-            RenderTarget::Owned(ref r, _, _) => r,
+            RenderTarget::Owned(ref r, _) => r,
             RenderTarget::PrimaryRT(_) => &super::RenderDevice::get().ensure_vk().primary_rt_pass
         }
     }
@@ -637,101 +1280,140 @@ impl RenderTarget
     {
         match *self
         {
-            RenderTarget::Owned(_, ref f, _) => f,
+            RenderTarget::Owned(_, ref f) => f,
             RenderTarget::PrimaryRT(n) => &super::RenderDevice::get().ensure_vk().rtsc[n]
         }
     }
-    fn opt_clear(&self) -> Option<&Color>
-    {
-        match *self
-        {
-            RenderTarget::Owned(_, _, ref o) => o.as_ref(),
-            RenderTarget::PrimaryRT(_) => Some(&Color(0.0, 0.0, 0.0, 0.5))
-        }
-    }
 }
 
-use std::sync::atomic::{Ordering, AtomicUsize, AtomicBool};
+/// Number of frames which can be recorded/queued before waiting for the GPU
+pub const FRAMES_IN_FLIGHT: usize = 2;
+/// Synchronization objects for a frame in flight
+pub struct FrameSync
+{
+    /// Signaled when the swapchain image is acquired
+    acquired: fe::Semaphore,
+    /// Signaled when the rendering commands are completed(waited by the presentation)
+    rendered: fe::Semaphore,
+    /// Signaled when the submission of the frame is completed on the GPU
+    completion: fe::Fence
+}
 pub struct RenderControl
 {
-    th: Option<::std::thread::JoinHandle<()>>,
-    next_index: Arc<AtomicUsize>, render_ready_flag: Arc<AtomicBool>,
-    ev_acquire_next: Event, ev_render_ready: Event, ev_thread_exit: Event
+    frames: Vec<FrameSync>, current: Cell<usize>,
+    /// The frame that last used each swapchain image
+    image_owners: RefCell<Vec<Option<usize>>>
 }
 impl RenderControl
 {
-    fn acquire_next_image_sync(sc: Option<&fe::Swapchain>, fence: &fe::Fence) -> fe::Result<u32>
+    fn init(device: &fe::Device, image_count: usize) -> fe::Result<Self>
     {
-        let sc = sc.unwrap_or_else(|| &super::RenderDevice::get().ensure_vk().swapchain);
-        let next = sc.acquire_next(None, None, Some(fence))?;
-        fence.wait()?; fence.reset()?;
-        Ok(next)
+        let frames = (0 .. FRAMES_IN_FLIGHT).map(|_| Ok(FrameSync
+        {
+            acquired: fe::Semaphore::new(device)?, rendered: fe::Semaphore::new(device)?,
+            completion: fe::Fence::new(device, true)?
+        })).collect::<fe::Result<_>>()?;
+        Ok(RenderControl { frames, current: Cell::new(0), image_owners: RefCell::new(vec![None; image_count]) })
     }
 
-    fn init(device: &fe::Device, swapchain: &fe::Swapchain) -> Self
+    /// Waits until the current frame slot is reusable, then acquires next image of the swapchain.
+    /// The fence of the frame is reset by the caller right before the submission signaling it
+    fn begin_frame(&self, swapchain: &fe::Swapchain) -> fe::Result<(&FrameSync, u32)>
     {
-        let render_ready = fe::Fence::new(device, false).expect("Failed to create a fence");
-        let render_ready_flag = Arc::new(AtomicBool::new(true));
-        let rrf_th = render_ready_flag.clone();
-        let (ev_acquire_next, ev_render_ready, ev_thread_exit) = (Event::new(), Event::new(), Event::new());
-        let (ean_s, err_s, ete_s) = (ev_acquire_next.share_inner(), ev_render_ready.share_inner(), ev_thread_exit.share_inner());
-        let next_index = Arc::new(AtomicUsize::new(Self::acquire_next_image_sync(Some(swapchain), &render_ready)
-            .expect("Failure while acquiring initial index of buffer") as _));
-        let ni_th = next_index.clone();
-        RenderControl
+        let current = self.current.get();
+        let frame = &self.frames[current];
+        frame.completion.wait()?;
+        let next = swapchain.acquire_next(None, Some(&frame.acquired), None)?;
+        // the image may still be in use by the other frame if the swapchain returns images out of order
+        let mut owners = self.image_owners.borrow_mut();
+        if let Some(owner) = owners[next as usize]
         {
-            th: Some(::std::thread::Builder::new().name("RenderControl Fence Observer".into()).spawn(move ||
-            {
-                let (ev_acquire_next, ev_render_ready, ev_thread_exit) = (ean_s, err_s, ete_s);
-                let render_ready_flag = rrf_th;
-
-                'mlp: loop
-                {
-                    loop
-                    {
-                        if Event::wait_any(&[&ev_acquire_next, &ev_thread_exit]) == Some(0) { ev_acquire_next.reset(); break; }
-                        else { ev_thread_exit.reset(); break 'mlp; }
-                    }
-                    let next = Self::acquire_next_image_sync(None, &render_ready).expect("Failure while acquiring next index of buffer");
-                    ni_th.store(next as _, Ordering::Release);
-                    render_ready_flag.store(true, Ordering::Release);
-                    ev_render_ready.set();
-                }
-            }).expect("Failed to spawn an observer thread")), next_index,
-            ev_acquire_next, ev_render_ready, ev_thread_exit, render_ready_flag
+            if owner != current { self.frames[owner].completion.wait()?; }
         }
+        owners[next as usize] = Some(current);
+        Ok((frame, next))
     }
-
-    pub fn check_ready_next(&self) -> fe::Result<Option<u32>>
-    {
-        if !self.render_ready_flag.load(Ordering::Acquire) { Ok(None) }
-        else { Ok(Some(self.next_index.load(Ordering::Acquire) as _)) }
-    }
-    pub fn wait_last_render_completion(&self) -> fe::Result<u32>
+    fn end_frame(&self)
     {
-        if let Some(n) = self.check_ready_next()? { Ok(n) }
-        else { self.ev_render_ready.wait(); self.wait_last_render_completion() }
+        self.current.set((self.current.get() + 1) % self.frames.len());
     }
-    pub fn begin_acquire_next(&self)
+    fn wait_all(&self) -> fe::Result<()>
     {
-        self.render_ready_flag.store(false, Ordering::Release);
-        self.ev_acquire_next.set();
+        for f in &self.frames { f.completion.wait()?; }
+        Ok(())
     }
 }
-impl Drop for RenderControl
+
+pub struct RenderCommands(fe::CommandPool, Vec<fe::CommandBuffer>, Vec<Cell<CommandStatistics>>);
+pub struct CommandRecorder<'d>
+{
+    rec: fe::CmdRecord<'d>, in_render_pass: bool,
+    /// Size of the current render target(for dynamic viewports)
+    target_size: Option<fe::Extent2D>,
+    /// Scissor of the following draws(the whole target if None)
+    clip: Option<fe::vk::VkRect2D>,
+    /// The swapchain image whose animated values are read, and the slots of the layer and the element
+    image: usize, animation: [usize; 2],
+    binds: BindTracker, stats_sink: &'d Cell<CommandStatistics>
+}
+
+impl<'d> Drop for CommandRecorder<'d>
 {
     fn drop(&mut self)
     {
-        self.ev_thread_exit.set(); replace(&mut self.th, None).unwrap().join().unwrap();
+        if self.in_render_pass { self.rec.end_render_pass(); }
+        self.stats_sink.set(self.binds.stats);
     }
 }
 
-pub struct RenderCommands(fe::CommandPool, Vec<fe::CommandBuffer>);
-pub struct CommandRecorder<'d> { rec: fe::CmdRecord<'d>, in_render_pass: bool }
-
-impl<'d> Drop for CommandRecorder<'d>
+impl<'d> CommandRecorder<'d>
 {
-    fn drop(&mut self) { if self.in_render_pass { self.rec.end_render_pass(); } }
+    fn new(rec: fe::CmdRecord<'d>, stats_sink: &'d Cell<CommandStatistics>, image: usize) -> Self
+    {
+        CommandRecorder
+        {
+            rec, in_render_pass: false, target_size: None, clip: None, image, animation: [0; 2], binds: BindTracker::default(), stats_sink
+        }
+    }
+
+    fn begin_render_pass(&mut self, target: &RenderTarget, load: &LoadOp)
+    {
+        let pass = target.passes().select(load);
+        self.target_size = Some(target.fb().size().clone());
+        self.clip = None;
+        if let LoadOp::Clear(ref c) = *load
+        {
+            self.rec.begin_render_pass(pass, target.fb(), target.fb().size().clone().into(), &[fe::ClearValue::Color(c.as_ref().clone())], false);
+        }
+        else { self.rec.begin_render_pass(pass, target.fb(), target.fb().size().clone().into(), &[], false); }
+    }
+    /// The whole target and the clip rectangle(dynamic states of the pipelines)
+    fn set_viewport_scissor(&mut self)
+    {
+        let fe::Extent2D(width, height) = self.target_size.clone().expect("No render targets are set");
+        let scissor = self.clip.clone().unwrap_or(fe::vk::VkRect2D { extent: fe::vk::VkExtent2D { width, height }, .. Default::default() });
+        self.rec.set_viewport(0, &[fe::vk::VkViewport { x: 0.0, y: 0.0, width: width as _, height: height as _, minDepth: 0.0, maxDepth: 1.0 }])
+            .set_scissor(0, &[scissor]);
+    }
+    /// Binds the animated values of the following draws into the set of the bound pipeline
+    fn bind_animation(&mut self, set: u32)
+    {
+        if !self.binds.animation(self.animation) { return; }
+        let animated = &super::RenderDevice::get().ensure_vk().animated;
+        let offsets = [animated.offset(self.image, self.animation[0]), animated.offset(self.image, self.animation[1])];
+        self.rec.bind_graphics_descriptor_sets(set, &[animated.set], &offsets);
+    }
+    /// Draws with one of the distance field pipelines
+    fn draw_field(&mut self, pipeline: &SdfPipeline, vertices: &super::VertexArray, field: &super::TextureBinding, style: &super::SdfStyle)
+    {
+        let field = unsafe { &*(field as *const _ as *const TextureBinding) };
+        if self.binds.pipeline(&pipeline.pipeline) { self.rec.bind_graphics_pipeline_pair(&pipeline.pipeline, &pipeline.layout); }
+        if self.binds.descriptor_set(field) { self.rec.bind_graphics_descriptor_sets(0, &[field.set], &[]); }
+        self.bind_animation(1);
+        self.set_viewport_scissor();
+        self.rec.push_graphics_constant(fe::ShaderStage::VERTEX.fragment(), 0, style);
+        super::RenderCommandsBasic::draw(self, vertices, 1);
+    }
 }
 
 impl super::CommandBuffer for fe::CommandBuffer {}
@@ -739,7 +1421,7 @@ impl super::RenderCommands for RenderCommands
 {
     fn begin_recording<'s>(&'s self, index: usize) -> Result<Box<super::RenderCommandsBasic + 's>, Box<Error>>
     {
-        Ok(box CommandRecorder { rec: self.1[index].begin()?, in_render_pass: false })
+        Ok(box CommandRecorder::new(self.1[index].begin()?, &self.2[index], index))
     }
 }
 impl<'d> super::RenderCommandsBasic for CommandRecorder<'d>
@@ -756,26 +1438,18 @@ impl<'d> super::RenderCommandsBasic for CommandRecorder<'d>
                 .. Default::default()
             }).collect::<Vec<_>>());
     }
-    fn set_render_target(&mut self, target: &super::RenderTarget)
+    fn set_render_target(&mut self, target: &super::RenderTarget, load: LoadOp)
     {
         let target = unsafe { &*(target as *const _ as *const RenderTarget) };
         if self.in_render_pass { self.rec.end_render_pass(); }
-        if let Some(ref c) = target.opt_clear()
-        {
-            self.rec.begin_render_pass(target.pass(), target.fb(), target.fb().size().clone().into(), &[fe::ClearValue::Color(c.as_ref().clone())], false);
-        }
-        else { self.rec.begin_render_pass(target.pass(), target.fb(), target.fb().size().clone().into(), &[], false); }
+        self.begin_render_pass(target, &load);
         self.in_render_pass = true;
     }
-    fn execute_subcommands_into(&mut self, target: &super::RenderTarget, subcommands: &[&super::CommandBuffer])
+    fn execute_subcommands_into(&mut self, target: &super::RenderTarget, load: LoadOp, subcommands: &[&super::CommandBuffer])
     {
         let target = unsafe { &*(target as *const _ as *const RenderTarget) };
         if self.in_render_pass { self.rec.end_render_pass(); }
-        if let Some(ref c) = target.opt_clear()
-        {
-            self.rec.begin_render_pass(target.pass(), target.fb(), target.fb().size().clone().into(), &[fe::ClearValue::Color(c.as_ref().clone())], false);
-        }
-        else { self.rec.begin_render_pass(target.pass(), target.fb(), target.fb().size().clone().into(), &[], false); }
+        self.begin_render_pass(target, &load);
         unsafe { self.rec
             .execute_commands(&subcommands.into_iter().map(|&sc| unsafe { &*(sc as *const _ as *const fe::CommandBuffer) }.native_ptr()).collect::<Vec<_>>())
             .end_render_pass(); }
@@ -784,7 +1458,8 @@ impl<'d> super::RenderCommandsBasic for CommandRecorder<'d>
     fn draw(&mut self, vertices: &super::VertexArray, instance_count: usize)
     {
         let va = unsafe { &*(vertices as *const _ as *const VertexArray) };
-        self.rec.bind_vertex_buffers(0, &[(va.vb_desc.buf, va.vb_desc.offset)]);
+        self.binds.draw(instance_count);
+        self.rec.bind_vertex_buffers(0, &[(va.vb_desc.buf, va.vb_desc.offset + self.image * va.vb_desc.image_stride)]);
         if let Some(ref ib) = va.ib_desc
         {
             self.rec.bind_index_buffer(&ib.buf, ib.offset, ib.format);
@@ -792,6 +1467,38 @@ impl<'d> super::RenderCommandsBasic for CommandRecorder<'d>
         }
         else { self.rec.draw(va.vb_desc.count as _, instance_count as _, 0, 0); }
     }
+    fn draw_sdf(&mut self, vertices: &super::VertexArray, field: &super::TextureBinding, style: &super::SdfStyle)
+    {
+        self.draw_field(&super::RenderDevice::get().ensure_vk().sdf, vertices, field, style);
+    }
+    fn draw_msdf(&mut self, vertices: &super::VertexArray, field: &super::TextureBinding, style: &super::SdfStyle)
+    {
+        self.draw_field(&super::RenderDevice::get().ensure_vk().msdf, vertices, field, style);
+    }
+    fn draw_vector(&mut self, vertices: &super::VertexArray, style: &super::VectorStyle)
+    {
+        let vector = &super::RenderDevice::get().ensure_vk().vector;
+        if self.binds.pipeline(&vector.pipeline) { self.rec.bind_graphics_pipeline_pair(&vector.pipeline, &vector.layout); }
+        self.bind_animation(0);
+        self.set_viewport_scissor();
+        self.rec.push_graphics_constant(fe::ShaderStage::VERTEX, 0, style);
+        super::RenderCommandsBasic::draw(self, vertices, 1);
+    }
+    fn set_animation(&mut self, slot: Option<&super::AnimatedSlot>) { self.animation[1] = slot.map(|s| s.index()).unwrap_or(0); }
+    fn set_layer_animation(&mut self, slot: Option<&super::AnimatedSlot>) { self.animation[0] = slot.map(|s| s.index()).unwrap_or(0); }
+    fn set_clip(&mut self, rect: Option<[u32; 4]>)
+    {
+        let fe::Extent2D(width, height) = self.target_size.clone().expect("No render targets are set");
+        self.clip = rect.map(|r|
+        {
+            let (l, t) = (r[0].min(width), r[1].min(height));
+            fe::vk::VkRect2D
+            {
+                offset: fe::vk::VkOffset2D { x: l as _, y: t as _ },
+                extent: fe::vk::VkExtent2D { width: r[2].min(width).max(l) - l, height: r[3].min(height).max(t) - t }
+            }
+        });
+    }
 }
 
 pub struct VertexArray<'b> { vb_desc: VertexBufferSlice<'b>, ib_desc: Option<IndexBufferSlice<'b>> }
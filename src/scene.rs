@@ -0,0 +1,417 @@
+//! Scenes(welcome, login, home, course detail..) and the stack of them with transitions
+
+use render::{RenderDevice, RenderCommandsBasic, LoadOp, Color, Animated, AnimatedSlot, SdfStyle, BuiltinResourceKey};
+use render::{ResourceBlock, TextureBinding, TextureParam, TextureUsage, ColorFormat};
+use input::InputEvent;
+use metrics::*;
+use std::error::Error;
+
+/// How a scene is drawn during transitions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Appearance
+{
+    pub opacity: f32,
+    /// Offset in the normalized device coordinates
+    pub offset: (f32, f32)
+}
+impl Appearance
+{
+    pub const IDENTITY: Appearance = Appearance { opacity: 1.0, offset: (0.0, 0.0) };
+    pub fn animated(&self) -> Animated { Animated { offset: [self.offset.0, self.offset.1], opacity: self.opacity, .. Animated::IDENTITY } }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transition
+{
+    None,
+    /// Seconds to fade the leaving scene out and the coming scene in
+    CrossFade(f32),
+    /// Seconds to slide the coming scene in from the right(from the left when popped)
+    Slide(f32)
+}
+impl Transition
+{
+    fn duration(&self) -> f32 { match *self { Transition::None => 0.0, Transition::CrossFade(d) | Transition::Slide(d) => d } }
+}
+
+pub enum SceneRequest { Push(Box<Scene>, Transition), Pop(Transition), Replace(Box<Scene>, Transition) }
+pub enum SceneUpdate
+{
+    /// Nothing changed since the last update
+    Idle,
+    /// Only the animated values(`RenderDevice::set_animated`) changed: the recorded commands render the frame
+    Animate,
+    /// The commands must be recorded again
+    Redraw,
+    Request(SceneRequest)
+}
+/// What the frame loop does after updating the scenes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FrameUpdate
+{
+    /// The last presented frame stays
+    Idle,
+    /// Renders a frame with the recorded commands
+    Render,
+    /// Records the commands again, then renders a frame
+    Record
+}
+
+pub trait Scene
+{
+    /// Creates the GPU resources. Called when the scene enters the stack
+    fn init_resources(&mut self) -> Result<(), Box<Error>>;
+    /// Drops the GPU resources. Called after the scene leaves the stack
+    fn release_resources(&mut self);
+    fn handle_input(&mut self, _event: &InputEvent) -> Option<SceneRequest> { None }
+    /// Advances the scene by `dt` seconds. Only the top scene is updated
+    fn update(&mut self, _dt: f32) -> SceneUpdate { SceneUpdate::Idle }
+    /// The color the render target is cleared with
+    fn background(&self) -> Color;
+    /// Records the draws of the scene into the render target set by the manager.
+    /// The layer animation is set by the manager(the appearance during transitions)
+    fn record(&self, rec: &mut RenderCommandsBasic);
+}
+
+/// A running transition between the top scene and the one it covers or replaced
+struct Running
+{
+    kind: Transition, elapsed: f32,
+    /// The popped or replaced scene, taken for the release when the transition ends. None for pushes(the covered scene stays in the stack)
+    leaving: Option<Box<Scene>>,
+    backwards: bool
+}
+
+/// The stack of scenes and the transitions between them, without the GPU resources of the manager.
+/// Scenes leaving the stack are kept until the owner takes them(`take_released`): the frames in flight may still read their resources
+pub struct SceneStack
+{
+    stack: Vec<Box<Scene>>, transition: Option<Running>, dirty: bool,
+    /// Popped or replaced scenes whose transitions ended, in the order of leaving
+    released: Vec<Box<Scene>>
+}
+impl SceneStack
+{
+    pub fn new() -> Self { SceneStack { stack: Vec::new(), transition: None, dirty: true, released: Vec::new() } }
+    pub fn len(&self) -> usize { self.stack.len() }
+    pub fn is_transitioning(&self) -> bool { self.transition.is_some() }
+
+    pub fn push(&mut self, mut scene: Box<Scene>, transition: Transition) -> Result<(), Box<Error>>
+    {
+        self.finish_transition();
+        scene.init_resources()?;
+        let covered = !self.stack.is_empty();
+        self.stack.push(scene);
+        if covered { self.start(transition, None, false); }
+        self.dirty = true;
+        Ok(())
+    }
+    /// The last scene is never popped
+    pub fn pop(&mut self, transition: Transition)
+    {
+        if self.stack.len() <= 1 { return; }
+        self.finish_transition();
+        let leaving = self.stack.pop();
+        self.start(transition, leaving, true);
+        self.dirty = true;
+    }
+    pub fn replace(&mut self, mut scene: Box<Scene>, transition: Transition) -> Result<(), Box<Error>>
+    {
+        self.finish_transition();
+        scene.init_resources()?;
+        let leaving = self.stack.pop();
+        self.stack.push(scene);
+        self.start(transition, leaving, false);
+        self.dirty = true;
+        Ok(())
+    }
+    pub fn apply(&mut self, request: SceneRequest) -> Result<(), Box<Error>>
+    {
+        match request
+        {
+            SceneRequest::Push(s, t) => self.push(s, t),
+            SceneRequest::Pop(t) => { self.pop(t); Ok(()) },
+            SceneRequest::Replace(s, t) => self.replace(s, t)
+        }
+    }
+
+    /// Delivers the input to the top scene. Input during transitions is dropped except resizes, which every scene in the stack gets
+    pub fn handle_input(&mut self, event: &InputEvent) -> Result<(), Box<Error>>
+    {
+        if let InputEvent::Resize(_) = *event
+        {
+            self.dirty = true;
+            for s in &mut self.stack { s.handle_input(event); }
+            return Ok(());
+        }
+        if self.transition.is_some() { return Ok(()); }
+        match self.stack.last_mut().and_then(|s| s.handle_input(event))
+        {
+            Some(r) => self.apply(r),
+            None => Ok(())
+        }
+    }
+    /// Advances the transition and the top scene
+    pub fn update(&mut self, dt: f32) -> Result<FrameUpdate, Box<Error>>
+    {
+        let mut frame = if ::std::mem::replace(&mut self.dirty, false) { FrameUpdate::Record } else { FrameUpdate::Idle };
+        if let Some(ref mut t) = self.transition { t.elapsed += dt; frame = frame.max(FrameUpdate::Render); }
+        // the leaving or covered scene is not drawn anymore
+        if self.transition.as_ref().map(|t| t.elapsed >= t.kind.duration()).unwrap_or(false) { self.finish_transition(); frame = FrameUpdate::Record; }
+        match self.stack.last_mut().map(|s| s.update(dt))
+        {
+            Some(SceneUpdate::Animate) => frame = frame.max(FrameUpdate::Render),
+            Some(SceneUpdate::Redraw) => frame = FrameUpdate::Record,
+            Some(SceneUpdate::Request(r)) => { self.apply(r)?; frame = FrameUpdate::Record; },
+            _ => ()
+        }
+        Ok(frame)
+    }
+
+    /// The scenes to draw from the bottom
+    pub fn layers(&self) -> Vec<(&Scene, Appearance)>
+    {
+        let top = match self.stack.last() { Some(s) => &**s, None => return Vec::new() };
+        let t = match self.transition { Some(ref t) => t, None => return vec![(top, Appearance::IDENTITY)] };
+        let other = match t.leaving
+        {
+            Some(ref s) => &**s,
+            None => &*self.stack[self.stack.len() - 2]
+        };
+        // smoothstep-eased progress
+        let x = (t.elapsed / t.kind.duration()).max(0.0).min(1.0);
+        let p = x * x * (3.0 - 2.0 * x);
+        match t.kind
+        {
+            Transition::Slide(_) =>
+            {
+                let dir = if t.backwards { -1.0 } else { 1.0 };
+                vec![(other, Appearance { offset: (-2.0 * p * dir, 0.0), .. Appearance::IDENTITY }),
+                    (top, Appearance { offset: (2.0 * (1.0 - p) * dir, 0.0), .. Appearance::IDENTITY })]
+            },
+            _ => vec![(other, Appearance { opacity: 1.0 - p, .. Appearance::IDENTITY }), (top, Appearance { opacity: p, .. Appearance::IDENTITY })]
+        }
+    }
+
+    /// The scenes which left the stack since the last call. The caller releases their resources after the frames in flight
+    pub fn take_released(&mut self) -> Vec<Box<Scene>> { ::std::mem::replace(&mut self.released, Vec::new()) }
+    /// Ends the transition and moves all the scenes into the released ones(from the top)
+    pub fn clear(&mut self)
+    {
+        self.finish_transition();
+        while let Some(s) = self.stack.pop() { self.released.push(s); }
+    }
+
+    fn start(&mut self, kind: Transition, leaving: Option<Box<Scene>>, backwards: bool)
+    {
+        self.transition = Some(Running { kind, elapsed: 0.0, leaving, backwards });
+        if kind.duration() <= 0.0 { self.finish_transition(); }
+    }
+    fn finish_transition(&mut self)
+    {
+        if let Some(Running { leaving: Some(s), .. }) = self.transition.take() { self.released.push(s); }
+    }
+}
+
+/// The scene stack drawn with the render device
+pub struct SceneManager
+{
+    scenes: SceneStack,
+    /// Created when the layers are animated or recorded first
+    layers: Option<LayerResources>
+}
+struct LayerResources
+{
+    /// Animated values of the layers from the bottom: transitions advance without recording the commands again
+    slots: [AnimatedSlot; 2],
+    /// A solid field drawing the backgrounds of the layers during transitions
+    solid: (Box<TextureBinding>, Box<ResourceBlock>)
+}
+impl LayerResources
+{
+    fn new() -> Result<Self, Box<Error>>
+    {
+        let slots = [RenderDevice::get().new_animated_slot()?, RenderDevice::get().new_animated_slot()?];
+        let res = RenderDevice::get().create_resources(&[], &[
+            TextureParam { size: Size2U(4, 4), color: ColorFormat::Grayscale, usage: TextureUsage::Immutable(&[0xff; 16]), .. Default::default() }
+        ])?;
+        let solid = (RenderDevice::get().bind_texture(&*res, 0)?, res);
+        Ok(LayerResources { slots, solid })
+    }
+}
+impl SceneManager
+{
+    /// The GPU resources are created when they are used first
+    pub fn new() -> Self { SceneManager { scenes: SceneStack::new(), layers: None } }
+
+    pub fn push(&mut self, scene: Box<Scene>, transition: Transition) -> Result<(), Box<Error>>
+    {
+        let r = self.scenes.push(scene, transition);
+        self.release_left();
+        r
+    }
+    /// `SceneStack::handle_input`
+    pub fn handle_input(&mut self, event: &InputEvent) -> Result<(), Box<Error>>
+    {
+        let r = self.scenes.handle_input(event);
+        self.release_left();
+        r
+    }
+    /// `SceneStack::update`, feeding the appearances of the layers into their animated values
+    pub fn update(&mut self, dt: f32) -> Result<FrameUpdate, Box<Error>>
+    {
+        let frame = self.scenes.update(dt);
+        self.release_left();
+        let frame = frame?;
+        if frame != FrameUpdate::Idle
+        {
+            self.create_layer_resources()?;
+            let slots = &self.layers.as_ref().unwrap().slots;
+            for (slot, &(_, ref a)) in slots.iter().zip(&self.scenes.layers()) { RenderDevice::get().set_animated(slot, a.animated()); }
+        }
+        Ok(frame)
+    }
+    /// Records the frame commands of all the swapchain images.
+    /// During transitions the background of each layer is drawn under it(blended by the opacities, moved by the offsets)
+    pub fn record_commands(&mut self) -> Result<(), Box<Error>>
+    {
+        self.create_layer_resources()?;
+        let (resources, layers) = (self.layers.as_ref().unwrap(), self.scenes.layers());
+        let clear = layers.first().map(|&(s, _)| s.background()).unwrap_or(Color(0.0, 0.0, 0.0, 1.0));
+        let unit_rect = RenderDevice::get().get_builtin_vertex_array(BuiltinResourceKey::UnitRect)?;
+        RenderDevice::get().update_render_commands(|rec, n|
+        {
+            let target = RenderDevice::get().get_primary_render_target(n);
+            rec.prepare_render_targets(&[&*target]);
+            rec.set_render_target(&*target, LoadOp::Clear(clear));
+            for (slot, &(scene, _)) in resources.slots.iter().zip(&layers)
+            {
+                rec.set_layer_animation(Some(slot));
+                rec.set_animation(None);
+                if layers.len() > 1 { rec.draw_sdf(&*unit_rect, &*resources.solid.0, &SdfStyle::new(scene.background())); }
+                scene.record(rec);
+            }
+        })
+    }
+
+    fn create_layer_resources(&mut self) -> Result<(), Box<Error>>
+    {
+        if self.layers.is_none() { self.layers = Some(LayerResources::new()?); }
+        Ok(())
+    }
+    /// Releases the scenes which left the stack after the frames in flight, which may still read their resources
+    fn release_left(&mut self)
+    {
+        let left = self.scenes.take_released();
+        if left.is_empty() { return; }
+        if let Err(e) = RenderDevice::get().wait_render_ready() { println!("!! Failed to wait for the frames in flight: {:?}", e); }
+        for mut s in left { s.release_resources(); }
+    }
+}
+impl Drop for SceneManager
+{
+    fn drop(&mut self)
+    {
+        self.scenes.clear();
+        self.release_left();
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use input::{KeyCode, Modifiers, PointerButton};
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    type Log = Rc<RefCell<Vec<String>>>;
+    struct Named { name: &'static str, log: Log, next: Option<&'static str> }
+    impl Scene for Named
+    {
+        fn init_resources(&mut self) -> Result<(), Box<Error>> { self.log.borrow_mut().push(format!("init {}", self.name)); Ok(()) }
+        fn release_resources(&mut self) { self.log.borrow_mut().push(format!("release {}", self.name)); }
+        fn handle_input(&mut self, event: &InputEvent) -> Option<SceneRequest>
+        {
+            match (event, self.next.take())
+            {
+                (e, _) if e.is_back() => Some(SceneRequest::Pop(Transition::Slide(1.0))),
+                (_, Some(next)) => Some(SceneRequest::Push(scene(next, &self.log), Transition::Slide(1.0))),
+                _ => None
+            }
+        }
+        fn background(&self) -> Color { if self.name == "welcome" { Color(0.0, 0.0, 1.0, 1.0) } else { Color(1.0, 1.0, 1.0, 1.0) } }
+        fn record(&self, _rec: &mut RenderCommandsBasic) {}
+    }
+    fn scene(name: &'static str, log: &Log) -> Box<Scene> { box Named { name, log: log.clone(), next: None } }
+
+    fn offsets(m: &SceneStack) -> Vec<(f32, f32)> { m.layers().iter().map(|&(_, a)| a.offset).collect() }
+    /// Releases the scenes taken from the stack, as `SceneManager` does after the frames in flight
+    fn release(m: &mut SceneStack) { for mut s in m.take_released() { s.release_resources(); } }
+
+    #[test] fn stack_and_transitions()
+    {
+        let log = Log::default();
+        let mut m = SceneStack::new();
+        m.push(scene("welcome", &log), Transition::None).unwrap();
+        assert_eq!(m.update(0.016).unwrap(), FrameUpdate::Record);
+        assert_eq!(m.update(0.016).unwrap(), FrameUpdate::Idle);
+        m.replace(scene("login", &log), Transition::CrossFade(0.5)).unwrap();
+        assert_eq!(m.update(0.125).unwrap(), FrameUpdate::Record);
+        // the transition advances in the animated values
+        assert_eq!(m.update(0.125).unwrap(), FrameUpdate::Render);
+        let layers = m.layers();
+        assert_eq!(layers.iter().map(|&(_, a)| a.opacity).collect::<Vec<_>>(), vec![0.5, 0.5]);
+        assert_eq!(layers.iter().map(|&(s, _)| s.background()).collect::<Vec<_>>(), vec![Color(0.0, 0.0, 1.0, 1.0), Color(1.0, 1.0, 1.0, 1.0)]);
+        // the leaving scene is kept until the transition ends
+        assert!(m.take_released().is_empty());
+        assert_eq!(m.update(0.25).unwrap(), FrameUpdate::Record);
+        assert!(!m.is_transitioning());
+        release(&mut m);
+        assert_eq!(*log.borrow(), vec!["init welcome", "init login", "release welcome"]);
+
+        m.stack[0] = box Named { name: "login", log: log.clone(), next: Some("home") };
+        m.handle_input(&InputEvent::PointerDown(Point2F(0.0, 0.0), PointerButton::Primary)).unwrap();
+        assert_eq!(m.len(), 2);
+        m.update(0.5).unwrap();
+        assert_eq!(offsets(&m), vec![(-1.0, 0.0), (1.0, 0.0)]);
+        // input is dropped until the transition ends
+        let back = InputEvent::KeyDown { key: KeyCode::Escape, modifiers: Modifiers::default(), repeat: false };
+        m.handle_input(&back).unwrap();
+        assert_eq!(m.len(), 2);
+        m.update(0.5).unwrap();
+        m.handle_input(&back).unwrap();
+        m.update(0.25).unwrap();
+        let slide = offsets(&m);
+        assert!(slide[0].0 > 0.0 && slide[1].0 < 0.0);
+        m.update(1.0).unwrap();
+        assert_eq!(offsets(&m), vec![(0.0, 0.0)]);
+        m.pop(Transition::None);
+        assert_eq!(m.len(), 1);
+        release(&mut m);
+        m.clear();
+        release(&mut m);
+        assert_eq!(m.len(), 0);
+        assert_eq!(&log.borrow()[3 ..], &["init home", "release home", "release login"]);
+    }
+    #[test] fn failed_init_keeps_the_current_scene()
+    {
+        struct Broken;
+        impl Scene for Broken
+        {
+            fn init_resources(&mut self) -> Result<(), Box<Error>> { Err(From::from("no resources")) }
+            fn release_resources(&mut self) {}
+            fn background(&self) -> Color { Color(0.0, 0.0, 0.0, 1.0) }
+            fn record(&self, _rec: &mut RenderCommandsBasic) {}
+        }
+        let log = Log::default();
+        let mut m = SceneStack::new();
+        m.push(scene("welcome", &log), Transition::None).unwrap();
+        m.update(0.016).unwrap();
+        assert!(m.push(box Broken, Transition::Slide(1.0)).is_err());
+        assert!(m.replace(box Broken, Transition::CrossFade(0.5)).is_err());
+        assert_eq!(m.len(), 1);
+        assert!(!m.is_transitioning());
+        assert_eq!(m.layers()[0].0.background(), Color(0.0, 0.0, 1.0, 1.0));
+    }
+}
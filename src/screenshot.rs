@@ -0,0 +1,142 @@
+//! Screenshot capturing
+
+use render::{RenderDevice, CapturedImage};
+use image;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::env;
+use std::fs;
+use std::io::Result as IOResult;
+
+static REQUESTED: AtomicBool = ATOMIC_BOOL_INIT;
+/// Requests a screenshot of the next frame(called from the key binding)
+pub fn request() { REQUESTED.store(true, Ordering::Release); }
+
+/// Decides which frames are captured and where they are saved
+pub struct Screenshots { scheduled: Option<(u64, PathBuf)> }
+impl Screenshots
+{
+    /// Parses `--screenshot-after <frames> <path>`
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Self
+    {
+        let mut scheduled = None;
+        while let Some(a) = args.next()
+        {
+            if a != "--screenshot-after" { continue; }
+            match (args.next().and_then(|n| n.parse().ok()), args.next())
+            {
+                (Some(frames), Some(path)) => scheduled = Some((frames, PathBuf::from(path))),
+                _ => println!("!! usage: --screenshot-after <frames> <path>")
+            }
+        }
+        Screenshots { scheduled }
+    }
+
+    /// The path to save the frame into, if the frame(counted from 0) should be captured
+    pub fn target_for_frame(&mut self, frame: u64) -> Option<PathBuf>
+    {
+        if self.scheduled.as_ref().map(|&(n, _)| n == frame).unwrap_or(false)
+        {
+            return self.scheduled.take().map(|(_, p)| p);
+        }
+        if REQUESTED.swap(false, Ordering::AcqRel) { Some(default_directory().join(timestamped_filename())) } else { None }
+    }
+
+    /// A capture is scheduled or requested: frames must be rendered even if nothing changed
+    pub fn is_pending(&self) -> bool { self.scheduled.is_some() || REQUESTED.load(Ordering::Acquire) }
+
+    /// Renders a frame, capturing it into the file if requested
+    pub fn render_frame(&mut self, frame: u64)
+    {
+        if let Some(path) = self.target_for_frame(frame)
+        {
+            let captured = match RenderDevice::get().do_render_and_capture()
+            {
+                Ok(c) => c,
+                Err(e) =>
+                {
+                    println!("!! Failed to capture a frame: {:?}", e);
+                    RenderDevice::get().do_render().expect("Failed to render a frame");
+                    return;
+                }
+            };
+            match save(&captured, &path)
+            {
+                Ok(()) => println!("Screenshot saved: {}", path.display()),
+                Err(e) => println!("!! Failed to save a screenshot to {}: {:?}", path.display(), e)
+            }
+        }
+        else { RenderDevice::get().do_render().expect("Failed to render a frame"); }
+    }
+}
+
+pub fn save(image: &CapturedImage, path: &Path) -> IOResult<()>
+{
+    if let Some(d) = path.parent() { fs::create_dir_all(d)?; }
+    image::save_buffer(path, &image.pixels, image.size.x(), image.size.y(), image::RGBA(8))
+}
+
+/// The pictures directory of the user, or the cache directory if there is not
+pub fn default_directory() -> PathBuf
+{
+    #[cfg(windows)] let home = env::var_os("USERPROFILE").map(PathBuf::from);
+    #[cfg(not(windows))] let home = env::var_os("HOME").map(PathBuf::from);
+    directory_from(home, env::var_os("XDG_PICTURES_DIR").map(PathBuf::from), env::var_os("XDG_CACHE_HOME").map(PathBuf::from))
+}
+/// `default_directory` with the variables of the environment given
+pub fn directory_from(home: Option<PathBuf>, pictures: Option<PathBuf>, cache: Option<PathBuf>) -> PathBuf
+{
+    let pictures = pictures.or_else(|| home.as_ref().map(|h| h.join("Pictures"))).and_then(|p| if p.is_dir() { Some(p) } else { None });
+    let base = pictures.or(cache).or_else(|| home.map(|h| h.join(".cache"))).unwrap_or_else(env::temp_dir);
+    base.join("dc2017")
+}
+fn timestamped_filename() -> String
+{
+    let t = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    format!("screenshot-{}.png", t)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn args(a: &[&str]) -> ::std::vec::IntoIter<String> { a.iter().map(|s| s.to_string()).collect::<Vec<_>>().into_iter() }
+
+    #[test] fn scheduling()
+    {
+        let mut s = Screenshots::from_args(args(&["dc2017", "--render-stats", "--screenshot-after", "3", "out/a.png"]));
+        assert!(s.is_pending());
+        assert_eq!(s.target_for_frame(2), None);
+        assert_eq!(s.target_for_frame(3), Some(PathBuf::from("out/a.png")));
+        assert!(!s.is_pending());
+        assert_eq!(s.target_for_frame(4), None);
+        // malformed arguments schedule nothing
+        assert!(!Screenshots::from_args(args(&["dc2017", "--screenshot-after", "x", "a.png"])).is_pending());
+        assert!(!Screenshots::from_args(args(&["dc2017", "--screenshot-after", "3"])).is_pending());
+
+        request();
+        assert!(s.is_pending());
+        let p = s.target_for_frame(10).unwrap();
+        assert!(p.file_name().unwrap().to_str().unwrap().starts_with("screenshot-") && p.extension().map(|e| e == "png").unwrap_or(false));
+        assert!(!s.is_pending());
+    }
+    #[test] fn directories()
+    {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+        let root = env::temp_dir().join(format!("dc2017-screenshot-test-{}", nanos));
+        let (home, cache) = (root.join("home"), root.join("cache"));
+        fs::create_dir_all(&home).unwrap();
+        // no pictures directory: the cache directory, or the one in the home
+        assert_eq!(directory_from(Some(home.clone()), None, Some(cache.clone())), cache.join("dc2017"));
+        assert_eq!(directory_from(Some(home.clone()), None, None), home.join(".cache").join("dc2017"));
+        assert_eq!(directory_from(None, None, None), env::temp_dir().join("dc2017"));
+        fs::create_dir_all(home.join("Pictures")).unwrap();
+        assert_eq!(directory_from(Some(home.clone()), None, Some(cache.clone())), home.join("Pictures").join("dc2017"));
+        assert_eq!(directory_from(Some(home.clone()), Some(root.clone()), Some(cache.clone())), root.join("dc2017"));
+        // a pictures directory which does not exist is skipped
+        assert_eq!(directory_from(Some(home.clone()), Some(root.join("none")), None), home.join(".cache").join("dc2017"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
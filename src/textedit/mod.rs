@@ -0,0 +1,338 @@
+//! Single-line text editing: cursor, selection, undo, password masking, clipboard and input method composition.
+//! Positions are byte offsets in the text, always on character boundaries
+
+pub mod x11;
+
+use std::ops::Range;
+
+/// Where cut and copied text goes
+pub trait Clipboard
+{
+    fn get(&mut self) -> Option<String>;
+    fn set(&mut self, text: &str);
+}
+/// In-process clipboard
+#[derive(Debug, Clone, Default)]
+pub struct LocalClipboard(pub Option<String>);
+impl Clipboard for LocalClipboard
+{
+    fn get(&mut self) -> Option<String> { self.0.clone() }
+    fn set(&mut self, text: &str) { self.0 = Some(text.to_owned()); }
+}
+
+/// Text being composed by an input method(preedit), not yet a part of the text
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Composition
+{
+    pub text: String,
+    /// Caret in the composed text(byte offset)
+    pub cursor: usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKey { Left, Right, Home, End, Backspace, Delete, SelectAll, Undo, Redo, Cut, Copy, Paste, Enter }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextInputEvent
+{
+    /// Characters typed without composition
+    Text(String),
+    /// `extend` keeps the selection anchor(shift), `word` moves or deletes by words(control)
+    Key { key: EditKey, extend: bool, word: bool },
+    /// The input method updated the composition. An empty one ends the composition
+    Preedit(Composition),
+    /// The input method finished the composition with the text
+    Commit(String)
+}
+
+/// What an event did to the field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditResponse { Unchanged, Changed, Submitted }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Snapshot { text: String, cursor: usize, anchor: usize }
+
+#[derive(Debug, Clone)]
+pub struct TextField
+{
+    text: String,
+    /// The selection spans between the anchor and the cursor
+    cursor: usize, anchor: usize,
+    composition: Option<Composition>,
+    masked: bool, max_chars: Option<usize>,
+    undo: Vec<Snapshot>, redo: Vec<Snapshot>,
+    /// Consecutive typing is undone at once
+    typing: bool
+}
+impl TextField
+{
+    /// Character shown in place of each character of masked fields
+    pub const MASK: char = '●';
+    const UNDO_LIMIT: usize = 100;
+
+    pub fn new() -> Self
+    {
+        TextField
+        {
+            text: String::new(), cursor: 0, anchor: 0, composition: None, masked: false, max_chars: None,
+            undo: Vec::new(), redo: Vec::new(), typing: false
+        }
+    }
+    /// A field for passwords: the text is masked, cannot be copied, and input methods are not used
+    pub fn password() -> Self { TextField { masked: true, .. Self::new() } }
+    pub fn max_chars(self, max_chars: usize) -> Self { TextField { max_chars: Some(max_chars), .. self } }
+
+    pub fn text(&self) -> &str { &self.text }
+    pub fn is_masked(&self) -> bool { self.masked }
+    pub fn cursor(&self) -> usize { self.cursor }
+    pub fn selection(&self) -> Range<usize> { self.anchor.min(self.cursor) .. self.anchor.max(self.cursor) }
+    pub fn selected_text(&self) -> &str { &self.text[self.selection()] }
+    pub fn composition(&self) -> Option<&Composition> { self.composition.as_ref() }
+    /// Replaces the whole text, clearing the history
+    pub fn set_text(&mut self, text: &str)
+    {
+        self.text = single_line(text);
+        self.cursor = self.text.len(); self.anchor = self.cursor;
+        self.composition = None; self.undo.clear(); self.redo.clear(); self.typing = false;
+    }
+
+    /// The text to draw: masked, with the composition inserted at the cursor.
+    /// Also returns the cursor and the range of the composition in the returned text
+    pub fn display_text(&self) -> (String, usize, Option<Range<usize>>)
+    {
+        let shown = |s: &str| if self.masked { s.chars().map(|_| Self::MASK).collect() } else { s.to_owned() };
+        let (before, after) = (shown(&self.text[.. self.cursor]), shown(&self.text[self.cursor ..]));
+        match self.composition
+        {
+            Some(ref c) =>
+            {
+                let range = before.len() .. before.len() + c.text.len();
+                (before.clone() + &c.text + &after, range.start + c.cursor, Some(range))
+            },
+            None => { let cursor = before.len(); (before + &after, cursor, None) }
+        }
+    }
+
+    pub fn handle(&mut self, event: &TextInputEvent, clipboard: &mut Clipboard) -> EditResponse
+    {
+        match *event
+        {
+            TextInputEvent::Text(ref t) => self.type_text(t),
+            TextInputEvent::Commit(ref t) => { self.composition = None; self.type_text(t) },
+            TextInputEvent::Preedit(ref c) =>
+            {
+                // the composition is drawn in clear text, so masked fields do not compose
+                if self.masked { return EditResponse::Unchanged; }
+                self.composition = if c.text.is_empty() { None } else { Some(c.clone()) };
+                EditResponse::Unchanged
+            },
+            // keys go to the input method while composing
+            TextInputEvent::Key { .. } if self.composition.is_some() => EditResponse::Unchanged,
+            TextInputEvent::Key { key, extend, word } => match key
+            {
+                EditKey::Left => { let p = if word { self.word_start() } else { self.prev_char() }; self.move_to(p, extend) },
+                EditKey::Right => { let p = if word { self.word_end() } else { self.next_char() }; self.move_to(p, extend) },
+                EditKey::Home => self.move_to(0, extend),
+                EditKey::End => { let p = self.text.len(); self.move_to(p, extend) },
+                EditKey::Backspace =>
+                {
+                    if self.anchor == self.cursor { self.anchor = if word { self.word_start() } else { self.prev_char() }; }
+                    self.replace_selection("")
+                },
+                EditKey::Delete =>
+                {
+                    if self.anchor == self.cursor { self.anchor = if word { self.word_end() } else { self.next_char() }; }
+                    self.replace_selection("")
+                },
+                EditKey::SelectAll => { self.anchor = 0; let p = self.text.len(); self.move_to(p, true) },
+                EditKey::Undo => self.undo(),
+                EditKey::Redo => self.redo(),
+                EditKey::Copy => { if !self.masked && self.anchor != self.cursor { clipboard.set(self.selected_text()); } EditResponse::Unchanged },
+                EditKey::Cut =>
+                {
+                    if self.masked || self.anchor == self.cursor { return EditResponse::Unchanged; }
+                    clipboard.set(self.selected_text());
+                    self.replace_selection("")
+                },
+                EditKey::Paste => match clipboard.get() { Some(t) => { self.typing = false; self.replace_selection(&t) }, None => EditResponse::Unchanged },
+                EditKey::Enter => EditResponse::Submitted
+            }
+        }
+    }
+
+    fn type_text(&mut self, text: &str) -> EditResponse
+    {
+        let coalesce = self.typing && self.anchor == self.cursor;
+        let undo_len = self.undo.len();
+        let r = self.replace_selection(text);
+        // merges into the previous typing step
+        if coalesce && r == EditResponse::Changed && self.undo.len() > undo_len { self.undo.pop(); }
+        self.typing = r == EditResponse::Changed;
+        r
+    }
+    /// Replaces the selection with the text(truncated to the maximum length), recording the previous state
+    fn replace_selection(&mut self, text: &str) -> EditResponse
+    {
+        let range = self.selection();
+        let mut text = single_line(text);
+        if let Some(max) = self.max_chars
+        {
+            let room = max.saturating_sub(self.text.chars().count() - self.text[range.clone()].chars().count());
+            if let Some((n, _)) = text.char_indices().nth(room) { text.truncate(n); }
+        }
+        if text.is_empty() && range.start == range.end { self.anchor = self.cursor; return EditResponse::Unchanged; }
+        self.record();
+        self.text = self.text[.. range.start].to_owned() + &text + &self.text[range.end ..];
+        self.cursor = range.start + text.len(); self.anchor = self.cursor;
+        self.typing = false;
+        EditResponse::Changed
+    }
+    fn record(&mut self)
+    {
+        if self.undo.len() >= Self::UNDO_LIMIT { self.undo.remove(0); }
+        self.undo.push(Snapshot { text: self.text.clone(), cursor: self.cursor, anchor: self.anchor });
+        self.redo.clear();
+    }
+    fn undo(&mut self) -> EditResponse
+    {
+        match self.undo.pop()
+        {
+            Some(s) => { let current = self.restore(s); self.redo.push(current); EditResponse::Changed },
+            None => EditResponse::Unchanged
+        }
+    }
+    fn redo(&mut self) -> EditResponse
+    {
+        match self.redo.pop()
+        {
+            Some(s) => { let current = self.restore(s); self.undo.push(current); EditResponse::Changed },
+            None => EditResponse::Unchanged
+        }
+    }
+    fn restore(&mut self, s: Snapshot) -> Snapshot
+    {
+        self.typing = false;
+        let current = Snapshot { text: ::std::mem::replace(&mut self.text, s.text), cursor: self.cursor, anchor: self.anchor };
+        self.cursor = s.cursor; self.anchor = s.anchor;
+        current
+    }
+
+    fn move_to(&mut self, position: usize, extend: bool) -> EditResponse
+    {
+        self.cursor = position;
+        if !extend { self.anchor = position; }
+        self.typing = false;
+        EditResponse::Unchanged
+    }
+    fn prev_char(&self) -> usize { self.text[.. self.cursor].char_indices().next_back().map(|(n, _)| n).unwrap_or(0) }
+    fn next_char(&self) -> usize { self.text[self.cursor ..].chars().next().map(|c| self.cursor + c.len_utf8()).unwrap_or(self.cursor) }
+    /// Start of the word before the cursor. Masked text is a single word
+    fn word_start(&self) -> usize
+    {
+        if self.masked { return 0; }
+        let before = self.text[.. self.cursor].trim_right();
+        before.rfind(char::is_whitespace).map(|n| n + before[n ..].chars().next().unwrap().len_utf8()).unwrap_or(0)
+    }
+    /// End of the word after the cursor
+    fn word_end(&self) -> usize
+    {
+        if self.masked { return self.text.len(); }
+        let after = &self.text[self.cursor ..];
+        let skipped = after.len() - after.trim_left().len();
+        self.cursor + skipped + after[skipped ..].find(char::is_whitespace).unwrap_or(after.len() - skipped)
+    }
+}
+
+/// Line breaks and other control characters are dropped
+fn single_line(text: &str) -> String { text.chars().filter(|c| !c.is_control()).collect() }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn key(key: EditKey) -> TextInputEvent { TextInputEvent::Key { key, extend: false, word: false } }
+    fn text(t: &str) -> TextInputEvent { TextInputEvent::Text(t.to_owned()) }
+
+    #[test] fn editing_and_selection()
+    {
+        let (mut f, mut cb) = (TextField::new(), LocalClipboard::default());
+        for t in &["学籍", "番号", " 12"] { f.handle(&text(t), &mut cb); }
+        assert_eq!((f.text(), f.cursor()), ("学籍番号 12", 15));
+        f.handle(&TextInputEvent::Key { key: EditKey::Left, extend: true, word: true }, &mut cb);
+        assert_eq!(f.selected_text(), "12");
+        assert_eq!(f.handle(&text("34\n5"), &mut cb), EditResponse::Changed);
+        assert_eq!(f.text(), "学籍番号 345");
+        f.handle(&key(EditKey::Home), &mut cb);
+        f.handle(&key(EditKey::Delete), &mut cb);
+        f.handle(&TextInputEvent::Key { key: EditKey::Right, extend: false, word: true }, &mut cb);
+        assert_eq!(f.cursor(), 9);
+        f.handle(&key(EditKey::Backspace), &mut cb);
+        assert_eq!((f.text(), f.cursor()), ("籍番 345", 6));
+        assert_eq!(f.handle(&key(EditKey::Enter), &mut cb), EditResponse::Submitted);
+    }
+
+    #[test] fn undo_and_redo()
+    {
+        let (mut f, mut cb) = (TextField::new(), LocalClipboard::default());
+        for t in &["a", "b", "c"] { f.handle(&text(t), &mut cb); }
+        f.handle(&key(EditKey::Left), &mut cb);
+        f.handle(&text("x"), &mut cb);
+        f.handle(&key(EditKey::Backspace), &mut cb);
+        f.handle(&key(EditKey::Backspace), &mut cb);
+        assert_eq!(f.text(), "ac");
+        f.handle(&key(EditKey::Undo), &mut cb);
+        f.handle(&key(EditKey::Undo), &mut cb);
+        assert_eq!((f.text(), f.cursor()), ("abxc", 3));
+        // consecutive typing is a step
+        f.handle(&key(EditKey::Undo), &mut cb);
+        f.handle(&key(EditKey::Undo), &mut cb);
+        assert_eq!(f.text(), "");
+        assert_eq!(f.handle(&key(EditKey::Undo), &mut cb), EditResponse::Unchanged);
+        f.handle(&key(EditKey::Redo), &mut cb);
+        assert_eq!(f.text(), "abc");
+        f.handle(&text("d"), &mut cb);
+        assert_eq!(f.handle(&key(EditKey::Redo), &mut cb), EditResponse::Unchanged);
+    }
+
+    #[test] fn clipboard_and_limits()
+    {
+        let (mut f, mut cb) = (TextField::new().max_chars(8), LocalClipboard::default());
+        f.handle(&text("s1234567"), &mut cb);
+        f.handle(&key(EditKey::SelectAll), &mut cb);
+        f.handle(&key(EditKey::Cut), &mut cb);
+        assert_eq!((f.text(), cb.0.as_ref().map(|s| &s[..])), ("", Some("s1234567")));
+        f.handle(&text("ab"), &mut cb);
+        f.handle(&key(EditKey::Paste), &mut cb);
+        assert_eq!(f.text(), "abs12345");
+        assert_eq!(f.handle(&text("z"), &mut cb), EditResponse::Unchanged);
+
+        let mut p = TextField::password();
+        p.handle(&text("pass"), &mut cb);
+        p.handle(&key(EditKey::SelectAll), &mut cb);
+        p.handle(&key(EditKey::Copy), &mut cb);
+        assert_eq!(cb.0.as_ref().map(|s| &s[..]), Some("s1234567"));
+        p.handle(&key(EditKey::Right), &mut cb);
+        assert_eq!(p.display_text(), ("●●●●".to_owned(), 12, None));
+    }
+
+    #[test] fn composition()
+    {
+        let (mut f, mut cb) = (TextField::new(), LocalClipboard::default());
+        f.handle(&text("ID:"), &mut cb);
+        f.handle(&TextInputEvent::Preedit(Composition { text: "がくせき".to_owned(), cursor: 6 }), &mut cb);
+        assert_eq!(f.display_text(), ("ID:がくせき".to_owned(), 9, Some(3 .. 15)));
+        // editing keys belong to the input method until the composition ends
+        assert_eq!(f.handle(&key(EditKey::Backspace), &mut cb), EditResponse::Unchanged);
+        assert_eq!(f.text(), "ID:");
+        f.handle(&TextInputEvent::Commit("学籍".to_owned()), &mut cb);
+        assert_eq!((f.text(), f.composition()), ("ID:学籍", None));
+        f.handle(&TextInputEvent::Preedit(Composition { text: "x".to_owned(), cursor: 1 }), &mut cb);
+        f.handle(&TextInputEvent::Preedit(Composition::default()), &mut cb);
+        assert_eq!(f.display_text(), ("ID:学籍".to_owned(), 9, None));
+
+        let mut p = TextField::password();
+        p.handle(&TextInputEvent::Preedit(Composition { text: "あ".to_owned(), cursor: 3 }), &mut cb);
+        assert!(p.composition().is_none());
+    }
+}
@@ -0,0 +1,100 @@
+//! Translation of X11 key presses and XIM preedit callbacks into text input events.
+//! Key presses come as the keysym and the modifier state of the event; committed text comes from `Xutf8LookupString`
+
+use super::{TextInputEvent, EditKey, Composition};
+
+pub const SHIFT_MASK: u16 = 1 << 0;
+pub const CONTROL_MASK: u16 = 1 << 2;
+
+/// Editing keys and typed characters. None for keys which do nothing in text fields
+pub fn translate_key(keysym: u32, state: u16) -> Option<TextInputEvent>
+{
+    let (extend, word) = (state & SHIFT_MASK != 0, state & CONTROL_MASK != 0);
+    let key = |key| Some(TextInputEvent::Key { key, extend, word });
+    let plain = |key| Some(TextInputEvent::Key { key, extend: false, word: false });
+    match keysym
+    {
+        0xff08 => key(EditKey::Backspace),
+        0xffff => key(EditKey::Delete),
+        0xff50 => key(EditKey::Home),
+        0xff51 => key(EditKey::Left),
+        0xff53 => key(EditKey::Right),
+        0xff57 => key(EditKey::End),
+        0xff0d | 0xff8d => plain(EditKey::Enter),
+        _ => match keysym_char(keysym)
+        {
+            Some(c) if word => match c
+            {
+                'a' | 'A' => plain(EditKey::SelectAll),
+                'c' | 'C' => plain(EditKey::Copy),
+                'x' | 'X' => plain(EditKey::Cut),
+                'v' | 'V' => plain(EditKey::Paste),
+                'z' | 'Z' if extend => plain(EditKey::Redo),
+                'z' | 'Z' => plain(EditKey::Undo),
+                'y' | 'Y' => plain(EditKey::Redo),
+                _ => None
+            },
+            Some(c) => Some(TextInputEvent::Text(c.to_string())),
+            None => None
+        }
+    }
+}
+/// Latin-1 keysyms are their code points; Unicode keysyms are the code point with 0x01000000
+fn keysym_char(keysym: u32) -> Option<char>
+{
+    match keysym
+    {
+        0x20 ... 0x7e | 0xa0 ... 0xff => ::std::char::from_u32(keysym),
+        0x0100_00a0 ... 0x0110_ffff => ::std::char::from_u32(keysym - 0x0100_0000),
+        _ => None
+    }
+}
+
+/// Composition kept across the XIM preedit callbacks
+#[derive(Debug, Clone, Default)]
+pub struct Preedit { chars: Vec<char> }
+impl Preedit
+{
+    /// XIMPreeditDrawCallback: replaces `length` characters from `first` with the text, then moves the caret
+    pub fn draw(&mut self, caret: usize, first: usize, length: usize, text: &str) -> TextInputEvent
+    {
+        let first = first.min(self.chars.len());
+        let end = (first + length).min(self.chars.len());
+        self.chars.splice(first .. end, text.chars());
+        let caret = caret.min(self.chars.len());
+        TextInputEvent::Preedit(Composition
+        {
+            text: self.chars.iter().collect(), cursor: self.chars[.. caret].iter().map(|c| c.len_utf8()).sum()
+        })
+    }
+    /// XIMPreeditCaretCallback
+    pub fn caret(&mut self, caret: usize) -> TextInputEvent { self.draw(caret, 0, 0, "") }
+    /// XIMPreeditDoneCallback
+    pub fn done(&mut self) -> TextInputEvent { self.chars.clear(); TextInputEvent::Preedit(Composition::default()) }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test] fn keys()
+    {
+        assert_eq!(translate_key(0x61, 0), Some(TextInputEvent::Text("a".to_owned())));
+        assert_eq!(translate_key(0x0100_3042, 0), Some(TextInputEvent::Text("あ".to_owned())));
+        assert_eq!(translate_key(0xff51, SHIFT_MASK | CONTROL_MASK), Some(TextInputEvent::Key { key: EditKey::Left, extend: true, word: true }));
+        assert_eq!(translate_key(0x56, CONTROL_MASK | SHIFT_MASK), Some(TextInputEvent::Key { key: EditKey::Paste, extend: false, word: false }));
+        assert_eq!(translate_key(0x7a, CONTROL_MASK | SHIFT_MASK), Some(TextInputEvent::Key { key: EditKey::Redo, extend: false, word: false }));
+        assert_eq!(translate_key(0xffbe, 0), None);
+    }
+
+    #[test] fn preedit()
+    {
+        let mut p = Preedit::default();
+        p.draw(2, 0, 0, "がく");
+        assert_eq!(p.draw(3, 2, 0, "せ"), TextInputEvent::Preedit(Composition { text: "がくせ".to_owned(), cursor: 9 }));
+        assert_eq!(p.draw(2, 0, 3, "学籍"), TextInputEvent::Preedit(Composition { text: "学籍".to_owned(), cursor: 6 }));
+        assert_eq!(p.caret(1), TextInputEvent::Preedit(Composition { text: "学籍".to_owned(), cursor: 3 }));
+        assert_eq!(p.done(), TextInputEvent::Preedit(Composition::default()));
+    }
+}
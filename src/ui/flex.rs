@@ -0,0 +1,340 @@
+//! Flexbox-style layout: rectangles of a tree of nodes laid out in rows and columns with grow/shrink,
+//! padding, margin, alignment and min/max constraints. Single-line(no wrapping) subset of CSS flexbox.
+//! The pass is pure: sizes of the contents(text, images) are given to the leaves beforehand
+
+use metrics::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction { Row, Column }
+/// Placement in the cross axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align { Start, Center, End, Stretch }
+/// Placement in the main axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Justify { Start, Center, End, SpaceBetween, SpaceAround }
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dimension
+{
+    /// The size of the contents(or the flex basis)
+    Auto,
+    Px(f32),
+    /// Ratio(0.0-1.0) of the inner size of the parent
+    Percent(f32)
+}
+impl Dimension
+{
+    fn resolve(&self, parent: f32) -> Option<f32>
+    {
+        match *self { Dimension::Auto => None, Dimension::Px(v) => Some(v), Dimension::Percent(r) => Some(parent * r) }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edges { pub left: f32, pub top: f32, pub right: f32, pub bottom: f32 }
+impl Edges
+{
+    pub const ZERO: Edges = Edges { left: 0.0, top: 0.0, right: 0.0, bottom: 0.0 };
+    pub fn all(v: f32) -> Self { Edges { left: v, top: v, right: v, bottom: v } }
+    pub fn symmetric(horizontal: f32, vertical: f32) -> Self { Edges { left: horizontal, top: vertical, right: horizontal, bottom: vertical } }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlexStyle
+{
+    /// Direction the children are laid out in
+    pub direction: Direction, pub justify: Justify, pub align_items: Align,
+    /// Overrides `align_items` of the parent
+    pub align_self: Option<Align>,
+    pub grow: f32, pub shrink: f32, pub basis: Dimension,
+    pub width: Dimension, pub height: Dimension,
+    pub min: Size2F, pub max: Size2F,
+    pub padding: Edges, pub margin: Edges,
+    /// Space between the children
+    pub gap: f32
+}
+impl Default for FlexStyle
+{
+    fn default() -> Self
+    {
+        FlexStyle
+        {
+            direction: Direction::Row, justify: Justify::Start, align_items: Align::Stretch, align_self: None,
+            grow: 0.0, shrink: 1.0, basis: Dimension::Auto, width: Dimension::Auto, height: Dimension::Auto,
+            min: Size2F(0.0, 0.0), max: Size2F(::std::f32::INFINITY, ::std::f32::INFINITY),
+            padding: Edges::ZERO, margin: Edges::ZERO, gap: 0.0
+        }
+    }
+}
+impl FlexStyle
+{
+    pub fn row() -> Self { FlexStyle::default() }
+    pub fn column() -> Self { FlexStyle { direction: Direction::Column, .. FlexStyle::default() } }
+    pub fn justify(self, justify: Justify) -> Self { FlexStyle { justify, .. self } }
+    pub fn align_items(self, align_items: Align) -> Self { FlexStyle { align_items, .. self } }
+    pub fn align_self(self, align: Align) -> Self { FlexStyle { align_self: Some(align), .. self } }
+    pub fn grow(self, grow: f32) -> Self { FlexStyle { grow, .. self } }
+    pub fn shrink(self, shrink: f32) -> Self { FlexStyle { shrink, .. self } }
+    pub fn basis(self, basis: Dimension) -> Self { FlexStyle { basis, .. self } }
+    pub fn size(self, width: Dimension, height: Dimension) -> Self { FlexStyle { width, height, .. self } }
+    pub fn min(self, width: f32, height: f32) -> Self { FlexStyle { min: Size2F(width, height), .. self } }
+    pub fn max(self, width: f32, height: f32) -> Self { FlexStyle { max: Size2F(width, height), .. self } }
+    pub fn padding(self, padding: Edges) -> Self { FlexStyle { padding, .. self } }
+    pub fn margin(self, margin: Edges) -> Self { FlexStyle { margin, .. self } }
+    pub fn gap(self, gap: f32) -> Self { FlexStyle { gap, .. self } }
+
+    fn main(&self) -> Axis { Axis(self.direction) }
+    fn cross(&self) -> Axis { Axis(match self.direction { Direction::Row => Direction::Column, Direction::Column => Direction::Row }) }
+}
+
+/// Picks the components along a direction
+#[derive(Clone, Copy)]
+struct Axis(Direction);
+impl Axis
+{
+    fn size(&self, s: Size2F) -> f32 { match self.0 { Direction::Row => s.0, Direction::Column => s.1 } }
+    fn dimension(&self, style: &FlexStyle) -> Dimension { match self.0 { Direction::Row => style.width, Direction::Column => style.height } }
+    /// Leading and trailing edges
+    fn edges(&self, e: &Edges) -> (f32, f32) { match self.0 { Direction::Row => (e.left, e.right), Direction::Column => (e.top, e.bottom) } }
+    fn edge_sum(&self, e: &Edges) -> f32 { let (a, b) = self.edges(e); a + b }
+    fn clamp(&self, style: &FlexStyle, v: f32) -> f32 { v.min(self.size(style.max)).max(self.size(style.min)) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+struct Node { style: FlexStyle, children: Vec<NodeId>, content: Size2F }
+/// A tree of nodes. Children are added before their parents
+pub struct FlexTree { nodes: Vec<Node> }
+impl FlexTree
+{
+    pub fn new() -> Self { FlexTree { nodes: Vec::new() } }
+    /// A node without children. `content` is the size of the contents(excluding the padding)
+    pub fn leaf(&mut self, style: FlexStyle, content: Size2F) -> NodeId
+    {
+        self.nodes.push(Node { style, children: Vec::new(), content });
+        NodeId(self.nodes.len() - 1)
+    }
+    pub fn container(&mut self, style: FlexStyle, children: Vec<NodeId>) -> NodeId
+    {
+        self.nodes.push(Node { style, children, content: Size2F(0.0, 0.0) });
+        NodeId(self.nodes.len() - 1)
+    }
+    pub fn style(&self, id: NodeId) -> &FlexStyle { &self.nodes[id.0].style }
+    pub fn style_mut(&mut self, id: NodeId) -> &mut FlexStyle { &mut self.nodes[id.0].style }
+    pub fn children(&self, id: NodeId) -> &[NodeId] { &self.nodes[id.0].children }
+
+    /// Lays out the tree from the root filling the area(pixels)
+    pub fn layout(&self, root: NodeId, area: Size2U) -> Layout
+    {
+        let mut rects = vec![LayoutRect::default(); self.nodes.len()];
+        let style = &self.nodes[root.0].style;
+        let size = Size2F(Axis(Direction::Row).clamp(style, area.0 as f32), Axis(Direction::Column).clamp(style, area.1 as f32));
+        self.place(root, LayoutRect { origin: Point2F(0.0, 0.0), size }, &mut rects);
+        Layout { rects }
+    }
+
+    /// The size along the axis the node takes with its contents, including the padding
+    fn intrinsic(&self, id: NodeId, axis: Axis) -> f32
+    {
+        let node = &self.nodes[id.0];
+        if let Dimension::Px(v) = axis.dimension(&node.style) { return axis.clamp(&node.style, v); }
+        let contents = if node.children.is_empty() { axis.size(node.content) } else
+        {
+            let outer = node.children.iter().map(|&c| self.intrinsic(c, axis) + axis.edge_sum(&self.nodes[c.0].style.margin));
+            if node.style.main().0 == axis.0
+            {
+                outer.sum::<f32>() + node.style.gap * (node.children.len() - 1) as f32
+            }
+            else { outer.fold(0.0, f32::max) }
+        };
+        axis.clamp(&node.style, contents + axis.edge_sum(&node.style.padding))
+    }
+
+    fn place(&self, id: NodeId, rect: LayoutRect, rects: &mut [LayoutRect])
+    {
+        rects[id.0] = rect;
+        let node = &self.nodes[id.0];
+        if node.children.is_empty() { return; }
+        let (style, main, cross) = (&node.style, node.style.main(), node.style.cross());
+        let inner_main = (main.size(rect.size) - main.edge_sum(&style.padding)).max(0.0);
+        let inner_cross = (cross.size(rect.size) - cross.edge_sum(&style.padding)).max(0.0);
+        let children: Vec<&FlexStyle> = node.children.iter().map(|c| &self.nodes[c.0].style).collect();
+
+        // hypothetical main sizes, then grow or shrink to fill the line
+        let bases: Vec<f32> = node.children.iter().zip(&children).map(|(&c, s)|
+        {
+            let base = s.basis.resolve(inner_main).or_else(|| main.dimension(s).resolve(inner_main)).unwrap_or_else(|| self.intrinsic(c, main));
+            base.max(0.0)
+        }).collect();
+        let margins: f32 = children.iter().map(|s| main.edge_sum(&s.margin)).sum();
+        let gaps = style.gap * (children.len() - 1) as f32;
+        let sizes = resolve_flexible(&children, &bases, main, inner_main - margins - gaps);
+        let remaining = inner_main - margins - gaps - sizes.iter().sum::<f32>();
+
+        let n = children.len() as f32;
+        let (mut pos, spacing) = match style.justify
+        {
+            _ if remaining <= 0.0 => (0.0, 0.0),
+            Justify::Start => (0.0, 0.0),
+            Justify::Center => (remaining * 0.5, 0.0),
+            Justify::End => (remaining, 0.0),
+            Justify::SpaceBetween => (0.0, if n > 1.0 { remaining / (n - 1.0) } else { 0.0 }),
+            Justify::SpaceAround => (remaining / n * 0.5, remaining / n)
+        };
+        pos += main.edges(&style.padding).0;
+        for ((&c, s), &size) in node.children.iter().zip(&children).zip(&sizes)
+        {
+            let (m_lead, m_trail) = main.edges(&s.margin);
+            let (c_lead, c_trail) = cross.edges(&s.margin);
+            let free_cross = inner_cross - c_lead - c_trail;
+            let align = s.align_self.unwrap_or(style.align_items);
+            let cross_size = match cross.dimension(s).resolve(inner_cross)
+            {
+                Some(v) => v,
+                None if align == Align::Stretch => free_cross,
+                None => self.intrinsic(c, cross)
+            };
+            let cross_size = cross.clamp(s, cross_size);
+            let cross_pos = cross.edges(&style.padding).0 + c_lead + match align
+            {
+                Align::Start | Align::Stretch => 0.0,
+                Align::Center => (free_cross - cross_size) * 0.5,
+                Align::End => free_cross - cross_size
+            };
+            let main_pos = pos + m_lead;
+            let (offset, size) = match style.direction
+            {
+                Direction::Row => (Point2F(main_pos, cross_pos), Size2F(size, cross_size)),
+                Direction::Column => (Point2F(cross_pos, main_pos), Size2F(cross_size, size))
+            };
+            self.place(c, LayoutRect { origin: Point2F(rect.origin.0 + offset.0, rect.origin.1 + offset.1), size }, rects);
+            pos = main_pos + main.size(size) + m_trail + style.gap + spacing;
+        }
+    }
+}
+
+/// Distributes the free space by the grow factors(or the lack of it by the shrink factors weighted by the bases),
+/// freezing the items which hit their min/max constraints until the rest absorbs the difference
+fn resolve_flexible(styles: &[&FlexStyle], bases: &[f32], main: Axis, available: f32) -> Vec<f32>
+{
+    let hypothetical: Vec<f32> = styles.iter().zip(bases).map(|(s, &b)| main.clamp(s, b)).collect();
+    let growing = hypothetical.iter().sum::<f32>() < available;
+    let mut sizes = hypothetical.clone();
+    let mut frozen: Vec<bool> = styles.iter().zip(&hypothetical).zip(bases).map(|((s, &h), &b)|
+    {
+        let factor = if growing { s.grow } else { s.shrink };
+        factor == 0.0 || (growing && h > b) || (!growing && h < b)
+    }).collect();
+    for _ in 0 .. styles.len()
+    {
+        if frozen.iter().all(|&f| f) { break; }
+        let used: f32 = (0 .. styles.len()).map(|i| if frozen[i] { sizes[i] } else { bases[i] }).sum();
+        let free = available - used;
+        let total: f32 = (0 .. styles.len()).filter(|&i| !frozen[i])
+            .map(|i| if growing { styles[i].grow } else { styles[i].shrink * bases[i] }).sum();
+        let mut violation = 0.0;
+        let mut clamped = vec![0.0; styles.len()];
+        for i in (0 .. styles.len()).filter(|&i| !frozen[i])
+        {
+            let weight = if growing { styles[i].grow } else { styles[i].shrink * bases[i] };
+            let target = if total > 0.0 { bases[i] + free * weight / total } else { bases[i] };
+            sizes[i] = main.clamp(styles[i], target.max(0.0));
+            clamped[i] = sizes[i] - target;
+            violation += clamped[i];
+        }
+        if violation == 0.0 { break; }
+        // freeze the items clamped in the direction of the total violation
+        for i in 0 .. styles.len()
+        {
+            if (violation > 0.0 && clamped[i] > 0.0) || (violation < 0.0 && clamped[i] < 0.0) { frozen[i] = true; }
+        }
+    }
+    sizes
+}
+
+/// Rectangle in pixels from the top-left corner of the layout area
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutRect { pub origin: Point2F, pub size: Size2F }
+impl Default for LayoutRect { fn default() -> Self { LayoutRect { origin: Point2F(0.0, 0.0), size: Size2F(0.0, 0.0) } } }
+impl LayoutRect
+{
+    pub fn right(&self) -> f32 { self.origin.0 + self.size.0 }
+    pub fn bottom(&self) -> f32 { self.origin.1 + self.size.1 }
+    pub fn contains(&self, p: Point2F) -> bool { self.origin.0 <= p.0 && p.0 < self.right() && self.origin.1 <= p.1 && p.1 < self.bottom() }
+}
+
+/// Result of the layout pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout { rects: Vec<LayoutRect> }
+impl Layout
+{
+    pub fn rect(&self, id: NodeId) -> LayoutRect { self.rects[id.0] }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn rect(x: f32, y: f32, w: f32, h: f32) -> LayoutRect { LayoutRect { origin: Point2F(x, y), size: Size2F(w, h) } }
+
+    #[test] fn row_grow_and_justify()
+    {
+        let mut t = FlexTree::new();
+        let a = t.leaf(FlexStyle::row(), Size2F(40.0, 10.0));
+        let b = t.leaf(FlexStyle::row().grow(1.0), Size2F(10.0, 10.0));
+        let c = t.leaf(FlexStyle::row().grow(3.0).max(50.0, 20.0), Size2F(10.0, 10.0));
+        let root = t.container(FlexStyle::row().padding(Edges::all(5.0)).gap(10.0), vec![a, b, c]);
+        let l = t.layout(root, Size2U(200, 50));
+        // 190 - 20 gaps - 60 bases = 110 free: c stops at its max and b takes the rest
+        assert_eq!(l.rect(a), rect(5.0, 5.0, 40.0, 40.0));
+        assert_eq!(l.rect(b), rect(55.0, 5.0, 80.0, 40.0));
+        assert_eq!(l.rect(c), rect(145.0, 5.0, 50.0, 20.0));
+
+        let items: Vec<_> = (0 .. 3).map(|_| t.leaf(FlexStyle::row().align_self(Align::Center), Size2F(20.0, 10.0))).collect();
+        let row = t.container(FlexStyle::row().justify(Justify::SpaceBetween), items.clone());
+        let l = t.layout(row, Size2U(100, 30));
+        assert_eq!(items.iter().map(|&i| l.rect(i)).collect::<Vec<_>>(),
+            vec![rect(0.0, 10.0, 20.0, 10.0), rect(40.0, 10.0, 20.0, 10.0), rect(80.0, 10.0, 20.0, 10.0)]);
+        t.style_mut(row).justify = Justify::SpaceAround;
+        assert_eq!(t.layout(row, Size2U(100, 30)).rect(items[0]).origin, Point2F(20.0 / 3.0, 10.0));
+    }
+
+    #[test] fn shrink_with_min()
+    {
+        let mut t = FlexTree::new();
+        let a = t.leaf(FlexStyle::row().basis(Dimension::Px(100.0)).min(80.0, 0.0), Size2F(0.0, 0.0));
+        let b = t.leaf(FlexStyle::row().basis(Dimension::Px(100.0)), Size2F(0.0, 0.0));
+        let fixed = t.leaf(FlexStyle::row().shrink(0.0).size(Dimension::Px(50.0), Dimension::Auto), Size2F(0.0, 0.0));
+        let root = t.container(FlexStyle::row(), vec![a, b, fixed]);
+        // 100 too wide: a stops at its min and b shrinks by the rest
+        let l = t.layout(root, Size2U(150, 10));
+        assert_eq!((l.rect(a).size.0, l.rect(b).size.0, l.rect(fixed).size.0), (80.0, 20.0, 50.0));
+        assert_eq!(l.rect(fixed).origin.0, 100.0);
+    }
+
+    /// A timetable screen: a header bar over a row of 5 day columns with the periods stacked in each
+    #[test] fn nested_columns()
+    {
+        let mut t = FlexTree::new();
+        let title = t.leaf(FlexStyle::row().margin(Edges::symmetric(8.0, 0.0)), Size2F(60.0, 16.0));
+        let header = t.container(FlexStyle::row().align_items(Align::Center).size(Dimension::Auto, Dimension::Px(40.0)), vec![title]);
+        let mut periods = Vec::new();
+        let days: Vec<_> = (0 .. 5).map(|_|
+        {
+            let p: Vec<_> = (0 .. 4).map(|_| t.leaf(FlexStyle::column().grow(1.0).margin(Edges::all(1.0)), Size2F(0.0, 0.0))).collect();
+            periods.push(p.clone());
+            t.container(FlexStyle::column().grow(1.0).basis(Dimension::Px(0.0)), p)
+        }).collect();
+        let week = t.container(FlexStyle::row().grow(1.0).padding(Edges::all(10.0)), days.clone());
+        let root = t.container(FlexStyle::column(), vec![header, week]);
+        let l = t.layout(root, Size2U(520, 480));
+
+        assert_eq!(l.rect(header), rect(0.0, 0.0, 520.0, 40.0));
+        assert_eq!(l.rect(title), rect(8.0, 12.0, 60.0, 16.0));
+        assert_eq!(l.rect(week), rect(0.0, 40.0, 520.0, 440.0));
+        assert_eq!(l.rect(days[2]), rect(210.0, 50.0, 100.0, 420.0));
+        assert_eq!(l.rect(periods[2][1]), rect(211.0, 156.0, 98.0, 103.0));
+        assert!(l.rect(periods[4][3]).contains(Point2F(500.0, 460.0)));
+    }
+}
@@ -0,0 +1,12 @@
+//! User interface: flexbox layout, a retained widget tree with the basic widgets, and their rendering
+
+mod flex;
+mod paint;
+mod widget;
+mod widgets;
+mod renderer;
+pub use self::flex::*;
+pub use self::paint::*;
+pub use self::widget::*;
+pub use self::widgets::*;
+pub use self::renderer::*;
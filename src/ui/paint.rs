@@ -0,0 +1,113 @@
+//! Display lists: what the widgets draw, in pixels from the top-left corner of the render target
+
+use metrics::*;
+use render::{Color, TextureBinding, SdfStyle};
+use font::TextLayout;
+use super::LayoutRect;
+use std::rc::Rc;
+
+pub enum DrawItem
+{
+    /// Filled rectangle with rounded corners
+    Rect { rect: LayoutRect, color: Color, radius: f32 },
+    /// Text with the top-left corner of the layout at `origin`. Glyphs are drawn at `size` pixels per em
+    Text { layout: TextLayout, origin: Point2F, size: f32, color: Color },
+    /// Distance field image stretched to the rectangle. The placement of the style is overwritten
+    Image { texture: Rc<TextureBinding>, rect: LayoutRect, style: SdfStyle },
+    /// Restricts the following items to the rectangle. None removes the restriction
+    Clip(Option<LayoutRect>)
+}
+
+/// Items in the order of drawing
+pub struct DisplayList { pub items: Vec<DrawItem>, clips: Vec<LayoutRect> }
+impl DisplayList
+{
+    pub fn new() -> Self { DisplayList { items: Vec::new(), clips: Vec::new() } }
+    pub fn rect(&mut self, rect: LayoutRect, color: Color, radius: f32) { self.items.push(DrawItem::Rect { rect, color, radius }); }
+    pub fn text(&mut self, layout: TextLayout, origin: Point2F, size: f32, color: Color)
+    {
+        self.items.push(DrawItem::Text { layout, origin, size, color });
+    }
+    pub fn image(&mut self, texture: Rc<TextureBinding>, rect: LayoutRect, style: SdfStyle) { self.items.push(DrawItem::Image { texture, rect, style }); }
+    /// Clips the items until the matching `pop_clip` into the rectangle(intersected with the current clip)
+    pub fn push_clip(&mut self, rect: LayoutRect)
+    {
+        let rect = match self.clips.last() { Some(c) => intersect(c, &rect), None => rect };
+        self.clips.push(rect);
+        self.items.push(DrawItem::Clip(Some(rect)));
+    }
+    pub fn pop_clip(&mut self)
+    {
+        self.clips.pop();
+        let current = self.clips.last().cloned();
+        self.items.push(DrawItem::Clip(current));
+    }
+    /// The current clip
+    pub fn clip(&self) -> Option<LayoutRect> { self.clips.last().cloned() }
+}
+
+pub fn intersect(a: &LayoutRect, b: &LayoutRect) -> LayoutRect
+{
+    let (l, t) = (a.origin.0.max(b.origin.0), a.origin.1.max(b.origin.1));
+    let (r, bottom) = (a.right().min(b.right()).max(l), a.bottom().min(b.bottom()).max(t));
+    LayoutRect { origin: Point2F(l, t), size: Size2F(r - l, bottom - t) }
+}
+
+/// Scale and offset of `SdfStyle::place` drawing the builtin unit rect([-1, 1]) over the pixel rectangle
+pub fn placement(rect: &LayoutRect, viewport: Size2U) -> ((f32, f32), (f32, f32))
+{
+    let (w, h) = (viewport.x() as f32, viewport.y() as f32);
+    ((rect.size.0 / w, rect.size.1 / h), ((2.0 * rect.origin.0 + rect.size.0) / w - 1.0, (2.0 * rect.origin.1 + rect.size.1) / h - 1.0))
+}
+
+/// Which part of the rounded corner texture a piece of a rounded rectangle samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner { TopLeft, TopRight, BottomLeft, BottomRight }
+/// Pieces of a rounded rectangle: 4 corners drawn with quarters of a circle and 3 filled bands between them
+pub fn rounded_rect_pieces(rect: &LayoutRect, radius: f32) -> Vec<(LayoutRect, Option<Corner>)>
+{
+    let r = radius.min(rect.size.0 * 0.5).min(rect.size.1 * 0.5).max(0.0);
+    if r <= 0.0 { return vec![(*rect, None)]; }
+    let piece = |x: f32, y: f32, w: f32, h: f32| LayoutRect { origin: Point2F(x, y), size: Size2F(w, h) };
+    let (l, t, rt, b) = (rect.origin.0, rect.origin.1, rect.right(), rect.bottom());
+    let mut pieces = vec![
+        (piece(l, t, r, r), Some(Corner::TopLeft)), (piece(rt - r, t, r, r), Some(Corner::TopRight)),
+        (piece(l, b - r, r, r), Some(Corner::BottomLeft)), (piece(rt - r, b - r, r, r), Some(Corner::BottomRight))
+    ];
+    if rect.size.0 > 2.0 * r { pieces.push((piece(l + r, t, rect.size.0 - 2.0 * r, rect.size.1), None)); }
+    if rect.size.1 > 2.0 * r
+    {
+        pieces.push((piece(l, t + r, r, rect.size.1 - 2.0 * r), None));
+        pieces.push((piece(rt - r, t + r, r, rect.size.1 - 2.0 * r), None));
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn rect(x: f32, y: f32, w: f32, h: f32) -> LayoutRect { LayoutRect { origin: Point2F(x, y), size: Size2F(w, h) } }
+
+    #[test] fn geometry()
+    {
+        assert_eq!(placement(&rect(0.0, 0.0, 100.0, 50.0), Size2U(100, 50)), ((1.0, 1.0), (0.0, 0.0)));
+        assert_eq!(placement(&rect(50.0, 0.0, 25.0, 25.0), Size2U(100, 50)), ((0.25, 0.5), (0.25, -0.5)));
+
+        let pieces = rounded_rect_pieces(&rect(10.0, 10.0, 40.0, 20.0), 15.0);
+        // the radius is limited to half of the height: no vertical bands
+        assert_eq!(pieces.len(), 5);
+        assert_eq!(pieces[3], (rect(40.0, 20.0, 10.0, 10.0), Some(Corner::BottomRight)));
+        assert_eq!(pieces[4], (rect(20.0, 10.0, 20.0, 20.0), None));
+        assert_eq!(rounded_rect_pieces(&rect(0.0, 0.0, 40.0, 40.0), 4.0).iter().map(|p| p.0.size.0 * p.0.size.1).sum::<f32>(), 1600.0);
+
+        let mut list = DisplayList::new();
+        list.push_clip(rect(0.0, 0.0, 50.0, 50.0));
+        list.push_clip(rect(25.0, 40.0, 50.0, 50.0));
+        assert_eq!(list.clip(), Some(rect(25.0, 40.0, 25.0, 10.0)));
+        list.pop_clip();
+        list.pop_clip();
+        match list.items[3] { DrawItem::Clip(None) => (), _ => panic!("The clip is not removed") }
+    }
+}
@@ -0,0 +1,209 @@
+//! Drawing display lists with the distance field pipeline: rectangles from a solid field and a circle field for the corners,
+//! text from a distance field glyph atlas(the glyph quads of the list are batched in one vertex buffer)
+
+use metrics::*;
+use render::{RenderDevice, RenderCommandsBasic, ResourceBlock, TextureBinding, BuiltinResourceKey, Color};
+use render::{TextureParam, TextureUsage, ColorFormat, SdfStyle, DynamicVertices};
+use font::{Font, GlyphAtlas, GlyphMode, GlyphKey, AtlasEntry};
+use super::{DisplayList, DrawItem, LayoutRect, Corner, placement, rounded_rect_pieces};
+use std::collections::HashMap;
+use std::error::Error;
+use std::rc::Rc;
+
+/// Texels of the corner field; the circle has `CORNER_RADIUS` texels of radius at the center
+const CORNER_SIZE: u32 = 64;
+const CORNER_RADIUS: f32 = 28.0;
+const CORNER_SPREAD: f32 = 4.0;
+const ATLAS_SIZE: u32 = 1024;
+const GLYPH_MODE: GlyphMode = GlyphMode::Sdf { base_size: 32.0, spread: 4.0 };
+/// Initial bytes of the glyph vertex buffer(1024 glyphs of 32-byte vertices); it grows into powers of two
+const GLYPH_BUFFER_BYTES: usize = 1024 * 6 * 32;
+
+pub struct UiRenderer
+{
+    // the bindings are dropped before the resource block
+    solid: Box<TextureBinding>, corner: Box<TextureBinding>, atlas_texture: Box<TextureBinding>,
+    resources: Box<ResourceBlock>,
+    /// The same fonts in the same order as the widget tree lays out with
+    fonts: Vec<Rc<Font>>, atlas: GlyphAtlas,
+    /// Glyph quads of the last prepared list in one vertex buffer, rewritten without waiting for the frames in flight, and its capacity
+    glyph_vertices: Option<(Box<DynamicVertices>, usize)>,
+    /// Runs of the last prepared list and the size of the target it was prepared for
+    runs: Vec<GlyphRun>, viewport: Size2U
+}
+/// Consecutive text items of the same color drawn at once: the first text item of the run, the color,
+/// the first vertex in the glyph vertex buffer and the number of the vertices(6 per glyph)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GlyphRun { item: usize, end: usize, color: Color, first: usize, vertex_count: usize }
+/// Vertex of the distance field pipeline: the position in the normalized device coordinates and the texture coordinates(`PosUV` of the backends)
+#[repr(C)] #[derive(Debug, Clone, Copy, PartialEq)]
+struct GlyphVertex { pos: [f32; 4], uv: [f32; 2], _pad: [f32; 2] }
+impl UiRenderer
+{
+    pub fn new(fonts: Vec<Rc<Font>>) -> Result<Self, Box<Error>>
+    {
+        let atlas = GlyphAtlas::with_mode(Size2U(ATLAS_SIZE, ATLAS_SIZE), GLYPH_MODE);
+        let (solid, corner) = ([0xffu8; 16], corner_field());
+        let resources = RenderDevice::get().create_resources(&[], &[
+            TextureParam { size: Size2U(4, 4), color: ColorFormat::Grayscale, usage: TextureUsage::Immutable(&solid), .. Default::default() },
+            TextureParam
+            {
+                size: Size2U(CORNER_SIZE, CORNER_SIZE), color: ColorFormat::Grayscale, usage: TextureUsage::Immutable(&corner), .. Default::default()
+            },
+            atlas.texture_param()
+        ])?;
+        Ok(UiRenderer
+        {
+            solid: RenderDevice::get().bind_texture(&*resources, 0)?, corner: RenderDevice::get().bind_texture(&*resources, 1)?,
+            atlas_texture: RenderDevice::get().bind_texture(&*resources, 2)?, resources,
+            fonts, atlas, glyph_vertices: None, runs: Vec::new(), viewport: Size2U(1, 1)
+        })
+    }
+
+    /// Puts the glyphs of the list into the atlas, uploads the new ones and batches the glyph quads for a render target of the size.
+    /// Called before recording the list. The atlas is uploaded only when glyphs are added, waiting for the frames in flight;
+    /// the quads are written into the region of each swapchain image when it is submitted next, so the frames in flight keep theirs
+    pub fn prepare(&mut self, list: &DisplayList, viewport: Size2U) -> Result<(), Box<Error>>
+    {
+        self.atlas.begin_frame();
+        let mut entries: HashMap<GlyphKey, AtlasEntry> = HashMap::new();
+        let (mut runs, mut vertices): (Vec<GlyphRun>, Vec<GlyphVertex>) = (Vec::new(), Vec::new());
+        for (n, item) in list.items.iter().enumerate()
+        {
+            let (layout, origin, size, color) = match *item { DrawItem::Text { ref layout, origin, size, color } => (layout, origin, size, color), _ => continue };
+            let scale = self.atlas.draw_scale(size);
+            let mut quads = Vec::with_capacity(layout.glyphs.len() * 6);
+            for g in &layout.glyphs
+            {
+                let key = GlyphKey::new(g.font, g.glyph, size);
+                let e = match entries.get(&key).cloned()
+                {
+                    Some(e) => e,
+                    None => { let e = self.atlas.get(&self.fonts[g.font], key)?; entries.insert(key, e); e }
+                };
+                if e.size.x() == 0 || e.size.y() == 0 { continue; }
+                let rect = LayoutRect
+                {
+                    origin: Point2F(origin.0 + g.position.0 + e.offset.0 as f32 * scale, origin.1 + g.position.1 + e.offset.1 as f32 * scale),
+                    size: Size2F(e.size.x() as f32 * scale, e.size.y() as f32 * scale)
+                };
+                quads.extend_from_slice(&glyph_quad(&rect, e.uv(self.atlas.size()), viewport));
+            }
+            if runs.last().map(|r| r.end == n && r.color == color).unwrap_or(false)
+            {
+                let r = runs.last_mut().unwrap();
+                r.end = n + 1; r.vertex_count += quads.len();
+            }
+            else { runs.push(GlyphRun { item: n, end: n + 1, color, first: vertices.len(), vertex_count: quads.len() }); }
+            vertices.extend(quads);
+        }
+        self.atlas.upload(&*self.resources, 2)?;
+
+        self.runs.clear();
+        self.viewport = viewport;
+        let bytes = vertex_bytes(&vertices);
+        let grows = match self.glyph_vertices { Some((_, capacity)) => capacity < bytes.len(), None => !bytes.is_empty() };
+        if grows
+        {
+            // the frames in flight may still read the smaller buffer
+            if self.glyph_vertices.is_some() { RenderDevice::get().wait_render_ready()?; }
+            self.glyph_vertices = None;
+            let capacity = bytes.len().next_power_of_two().max(GLYPH_BUFFER_BYTES);
+            self.glyph_vertices = Some((RenderDevice::get().new_dynamic_vertices(capacity)?, capacity));
+        }
+        if let Some((ref b, _)) = self.glyph_vertices { RenderDevice::get().set_dynamic_vertices(&**b, bytes)?; }
+        self.runs = runs.into_iter().filter(|r| r.vertex_count > 0).collect();
+        Ok(())
+    }
+
+    /// Records the list drawn into the render target it was prepared for: a draw per glyph run.
+    /// Clips are in the pixels of the target: they do not follow the layer animation(e.g. slides of the scene)
+    pub fn record(&self, rec: &mut RenderCommandsBasic, list: &DisplayList)
+    {
+        let viewport = self.viewport;
+        let unit_rect = RenderDevice::get().get_builtin_vertex_array(BuiltinResourceKey::UnitRect).expect("Failed to get the unit rect");
+        let draw = |rec: &mut RenderCommandsBasic, field: &TextureBinding, style: SdfStyle, rect: &LayoutRect|
+        {
+            let (scale, offset) = placement(rect, viewport);
+            rec.draw_sdf(&*unit_rect, field, &style.place(scale, offset));
+        };
+        let mut next_run = 0;
+        for (n, item) in list.items.iter().enumerate()
+        {
+            match *item
+            {
+                DrawItem::Rect { rect, color, radius } => for (piece, corner) in rounded_rect_pieces(&rect, radius)
+                {
+                    match corner
+                    {
+                        Some(c) => draw(rec, &*self.corner, SdfStyle::new(color).uv_rect(corner_uv(c)), &piece),
+                        None => draw(rec, &*self.solid, SdfStyle::new(color), &piece)
+                    }
+                },
+                DrawItem::Text { .. } => if let Some((ref b, _)) = self.glyph_vertices
+                {
+                    match self.runs.get(next_run)
+                    {
+                        Some(r) if r.item == n =>
+                        {
+                            // the positions and the texture coordinates are in the vertices
+                            let offset = r.first * ::std::mem::size_of::<GlyphVertex>();
+                            let vertices = RenderDevice::get().get_dynamic_vertex_array(&**b, offset, r.vertex_count);
+                            let style = SdfStyle::new(r.color);
+                            if self.atlas.mode().channels() > 1 { rec.draw_msdf(&*vertices, &*self.atlas_texture, &style); }
+                            else { rec.draw_sdf(&*vertices, &*self.atlas_texture, &style); }
+                            next_run += 1;
+                        },
+                        _ => ()
+                    }
+                },
+                DrawItem::Image { ref texture, rect, style } => draw(rec, &**texture, style, &rect),
+                DrawItem::Clip(clip) => rec.set_clip(clip.map(|c|
+                [
+                    c.origin.0.max(0.0) as u32, c.origin.1.max(0.0) as u32, c.right().ceil().max(0.0) as u32, c.bottom().ceil().max(0.0) as u32
+                ]))
+            }
+        }
+        rec.set_clip(None);
+    }
+}
+
+/// Two triangles covering the pixel rectangle(in the order of the builtin unit rect)
+fn glyph_quad(rect: &LayoutRect, uv: [f32; 4], viewport: Size2U) -> [GlyphVertex; 6]
+{
+    let (w, h) = (viewport.x() as f32, viewport.y() as f32);
+    let (l, t, r, b) = (2.0 * rect.origin.0 / w - 1.0, 2.0 * rect.origin.1 / h - 1.0, 2.0 * rect.right() / w - 1.0, 2.0 * rect.bottom() / h - 1.0);
+    let vertex = |x: f32, y: f32, u: f32, v: f32| GlyphVertex { pos: [x, y, 0.0, 1.0], uv: [u, v], _pad: [0.0; 2] };
+    let (tl, tr) = (vertex(l, t, uv[0], uv[1]), vertex(r, t, uv[2], uv[1]));
+    let (bl, br) = (vertex(l, b, uv[0], uv[3]), vertex(r, b, uv[2], uv[3]));
+    [tl, tr, bl, bl, tr, br]
+}
+fn vertex_bytes(vertices: &[GlyphVertex]) -> &[u8]
+{
+    unsafe { ::std::slice::from_raw_parts(vertices.as_ptr() as *const u8, vertices.len() * ::std::mem::size_of::<GlyphVertex>()) }
+}
+
+/// Distance field of a circle: 0.5 at the edge, 1 inside and 0 outside `CORNER_SPREAD` texels away from the edge
+fn corner_field() -> Vec<u8>
+{
+    let c = CORNER_SIZE as f32 * 0.5;
+    (0 .. CORNER_SIZE * CORNER_SIZE).map(|n|
+    {
+        let (x, y) = ((n % CORNER_SIZE) as f32 + 0.5 - c, (n / CORNER_SIZE) as f32 + 0.5 - c);
+        let d = CORNER_RADIUS - (x * x + y * y).sqrt();
+        ((0.5 + d / (2.0 * CORNER_SPREAD)).max(0.0).min(1.0) * 255.0).round() as u8
+    }).collect()
+}
+/// The quarter of the circle in the corner field: left, top, right, bottom
+fn corner_uv(corner: Corner) -> [f32; 4]
+{
+    let size = CORNER_SIZE as f32;
+    let (near, center, far) = ((size * 0.5 - CORNER_RADIUS) / size, 0.5, (size * 0.5 + CORNER_RADIUS) / size);
+    match corner
+    {
+        Corner::TopLeft => [near, near, center, center],
+        Corner::TopRight => [center, near, far, center],
+        Corner::BottomLeft => [near, center, center, far],
+        Corner::BottomRight => [center, center, far, far]
+    }
+}
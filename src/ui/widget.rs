@@ -0,0 +1,560 @@
+//! Retained widget tree: layout with the flex engine, painting into display lists, dirty tracking and event routing
+
+use metrics::*;
+use render::Color;
+use font::{LayoutFont, TextLayout, TextStyle};
+use input::InputEvent;
+use super::{FlexTree, FlexStyle, NodeId, LayoutRect, DisplayList, intersect};
+use std::any::Any;
+use std::rc::Rc;
+
+/// Colors and metrics shared by the widgets
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme
+{
+    pub text: Color, pub text_on_accent: Color, pub accent: Color, pub surface: Color, pub pressed: Color, pub separator: Color,
+    /// Dims the screen under modal dialogs
+    pub scrim: Color,
+    /// Pixels per em of the body text
+    pub font_size: f32, pub radius: f32
+}
+impl Default for Theme
+{
+    fn default() -> Self
+    {
+        Theme
+        {
+            text: Color(0.1, 0.1, 0.12, 1.0), text_on_accent: Color(1.0, 1.0, 1.0, 1.0), accent: Color(0.0, 0.24, 0.47, 1.0),
+            surface: Color(1.0, 1.0, 1.0, 1.0), pressed: Color(0.85, 0.88, 0.92, 1.0), separator: Color(0.8, 0.8, 0.82, 1.0),
+            scrim: Color(0.0, 0.0, 0.0, 0.4), font_size: 16.0, radius: 6.0
+        }
+    }
+}
+
+/// What the widgets measure and paint with
+pub struct UiContext<'a> { pub fonts: &'a [&'a LayoutFont], pub theme: &'a Theme }
+impl<'a> UiContext<'a>
+{
+    pub fn layout_text(&self, text: &str, style: &TextStyle) -> TextLayout { TextLayout::new(text, self.fonts, style) }
+}
+
+/// How a widget took an event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reaction
+{
+    /// Passed to the parent
+    Ignored,
+    /// Taken without visible changes
+    Handled,
+    /// Taken and the widget must be painted again
+    Repaint,
+    /// Taken, painted again and reported to the application
+    Notify(Notice)
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Notice
+{
+    Clicked, Toggled(bool),
+    /// An item of a list or a tab was selected
+    Selected(usize),
+    /// A dialog was closed without choosing
+    Dismissed,
+    /// The text of an input was edited, or submitted with the enter key
+    Edited, Submitted
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WidgetId(usize);
+/// A notice from a widget
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Notification { pub widget: WidgetId, pub notice: Notice }
+#[derive(Debug, Clone, PartialEq)]
+pub struct DispatchResult
+{
+    /// Some widget took the event
+    pub handled: bool,
+    pub notifications: Vec<Notification>
+}
+
+pub trait Widget
+{
+    /// Layout of the widget in its parent and of its children
+    fn style(&self, _cx: &UiContext) -> FlexStyle { FlexStyle::default() }
+    /// Size of the contents of widgets without children(excluding the padding)
+    fn measure(&self, _cx: &UiContext) -> Size2F { Size2F(0.0, 0.0) }
+    /// Called after the layout with the rectangle of the widget and the extent of its children from its top-left corner
+    fn arranged(&mut self, _rect: LayoutRect, _children: Size2F) {}
+    /// Paints under the children
+    fn paint(&self, rect: LayoutRect, cx: &UiContext, list: &mut DisplayList);
+    /// Paints over the children
+    fn paint_over(&self, _rect: LayoutRect, _cx: &UiContext, _list: &mut DisplayList) {}
+    /// Children are clipped into the widget and moved by the offset(scrolling)
+    fn clips_children(&self) -> bool { false }
+    fn child_offset(&self) -> (f32, f32) { (0.0, 0.0) }
+
+    /// Events from the pointer captured by the widget or on the widget or its descendants, and other events while focused
+    fn handle(&mut self, _event: &InputEvent, _rect: LayoutRect) -> Reaction { Reaction::Ignored }
+    /// Capture phase: offered the events targeting descendants before them, from the root.
+    /// Returning true takes the event(and the pointer from the descendant capturing it)
+    fn intercept(&mut self, _event: &InputEvent, _rect: LayoutRect) -> bool { false }
+    /// The captured pointer was taken by an ancestor
+    fn pointer_cancelled(&mut self) -> Reaction { Reaction::Ignored }
+    /// The widget got(true) or lost(false) the focus of the tree
+    fn focus_changed(&mut self, _focused: bool) -> Reaction { Reaction::Ignored }
+    /// Advances animations by `dt` seconds. Returns whether the widget must be painted again
+    fn update(&mut self, _dt: f32) -> bool { false }
+
+    fn as_any(&self) -> &Any;
+    fn as_any_mut(&mut self) -> &mut Any;
+}
+
+struct Entry { widget: Box<Widget>, parent: Option<WidgetId>, children: Vec<WidgetId>, rect: LayoutRect }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Dirty { Clean, Repaint, Relayout }
+
+/// Widgets in a tree with one optional modal layer over it
+pub struct WidgetTree
+{
+    entries: Vec<Option<Entry>>, root: Option<WidgetId>, modal: Option<WidgetId>,
+    fonts: Vec<Rc<LayoutFont>>, theme: Theme,
+    area: Size2U, dirty: Dirty,
+    /// The widget receiving the pointer events until the pointer is released
+    capture: Option<WidgetId>, focus: Option<WidgetId>
+}
+impl WidgetTree
+{
+    /// `fonts` in the order of fallback
+    pub fn new(fonts: Vec<Rc<LayoutFont>>, theme: Theme) -> Self
+    {
+        WidgetTree
+        {
+            entries: Vec::new(), root: None, modal: None, fonts, theme, area: Size2U(0, 0), dirty: Dirty::Relayout, capture: None, focus: None
+        }
+    }
+    pub fn theme(&self) -> &Theme { &self.theme }
+
+    fn insert(&mut self, widget: Box<Widget>, parent: Option<WidgetId>) -> WidgetId
+    {
+        let entry = Entry { widget, parent, children: Vec::new(), rect: LayoutRect::default() };
+        let id = match self.entries.iter().position(|e| e.is_none())
+        {
+            Some(n) => { self.entries[n] = Some(entry); WidgetId(n) },
+            None => { self.entries.push(Some(entry)); WidgetId(self.entries.len() - 1) }
+        };
+        if let Some(p) = parent { self.entry_mut(p).children.push(id); }
+        self.dirty = Dirty::Relayout;
+        id
+    }
+    /// Replaces the whole tree
+    pub fn set_root(&mut self, widget: Box<Widget>) -> WidgetId
+    {
+        if let Some(r) = self.root { self.remove(r); }
+        let id = self.insert(widget, None);
+        self.root = Some(id);
+        id
+    }
+    pub fn add(&mut self, parent: WidgetId, widget: Box<Widget>) -> WidgetId { self.insert(widget, Some(parent)) }
+    /// Shows the widget over the tree. Input goes only to the modal layer while it is open
+    pub fn open_modal(&mut self, widget: Box<Widget>) -> WidgetId
+    {
+        self.close_modal();
+        let id = self.insert(widget, None);
+        self.modal = Some(id);
+        self.capture = None;
+        id
+    }
+    pub fn close_modal(&mut self) { if let Some(m) = self.modal.take() { self.remove(m); } }
+    pub fn modal(&self) -> Option<WidgetId> { self.modal }
+    /// Removes the widget with its descendants
+    pub fn remove(&mut self, id: WidgetId)
+    {
+        let parent = match self.entries.get(id.0).and_then(|e| e.as_ref()) { Some(e) => e.parent, None => return };
+        if let Some(p) = parent { self.entry_mut(p).children.retain(|&c| c != id); }
+        self.drop_subtree(id);
+        self.dirty = Dirty::Relayout;
+    }
+    fn drop_subtree(&mut self, id: WidgetId)
+    {
+        let entry = self.entries[id.0].take().expect("Removed widget");
+        if self.root == Some(id) { self.root = None; }
+        if self.modal == Some(id) { self.modal = None; }
+        if self.capture == Some(id) { self.capture = None; }
+        if self.focus == Some(id) { self.focus = None; }
+        for c in entry.children { self.drop_subtree(c); }
+    }
+    pub fn children(&self, id: WidgetId) -> &[WidgetId] { &self.entry(id).children }
+    pub fn parent(&self, id: WidgetId) -> Option<WidgetId> { self.entry(id).parent }
+    /// The widget if it is a `T`
+    pub fn get<T: Widget + 'static>(&self, id: WidgetId) -> Option<&T> { self.entry(id).widget.as_any().downcast_ref() }
+    /// The widget if it is a `T`. The tree is laid out again
+    pub fn get_mut<T: Widget + 'static>(&mut self, id: WidgetId) -> Option<&mut T>
+    {
+        self.dirty = Dirty::Relayout;
+        self.entry_mut(id).widget.as_any_mut().downcast_mut()
+    }
+    /// The rectangle from the last layout, without the scrolling of the ancestors
+    pub fn rect(&self, id: WidgetId) -> LayoutRect { self.entry(id).rect }
+    pub fn focus(&self) -> Option<WidgetId> { self.focus }
+    /// Moves the focus(e.g. to the first field of a form)
+    pub fn set_focus(&mut self, id: Option<WidgetId>)
+    {
+        let mut result = DispatchResult { handled: false, notifications: Vec::new() };
+        self.move_focus(id, &mut result);
+    }
+
+    fn entry(&self, id: WidgetId) -> &Entry { self.entries[id.0].as_ref().expect("Removed widget") }
+    fn entry_mut(&mut self, id: WidgetId) -> &mut Entry { self.entries[id.0].as_mut().expect("Removed widget") }
+    fn mark(&mut self, dirty: Dirty) { self.dirty = self.dirty.max(dirty); }
+
+    /// Whether something changed since the last paint
+    pub fn needs_paint(&self) -> bool { self.dirty != Dirty::Clean }
+    /// Resizes the area the root and the modal layer fill
+    pub fn resize(&mut self, area: Size2U) { if self.area != area { self.area = area; self.dirty = Dirty::Relayout; } }
+    /// Advances the animations of the widgets. Returns whether the tree must be painted again
+    pub fn update(&mut self, dt: f32) -> bool
+    {
+        let mut changed = false;
+        for e in self.entries.iter_mut().filter_map(|e| e.as_mut()) { changed |= e.widget.update(dt); }
+        if changed { self.mark(Dirty::Repaint); }
+        self.needs_paint()
+    }
+
+    /// Lays out the tree if needed
+    pub fn layout(&mut self)
+    {
+        if self.dirty != Dirty::Relayout { return; }
+        for root in self.root.into_iter().chain(self.modal)
+        {
+            let nodes: Vec<(WidgetId, LayoutRect)> =
+            {
+                let fonts: Vec<&LayoutFont> = self.fonts.iter().map(|f| &**f).collect();
+                let cx = UiContext { fonts: &fonts, theme: &self.theme };
+                let mut flex = FlexTree::new();
+                let mut nodes = Vec::new();
+                let node = self.flex_node(root, &cx, &mut flex, &mut nodes);
+                let layout = flex.layout(node, self.area);
+                nodes.into_iter().map(|(id, n)| (id, layout.rect(n))).collect()
+            };
+            for &(id, rect) in &nodes { self.entry_mut(id).rect = rect; }
+            for &(id, rect) in &nodes
+            {
+                let extent = self.entry(id).children.iter().map(|&c| self.entry(c).rect).fold(Size2F(0.0, 0.0), |e, r|
+                    Size2F(e.0.max(r.right() - rect.origin.0), e.1.max(r.bottom() - rect.origin.1)));
+                self.entry_mut(id).widget.arranged(rect, extent);
+            }
+        }
+        self.dirty = Dirty::Repaint;
+    }
+    fn flex_node(&self, id: WidgetId, cx: &UiContext, flex: &mut FlexTree, nodes: &mut Vec<(WidgetId, NodeId)>) -> NodeId
+    {
+        let e = self.entry(id);
+        let style = e.widget.style(cx);
+        let node = if e.children.is_empty() { flex.leaf(style, e.widget.measure(cx)) } else
+        {
+            let scrolls = e.widget.clips_children();
+            let children = e.children.iter().map(|&c|
+            {
+                let n = self.flex_node(c, cx, flex, nodes);
+                // contents of scroll views keep their sizes and overflow
+                if scrolls { flex.style_mut(n).shrink = 0.0; }
+                n
+            }).collect();
+            flex.container(style, children)
+        };
+        nodes.push((id, node));
+        node
+    }
+
+    /// Paints the tree and the modal layer over it
+    pub fn paint(&mut self) -> DisplayList
+    {
+        self.layout();
+        let mut list = DisplayList::new();
+        {
+            let fonts: Vec<&LayoutFont> = self.fonts.iter().map(|f| &**f).collect();
+            let cx = UiContext { fonts: &fonts, theme: &self.theme };
+            for root in self.root.into_iter().chain(self.modal) { self.paint_widget(root, (0.0, 0.0), &cx, &mut list); }
+        }
+        self.dirty = Dirty::Clean;
+        list
+    }
+    fn paint_widget(&self, id: WidgetId, offset: (f32, f32), cx: &UiContext, list: &mut DisplayList)
+    {
+        let e = self.entry(id);
+        let rect = moved(e.rect, offset);
+        // outside the scroll views
+        if let Some(clip) = list.clip()
+        {
+            let visible = intersect(&clip, &rect);
+            if visible.size.0 <= 0.0 || visible.size.1 <= 0.0 { return; }
+        }
+        e.widget.paint(rect, cx, list);
+        let clips = e.widget.clips_children();
+        if clips { list.push_clip(rect); }
+        let (dx, dy) = e.widget.child_offset();
+        for &c in &e.children { self.paint_widget(c, (offset.0 + dx, offset.1 + dy), cx, list); }
+        if clips { list.pop_clip(); }
+        e.widget.paint_over(rect, cx, list);
+    }
+
+    /// Widgets under the point from the root of the active layer to the deepest one, with their rectangles on the screen
+    fn hit_path(&self, p: Point2F) -> Vec<(WidgetId, LayoutRect)>
+    {
+        let mut path = Vec::new();
+        let mut current = self.modal.or(self.root).map(|r| (r, (0.0, 0.0)));
+        while let Some((id, offset)) = current.take()
+        {
+            let e = self.entry(id);
+            let rect = moved(e.rect, offset);
+            if !rect.contains(p) { break; }
+            path.push((id, rect));
+            let (dx, dy) = e.widget.child_offset();
+            let offset = (offset.0 + dx, offset.1 + dy);
+            // the last child is painted on top
+            current = e.children.iter().rev().find(|&&c| moved(self.entry(c).rect, offset).contains(p)).map(|&c| (c, offset));
+        }
+        path
+    }
+    /// Rectangle on the screen: the layout moved by the offsets of the scrolling ancestors
+    fn screen_rect(&self, id: WidgetId) -> LayoutRect
+    {
+        let (mut dx, mut dy) = (0.0, 0.0);
+        let mut p = self.entry(id).parent;
+        while let Some(a) = p { let (x, y) = self.entry(a).widget.child_offset(); dx += x; dy += y; p = self.entry(a).parent; }
+        moved(self.entry(id).rect, (dx, dy))
+    }
+    fn ancestors(&self, id: WidgetId) -> Vec<WidgetId>
+    {
+        let mut a = Vec::new();
+        let mut p = self.entry(id).parent;
+        while let Some(x) = p { a.push(x); p = self.entry(x).parent; }
+        a.reverse();
+        a
+    }
+
+    /// Routes the event along the path from the root of the active layer to the target: the widget capturing the pointer or the deepest
+    /// one under it for pointer events(the wheel always goes under the pointer), and the focused widget(the last one which took a press)
+    /// for the others. In the capture phase the ancestors of the target may intercept the event from the root(e.g. scroll views taking
+    /// drags). Then the event bubbles up from the target until a widget takes it(and captures the pointer if it was pressed);
+    /// the captured pointer does not bubble. Resizes lay out the tree again and losing the focus cancels the captured pointer
+    pub fn dispatch(&mut self, event: &InputEvent) -> DispatchResult
+    {
+        let mut result = DispatchResult { handled: false, notifications: Vec::new() };
+        match *event
+        {
+            InputEvent::Resize(size) => { self.resize(size); return result; },
+            InputEvent::Focus(false) => { self.cancel_capture(&mut result); return result; },
+            _ => ()
+        }
+        self.layout();
+        let point = event.position();
+        let captured = match *event { InputEvent::Wheel { .. } => None, _ => self.capture };
+        let path: Vec<WidgetId> = match (point, captured)
+        {
+            (Some(_), Some(c)) => { let mut a = self.ancestors(c); a.push(c); a },
+            (Some(p), None) => self.hit_path(p).into_iter().map(|(id, _)| id).collect(),
+            (None, _) => match self.focused_in_layer()
+            {
+                Some(f) => { let mut a = self.ancestors(f); a.push(f); a },
+                None => Vec::new()
+            }
+        };
+        let target = match path.last() { Some(&t) => t, None => return result };
+
+        for &a in &path[.. path.len() - 1]
+        {
+            let rect = self.screen_rect(a);
+            if !self.entry_mut(a).widget.intercept(event, rect) { continue; }
+            if captured.is_some() || is_press(event)
+            {
+                self.cancel_capture(&mut result);
+                self.capture = Some(a);
+            }
+            let r = self.entry_mut(a).widget.handle(event, rect);
+            self.react(a, r, &mut result);
+            result.handled = true;
+            return self.finish_pointer(event, result);
+        }
+        let bubble: Vec<WidgetId> = if captured.is_some() { vec![target] } else { path.iter().rev().cloned().collect() };
+        for id in bubble
+        {
+            let rect = self.screen_rect(id);
+            let r = self.entry_mut(id).widget.handle(event, rect);
+            if r == Reaction::Ignored { continue; }
+            self.react(id, r, &mut result);
+            result.handled = true;
+            if is_press(event) { self.capture = Some(id); self.move_focus(Some(id), &mut result); }
+            break;
+        }
+        self.finish_pointer(event, result)
+    }
+    fn cancel_capture(&mut self, result: &mut DispatchResult)
+    {
+        if let Some(c) = self.capture.take()
+        {
+            let r = self.entry_mut(c).widget.pointer_cancelled();
+            self.react(c, r, result);
+        }
+    }
+    fn move_focus(&mut self, id: Option<WidgetId>, result: &mut DispatchResult)
+    {
+        if self.focus == id { return; }
+        for (widget, focused) in self.focus.map(|f| (f, false)).into_iter().chain(id.map(|w| (w, true)))
+        {
+            let r = self.entry_mut(widget).widget.focus_changed(focused);
+            self.react(widget, r, result);
+        }
+        self.focus = id;
+    }
+    /// The focused widget, or the modal layer when the focus is under it
+    fn focused_in_layer(&self) -> Option<WidgetId>
+    {
+        match (self.focus, self.modal)
+        {
+            (Some(f), Some(m)) => if f == m || self.ancestors(f).contains(&m) { Some(f) } else { Some(m) },
+            (f, m) => f.or(m)
+        }
+    }
+    fn finish_pointer(&mut self, event: &InputEvent, result: DispatchResult) -> DispatchResult
+    {
+        if let InputEvent::PointerUp(..) = *event { self.capture = None; }
+        result
+    }
+    fn react(&mut self, id: WidgetId, reaction: Reaction, result: &mut DispatchResult)
+    {
+        match reaction
+        {
+            Reaction::Ignored | Reaction::Handled => (),
+            Reaction::Repaint => self.mark(Dirty::Repaint),
+            Reaction::Notify(notice) => { self.mark(Dirty::Repaint); result.notifications.push(Notification { widget: id, notice }); }
+        }
+    }
+}
+
+fn is_press(event: &InputEvent) -> bool { match *event { InputEvent::PointerDown(..) => true, _ => false } }
+fn moved(r: LayoutRect, (dx, dy): (f32, f32)) -> LayoutRect { LayoutRect { origin: Point2F(r.origin.0 + dx, r.origin.1 + dy), .. r } }
+
+#[cfg(test)]
+pub mod tests
+{
+    use super::*;
+    use font::FontMetrics;
+    use input::{PointerButton, KeyCode, Modifiers};
+    use ui::{Panel, Button, ScrollView, Dialog, Label, TextInput, Edges, Dimension};
+    use textedit::{TextField, TextInputEvent, Composition, EditKey};
+
+    /// Every character is half an em wide
+    pub struct Narrow;
+    impl LayoutFont for Narrow
+    {
+        fn glyph_index(&self, c: char) -> Option<u16> { Some(c as u32 as u16) }
+        fn advance(&self, _glyph: u16, size: f32) -> f32 { size * 0.5 }
+        fn metrics(&self, size: f32) -> FontMetrics { FontMetrics { ascent: size * 0.8, descent: size * 0.2, line_gap: 0.0 } }
+    }
+    pub fn tree() -> WidgetTree { WidgetTree::new(vec![Rc::new(Narrow)], Theme::default()) }
+
+    #[test] fn layout_and_paint()
+    {
+        let mut t = tree();
+        let root = t.set_root(box Panel::new(FlexStyle::column().padding(Edges::all(10.0)).gap(4.0), None));
+        let title = t.add(root, box Label::new("Timetable"));
+        let ok = t.add(root, box Button::new("OK"));
+        t.resize(Size2U(320, 240));
+        assert!(t.needs_paint());
+        let list = t.paint();
+        assert!(!t.needs_paint());
+        // 9 characters of 8 pixels, one line of 16 pixels
+        assert_eq!(t.rect(title), LayoutRect { origin: Point2F(10.0, 10.0), size: Size2F(300.0, 16.0) });
+        assert_eq!(t.rect(ok).origin, Point2F(10.0, 30.0));
+        assert!(list.items.len() >= 3);
+
+        t.get_mut::<Label>(title).unwrap().set_text("Timetable\nMonday");
+        t.paint();
+        assert_eq!(t.rect(ok).origin, Point2F(10.0, 46.0));
+        assert!(t.get::<Button>(title).is_none());
+    }
+
+    #[test] fn routing()
+    {
+        let mut t = tree();
+        let root = t.set_root(box Panel::new(FlexStyle::column(), None));
+        let scroll = t.add(root, box ScrollView::new());
+        let buttons: Vec<_> = (0 .. 10).map(|n| t.add(scroll, box Button::new(&format!("Period {}", n)))).collect();
+        t.resize(Size2U(200, 100));
+        t.paint();
+        let h = t.rect(buttons[1]).origin.1;
+
+        // a press and a release on a button clicks it
+        let (down, up, drag) = (|p| InputEvent::PointerDown(p, PointerButton::Primary), |p| InputEvent::PointerUp(p, PointerButton::Primary), InputEvent::PointerMove);
+        let at = Point2F(20.0, h + 5.0);
+        assert_eq!(t.dispatch(&down(at)).notifications, vec![]);
+        assert_eq!(t.dispatch(&up(at)).notifications, vec![Notification { widget: buttons[1], notice: Notice::Clicked }]);
+
+        // dragging takes the pointer from the button to the scroll view
+        t.dispatch(&down(at));
+        assert!(t.get::<Button>(buttons[1]).unwrap().is_pressed());
+        t.dispatch(&drag(Point2F(20.0, h - 25.0)));
+        assert!(!t.get::<Button>(buttons[1]).unwrap().is_pressed());
+        assert!(t.dispatch(&up(Point2F(20.0, h - 25.0))).notifications.is_empty());
+        assert_eq!(t.get::<ScrollView>(scroll).unwrap().offset(), 30.0);
+        // the scrolled button is hit at its new place
+        t.paint();
+        t.dispatch(&down(Point2F(20.0, h - 25.0)));
+        assert_eq!(t.dispatch(&up(Point2F(20.0, h - 25.0))).notifications[0].widget, buttons[1]);
+        // the wheel scrolls the view under the pointer
+        assert!(t.dispatch(&InputEvent::Wheel { position: at, delta: (0.0, 48.0) }).handled);
+        assert_eq!(t.get::<ScrollView>(scroll).unwrap().offset(), 78.0);
+        assert!(t.dispatch(&InputEvent::Wheel { position: at, delta: (0.0, -1000.0) }).handled);
+        assert!(!t.dispatch(&InputEvent::Wheel { position: at, delta: (0.0, -48.0) }).handled);
+        t.paint();
+        // losing the focus cancels the press
+        t.dispatch(&down(at));
+        t.dispatch(&InputEvent::Focus(false));
+        assert!(!t.get::<Button>(buttons[1]).unwrap().is_pressed());
+        assert!(t.dispatch(&up(at)).notifications.is_empty());
+
+        // the modal layer takes all the input
+        let dialog = t.open_modal(box Dialog::new());
+        let card = t.add(dialog, box Panel::new(FlexStyle::column().size(Dimension::Px(100.0), Dimension::Px(50.0)), Some(Color(1.0, 1.0, 1.0, 1.0))));
+        t.paint();
+        assert_eq!(t.rect(card).origin, Point2F(50.0, 25.0));
+        assert!(t.dispatch(&down(at)).handled);
+        assert_eq!(t.dispatch(&up(at)).notifications, vec![Notification { widget: dialog, notice: Notice::Dismissed }]);
+        let back = InputEvent::KeyDown { key: KeyCode::Escape, modifiers: Modifiers::default(), repeat: false };
+        assert_eq!(t.dispatch(&back).notifications, vec![Notification { widget: dialog, notice: Notice::Dismissed }]);
+        t.close_modal();
+        assert!(!t.dispatch(&back).handled);
+    }
+
+    #[test] fn text_input_focus()
+    {
+        let mut t = tree();
+        let root = t.set_root(box Panel::new(FlexStyle::column().gap(4.0), None));
+        let (id, password) = (t.add(root, box TextInput::new(TextField::new(), "Student ID")), t.add(root, box TextInput::new(TextField::password(), "Password")));
+        t.resize(Size2U(320, 240));
+        t.paint();
+        let text = |s: &str| InputEvent::Text(TextInputEvent::Text(s.to_owned()));
+        // nothing is focused yet
+        assert!(!t.dispatch(&text("a")).handled);
+        t.set_focus(Some(id));
+        assert!(t.get::<TextInput>(id).unwrap().is_focused());
+        assert_eq!(t.dispatch(&text("17")).notifications, vec![Notification { widget: id, notice: Notice::Edited }]);
+        // the composition is shown but not a part of the text
+        t.dispatch(&InputEvent::Text(TextInputEvent::Preedit(Composition { text: "がく".to_owned(), cursor: 6 })));
+        assert!(t.needs_paint());
+        assert_eq!(t.get::<TextInput>(id).unwrap().field().display_text().0, "17がく");
+        t.paint();
+
+        // a press moves the focus
+        let at = t.rect(password).origin;
+        t.dispatch(&InputEvent::PointerDown(Point2F(at.0 + 1.0, at.1 + 1.0), PointerButton::Primary));
+        t.dispatch(&InputEvent::PointerUp(Point2F(at.0 + 1.0, at.1 + 1.0), PointerButton::Primary));
+        assert_eq!(t.focus(), Some(password));
+        assert!(!t.get::<TextInput>(id).unwrap().is_focused() && t.get::<TextInput>(password).unwrap().is_focused());
+        t.dispatch(&text("secret"));
+        assert_eq!(t.get::<TextInput>(password).unwrap().field().text(), "secret");
+        assert_eq!(t.get::<TextInput>(id).unwrap().field().text(), "17");
+        let enter = InputEvent::Text(TextInputEvent::Key { key: EditKey::Enter, extend: false, word: false });
+        assert_eq!(t.dispatch(&enter).notifications, vec![Notification { widget: password, notice: Notice::Submitted }]);
+        assert!(t.paint().items.len() >= 4);
+    }
+}
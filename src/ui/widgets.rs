@@ -0,0 +1,639 @@
+//! The basic widget set: panel, label, image, button, checkbox, text input, list view, scroll view, tab bar and modal dialog
+
+use metrics::*;
+use render::{Color, TextureBinding, SdfStyle};
+use font::{TextStyle, TextAlign, TextLayout};
+use input::InputEvent;
+use textedit::{TextField, EditResponse, Clipboard, LocalClipboard};
+use super::{Widget, UiContext, Reaction, Notice, FlexStyle, Align, Justify, Dimension, Edges, LayoutRect, DisplayList};
+use std::any::Any;
+use std::rc::Rc;
+
+macro_rules! any_impl
+{
+    () =>
+    {
+        fn as_any(&self) -> &Any { self }
+        fn as_any_mut(&mut self) -> &mut Any { self }
+    }
+}
+
+fn rect(x: f32, y: f32, w: f32, h: f32) -> LayoutRect { LayoutRect { origin: Point2F(x, y), size: Size2F(w, h) } }
+/// Top-left corner placing the text at the center of the rectangle
+fn centered(layout: &TextLayout, r: &LayoutRect) -> Point2F
+{
+    Point2F(r.origin.0 + (r.size.0 - layout.size.0) * 0.5, r.origin.1 + (r.size.1 - layout.size.1) * 0.5)
+}
+fn with_alpha(c: Color, a: f32) -> Color { Color(c.0, c.1, c.2, c.3 * a) }
+
+/// A container with an optional background. Panels with backgrounds take the presses on them
+pub struct Panel { style: FlexStyle, background: Option<Color>, radius: f32 }
+impl Panel
+{
+    pub fn new(style: FlexStyle, background: Option<Color>) -> Self { Panel { style, background, radius: 0.0 } }
+    pub fn rounded(self, radius: f32) -> Self { Panel { radius, .. self } }
+}
+impl Widget for Panel
+{
+    fn style(&self, _cx: &UiContext) -> FlexStyle { self.style }
+    fn paint(&self, rect: LayoutRect, _cx: &UiContext, list: &mut DisplayList)
+    {
+        if let Some(c) = self.background { list.rect(rect, c, self.radius); }
+    }
+    fn handle(&mut self, event: &InputEvent, _rect: LayoutRect) -> Reaction
+    {
+        match *event
+        {
+            InputEvent::PointerDown(..) | InputEvent::PointerUp(..) if self.background.is_some() => Reaction::Handled,
+            _ => Reaction::Ignored
+        }
+    }
+    any_impl!();
+}
+
+/// Text laid out with the fonts of the tree. Lines are broken at the width if given
+pub struct Label
+{
+    text: String, size: Option<f32>, width: Option<f32>, max_lines: Option<usize>, align: TextAlign, color: Option<Color>
+}
+impl Label
+{
+    pub fn new(text: &str) -> Self { Label { text: text.to_owned(), size: None, width: None, max_lines: None, align: TextAlign::Left, color: None } }
+    /// Pixels per em(the size of the theme by default)
+    pub fn size(self, size: f32) -> Self { Label { size: Some(size), .. self } }
+    pub fn width(self, width: f32) -> Self { Label { width: Some(width), .. self } }
+    pub fn max_lines(self, lines: usize) -> Self { Label { max_lines: Some(lines), .. self } }
+    pub fn align(self, align: TextAlign) -> Self { Label { align, .. self } }
+    pub fn color(self, color: Color) -> Self { Label { color: Some(color), .. self } }
+    pub fn text(&self) -> &str { &self.text }
+    pub fn set_text(&mut self, text: &str) { self.text = text.to_owned(); }
+
+    fn text_style(&self, cx: &UiContext) -> TextStyle
+    {
+        let mut style = TextStyle::new(self.size.unwrap_or(cx.theme.font_size)).align(self.align);
+        style.max_width = self.width;
+        style.max_lines = self.max_lines;
+        style
+    }
+}
+impl Widget for Label
+{
+    fn measure(&self, cx: &UiContext) -> Size2F { cx.layout_text(&self.text, &self.text_style(cx)).size }
+    fn paint(&self, rect: LayoutRect, cx: &UiContext, list: &mut DisplayList)
+    {
+        let style = self.text_style(cx);
+        list.text(cx.layout_text(&self.text, &style), rect.origin, style.size, self.color.unwrap_or(cx.theme.text));
+    }
+    any_impl!();
+}
+
+/// A distance field image(e.g. logos and icons) at a fixed size
+pub struct Image { texture: Rc<TextureBinding>, size: Size2F, color: Color }
+impl Image
+{
+    pub fn new(texture: Rc<TextureBinding>, size: Size2F, color: Color) -> Self { Image { texture, size, color } }
+}
+impl Widget for Image
+{
+    fn measure(&self, _cx: &UiContext) -> Size2F { self.size }
+    fn paint(&self, rect: LayoutRect, _cx: &UiContext, list: &mut DisplayList)
+    {
+        list.image(self.texture.clone(), rect, SdfStyle::new(self.color));
+    }
+    any_impl!();
+}
+
+/// Press tracking of buttons, checkboxes and items: clicked when released on the pressed place
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Press { tracking: bool, inside: bool }
+impl Press
+{
+    /// Some(whether clicked) when released
+    fn handle(&mut self, event: &InputEvent, rect: &LayoutRect) -> (Reaction, Option<bool>)
+    {
+        match *event
+        {
+            InputEvent::PointerDown(..) => { *self = Press { tracking: true, inside: true }; (Reaction::Repaint, None) },
+            InputEvent::PointerMove(p) if self.tracking =>
+            {
+                let inside = rect.contains(p);
+                let changed = inside != self.inside;
+                self.inside = inside;
+                (if changed { Reaction::Repaint } else { Reaction::Handled }, None)
+            },
+            InputEvent::PointerUp(p, _) if self.tracking =>
+            {
+                let clicked = self.inside && rect.contains(p);
+                *self = Press::default();
+                (Reaction::Repaint, Some(clicked))
+            },
+            _ => (Reaction::Ignored, None)
+        }
+    }
+    fn pressed(&self) -> bool { self.tracking && self.inside }
+}
+
+pub struct Button { label: String, press: Press, enabled: bool }
+impl Button
+{
+    pub fn new(label: &str) -> Self { Button { label: label.to_owned(), press: Press::default(), enabled: true } }
+    pub fn set_label(&mut self, label: &str) { self.label = label.to_owned(); }
+    pub fn set_enabled(&mut self, enabled: bool) { self.enabled = enabled; self.press = Press::default(); }
+    pub fn is_pressed(&self) -> bool { self.press.pressed() }
+}
+impl Widget for Button
+{
+    fn style(&self, _cx: &UiContext) -> FlexStyle { FlexStyle::row().padding(Edges::symmetric(12.0, 8.0)) }
+    fn measure(&self, cx: &UiContext) -> Size2F { cx.layout_text(&self.label, &TextStyle::new(cx.theme.font_size)).size }
+    fn paint(&self, rect: LayoutRect, cx: &UiContext, list: &mut DisplayList)
+    {
+        let t = cx.theme;
+        let background = if !self.enabled { t.separator } else if self.press.pressed() { with_alpha(t.accent, 0.75) } else { t.accent };
+        list.rect(rect, background, t.radius);
+        let layout = cx.layout_text(&self.label, &TextStyle::new(t.font_size));
+        let origin = centered(&layout, &rect);
+        list.text(layout, origin, t.font_size, t.text_on_accent);
+    }
+    fn handle(&mut self, event: &InputEvent, rect: LayoutRect) -> Reaction
+    {
+        if !self.enabled { return Reaction::Ignored; }
+        match self.press.handle(event, &rect)
+        {
+            (_, Some(true)) => Reaction::Notify(Notice::Clicked),
+            (r, _) => r
+        }
+    }
+    fn pointer_cancelled(&mut self) -> Reaction { self.press = Press::default(); Reaction::Repaint }
+    any_impl!();
+}
+
+pub struct Checkbox { label: String, checked: bool, press: Press }
+impl Checkbox
+{
+    /// Edge of the box in em
+    const BOX: f32 = 1.125;
+
+    pub fn new(label: &str, checked: bool) -> Self { Checkbox { label: label.to_owned(), checked, press: Press::default() } }
+    pub fn is_checked(&self) -> bool { self.checked }
+    pub fn set_checked(&mut self, checked: bool) { self.checked = checked; }
+}
+impl Widget for Checkbox
+{
+    fn measure(&self, cx: &UiContext) -> Size2F
+    {
+        let b = Self::BOX * cx.theme.font_size;
+        let text = cx.layout_text(&self.label, &TextStyle::new(cx.theme.font_size)).size;
+        Size2F(b + cx.theme.font_size * 0.5 + text.0, b.max(text.1))
+    }
+    fn paint(&self, r: LayoutRect, cx: &UiContext, list: &mut DisplayList)
+    {
+        let t = cx.theme;
+        let b = Self::BOX * t.font_size;
+        let top = r.origin.1 + (r.size.1 - b) * 0.5;
+        list.rect(rect(r.origin.0, top, b, b), if self.checked { t.accent } else { t.separator }, t.radius * 0.5);
+        let inset = if self.checked { b * 0.3 } else { 2.0 };
+        let inner = if self.checked { t.text_on_accent } else if self.press.pressed() { t.pressed } else { t.surface };
+        list.rect(rect(r.origin.0 + inset, top + inset, b - 2.0 * inset, b - 2.0 * inset), inner, t.radius * 0.25);
+        let layout = cx.layout_text(&self.label, &TextStyle::new(t.font_size));
+        let origin = Point2F(r.origin.0 + b + t.font_size * 0.5, r.origin.1 + (r.size.1 - layout.size.1) * 0.5);
+        list.text(layout, origin, t.font_size, t.text);
+    }
+    fn handle(&mut self, event: &InputEvent, rect: LayoutRect) -> Reaction
+    {
+        match self.press.handle(event, &rect)
+        {
+            (_, Some(true)) => { self.checked = !self.checked; Reaction::Notify(Notice::Toggled(self.checked)) },
+            (r, _) => r
+        }
+    }
+    fn pointer_cancelled(&mut self) -> Reaction { self.press = Press::default(); Reaction::Repaint }
+    any_impl!();
+}
+
+/// Single-line text input editing a `TextField`. Takes the text input(`InputEvent::Text`) while focused; presses focus it
+pub struct TextInput { field: TextField, placeholder: String, width: Option<f32>, focused: bool, clipboard: Box<Clipboard> }
+impl TextInput
+{
+    /// Width of the field in em if not given
+    const WIDTH: f32 = 16.0;
+    const CARET_WIDTH: f32 = 1.5;
+
+    pub fn new(field: TextField, placeholder: &str) -> Self
+    {
+        TextInput { field, placeholder: placeholder.to_owned(), width: None, focused: false, clipboard: box LocalClipboard::default() }
+    }
+    pub fn width(self, width: f32) -> Self { TextInput { width: Some(width), .. self } }
+    pub fn clipboard(self, clipboard: Box<Clipboard>) -> Self { TextInput { clipboard, .. self } }
+    pub fn field(&self) -> &TextField { &self.field }
+    pub fn field_mut(&mut self) -> &mut TextField { &mut self.field }
+    pub fn is_focused(&self) -> bool { self.focused }
+
+    /// Left end of the character at the byte offset of the display text
+    fn x_at(cx: &UiContext, shown: &str, offset: usize, style: &TextStyle) -> f32
+    {
+        // a sentinel character after the prefix: trailing spaces are not a part of the line width
+        let layout = cx.layout_text(&(shown[.. offset].to_owned() + "|"), style);
+        layout.glyphs.last().map(|g| g.position.0).unwrap_or(0.0)
+    }
+    /// Byte offset in the display text of an offset in the text, without a composition
+    fn display_offset(&self, offset: usize) -> usize
+    {
+        if self.field.is_masked() { self.field.text()[.. offset].chars().count() * TextField::MASK.len_utf8() } else { offset }
+    }
+}
+impl Widget for TextInput
+{
+    fn style(&self, _cx: &UiContext) -> FlexStyle { FlexStyle::row().padding(Edges::symmetric(8.0, 6.0)) }
+    fn measure(&self, cx: &UiContext) -> Size2F
+    {
+        let line = cx.layout_text("M", &TextStyle::new(cx.theme.font_size)).size.1;
+        Size2F(self.width.unwrap_or(Self::WIDTH * cx.theme.font_size), line)
+    }
+    fn paint(&self, r: LayoutRect, cx: &UiContext, list: &mut DisplayList)
+    {
+        let t = cx.theme;
+        let border = if self.focused { 2.0 } else { 1.0 };
+        list.rect(r, if self.focused { t.accent } else { t.separator }, t.radius);
+        list.rect(rect(r.origin.0 + border, r.origin.1 + border, r.size.0 - 2.0 * border, r.size.1 - 2.0 * border), t.surface, t.radius - border);
+        let style = TextStyle::new(t.font_size);
+        let line = cx.layout_text("M", &style).size.1;
+        let inner = rect(r.origin.0 + 8.0, r.origin.1 + (r.size.1 - line) * 0.5, (r.size.0 - 16.0).max(0.0), line);
+        let (shown, cursor, composing) = self.field.display_text();
+        if shown.is_empty()
+        {
+            list.text(cx.layout_text(&self.placeholder, &style), inner.origin, t.font_size, t.separator);
+            if self.focused { list.rect(rect(inner.origin.0, inner.origin.1, Self::CARET_WIDTH, line), t.text, 0.0); }
+            return;
+        }
+        // scrolled to keep the caret visible
+        let caret = Self::x_at(cx, &shown, cursor, &style);
+        let scroll = (caret + Self::CARET_WIDTH - inner.size.0).max(0.0);
+        let x = |offset| inner.origin.0 - scroll + Self::x_at(cx, &shown, offset, &style);
+        list.push_clip(inner);
+        let selection = self.field.selection();
+        if composing.is_none() && selection.start != selection.end
+        {
+            let (x0, x1) = (x(self.display_offset(selection.start)), x(self.display_offset(selection.end)));
+            list.rect(rect(x0, inner.origin.1, x1 - x0, line), t.pressed, 0.0);
+        }
+        list.text(cx.layout_text(&shown, &style), Point2F(inner.origin.0 - scroll, inner.origin.1), t.font_size, t.text);
+        if let Some(c) = composing
+        {
+            let (x0, x1) = (x(c.start), x(c.end));
+            list.rect(rect(x0, inner.bottom() - 1.0, x1 - x0, 1.0), t.text, 0.0);
+        }
+        if self.focused { list.rect(rect(inner.origin.0 - scroll + caret, inner.origin.1, Self::CARET_WIDTH, line), t.text, 0.0); }
+        list.pop_clip();
+    }
+    fn handle(&mut self, event: &InputEvent, _rect: LayoutRect) -> Reaction
+    {
+        match *event
+        {
+            InputEvent::PointerDown(..) => Reaction::Repaint,
+            InputEvent::PointerUp(..) => Reaction::Handled,
+            InputEvent::Text(ref e) => match self.field.handle(e, &mut *self.clipboard)
+            {
+                EditResponse::Changed => Reaction::Notify(Notice::Edited),
+                EditResponse::Submitted => Reaction::Notify(Notice::Submitted),
+                // the caret, the selection or the composition may be moved
+                EditResponse::Unchanged => Reaction::Repaint
+            },
+            _ => Reaction::Ignored
+        }
+    }
+    fn focus_changed(&mut self, focused: bool) -> Reaction { self.focused = focused; Reaction::Repaint }
+    any_impl!();
+}
+
+/// Rows of text with a single selection. Only the rows in the clip are painted, so long lists go in scroll views
+pub struct ListView { items: Vec<String>, row_height: f32, selected: Option<usize>, pressed: Option<usize> }
+impl ListView
+{
+    pub fn new(items: Vec<String>) -> Self { ListView { items, row_height: 40.0, selected: None, pressed: None } }
+    pub fn row_height(self, row_height: f32) -> Self { ListView { row_height, .. self } }
+    pub fn items(&self) -> &[String] { &self.items }
+    pub fn set_items(&mut self, items: Vec<String>) { self.items = items; self.selected = None; self.pressed = None; }
+    pub fn selected(&self) -> Option<usize> { self.selected }
+    pub fn select(&mut self, index: Option<usize>) { self.selected = index; }
+
+    fn row_at(&self, rect: &LayoutRect, p: Point2F) -> Option<usize>
+    {
+        if !rect.contains(p) { return None; }
+        let n = ((p.1 - rect.origin.1) / self.row_height) as usize;
+        if n < self.items.len() { Some(n) } else { None }
+    }
+}
+impl Widget for ListView
+{
+    fn measure(&self, cx: &UiContext) -> Size2F
+    {
+        let style = TextStyle::new(cx.theme.font_size);
+        let width = self.items.iter().map(|i| cx.layout_text(i, &style).size.0).fold(0.0, f32::max);
+        Size2F(width + cx.theme.font_size * 1.5, self.items.len() as f32 * self.row_height)
+    }
+    fn paint(&self, r: LayoutRect, cx: &UiContext, list: &mut DisplayList)
+    {
+        let t = cx.theme;
+        let row = self.row_height;
+        let visible = list.clip().unwrap_or(r);
+        let first = ((visible.origin.1 - r.origin.1) / row).max(0.0) as usize;
+        let last = (((visible.bottom() - r.origin.1) / row).ceil().max(0.0) as usize).min(self.items.len());
+        let style = TextStyle::new(t.font_size);
+        for n in first .. last
+        {
+            let y = r.origin.1 + n as f32 * row;
+            if self.selected == Some(n) || self.pressed == Some(n) { list.rect(rect(r.origin.0, y, r.size.0, row), t.pressed, 0.0); }
+            let layout = cx.layout_text(&self.items[n], &style);
+            let origin = Point2F(r.origin.0 + t.font_size * 0.75, y + (row - layout.size.1) * 0.5);
+            list.text(layout, origin, t.font_size, t.text);
+            list.rect(rect(r.origin.0, y + row - 1.0, r.size.0, 1.0), t.separator, 0.0);
+        }
+    }
+    fn handle(&mut self, event: &InputEvent, rect: LayoutRect) -> Reaction
+    {
+        match *event
+        {
+            InputEvent::PointerDown(p, _) => { self.pressed = self.row_at(&rect, p); Reaction::Repaint },
+            InputEvent::PointerMove(_) => Reaction::Handled,
+            InputEvent::PointerUp(p, _) => match (self.pressed.take(), self.row_at(&rect, p))
+            {
+                (Some(a), Some(b)) if a == b => { self.selected = Some(a); Reaction::Notify(Notice::Selected(a)) },
+                _ => Reaction::Repaint
+            },
+            _ => Reaction::Ignored
+        }
+    }
+    fn pointer_cancelled(&mut self) -> Reaction { self.pressed = None; Reaction::Repaint }
+    any_impl!();
+}
+
+/// Vertical scrolling of the children by dragging, continued by inertia after the release
+pub struct ScrollView
+{
+    offset: f32, velocity: f32, viewport: f32, content: f32,
+    /// Pointer y and offset when pressed
+    press: Option<(f32, f32)>, dragging: bool,
+    /// Distance dragged since the last update(for the velocity)
+    dragged: f32
+}
+impl ScrollView
+{
+    /// Distance the pointer moves before a press becomes a drag
+    const SLOP: f32 = 8.0;
+    /// Rate(1/s) the inertia decays at
+    const FRICTION: f32 = 4.0;
+    const MIN_VELOCITY: f32 = 5.0;
+
+    pub fn new() -> Self { ScrollView { offset: 0.0, velocity: 0.0, viewport: 0.0, content: 0.0, press: None, dragging: false, dragged: 0.0 } }
+    pub fn offset(&self) -> f32 { self.offset }
+    pub fn max_offset(&self) -> f32 { (self.content - self.viewport).max(0.0) }
+    pub fn scroll_to(&mut self, offset: f32) { self.offset = offset.max(0.0).min(self.max_offset()); self.velocity = 0.0; }
+    /// Starts scrolling by inertia(pixels per second)
+    pub fn fling(&mut self, velocity: f32) { self.velocity = velocity; }
+
+    fn drag_to(&mut self, y: f32) -> Reaction
+    {
+        let (y0, o0) = match self.press { Some(p) => p, None => return Reaction::Ignored };
+        if !self.dragging && (y - y0).abs() <= Self::SLOP { return Reaction::Handled; }
+        self.dragging = true;
+        let offset = (o0 + y0 - y).max(0.0).min(self.max_offset());
+        self.dragged += offset - self.offset;
+        self.offset = offset;
+        Reaction::Repaint
+    }
+}
+impl Widget for ScrollView
+{
+    fn style(&self, _cx: &UiContext) -> FlexStyle { FlexStyle::column().grow(1.0).basis(Dimension::Px(0.0)) }
+    fn arranged(&mut self, rect: LayoutRect, children: Size2F)
+    {
+        self.viewport = rect.size.1;
+        self.content = children.1;
+        self.offset = self.offset.min(self.max_offset());
+    }
+    fn paint(&self, _rect: LayoutRect, _cx: &UiContext, _list: &mut DisplayList) {}
+    /// Scroll indicator
+    fn paint_over(&self, r: LayoutRect, cx: &UiContext, list: &mut DisplayList)
+    {
+        if self.content <= self.viewport || self.content <= 0.0 { return; }
+        let height = self.viewport * self.viewport / self.content;
+        let y = r.origin.1 + self.offset / self.content * self.viewport;
+        list.rect(rect(r.right() - 5.0, y, 3.0, height), cx.theme.separator, 1.5);
+    }
+    fn clips_children(&self) -> bool { true }
+    fn child_offset(&self) -> (f32, f32) { (0.0, -self.offset) }
+
+    fn intercept(&mut self, event: &InputEvent, _rect: LayoutRect) -> bool
+    {
+        match *event
+        {
+            // a press stops the inertia
+            InputEvent::PointerDown(p, _) => { self.press = Some((p.1, self.offset)); self.velocity = 0.0; false },
+            InputEvent::PointerMove(p) => self.press.map(|(y0, _)| (p.1 - y0).abs() > Self::SLOP).unwrap_or(false),
+            InputEvent::PointerUp(..) => { self.press = None; false },
+            _ => false
+        }
+    }
+    fn handle(&mut self, event: &InputEvent, _rect: LayoutRect) -> Reaction
+    {
+        match *event
+        {
+            InputEvent::PointerDown(p, _) => { self.press = Some((p.1, self.offset)); self.velocity = 0.0; Reaction::Handled },
+            InputEvent::PointerMove(p) => self.drag_to(p.1),
+            InputEvent::PointerUp(p, _) if self.press.is_some() =>
+            {
+                let r = self.drag_to(p.1);
+                self.press = None;
+                self.dragging = false;
+                if r == Reaction::Ignored { Reaction::Handled } else { r }
+            },
+            // scrolled to the end: the outer views may take the rest
+            InputEvent::Wheel { delta: (_, dy), .. } =>
+            {
+                let offset = (self.offset + dy).max(0.0).min(self.max_offset());
+                if offset == self.offset { return Reaction::Ignored; }
+                self.offset = offset;
+                self.velocity = 0.0;
+                Reaction::Repaint
+            },
+            _ => Reaction::Ignored
+        }
+    }
+    fn update(&mut self, dt: f32) -> bool
+    {
+        if dt <= 0.0 { return false; }
+        if self.dragging
+        {
+            // smoothed velocity of the drag, kept when released
+            self.velocity = self.velocity * 0.5 + self.dragged / dt * 0.5;
+            self.dragged = 0.0;
+            return false;
+        }
+        if self.velocity == 0.0 { return false; }
+        let offset = self.offset + self.velocity * dt;
+        self.offset = offset.max(0.0).min(self.max_offset());
+        self.velocity *= (-Self::FRICTION * dt).exp();
+        if self.offset != offset || self.velocity.abs() < Self::MIN_VELOCITY { self.velocity = 0.0; }
+        true
+    }
+    any_impl!();
+}
+
+/// Tabs of equal widths with an indicator under the selected one
+pub struct TabBar { tabs: Vec<String>, selected: usize, pressed: Option<usize> }
+impl TabBar
+{
+    /// Height of the bar in em
+    const HEIGHT: f32 = 2.75;
+
+    pub fn new(tabs: Vec<String>) -> Self { TabBar { tabs, selected: 0, pressed: None } }
+    pub fn selected(&self) -> usize { self.selected }
+    pub fn select(&mut self, index: usize) { self.selected = index.min(self.tabs.len().saturating_sub(1)); }
+
+    fn tab_at(&self, r: &LayoutRect, p: Point2F) -> Option<usize>
+    {
+        if !r.contains(p) || self.tabs.is_empty() { return None; }
+        Some((((p.0 - r.origin.0) / r.size.0 * self.tabs.len() as f32) as usize).min(self.tabs.len() - 1))
+    }
+}
+impl Widget for TabBar
+{
+    fn measure(&self, cx: &UiContext) -> Size2F
+    {
+        let style = TextStyle::new(cx.theme.font_size);
+        let widest = self.tabs.iter().map(|t| cx.layout_text(t, &style).size.0).fold(0.0, f32::max);
+        Size2F((widest + cx.theme.font_size * 1.5) * self.tabs.len() as f32, Self::HEIGHT * cx.theme.font_size)
+    }
+    fn paint(&self, r: LayoutRect, cx: &UiContext, list: &mut DisplayList)
+    {
+        let t = cx.theme;
+        list.rect(r, t.surface, 0.0);
+        let w = r.size.0 / self.tabs.len().max(1) as f32;
+        for (n, label) in self.tabs.iter().enumerate()
+        {
+            let tab = rect(r.origin.0 + w * n as f32, r.origin.1, w, r.size.1);
+            if self.pressed == Some(n) { list.rect(tab, t.pressed, 0.0); }
+            let layout = cx.layout_text(label, &TextStyle::new(t.font_size));
+            let origin = centered(&layout, &tab);
+            list.text(layout, origin, t.font_size, if n == self.selected { t.accent } else { t.text });
+        }
+        list.rect(rect(r.origin.0, r.bottom() - 1.0, r.size.0, 1.0), t.separator, 0.0);
+        list.rect(rect(r.origin.0 + w * self.selected as f32, r.bottom() - 3.0, w, 3.0), t.accent, 0.0);
+    }
+    fn handle(&mut self, event: &InputEvent, rect: LayoutRect) -> Reaction
+    {
+        match *event
+        {
+            InputEvent::PointerDown(p, _) => { self.pressed = self.tab_at(&rect, p); Reaction::Repaint },
+            InputEvent::PointerMove(_) => Reaction::Handled,
+            InputEvent::PointerUp(p, _) => match (self.pressed.take(), self.tab_at(&rect, p))
+            {
+                (Some(a), Some(b)) if a == b => { self.selected = a; Reaction::Notify(Notice::Selected(a)) },
+                _ => Reaction::Repaint
+            },
+            _ => Reaction::Ignored
+        }
+    }
+    fn pointer_cancelled(&mut self) -> Reaction { self.pressed = None; Reaction::Repaint }
+    any_impl!();
+}
+
+/// Root of the modal layer(`WidgetTree::open_modal`): dims the screen and centers its children.
+/// A press outside the children or the back key dismisses the dialog
+pub struct Dialog { pressed: bool }
+impl Dialog
+{
+    pub fn new() -> Self { Dialog { pressed: false } }
+}
+impl Widget for Dialog
+{
+    fn style(&self, _cx: &UiContext) -> FlexStyle
+    {
+        FlexStyle::column().justify(Justify::Center).align_items(Align::Center).padding(Edges::all(24.0))
+    }
+    fn paint(&self, r: LayoutRect, cx: &UiContext, list: &mut DisplayList) { list.rect(r, cx.theme.scrim, 0.0); }
+    fn handle(&mut self, event: &InputEvent, _rect: LayoutRect) -> Reaction
+    {
+        match *event
+        {
+            InputEvent::PointerDown(..) => { self.pressed = true; Reaction::Handled },
+            InputEvent::PointerUp(..) if self.pressed => { self.pressed = false; Reaction::Notify(Notice::Dismissed) },
+            ref e if e.is_back() => Reaction::Notify(Notice::Dismissed),
+            // nothing under the dialog takes the input
+            _ => Reaction::Handled
+        }
+    }
+    fn pointer_cancelled(&mut self) -> Reaction { self.pressed = false; Reaction::Ignored }
+    any_impl!();
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use ui::widget::tests::tree;
+    use input::PointerButton;
+    use ui::{Notification, DrawItem};
+
+    #[test] fn list_tabs_and_checkbox()
+    {
+        let mut t = tree();
+        let root = t.set_root(box Panel::new(FlexStyle::column(), None));
+        let tabs = t.add(root, box TabBar::new(vec!["月".to_owned(), "火".to_owned(), "水".to_owned()]));
+        let check = t.add(root, box Checkbox::new("Notify", false));
+        let scroll = t.add(root, box ScrollView::new());
+        let list = t.add(scroll, box ListView::new((0 .. 20).map(|n| format!("Lecture {}", n)).collect()));
+        t.resize(Size2U(300, 200));
+        let items = t.paint().items;
+        // rows of 40 pixels under the tab bar(44) and the checkbox(18): 4 rows are partially visible in 138 pixels
+        assert_eq!(t.rect(scroll).origin.1, 62.0);
+        let texts = items.iter().filter(|i| match **i { DrawItem::Text { .. } => true, _ => false }).count();
+        assert_eq!(texts, 3 + 1 + 4);
+
+        let click = |t: &mut ::ui::WidgetTree, p: Point2F|
+        {
+            t.dispatch(&InputEvent::PointerDown(p, PointerButton::Primary));
+            t.dispatch(&InputEvent::PointerUp(p, PointerButton::Primary)).notifications
+        };
+        assert_eq!(click(&mut t, Point2F(250.0, 20.0)), vec![Notification { widget: tabs, notice: Notice::Selected(2) }]);
+        assert_eq!(click(&mut t, Point2F(5.0, 55.0)), vec![Notification { widget: check, notice: Notice::Toggled(true) }]);
+        assert_eq!(click(&mut t, Point2F(100.0, 62.0 + 85.0)), vec![Notification { widget: list, notice: Notice::Selected(2) }]);
+        assert_eq!(t.get::<ListView>(list).unwrap().selected(), Some(2));
+        assert!(t.get::<Checkbox>(check).unwrap().is_checked());
+        // released on another row
+        t.dispatch(&InputEvent::PointerDown(Point2F(100.0, 70.0), PointerButton::Primary));
+        assert!(t.dispatch(&InputEvent::PointerUp(Point2F(305.0, 70.0), PointerButton::Primary)).notifications.is_empty());
+    }
+
+    #[test] fn inertial_scrolling()
+    {
+        let mut t = tree();
+        let root = t.set_root(box Panel::new(FlexStyle::column(), None));
+        let scroll = t.add(root, box ScrollView::new());
+        t.add(scroll, box ListView::new((0 .. 50).map(|n| n.to_string()).collect()));
+        t.resize(Size2U(100, 400));
+        t.paint();
+        // dragged upwards by 100 pixels over 0.1 seconds
+        t.dispatch(&InputEvent::PointerDown(Point2F(50.0, 300.0), PointerButton::Primary));
+        for n in 1 .. 11
+        {
+            t.dispatch(&InputEvent::PointerMove(Point2F(50.0, 300.0 - n as f32 * 10.0)));
+            t.update(0.01);
+            t.paint();
+        }
+        t.dispatch(&InputEvent::PointerUp(Point2F(50.0, 200.0), PointerButton::Primary));
+        assert_eq!(t.get::<ScrollView>(scroll).unwrap().offset(), 100.0);
+        let mut frames = 0;
+        while t.update(1.0 / 60.0) { t.paint(); frames += 1; }
+        let s = t.get::<ScrollView>(scroll).unwrap();
+        // about 1000 px/s decaying at 4/s travels 250 pixels
+        assert!(frames > 30 && s.offset() > 300.0 && s.offset() < 370.0, "{} frames, offset {}", frames, s.offset());
+
+        // flings stop at the end
+        t.get_mut::<ScrollView>(scroll).unwrap().fling(1.0e5);
+        t.update(0.1);
+        t.paint();
+        assert_eq!(t.get::<ScrollView>(scroll).unwrap().offset(), 50.0 * 40.0 - 400.0);
+        assert!(!t.update(0.1));
+    }
+}
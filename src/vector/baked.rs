@@ -0,0 +1,320 @@
+//! Baked vector images: compact binary form of the paths of an SVG document with their paints(written by `src/bin/bake_paths.rs`)
+//!
+//! Layout(little endian):
+//! - header: b"DCVP", u16 version, f32 width, f32 height, u32 shape count
+//! - shape: u8 flags(1 = even-odd fill rule, 2 = filled with a transparent paint), fill paint, stroke paint, stroke style(only if stroked), u32 command count, commands
+//! - paint: u8 tag(0 = none, 1 = solid color, 2 = linear gradient, 3 = radial gradient) followed by
+//!   - solid color: [u8; 4] color(RGBA8)
+//!   - gradients: the geometry(linear: f32 x1, y1, x2, y2 / radial: f32 cx, cy, r, fx, fy), u8 spread method(0 = pad, 1 = reflect, 2 = repeat),
+//!     f32 a, b, c, d, e, f(gradient transform), u32 stop count, stops(f32 offset, [u8; 4] color)
+//! - stroke style: f32 width, u8 join(0 = miter, 1 = round, 2 = bevel), u8 cap(0 = butt, 1 = round, 2 = square), f32 miter limit,
+//!   u32 dash count, f32 dashes, f32 dash offset
+//! - command: u8 tag(0 = MoveTo, 1 = LineTo, 2 = QuadTo, 3 = CubicTo, 4 = Close) followed by its points as f32 pairs
+//!
+//! Paths are in the image space with absolute coordinates; transforms are applied and arcs are converted into cubic curves.
+//! Paints are resolved into the image space with the opacities applied.
+
+use metrics::*;
+use color::Color;
+use super::{Path, PathCommand, FillRule, SvgImage, Paint, Mesh, Transform2D, StrokeStyle, LineJoin, LineCap};
+use super::{Gradient, GradientShape, GradientStop, SpreadMethod};
+use std::io::prelude::*;
+use std::io::{Result as IOResult, Error as IOError, ErrorKind, BufReader, BufWriter};
+use std::path::Path as FilePath;
+use std::fs::File;
+
+const MAGIC: &'static [u8; 4] = b"DCVP";
+const VERSION: u16 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BakedShape
+{
+    pub path: Path, pub fill: Paint, pub fill_rule: FillRule, pub stroke: Paint,
+    /// The fill is hit by the queries even if its paint is transparent(resolved into `Paint::None`), as `Shape::fill_contains` does
+    pub has_fill: bool,
+    /// In the image space: lengths are scaled by the mean scale of the transform of the shape
+    pub stroke_style: StrokeStyle
+}
+impl BakedShape
+{
+    /// Stroke geometry mapped by `t`. Tolerance is in the mapped coordinates
+    pub fn stroke_mesh(&self, t: &Transform2D, tolerance: f32) -> Mesh
+    {
+        self.path.stroke(&self.stroke_style, tolerance / t.mean_scale().max(::std::f32::EPSILON)).transformed(t)
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct BakedImage { pub size: Size2F, pub shapes: Vec<BakedShape> }
+impl BakedImage
+{
+    /// Collects all shapes in drawing order, including the ones painting nothing(kept for the queries of the geometry)
+    pub fn from_svg(image: &SvgImage) -> Self
+    {
+        let mut shapes = Vec::new();
+        image.visit(|s, t, opacity|
+        {
+            let (opacity, scale) = (s.style.opacity * opacity, t.mean_scale());
+            let stroke_style = StrokeStyle
+            {
+                width: s.style.stroke_style.width * scale, dash_array: s.style.stroke_style.dash_array.iter().map(|d| d * scale).collect(),
+                dash_offset: s.style.stroke_style.dash_offset * scale, .. s.style.stroke_style.clone()
+            };
+            shapes.push(BakedShape
+            {
+                path: s.path.transformed(t), fill_rule: s.style.fill_rule, stroke_style,
+                fill: s.style.fill.resolve(&s.path, t, s.style.fill_opacity * opacity).unwrap_or(Paint::None),
+                stroke: s.style.stroke.resolve(&s.path, t, s.style.stroke_opacity * opacity).unwrap_or(Paint::None),
+                has_fill: s.style.fill != Paint::None
+            });
+        });
+        BakedImage { size: image.size, shapes }
+    }
+    /// Tessellates fills and strokes of all shapes in drawing order, as `SvgImage::meshes` does
+    pub fn meshes(&self, transform: &Transform2D, tolerance: f32) -> Vec<(Mesh, Paint)>
+    {
+        let mut meshes = Vec::new();
+        for s in &self.shapes
+        {
+            if let Some(paint) = s.fill.resolve(&s.path, transform, 1.0)
+            {
+                let mesh = s.path.transformed(transform).fill(tolerance, s.fill_rule);
+                if mesh.triangle_count() > 0 { meshes.push((mesh, paint)); }
+            }
+            if let Some(paint) = s.stroke.resolve(&s.path, transform, 1.0)
+            {
+                let mesh = s.stroke_mesh(transform, tolerance);
+                if mesh.triangle_count() > 0 { meshes.push((mesh, paint)); }
+            }
+        }
+        meshes
+    }
+
+    pub fn load<P: AsRef<FilePath>>(path: P) -> IOResult<Self> { Self::read(&mut BufReader::new(File::open(path)?)) }
+    pub fn save<P: AsRef<FilePath>>(&self, path: P) -> IOResult<()> { self.write(&mut BufWriter::new(File::create(path)?)) }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> IOResult<()>
+    {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION as u8, (VERSION >> 8) as u8])?;
+        write_f32(w, self.size.x())?; write_f32(w, self.size.y())?;
+        write_u32(w, self.shapes.len() as _)?;
+        for s in &self.shapes
+        {
+            let transparent_fill = s.has_fill && s.fill == Paint::None;
+            w.write_all(&[if s.fill_rule == FillRule::EvenOdd { 1 } else { 0 } | if transparent_fill { 2 } else { 0 }])?;
+            write_paint(w, &s.fill)?; write_paint(w, &s.stroke)?;
+            if s.stroke != Paint::None { write_stroke_style(w, &s.stroke_style)?; }
+            let path = s.path.arcs_to_cubics();
+            write_u32(w, path.commands.len() as _)?;
+            for c in &path.commands
+            {
+                let (tag, points) = match *c
+                {
+                    PathCommand::MoveTo(p) => (0, vec![p]),
+                    PathCommand::LineTo(p) => (1, vec![p]),
+                    PathCommand::QuadTo(c, p) => (2, vec![c, p]),
+                    PathCommand::CubicTo(c1, c2, p) => (3, vec![c1, c2, p]),
+                    PathCommand::ArcTo { .. } => unreachable!("arcs are converted before writing"),
+                    PathCommand::Close => (4, vec![])
+                };
+                w.write_all(&[tag])?; write_points(w, &points)?;
+            }
+        }
+        w.flush()
+    }
+    pub fn read<R: Read>(r: &mut R) -> IOResult<Self>
+    {
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC { return Err(IOError::new(ErrorKind::InvalidData, "Not a baked vector image")); }
+        let mut version = [0u8; 2];
+        r.read_exact(&mut version)?;
+        let version = version[0] as u16 | (version[1] as u16) << 8;
+        if version != VERSION
+        {
+            return Err(IOError::new(ErrorKind::InvalidData, format!("Unsupported version of baked vector image: {}", version)));
+        }
+        let size = Size2F(read_f32(r)?, read_f32(r)?);
+        let shape_count = read_u32(r)?;
+        let mut shapes = Vec::new();
+        for _ in 0 .. shape_count
+        {
+            let flags = read_u8(r)?;
+            let fill_rule = if (flags & 1) != 0 { FillRule::EvenOdd } else { FillRule::NonZero };
+            let (fill, stroke) = (read_paint(r)?, read_paint(r)?);
+            let has_fill = (flags & 2) != 0 || fill != Paint::None;
+            let stroke_style = if stroke != Paint::None { read_stroke_style(r)? } else { StrokeStyle::default() };
+            let count = read_u32(r)?;
+            let mut path = Path::new();
+            for _ in 0 .. count
+            {
+                match read_u8(r)?
+                {
+                    0 => { path.move_to(read_point(r)?); },
+                    1 => { path.line_to(read_point(r)?); },
+                    2 => { let c = read_point(r)?; path.quad_to(c, read_point(r)?); },
+                    3 => { let (c1, c2) = (read_point(r)?, read_point(r)?); path.cubic_to(c1, c2, read_point(r)?); },
+                    4 => { path.close(); },
+                    t => return Err(IOError::new(ErrorKind::InvalidData, format!("Unknown path command tag: {}", t)))
+                }
+            }
+            shapes.push(BakedShape { path, fill, fill_rule, stroke, has_fill, stroke_style });
+        }
+        Ok(BakedImage { size, shapes })
+    }
+}
+
+fn write_color<W: Write>(w: &mut W, c: Color) -> IOResult<()>
+{
+    let to_u8 = |v: f32| (v.max(0.0).min(1.0) * 255.0).round() as u8;
+    w.write_all(&[to_u8(c.0), to_u8(c.1), to_u8(c.2), to_u8(c.3)])
+}
+fn write_paint<W: Write>(w: &mut W, paint: &Paint) -> IOResult<()>
+{
+    let g = match *paint
+    {
+        Paint::None => return w.write_all(&[0]),
+        Paint::Color(c) => { w.write_all(&[1])?; return write_color(w, c); },
+        Paint::Gradient(ref g) => g
+    };
+    match g.shape
+    {
+        GradientShape::Linear { from, to } => { w.write_all(&[2])?; write_points(w, &[from, to])?; },
+        GradientShape::Radial { center, radius, focus } =>
+        {
+            w.write_all(&[3])?; write_points(w, &[center])?; write_f32(w, radius)?; write_points(w, &[focus])?;
+        }
+    }
+    w.write_all(&[match g.spread { SpreadMethod::Pad => 0, SpreadMethod::Reflect => 1, SpreadMethod::Repeat => 2 }])?;
+    let t = g.gradient_transform();
+    for &v in &[t.a, t.b, t.c, t.d, t.e, t.f] { write_f32(w, v)?; }
+    write_u32(w, g.stops.len() as _)?;
+    for s in &g.stops { write_f32(w, s.offset)?; write_color(w, s.color)?; }
+    Ok(())
+}
+fn write_stroke_style<W: Write>(w: &mut W, style: &StrokeStyle) -> IOResult<()>
+{
+    write_f32(w, style.width)?;
+    w.write_all(&[
+        match style.join { LineJoin::Miter => 0, LineJoin::Round => 1, LineJoin::Bevel => 2 },
+        match style.cap { LineCap::Butt => 0, LineCap::Round => 1, LineCap::Square => 2 }
+    ])?;
+    write_f32(w, style.miter_limit)?;
+    write_u32(w, style.dash_array.len() as _)?;
+    for &d in &style.dash_array { write_f32(w, d)?; }
+    write_f32(w, style.dash_offset)
+}
+fn read_color<R: Read>(r: &mut R) -> IOResult<Color>
+{
+    let mut c = [0u8; 4];
+    r.read_exact(&mut c)?;
+    Ok(Color(c[0] as f32 / 255.0, c[1] as f32 / 255.0, c[2] as f32 / 255.0, c[3] as f32 / 255.0))
+}
+fn read_paint<R: Read>(r: &mut R) -> IOResult<Paint>
+{
+    let shape = match read_u8(r)?
+    {
+        0 => return Ok(Paint::None),
+        1 => return read_color(r).map(Paint::Color),
+        2 => GradientShape::Linear { from: read_point(r)?, to: read_point(r)? },
+        3 => GradientShape::Radial { center: read_point(r)?, radius: read_f32(r)?, focus: read_point(r)? },
+        t => return Err(IOError::new(ErrorKind::InvalidData, format!("Unknown paint tag: {}", t)))
+    };
+    let spread = match read_u8(r)?
+    {
+        0 => SpreadMethod::Pad, 1 => SpreadMethod::Reflect, 2 => SpreadMethod::Repeat,
+        s => return Err(IOError::new(ErrorKind::InvalidData, format!("Unknown spread method: {}", s)))
+    };
+    let transform = Transform2D { a: read_f32(r)?, b: read_f32(r)?, c: read_f32(r)?, d: read_f32(r)?, e: read_f32(r)?, f: read_f32(r)? };
+    let count = read_u32(r)?;
+    let mut g = Gradient::new(shape).spread(spread).transform(transform);
+    for _ in 0 .. count { g.stops.push(GradientStop { offset: read_f32(r)?, color: read_color(r)? }); }
+    Ok(Paint::Gradient(g))
+}
+fn read_stroke_style<R: Read>(r: &mut R) -> IOResult<StrokeStyle>
+{
+    let width = read_f32(r)?;
+    let join = match read_u8(r)? { 0 => LineJoin::Miter, 1 => LineJoin::Round, _ => LineJoin::Bevel };
+    let cap = match read_u8(r)? { 0 => LineCap::Butt, 1 => LineCap::Round, _ => LineCap::Square };
+    let miter_limit = read_f32(r)?;
+    let count = read_u32(r)?;
+    let mut dash_array = Vec::new();
+    for _ in 0 .. count { dash_array.push(read_f32(r)?); }
+    Ok(StrokeStyle { width, join, cap, miter_limit, dash_array, dash_offset: read_f32(r)? })
+}
+
+fn write_u32<W: Write>(w: &mut W, v: u32) -> IOResult<()> { w.write_all(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]) }
+fn write_f32<W: Write>(w: &mut W, v: f32) -> IOResult<()> { write_u32(w, v.to_bits()) }
+fn write_points<W: Write>(w: &mut W, points: &[Point2F]) -> IOResult<()>
+{
+    for p in points { write_f32(w, p.0)?; write_f32(w, p.1)?; }
+    Ok(())
+}
+fn read_u8<R: Read>(r: &mut R) -> IOResult<u8>
+{
+    let mut b = [0u8; 1];
+    r.read_exact(&mut b)?;
+    Ok(b[0])
+}
+fn read_u32<R: Read>(r: &mut R) -> IOResult<u32>
+{
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+}
+fn read_f32<R: Read>(r: &mut R) -> IOResult<f32> { read_u32(r).map(f32::from_bits) }
+fn read_point<R: Read>(r: &mut R) -> IOResult<Point2F> { Ok(Point2F(read_f32(r)?, read_f32(r)?)) }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use vector::GradientUnits;
+
+    #[test] fn roundtrip()
+    {
+        let svg = SvgImage::parse(r##"<svg width="20" height="10">
+            <defs><linearGradient id="g"><stop offset="0" stop-color="#000"/><stop offset="1" stop-color="#fff"/></linearGradient></defs>
+            <g transform="translate(5 0) scale(2)">
+            <circle cx="2.5" cy="2.5" r="2.5" fill="#ff0000" fill-rule="evenodd" opacity="0.5"/>
+            <rect width="2" height="2" fill="none" stroke="#0f0" stroke-linejoin="round" stroke-dasharray="1 2"/>
+            <path d="M0 0 Q 2.5 2.5 5 0 z" fill="url(#g)"/><rect width="1" height="1" fill="none"/></g></svg>"##).unwrap();
+        let baked = BakedImage::from_svg(&svg);
+        assert_eq!(baked.shapes.len(), 4);
+        assert_eq!(baked.shapes[2].path.commands[0], PathCommand::MoveTo(Point2F(5.0, 0.0)));
+        // stroke lengths in the image space
+        assert_eq!((baked.shapes[1].fill.clone(), baked.shapes[1].stroke_style.width), (Paint::None, 2.0));
+        assert_eq!(baked.shapes[1].stroke_style.dash_array, vec![2.0, 4.0]);
+        // the bounding box units are resolved against the untransformed path
+        match baked.shapes[2].fill
+        {
+            Paint::Gradient(ref g) =>
+            {
+                assert_eq!(g.units, GradientUnits::UserSpaceOnUse);
+                assert_eq!(g.color_at(Point2F(15.0, 0.0)), Color(1.0, 1.0, 1.0, 1.0));
+            },
+            ref p => panic!("not a gradient: {:?}", p)
+        }
+
+        let mut bytes = Vec::new();
+        baked.write(&mut bytes).unwrap();
+        let loaded = BakedImage::read(&mut &bytes[..]).unwrap();
+        assert_eq!(loaded.size, Size2F(20.0, 10.0));
+        assert_eq!(loaded.shapes[0].fill_rule, FillRule::EvenOdd);
+        assert_eq!(loaded.shapes[0].fill, Paint::Color(Color(1.0, 0.0, 0.0, 128.0 / 255.0)));
+        // arcs are baked as cubic curves
+        assert!(loaded.shapes[0].path.commands.iter().all(|c| match *c { PathCommand::ArcTo { .. } => false, _ => true }));
+        assert_eq!(loaded.shapes[1].stroke, Paint::Color(Color(0.0, 1.0, 0.0, 1.0)));
+        assert_eq!(loaded.shapes[1].stroke_style, StrokeStyle::new(2.0).join(LineJoin::Round).dashes(vec![2.0, 4.0], 0.0));
+        assert_eq!(loaded.shapes[2].path, baked.shapes[2].path);
+        assert_eq!(loaded.shapes[2].fill, baked.shapes[2].fill);
+        assert_eq!((loaded.shapes[3].fill.clone(), loaded.shapes[3].stroke.clone()), (Paint::None, Paint::None));
+        // the same geometry as the source
+        let (from_svg, from_baked) = (svg.meshes(&Transform2D::IDENTITY, 0.1), loaded.meshes(&Transform2D::IDENTITY, 0.1));
+        assert_eq!(from_svg.len(), from_baked.len());
+        assert_eq!(from_svg.iter().map(|m| m.0.triangle_count()).collect::<Vec<_>>(), from_baked.iter().map(|m| m.0.triangle_count()).collect::<Vec<_>>());
+
+        bytes[4] = 0xff;
+        assert!(BakedImage::read(&mut &bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Gradient paints(SVG `linearGradient` and `radialGradient`)
+
+use metrics::*;
+use color::Color;
+use super::Transform2D;
+
+/// How offsets outside [0, 1] are painted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpreadMethod { Pad, Reflect, Repeat }
+/// Coordinate system of the geometry of a gradient
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientUnits
+{
+    /// Fractions of the bounding box of the painted shape
+    ObjectBoundingBox,
+    /// The user space of the painted shape
+    UserSpaceOnUse
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientShape
+{
+    /// Offset 0 at `from` and 1 at `to`, constant along the perpendicular lines
+    Linear { from: Point2F, to: Point2F },
+    /// Offset 0 at `focus` and 1 on the circle
+    Radial { center: Point2F, radius: f32, focus: Point2F }
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientStop { pub offset: f32, pub color: Color }
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient
+{
+    pub shape: GradientShape, pub stops: Vec<GradientStop>, pub spread: SpreadMethod, pub units: GradientUnits,
+    /// From the gradient space into the units(`gradientTransform`), and its inverse(None if singular) for `color_at`
+    transform: Transform2D, inverse: Option<Transform2D>
+}
+impl Gradient
+{
+    pub fn linear(from: Point2F, to: Point2F) -> Self { Self::new(GradientShape::Linear { from, to }) }
+    pub fn radial(center: Point2F, radius: f32) -> Self { Self::new(GradientShape::Radial { center, radius, focus: center }) }
+    pub fn new(shape: GradientShape) -> Self
+    {
+        Gradient
+        {
+            shape, stops: Vec::new(), spread: SpreadMethod::Pad, units: GradientUnits::UserSpaceOnUse,
+            transform: Transform2D::IDENTITY, inverse: Some(Transform2D::IDENTITY)
+        }
+    }
+    /// Moves the focus of a radial gradient
+    pub fn focus(mut self, focus: Point2F) -> Self
+    {
+        if let GradientShape::Radial { focus: ref mut f, .. } = self.shape { *f = focus; }
+        self
+    }
+    /// Appends a stop. Offsets are clamped into [0, 1] and to the offset of the previous stop
+    pub fn stop(mut self, offset: f32, color: Color) -> Self
+    {
+        let min = self.stops.last().map(|s| s.offset).unwrap_or(0.0);
+        self.stops.push(GradientStop { offset: offset.min(1.0).max(min), color });
+        self
+    }
+    pub fn spread(self, spread: SpreadMethod) -> Self { Gradient { spread, .. self } }
+    pub fn units(self, units: GradientUnits) -> Self { Gradient { units, .. self } }
+    pub fn transform(self, transform: Transform2D) -> Self { Gradient { transform, inverse: transform.inverse(), .. self } }
+    pub fn gradient_transform(&self) -> &Transform2D { &self.transform }
+
+    /// The gradient in the user space of a shape with the bounds, then transformed by `t`.
+    /// None if the bounding box units cannot be resolved(no bounds, or zero width or height)
+    pub fn resolve(&self, bounds: Option<(Point2F, Point2F)>, t: &Transform2D) -> Option<Gradient>
+    {
+        let units = match self.units
+        {
+            GradientUnits::UserSpaceOnUse => Transform2D::IDENTITY,
+            GradientUnits::ObjectBoundingBox =>
+            {
+                let (min, max) = bounds?;
+                let (w, h) = (max.0 - min.0, max.1 - min.1);
+                if w <= 0.0 || h <= 0.0 { return None; }
+                Transform2D::scale(w, h).then(&Transform2D::translate(min.0, min.1))
+            }
+        };
+        Some(Gradient { units: GradientUnits::UserSpaceOnUse, .. self.clone() }.transform(self.transform.then(&units).then(t)))
+    }
+    /// Multiplies the alpha of all stops
+    pub fn with_opacity(&self, opacity: f32) -> Gradient
+    {
+        let stops = self.stops.iter().map(|s| GradientStop { color: Color(s.color.0, s.color.1, s.color.2, s.color.3 * opacity), .. *s }).collect();
+        Gradient { stops, .. self.clone() }
+    }
+    pub fn is_transparent(&self) -> bool { self.stops.iter().all(|s| s.color.3 <= 0.0) }
+
+    /// Color at the point in the space the units are resolved into.
+    /// Degenerate geometries(zero length or radius, singular transform) are painted with the last stop
+    pub fn color_at(&self, p: Point2F) -> Color
+    {
+        let last = self.stops.last().map(|s| s.color).unwrap_or(Color(0.0, 0.0, 0.0, 0.0));
+        let q = match self.inverse { Some(ref inv) => inv.apply(p), None => return last };
+        let offset = match self.shape
+        {
+            GradientShape::Linear { from, to } =>
+            {
+                let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+                let len2 = dx * dx + dy * dy;
+                if len2 <= 0.0 { return last; }
+                ((q.0 - from.0) * dx + (q.1 - from.1) * dy) / len2
+            },
+            GradientShape::Radial { center, radius, focus } =>
+            {
+                if radius <= 0.0 { return last; }
+                // the focus outside the circle is moved onto(just inside) it, as SVG 1.1 does
+                let (mut fx, mut fy) = (focus.0 - center.0, focus.1 - center.1);
+                let (fd, limit) = ((fx * fx + fy * fy).sqrt(), radius * 0.999);
+                if fd > limit { fx *= limit / fd; fy *= limit / fd; }
+                // q = f + d reaches the circle at f + k * d: |f + k * d| = r, so the offset is 1 / k
+                let (dx, dy) = (q.0 - center.0 - fx, q.1 - center.1 - fy);
+                let a = dx * dx + dy * dy;
+                if a <= 0.0 { 0.0 } else
+                {
+                    let (b, c) = (fx * dx + fy * dy, fx * fx + fy * fy - radius * radius);
+                    a / (-b + (b * b - a * c).sqrt())
+                }
+            }
+        };
+        self.color_at_offset(offset)
+    }
+    /// Length in the resolved space over which colors change nearly linearly: meshes are subdivided into it before the colors are
+    /// sampled at the vertices(`MeshCache`). A fraction of the distance between the closest stops, but at least 1/256 of the extent
+    pub fn sampling_step(&self) -> f32
+    {
+        let extent = match self.shape
+        {
+            GradientShape::Linear { from, to } => ((to.0 - from.0) * (to.0 - from.0) + (to.1 - from.1) * (to.1 - from.1)).sqrt(),
+            GradientShape::Radial { radius, .. } => radius
+        } * self.transform.mean_scale();
+        let gap = self.stops.windows(2).map(|s| s[1].offset - s[0].offset).fold(1.0, f32::min);
+        extent * (gap * 0.25).max(1.0 / 256.0)
+    }
+    /// Color at the offset(before the spread method is applied). Colors are interpolated with premultiplied alpha
+    pub fn color_at_offset(&self, offset: f32) -> Color
+    {
+        let t = match self.spread
+        {
+            SpreadMethod::Pad => offset.max(0.0).min(1.0),
+            SpreadMethod::Repeat => offset - offset.floor(),
+            SpreadMethod::Reflect => { let m = offset.abs() % 2.0; if m > 1.0 { 2.0 - m } else { m } }
+        };
+        match self.stops.iter().position(|s| s.offset > t)
+        {
+            None => self.stops.last().map(|s| s.color).unwrap_or(Color(0.0, 0.0, 0.0, 0.0)),
+            Some(0) => self.stops[0].color,
+            Some(n) =>
+            {
+                let (a, b) = (&self.stops[n - 1], &self.stops[n]);
+                mix(a.color, b.color, (t - a.offset) / (b.offset - a.offset))
+            }
+        }
+    }
+}
+
+fn mix(a: Color, b: Color, f: f32) -> Color
+{
+    let alpha = a.3 + (b.3 - a.3) * f;
+    if alpha <= 0.0 { return Color(0.0, 0.0, 0.0, 0.0); }
+    let channel = |x: f32, y: f32| (x * a.3 + (y * b.3 - x * a.3) * f) / alpha;
+    Color(channel(a.0, b.0), channel(a.1, b.1), channel(a.2, b.2), alpha)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const RED: Color = Color(1.0, 0.0, 0.0, 1.0);
+    const BLUE: Color = Color(0.0, 0.0, 1.0, 1.0);
+    fn approx(a: Color, b: Color) -> bool
+    {
+        (a.0 - b.0).abs() < 1.0e-4 && (a.1 - b.1).abs() < 1.0e-4 && (a.2 - b.2).abs() < 1.0e-4 && (a.3 - b.3).abs() < 1.0e-4
+    }
+
+    #[test] fn stops_and_spread()
+    {
+        let g = Gradient::linear(Point2F(0.0, 0.0), Point2F(10.0, 0.0)).stop(0.0, RED).stop(0.5, BLUE).stop(0.25, RED);
+        assert_eq!(g.stops[2].offset, 0.5);
+        assert!(approx(g.color_at(Point2F(2.5, 7.0)), Color(0.5, 0.0, 0.5, 1.0)));
+        // the later of the stops at the same offset wins
+        assert!(approx(g.color_at(Point2F(5.0, 0.0)), RED));
+        assert!(approx(g.color_at(Point2F(-3.0, 0.0)), RED));
+
+        let g = Gradient::linear(Point2F(0.0, 0.0), Point2F(10.0, 0.0)).stop(0.0, RED).stop(1.0, BLUE);
+        assert!(approx(g.color_at(Point2F(12.5, 0.0)), BLUE));
+        assert!(approx(g.clone().spread(SpreadMethod::Repeat).color_at(Point2F(12.5, 0.0)), Color(0.75, 0.0, 0.25, 1.0)));
+        assert!(approx(g.clone().spread(SpreadMethod::Reflect).color_at(Point2F(12.5, 0.0)), Color(0.25, 0.0, 0.75, 1.0)));
+        assert!(approx(g.clone().spread(SpreadMethod::Reflect).color_at(Point2F(-2.5, 0.0)), Color(0.75, 0.0, 0.25, 1.0)));
+    }
+
+    #[test] fn premultiplied_interpolation()
+    {
+        let g = Gradient::linear(Point2F(0.0, 0.0), Point2F(1.0, 0.0)).stop(0.0, RED).stop(1.0, Color(0.0, 0.0, 1.0, 0.0));
+        // no blue fringe from the transparent stop
+        assert!(approx(g.color_at_offset(0.5), Color(1.0, 0.0, 0.0, 0.5)));
+        assert!(g.with_opacity(0.0).is_transparent());
+    }
+
+    #[test] fn radial()
+    {
+        let g = Gradient::radial(Point2F(0.0, 0.0), 10.0).stop(0.0, RED).stop(1.0, BLUE);
+        assert!(approx(g.color_at(Point2F(0.0, 5.0)), Color(0.5, 0.0, 0.5, 1.0)));
+        assert!(approx(g.color_at(Point2F(-8.0, -6.0)), BLUE));
+        let f = g.clone().focus(Point2F(5.0, 0.0));
+        assert!(approx(f.color_at(Point2F(5.0, 0.0)), RED));
+        // halfway from the focus to the circle
+        assert!(approx(f.color_at(Point2F(7.5, 0.0)), Color(0.5, 0.0, 0.5, 1.0)));
+        assert!(approx(f.color_at(Point2F(-2.5, 0.0)), Color(0.5, 0.0, 0.5, 1.0)));
+        // a focus outside the circle is clamped
+        assert!(approx(g.clone().focus(Point2F(20.0, 0.0)).color_at(Point2F(9.99, 0.0)), RED));
+    }
+
+    #[test] fn resolve_units()
+    {
+        let g = Gradient::linear(Point2F(0.0, 0.0), Point2F(1.0, 0.0)).stop(0.0, RED).stop(1.0, BLUE)
+            .units(GradientUnits::ObjectBoundingBox).transform(Transform2D::rotate(90.0));
+        assert_eq!(g.resolve(None, &Transform2D::IDENTITY), None);
+        assert_eq!(g.resolve(Some((Point2F(0.0, 0.0), Point2F(10.0, 0.0))), &Transform2D::IDENTITY), None);
+        // rotated in the bounding box space, then stretched to the box, then translated by the outer transform
+        let r = g.resolve(Some((Point2F(10.0, 10.0), Point2F(30.0, 50.0))), &Transform2D::translate(100.0, 0.0)).unwrap();
+        assert_eq!(r.units, GradientUnits::UserSpaceOnUse);
+        assert!(approx(r.color_at(Point2F(0.0, 30.0)), Color(0.5, 0.0, 0.5, 1.0)));
+        assert!(approx(r.color_at(Point2F(200.0, 50.0)), BLUE));
+        // a singular transform has no inverse: the last stop everywhere
+        let s = Gradient::linear(Point2F(0.0, 0.0), Point2F(1.0, 0.0)).stop(0.0, RED).stop(1.0, BLUE).transform(Transform2D::scale(0.0, 1.0));
+        assert!(approx(s.color_at(Point2F(0.0, 0.0)), BLUE));
+    }
+
+    #[test] fn sampling_step()
+    {
+        let g = Gradient::linear(Point2F(0.0, 0.0), Point2F(100.0, 0.0)).stop(0.0, RED).stop(0.5, BLUE).stop(1.0, RED);
+        assert_eq!(g.sampling_step(), 12.5);
+        assert_eq!(g.clone().transform(Transform2D::scale(2.0, 2.0)).sampling_step(), 25.0);
+        // stops at the same offset are a hard edge: limited to 1/256 of the extent
+        assert_eq!(g.clone().stop(0.5, RED).sampling_step(), 100.0 / 256.0);
+        assert_eq!(Gradient::radial(Point2F(0.0, 0.0), 0.0).stop(0.0, RED).sampling_step(), 0.0);
+    }
+}
@@ -0,0 +1,253 @@
+//! On-disk cache of tessellated meshes keyed by the hash of the source document and the tolerance
+//!
+//! Layout(little endian):
+//! - header: b"DCMC", u16 version, u64 source hash, f32 tolerance, f32 width, f32 height(of the image), u32 vertex count, u32 index count
+//! - vertices: `ColoredVertex`(f32 x, f32 y, [u8; 4] RGBA8 color)
+//! - indices: u32(a triangle list)
+//!
+//! The vertices and the indices fill the rest of the file exactly.
+//! The vertex and index blobs are kept as they are in the file and uploaded into buffers without conversion
+//! (little endian platforms only, as are all of our targets).
+
+use metrics::*;
+use super::{Mesh, Paint, SvgImage, BakedImage, Transform2D};
+use std::io::prelude::*;
+use std::io::{Result as IOResult, Error as IOError, ErrorKind, BufReader, BufWriter};
+use std::path::Path as FilePath;
+use std::fs::File;
+use std::mem::size_of;
+
+const MAGIC: &'static [u8; 4] = b"DCMC";
+/// Bumped whenever the layout or the output of the tessellator changes
+const VERSION: u16 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshCacheKey { pub source_hash: u64, pub tolerance: f32 }
+impl MeshCacheKey
+{
+    /// FNV-1a hash of the source(stable across builds unlike `DefaultHasher`)
+    pub fn new(source: &[u8], tolerance: f32) -> Self
+    {
+        let source_hash = source.iter().fold(0xcbf2_9ce4_8422_2325u64, |h, &b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+        MeshCacheKey { source_hash, tolerance }
+    }
+}
+
+/// Vertex layout of cached meshes
+#[repr(C)] #[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColoredVertex { pub pos: [f32; 2], pub color: [u8; 4] }
+
+/// Meshes merged into one indexed triangle list, in the coordinate space of the image of `size`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshCache { pub key: MeshCacheKey, pub size: Size2F, vertices: Vec<u8>, indices: Vec<u8> }
+impl MeshCache
+{
+    /// Colors are baked into the vertices so that the whole list is drawn at once.
+    /// Gradients are sampled at the vertices and interpolated linearly over the triangles, subdivided into `Gradient::sampling_step`
+    pub fn from_meshes(key: MeshCacheKey, size: Size2F, meshes: &[(Mesh, Paint)]) -> Self
+    {
+        let to_u8 = |v: f32| (v.max(0.0).min(1.0) * 255.0).round() as u8;
+        let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+        let mut base = 0;
+        for &(ref mesh, ref paint) in meshes.iter().filter(|&&(_, ref p)| *p != Paint::None)
+        {
+            let subdivided;
+            let mesh = match *paint
+            {
+                Paint::Gradient(ref g) => { subdivided = mesh.subdivided(g.sampling_step()); &subdivided },
+                _ => mesh
+            };
+            for v in &mesh.vertices
+            {
+                let c = match *paint
+                {
+                    Paint::Gradient(ref g) => g.color_at(Point2F(v[0], v[1])),
+                    Paint::Color(c) => c, Paint::None => unreachable!()
+                };
+                put_u32(&mut vertices, v[0].to_bits()); put_u32(&mut vertices, v[1].to_bits());
+                vertices.extend_from_slice(&[to_u8(c.0), to_u8(c.1), to_u8(c.2), to_u8(c.3)]);
+            }
+            for &i in &mesh.indices { put_u32(&mut indices, base + i); }
+            base += mesh.vertices.len() as u32;
+        }
+        MeshCache { key, size, vertices, indices }
+    }
+    /// The cache of the baked vector image(.dcvp) or the SVG document(tessellated in its own coordinate space) if it is up to date.
+    /// Otherwise the image is tessellated and the cache is rewritten
+    pub fn load_or_tessellate<P: AsRef<FilePath>, C: AsRef<FilePath>>(source: P, cache: C, tolerance: f32) -> IOResult<Self>
+    {
+        let mut bytes = Vec::new();
+        File::open(source)?.read_to_end(&mut bytes)?;
+        let key = MeshCacheKey::new(&bytes, tolerance);
+        // unreadable caches(missing, corrupted or of other versions) are just rebuilt
+        if let Ok(c) = Self::load(&cache) { if c.key == key { return Ok(c); } }
+        let c = if bytes.starts_with(b"DCVP")
+        {
+            let image = BakedImage::read(&mut &bytes[..])?;
+            Self::from_meshes(key, image.size, &image.meshes(&Transform2D::IDENTITY, tolerance))
+        }
+        else
+        {
+            let image = SvgImage::from_bytes(&bytes)?;
+            Self::from_meshes(key, image.size, &image.meshes(&Transform2D::IDENTITY, tolerance))
+        };
+        if let Err(e) = c.save(&cache) { println!("!! Failed to write the mesh cache {}: {:?}", cache.as_ref().display(), e); }
+        Ok(c)
+    }
+
+    pub fn vertex_count(&self) -> usize { self.vertices.len() / size_of::<ColoredVertex>() }
+    pub fn index_count(&self) -> usize { self.indices.len() / size_of::<u32>() }
+    /// Initial data of a vertex buffer(`BufferContent::with_data`)
+    pub fn vertex_bytes(&self) -> &[u8] { &self.vertices }
+    /// Initial data of an index buffer(drawn with 32-bit indices)
+    pub fn index_bytes(&self) -> &[u8] { &self.indices }
+    pub fn vertex(&self, n: usize) -> ColoredVertex
+    {
+        let b = &self.vertices[n * size_of::<ColoredVertex>() ..];
+        ColoredVertex { pos: [f32::from_bits(get_u32(&b[0..])), f32::from_bits(get_u32(&b[4..]))], color: [b[8], b[9], b[10], b[11]] }
+    }
+    pub fn index(&self, n: usize) -> u32 { get_u32(&self.indices[n * 4 ..]) }
+
+    pub fn load<P: AsRef<FilePath>>(path: P) -> IOResult<Self> { Self::read(&mut BufReader::new(File::open(path)?)) }
+    pub fn save<P: AsRef<FilePath>>(&self, path: P) -> IOResult<()> { self.write(&mut BufWriter::new(File::create(path)?)) }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> IOResult<()>
+    {
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&[VERSION as u8, (VERSION >> 8) as u8]);
+        put_u32(&mut header, self.key.source_hash as u32); put_u32(&mut header, (self.key.source_hash >> 32) as u32);
+        put_u32(&mut header, self.key.tolerance.to_bits());
+        put_u32(&mut header, self.size.x().to_bits()); put_u32(&mut header, self.size.y().to_bits());
+        put_u32(&mut header, self.vertex_count() as _); put_u32(&mut header, self.index_count() as _);
+        w.write_all(&header)?; w.write_all(&self.vertices)?; w.write_all(&self.indices)?;
+        w.flush()
+    }
+    pub fn read<R: Read>(r: &mut R) -> IOResult<Self>
+    {
+        let mut header = [0u8; 34];
+        r.read_exact(&mut header)?;
+        if &header[..4] != MAGIC { return Err(IOError::new(ErrorKind::InvalidData, "Not a mesh cache")); }
+        let version = header[4] as u16 | (header[5] as u16) << 8;
+        if version != VERSION
+        {
+            return Err(IOError::new(ErrorKind::InvalidData, format!("Unsupported version of mesh cache: {}", version)));
+        }
+        let source_hash = get_u32(&header[6..]) as u64 | (get_u32(&header[10..]) as u64) << 32;
+        let key = MeshCacheKey { source_hash, tolerance: f32::from_bits(get_u32(&header[14..])) };
+        let size = Size2F(f32::from_bits(get_u32(&header[18..])), f32::from_bits(get_u32(&header[22..])));
+        let (vertex_count, index_count) = (get_u32(&header[26..]), get_u32(&header[30..]));
+        // the counts are checked before being trusted
+        let mut body = Vec::new();
+        r.read_to_end(&mut body)?;
+        let vertex_bytes = vertex_count as u64 * size_of::<ColoredVertex>() as u64;
+        if body.len() as u64 != vertex_bytes + index_count as u64 * size_of::<u32>() as u64 || index_count % 3 != 0
+        {
+            return Err(IOError::new(ErrorKind::InvalidData, format!("Mesh cache has {} bytes for {} vertices and {} indices",
+                body.len(), vertex_count, index_count)));
+        }
+        let indices = body.split_off(vertex_bytes as usize);
+        let vertices = body;
+        // indices go to the GPU as they are
+        if indices.chunks(4).any(|i| get_u32(i) >= vertex_count)
+        {
+            return Err(IOError::new(ErrorKind::InvalidData, "Mesh cache has an index out of range"));
+        }
+        Ok(MeshCache { key, size, vertices, indices })
+    }
+}
+
+fn put_u32(sink: &mut Vec<u8>, v: u32) { sink.extend_from_slice(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]); }
+fn get_u32(b: &[u8]) -> u32 { b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use color::Color;
+    use vector::Gradient;
+    use std::fs::{self, File};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn write_file(path: &FilePath, text: &str) { File::create(path).unwrap().write_all(text.as_bytes()).unwrap(); }
+
+    fn triangle(offset: f32) -> Mesh
+    {
+        Mesh { vertices: vec![[offset, 0.0], [offset + 1.0, 0.0], [offset, 1.0]], indices: vec![0, 1, 2] }
+    }
+
+    #[test] fn roundtrip()
+    {
+        let key = MeshCacheKey::new(b"<svg/>", 0.25);
+        let cache = MeshCache::from_meshes(key, Size2F(10.0, 5.0), &[(triangle(0.0), Paint::Color(Color(1.0, 0.0, 0.0, 1.0))),
+            (triangle(5.0), Paint::Color(Color(0.0, 0.0, 1.0, 0.5)))]);
+        assert_eq!((cache.vertex_count(), cache.index_count()), (6, 6));
+        assert_eq!((3 .. 6).map(|n| cache.index(n)).collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert_eq!(cache.vertex(4), ColoredVertex { pos: [6.0, 0.0], color: [0, 0, 255, 128] });
+
+        let mut bytes = Vec::new();
+        cache.write(&mut bytes).unwrap();
+        assert_eq!(MeshCache::read(&mut &bytes[..]).unwrap(), cache);
+
+        // truncated, trailing bytes, and counts not matching the length
+        assert!(MeshCache::read(&mut &bytes[.. bytes.len() - 1]).is_err());
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(MeshCache::read(&mut &longer[..]).is_err());
+        let mut huge = bytes.clone();
+        huge[29] = 0xff;
+        assert!(MeshCache::read(&mut &huge[..]).is_err());
+
+        let last = bytes.len() - 4;
+        bytes[last] = 6;
+        assert!(MeshCache::read(&mut &bytes[..]).is_err());
+        bytes[4] = 0xff;
+        assert!(MeshCache::read(&mut &bytes[..]).is_err());
+    }
+
+    #[test] fn gradient_subdivision()
+    {
+        let key = MeshCacheKey::new(b"<svg/>", 0.25);
+        let g = Gradient::linear(Point2F(0.0, 0.0), Point2F(8.0, 0.0)).stop(0.0, Color(0.0, 0.0, 0.0, 1.0)).stop(1.0, Color(1.0, 1.0, 1.0, 1.0));
+        let big = Mesh { vertices: vec![[0.0, 0.0], [8.0, 0.0], [0.0, 8.0]], indices: vec![0, 1, 2] };
+        let cache = MeshCache::from_meshes(key, Size2F(8.0, 8.0), &[(big, Paint::Gradient(g.clone()))]);
+        assert!(cache.vertex_count() > 3);
+        // every vertex has the color of its position, not an interpolation over the whole triangle
+        for n in 0 .. cache.vertex_count()
+        {
+            let v = cache.vertex(n);
+            assert_eq!(v.color[0], (g.color_at(Point2F(v.pos[0], v.pos[1])).0 * 255.0).round() as u8);
+        }
+    }
+
+    #[test] fn rebuilds_stale_cache()
+    {
+        // unique for concurrent runs of the tests
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+        let dir = ::std::env::temp_dir().join(format!("dc2017-mesh-cache-test-{}", nanos));
+        fs::create_dir_all(&dir).unwrap();
+        let (source, cache_path) = (dir.join("image.svg"), dir.join("image.dcmc"));
+        write_file(&source, r##"<svg width="10" height="10"><circle cx="5" cy="5" r="5" fill="#0f0"/></svg>"##);
+        let _ = fs::remove_file(&cache_path);
+
+        let fine = MeshCache::load_or_tessellate(&source, &cache_path, 0.01).unwrap();
+        assert_eq!(MeshCache::load(&cache_path).unwrap(), fine);
+        assert_eq!((fine.vertex(0).color, fine.size), ([0, 255, 0, 255], Size2F(10.0, 10.0)));
+        // the cached one is returned while the key matches
+        assert_eq!(MeshCache::load_or_tessellate(&source, &cache_path, 0.01).unwrap(), fine);
+        let coarse = MeshCache::load_or_tessellate(&source, &cache_path, 1.0).unwrap();
+        assert!(coarse.vertex_count() < fine.vertex_count());
+        assert_eq!(MeshCache::load(&cache_path).unwrap().key, coarse.key);
+
+        write_file(&source, r##"<svg width="10" height="10"><rect width="10" height="10"/></svg>"##);
+        let rect = MeshCache::load_or_tessellate(&source, &cache_path, 1.0).unwrap();
+        assert_eq!(rect.vertex_count(), 4);
+        // baked images are tessellated in the same way
+        let baked = dir.join("image.dcvp");
+        BakedImage::from_svg(&SvgImage::load(&source).unwrap()).save(&baked).unwrap();
+        let from_baked = MeshCache::load_or_tessellate(&baked, &cache_path, 1.0).unwrap();
+        assert!(from_baked.key != rect.key);
+        assert_eq!((from_baked.vertex_bytes(), from_baked.index_bytes()), (rect.vertex_bytes(), rect.index_bytes()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
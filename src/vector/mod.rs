@@ -0,0 +1,24 @@
+//! Backend-independent vector graphics
+
+mod path;
+mod tessellate;
+mod stroke;
+mod transform;
+mod raster;
+mod svg;
+mod baked;
+mod sdf;
+mod mesh_cache;
+mod gradient;
+mod query;
+pub use self::path::*;
+pub use self::tessellate::*;
+pub use self::stroke::*;
+pub use self::transform::*;
+pub use self::raster::*;
+pub use self::svg::*;
+pub use self::baked::*;
+pub use self::sdf::*;
+pub use self::mesh_cache::*;
+pub use self::gradient::*;
+pub use self::query::*;
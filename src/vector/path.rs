@@ -0,0 +1,438 @@
+//! Path model
+
+use metrics::*;
+use svgparser::path::Token;
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathCommand
+{
+    MoveTo(Point2F), LineTo(Point2F),
+    /// control point, end point
+    QuadTo(Point2F, Point2F),
+    /// first control point, second control point, end point
+    CubicTo(Point2F, Point2F, Point2F),
+    /// Elliptical arc in the endpoint parameterization of SVG(rotation in degrees)
+    ArcTo { radii: Point2F, x_axis_rotation: f32, large_arc: bool, sweep: bool, to: Point2F },
+    Close
+}
+
+/// Sequence of figures. All coordinates are absolute
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Path { pub commands: Vec<PathCommand> }
+
+/// A flattened figure
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline { pub points: Vec<Point2F>, pub closed: bool }
+
+impl Path
+{
+    pub fn new() -> Self { Path { commands: Vec::new() } }
+    pub fn move_to(&mut self, p: Point2F) -> &mut Self { self.commands.push(PathCommand::MoveTo(p)); self }
+    pub fn line_to(&mut self, p: Point2F) -> &mut Self { self.commands.push(PathCommand::LineTo(p)); self }
+    pub fn quad_to(&mut self, c: Point2F, p: Point2F) -> &mut Self { self.commands.push(PathCommand::QuadTo(c, p)); self }
+    pub fn cubic_to(&mut self, c1: Point2F, c2: Point2F, p: Point2F) -> &mut Self { self.commands.push(PathCommand::CubicTo(c1, c2, p)); self }
+    pub fn arc_to(&mut self, radii: Point2F, x_axis_rotation: f32, large_arc: bool, sweep: bool, to: Point2F) -> &mut Self
+    {
+        self.commands.push(PathCommand::ArcTo { radii, x_axis_rotation, large_arc, sweep, to }); self
+    }
+    pub fn close(&mut self) -> &mut Self { self.commands.push(PathCommand::Close); self }
+
+    /// Builds a path from the tokens of SVG path data, resolving relative coordinates and shorthand forms.
+    /// The first control point of `S`/`T` is the reflection of the last control point only if the previous segment is `C`/`S` or `Q`/`T`
+    /// respectively, otherwise the current point(SVG 1.1 8.3.6, 8.3.7)
+    pub fn from_svg_tokens<'a, I: IntoIterator<Item = &'a Token>>(tokens: I) -> Self
+    {
+        let mut path = Path::new();
+        let (mut prev, mut figure_begin) = (Point2F::ZERO, Point2F::ZERO);
+        // last control point of the previous segment, if it was a cubic/quadratic curve
+        let (mut last_cubic_ctrl, mut last_quad_ctrl) = (None, None);
+        for t in tokens
+        {
+            let abs_point = |abs: bool, x: f64, y: f64, prev: Point2F| Point2F(x as _, y as _) + &if abs { Point2F::ZERO } else { prev };
+            let reflect = |c: Option<Point2F>, prev: Point2F| c.map(|c| Point2F(2.0 * prev.0 - c.0, 2.0 * prev.1 - c.1)).unwrap_or(prev);
+            let (mut cubic_ctrl, mut quad_ctrl) = (None, None);
+            match *t
+            {
+                Token::MoveTo { abs, x, y } =>
+                {
+                    prev = abs_point(abs, x, y, prev); figure_begin = prev;
+                    path.move_to(prev);
+                },
+                Token::LineTo { abs, x, y } => { prev = abs_point(abs, x, y, prev); path.line_to(prev); },
+                Token::HorizontalLineTo { abs, x } => { prev.0 = if abs { x as _ } else { x as f32 + prev.0 }; path.line_to(prev); },
+                Token::VerticalLineTo   { abs, y } => { prev.1 = if abs { y as _ } else { y as f32 + prev.1 }; path.line_to(prev); },
+                Token::CurveTo { abs, x1, y1, x2, y2, x, y } =>
+                {
+                    let (p1, p2) = (abs_point(abs, x1, y1, prev), abs_point(abs, x2, y2, prev));
+                    prev = abs_point(abs, x, y, prev);
+                    path.cubic_to(p1, p2, prev); cubic_ctrl = Some(p2);
+                },
+                Token::SmoothCurveTo { abs, x2, y2, x, y } =>
+                {
+                    let p1 = reflect(last_cubic_ctrl, prev);
+                    let p2 = abs_point(abs, x2, y2, prev);
+                    prev = abs_point(abs, x, y, prev);
+                    path.cubic_to(p1, p2, prev); cubic_ctrl = Some(p2);
+                },
+                Token::Quadratic { abs, x1, y1, x, y } =>
+                {
+                    let p1 = abs_point(abs, x1, y1, prev);
+                    prev = abs_point(abs, x, y, prev);
+                    path.quad_to(p1, prev); quad_ctrl = Some(p1);
+                },
+                Token::SmoothQuadratic { abs, x, y } =>
+                {
+                    let p1 = reflect(last_quad_ctrl, prev);
+                    prev = abs_point(abs, x, y, prev);
+                    path.quad_to(p1, prev); quad_ctrl = Some(p1);
+                },
+                Token::EllipticalArc { abs, rx, ry, x_axis_rotation, large_arc, sweep, x, y } =>
+                {
+                    prev = abs_point(abs, x, y, prev);
+                    path.arc_to(Point2F(rx as _, ry as _), x_axis_rotation as _, large_arc, sweep, prev);
+                },
+                Token::ClosePath { .. } => { prev = figure_begin; path.close(); }
+            }
+            last_cubic_ctrl = cubic_ctrl; last_quad_ctrl = quad_ctrl;
+        }
+        path
+    }
+
+    /// Replaces arcs with cubic bezier curves
+    pub fn arcs_to_cubics(&self) -> Path
+    {
+        let mut commands = Vec::with_capacity(self.commands.len());
+        let mut prev = Point2F::ZERO;
+        let mut figure_begin = Point2F::ZERO;
+        for c in &self.commands
+        {
+            match *c
+            {
+                PathCommand::ArcTo { radii, x_axis_rotation, large_arc, sweep, to } =>
+                {
+                    match CenterArc::from_endpoints(prev, radii, x_axis_rotation, large_arc, sweep, to)
+                    {
+                        Some(a) => commands.extend(a.to_cubics().into_iter().map(|(c1, c2, p)| PathCommand::CubicTo(c1, c2, p))),
+                        // degenerated into a line(or nothing if the endpoints are identical)
+                        None => if prev != to { commands.push(PathCommand::LineTo(to)); }
+                    }
+                },
+                c => commands.push(c)
+            }
+            match *c
+            {
+                PathCommand::MoveTo(p) => { prev = p; figure_begin = p; },
+                PathCommand::LineTo(p) | PathCommand::QuadTo(_, p) | PathCommand::CubicTo(_, _, p) | PathCommand::ArcTo { to: p, .. } => prev = p,
+                PathCommand::Close => prev = figure_begin
+            }
+        }
+        Path { commands }
+    }
+
+    /// Approximates all curves with lines so that the distance from the curves does not exceed the tolerance
+    pub fn flatten(&self, tolerance: f32) -> Vec<Polyline>
+    {
+        if self.commands.iter().any(|c| match *c { PathCommand::ArcTo { .. } => true, _ => false })
+        {
+            return self.arcs_to_cubics().flatten(tolerance);
+        }
+        let mut figures = Vec::new();
+        let mut current = Polyline { points: Vec::new(), closed: false };
+        let mut prev = Point2F::ZERO;
+        for c in &self.commands
+        {
+            match *c
+            {
+                PathCommand::MoveTo(p) =>
+                {
+                    if current.points.len() > 1 { figures.push(current); }
+                    current = Polyline { points: vec![p], closed: false };
+                    prev = p;
+                },
+                PathCommand::LineTo(p) => { current.points.push(p); prev = p; },
+                PathCommand::QuadTo(c, p) =>
+                {
+                    // Wang's formula: n = sqrt(d(d-1)/8 * max|second difference| / tolerance)
+                    let dd = length(Point2F(prev.0 - 2.0 * c.0 + p.0, prev.1 - 2.0 * c.1 + p.1));
+                    let n = segment_count(0.25 * dd, tolerance);
+                    for i in 1 .. n + 1
+                    {
+                        let t = i as f32 / n as f32; let u = 1.0 - t;
+                        current.points.push(Point2F(u * u * prev.0 + 2.0 * u * t * c.0 + t * t * p.0, u * u * prev.1 + 2.0 * u * t * c.1 + t * t * p.1));
+                    }
+                    prev = p;
+                },
+                PathCommand::CubicTo(c1, c2, p) =>
+                {
+                    let dd1 = length(Point2F(prev.0 - 2.0 * c1.0 + c2.0, prev.1 - 2.0 * c1.1 + c2.1));
+                    let dd2 = length(Point2F(c1.0 - 2.0 * c2.0 + p.0, c1.1 - 2.0 * c2.1 + p.1));
+                    let n = segment_count(0.75 * dd1.max(dd2), tolerance);
+                    for i in 1 .. n + 1
+                    {
+                        let t = i as f32 / n as f32; let u = 1.0 - t;
+                        let (b0, b1, b2, b3) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                        current.points.push(Point2F(b0 * prev.0 + b1 * c1.0 + b2 * c2.0 + b3 * p.0, b0 * prev.1 + b1 * c1.1 + b2 * c2.1 + b3 * p.1));
+                    }
+                    prev = p;
+                },
+                PathCommand::ArcTo { .. } => unreachable!("arcs are converted into curves before flattening"),
+                PathCommand::Close =>
+                {
+                    let begin = current.points.first().cloned().unwrap_or(prev);
+                    if current.points.len() > 1 { current.closed = true; figures.push(current); }
+                    // subsequent commands without MoveTo start from the beginning of the closed figure
+                    current = Polyline { points: vec![begin], closed: false };
+                    prev = begin;
+                }
+            }
+        }
+        if current.points.len() > 1 { figures.push(current); }
+        figures
+    }
+
+    /// Tight bounding box(min, max) including the extrema of curves. None for an empty path
+    pub fn bounds(&self) -> Option<(Point2F, Point2F)>
+    {
+        if self.commands.iter().any(|c| match *c { PathCommand::ArcTo { .. } => true, _ => false })
+        {
+            return self.arcs_to_cubics().bounds();
+        }
+        fn include(b: &mut Option<(Point2F, Point2F)>, p: Point2F)
+        {
+            *b = Some(match *b
+            {
+                None => (p, p),
+                Some((min, max)) => (Point2F(min.0.min(p.0), min.1.min(p.1)), Point2F(max.0.max(p.0), max.1.max(p.1)))
+            });
+        }
+        let mut b = None;
+        let (mut prev, mut figure_begin) = (Point2F::ZERO, Point2F::ZERO);
+        for c in &self.commands
+        {
+            match *c
+            {
+                PathCommand::MoveTo(p) => { include(&mut b, p); prev = p; figure_begin = p; },
+                PathCommand::LineTo(p) => { include(&mut b, p); prev = p; },
+                PathCommand::QuadTo(c, p) =>
+                {
+                    include(&mut b, p);
+                    // the derivative vanishes at (p0 - c) / (p0 - 2c + p1) on each axis
+                    for &(a0, a1, a2) in &[(prev.0, c.0, p.0), (prev.1, c.1, p.1)]
+                    {
+                        let denom = a0 - 2.0 * a1 + a2;
+                        if denom == 0.0 { continue; }
+                        let t = (a0 - a1) / denom;
+                        if t > 0.0 && t < 1.0
+                        {
+                            let u = 1.0 - t;
+                            include(&mut b, Point2F(u * u * prev.0 + 2.0 * u * t * c.0 + t * t * p.0, u * u * prev.1 + 2.0 * u * t * c.1 + t * t * p.1));
+                        }
+                    }
+                    prev = p;
+                },
+                PathCommand::CubicTo(c1, c2, p) =>
+                {
+                    include(&mut b, p);
+                    let mut ts = cubic_extrema(prev.0, c1.0, c2.0, p.0);
+                    ts.extend(cubic_extrema(prev.1, c1.1, c2.1, p.1));
+                    for t in ts
+                    {
+                        let u = 1.0 - t;
+                        let (b0, b1, b2, b3) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                        include(&mut b, Point2F(b0 * prev.0 + b1 * c1.0 + b2 * c2.0 + b3 * p.0, b0 * prev.1 + b1 * c1.1 + b2 * c2.1 + b3 * p.1));
+                    }
+                    prev = p;
+                },
+                PathCommand::ArcTo { .. } => unreachable!("arcs are converted into curves before measuring"),
+                PathCommand::Close => prev = figure_begin
+            }
+        }
+        b
+    }
+}
+/// Parameters in (0, 1) where the derivative of a cubic bezier curve(on one axis) vanishes
+fn cubic_extrema(p0: f32, c1: f32, c2: f32, p3: f32) -> Vec<f32>
+{
+    // B'(t) / 3 = a t^2 + b t + c
+    let (a, b, c) = (3.0 * (c1 - c2) + p3 - p0, 2.0 * (p0 - 2.0 * c1 + c2), c1 - p0);
+    let roots = if a.abs() <= 1.0e-12
+    {
+        if b.abs() <= 1.0e-12 { Vec::new() } else { vec![-c / b] }
+    }
+    else
+    {
+        let d = b * b - 4.0 * a * c;
+        if d < 0.0 { Vec::new() } else { let s = d.sqrt(); vec![(-b + s) / (2.0 * a), (-b - s) / (2.0 * a)] }
+    };
+    roots.into_iter().filter(|&t| t > 0.0 && t < 1.0).collect()
+}
+
+/// Elliptical arc in the center parameterization(angles in radians)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CenterArc { pub center: Point2F, pub radii: Point2F, pub x_axis_rotation: f32, pub start_angle: f32, pub sweep_angle: f32 }
+impl CenterArc
+{
+    /// Endpoint to center parameterization conversion(SVG 1.1 F.6.5), with correction of out-of-range radii(F.6.6).
+    /// None if the arc is treated as a straight line: zero radius or identical endpoints
+    pub fn from_endpoints(from: Point2F, radii: Point2F, x_axis_rotation: f32, large_arc: bool, sweep: bool, to: Point2F) -> Option<Self>
+    {
+        let (mut rx, mut ry) = (radii.0.abs(), radii.1.abs());
+        if from == to || rx == 0.0 || ry == 0.0 { return None; }
+        let phi = x_axis_rotation.to_radians();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let (dx2, dy2) = ((from.0 - to.0) * 0.5, (from.1 - to.1) * 0.5);
+        let (x1, y1) = (cos_phi * dx2 + sin_phi * dy2, -sin_phi * dx2 + cos_phi * dy2);
+        let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if lambda > 1.0 { rx *= lambda.sqrt(); ry *= lambda.sqrt(); }
+        let (rx2, ry2, x12, y12) = (rx * rx, ry * ry, x1 * x1, y1 * y1);
+        let num = rx2 * ry2 - rx2 * y12 - ry2 * x12;
+        let coef = (num.max(0.0) / (rx2 * y12 + ry2 * x12)).sqrt() * if large_arc == sweep { -1.0 } else { 1.0 };
+        let (cx1, cy1) = (coef * rx * y1 / ry, -coef * ry * x1 / rx);
+        let center = Point2F(cos_phi * cx1 - sin_phi * cy1 + (from.0 + to.0) * 0.5, sin_phi * cx1 + cos_phi * cy1 + (from.1 + to.1) * 0.5);
+        let start_angle = vector_angle((1.0, 0.0), ((x1 - cx1) / rx, (y1 - cy1) / ry));
+        let mut sweep_angle = vector_angle(((x1 - cx1) / rx, (y1 - cy1) / ry), ((-x1 - cx1) / rx, (-y1 - cy1) / ry));
+        if !sweep && sweep_angle > 0.0 { sweep_angle -= 2.0 * PI; }
+        if sweep && sweep_angle < 0.0 { sweep_angle += 2.0 * PI; }
+        Some(CenterArc { center, radii: Point2F(rx, ry), x_axis_rotation: phi, start_angle, sweep_angle })
+    }
+
+    pub fn point_at(&self, angle: f32) -> Point2F
+    {
+        let (s, c) = angle.sin_cos();
+        self.map_unit(c, s)
+    }
+    fn map_unit(&self, x: f32, y: f32) -> Point2F
+    {
+        let (sin_phi, cos_phi) = self.x_axis_rotation.sin_cos();
+        let (x, y) = (self.radii.0 * x, self.radii.1 * y);
+        Point2F(self.center.0 + cos_phi * x - sin_phi * y, self.center.1 + sin_phi * x + cos_phi * y)
+    }
+
+    /// Approximates with cubic bezier curves, each spanning at most 90 degrees: (control point 1, control point 2, end point)
+    pub fn to_cubics(&self) -> Vec<(Point2F, Point2F, Point2F)>
+    {
+        let n = (self.sweep_angle.abs() / (PI * 0.5) - 1.0e-4).ceil().max(1.0) as usize;
+        let delta = self.sweep_angle / n as f32;
+        let k = 4.0 / 3.0 * (delta * 0.25).tan();
+        (0 .. n).map(|i|
+        {
+            let (a0, a1) = (self.start_angle + delta * i as f32, self.start_angle + delta * (i + 1) as f32);
+            let ((s0, c0), (s1, c1)) = (a0.sin_cos(), a1.sin_cos());
+            (self.map_unit(c0 - k * s0, s0 + k * c0), self.map_unit(c1 + k * s1, s1 - k * c1), self.map_unit(c1, s1))
+        }).collect()
+    }
+}
+/// Signed angle from u to v
+fn vector_angle(u: (f32, f32), v: (f32, f32)) -> f32 { (u.0 * v.1 - u.1 * v.0).atan2(u.0 * v.0 + u.1 * v.1) }
+
+fn length(v: Point2F) -> f32 { (v.0 * v.0 + v.1 * v.1).sqrt() }
+fn segment_count(deviation: f32, tolerance: f32) -> usize
+{
+    let n = (deviation / tolerance).sqrt().ceil();
+    if n.is_finite() && n >= 1.0 { n as usize } else { 1 }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use svgparser::path::Tokenizer;
+    use svgparser::FromSpan;
+
+    fn parse(d: &str) -> Path
+    {
+        let tokens: Vec<_> = Tokenizer::from_str(d).collect();
+        Path::from_svg_tokens(&tokens)
+    }
+    fn near(a: Point2F, b: Point2F) -> bool { (a.0 - b.0).abs() < 1.0e-3 && (a.1 - b.1).abs() < 1.0e-3 }
+    fn cubic(p: &Path, n: usize) -> (Point2F, Point2F, Point2F)
+    {
+        match p.commands[n] { PathCommand::CubicTo(c1, c2, e) => (c1, c2, e), ref c => panic!("not a cubic: {:?}", c) }
+    }
+    fn quad(p: &Path, n: usize) -> (Point2F, Point2F)
+    {
+        match p.commands[n] { PathCommand::QuadTo(c, e) => (c, e), ref c => panic!("not a quadratic: {:?}", c) }
+    }
+
+    #[test] fn smooth_cubic_reflects_previous_cubic()
+    {
+        let p = parse("M10 10 C20 0 30 0 40 10 S60 20 70 10");
+        assert_eq!(cubic(&p, 2), (Point2F(50.0, 20.0), Point2F(60.0, 20.0), Point2F(70.0, 10.0)));
+        assert_eq!(parse("m10 10 c10 -10 20 -10 30 0 s20 10 30 0"), p);
+    }
+    #[test] fn smooth_cubic_after_line_starts_at_current_point()
+    {
+        let p = parse("M0 0 C0 10 10 10 10 0 L20 0 S30 10 40 0");
+        assert_eq!(cubic(&p, 3).0, Point2F(20.0, 0.0));
+    }
+    #[test] fn smooth_cubic_after_quadratic_starts_at_current_point()
+    {
+        let p = parse("M0 0 Q10 10 20 0 S30 10 40 0");
+        assert_eq!(cubic(&p, 2).0, Point2F(20.0, 0.0));
+    }
+    #[test] fn smooth_quadratic_reflects_previous_quadratic()
+    {
+        let p = parse("M0 0 Q10 10 20 0 T40 0 T60 0");
+        assert_eq!(quad(&p, 2), (Point2F(30.0, -10.0), Point2F(40.0, 0.0)));
+        assert_eq!(quad(&p, 3), (Point2F(50.0, 10.0), Point2F(60.0, 0.0)));
+    }
+    #[test] fn smooth_quadratic_after_cubic_starts_at_current_point()
+    {
+        let p = parse("M0 0 C0 10 10 10 10 0 T20 0");
+        assert_eq!(quad(&p, 2).0, Point2F(10.0, 0.0));
+    }
+    #[test] fn close_path_resets_current_point()
+    {
+        let p = parse("M10 10 L20 10 L20 20 Z l5 0");
+        assert_eq!(p.commands[4], PathCommand::LineTo(Point2F(15.0, 10.0)));
+    }
+
+    #[test] fn arc_center_small_and_large()
+    {
+        let a = CenterArc::from_endpoints(Point2F(0.0, 0.0), Point2F(10.0, 10.0), 0.0, false, true, Point2F(10.0, 10.0)).unwrap();
+        assert!(near(a.center, Point2F(0.0, 10.0)));
+        assert!((a.sweep_angle - PI * 0.5).abs() < 1.0e-4);
+        let a = CenterArc::from_endpoints(Point2F(0.0, 0.0), Point2F(10.0, 10.0), 0.0, true, true, Point2F(10.0, 10.0)).unwrap();
+        assert!(near(a.center, Point2F(10.0, 0.0)));
+        assert!((a.sweep_angle - PI * 1.5).abs() < 1.0e-4);
+        let a = CenterArc::from_endpoints(Point2F(0.0, 0.0), Point2F(10.0, 10.0), 0.0, false, false, Point2F(10.0, 10.0)).unwrap();
+        assert!(near(a.center, Point2F(10.0, 0.0)));
+        assert!((a.sweep_angle + PI * 0.5).abs() < 1.0e-4);
+    }
+    #[test] fn arc_radii_are_scaled_up()
+    {
+        let a = CenterArc::from_endpoints(Point2F(0.0, 0.0), Point2F(1.0, 1.0), 0.0, false, true, Point2F(20.0, 0.0)).unwrap();
+        assert!(near(a.center, Point2F(10.0, 0.0)));
+        assert!(near(a.radii, Point2F(10.0, 10.0)));
+    }
+    #[test] fn arc_with_rotation_passes_endpoints()
+    {
+        let a = CenterArc::from_endpoints(Point2F(3.0, 7.0), Point2F(25.0, 10.0), 30.0, true, false, Point2F(40.0, 20.0)).unwrap();
+        assert!(near(a.point_at(a.start_angle), Point2F(3.0, 7.0)));
+        assert!(near(a.point_at(a.start_angle + a.sweep_angle), Point2F(40.0, 20.0)));
+    }
+    #[test] fn arc_to_cubics()
+    {
+        let p = parse("M0 0 A10 10 0 0 1 20 0").arcs_to_cubics();
+        assert_eq!(p.commands.len(), 3);
+        let (c1, c2, e) = cubic(&p, 1);
+        let k = 10.0 * 4.0 / 3.0 * (PI / 8.0).tan();
+        assert!(near(c1, Point2F(0.0, -k)) && near(c2, Point2F(10.0 - k, -10.0)) && near(e, Point2F(10.0, -10.0)));
+        assert!(near(cubic(&p, 2).2, Point2F(20.0, 0.0)));
+    }
+    #[test] fn degenerated_arcs()
+    {
+        assert_eq!(parse("M0 0 A0 5 0 0 1 10 0").arcs_to_cubics().commands[1], PathCommand::LineTo(Point2F(10.0, 0.0)));
+        assert_eq!(parse("M0 0 A5 5 0 0 1 0 0").arcs_to_cubics().commands.len(), 1);
+    }
+    #[test] fn bounds()
+    {
+        assert_eq!(Path::new().bounds(), None);
+        // control points outside the curves do not count
+        let (min, max) = parse("M0 0 Q 5 10 10 0 C 10 -10 20 -10 20 0").bounds().unwrap();
+        assert!(near(min, Point2F(0.0, -7.5)) && near(max, Point2F(20.0, 5.0)));
+        let (min, max) = parse("M10 0 A10 10 0 0 1 -10 0 A10 10 0 0 1 10 0z").bounds().unwrap();
+        assert!(near(min, Point2F(-10.0, -10.0)) && near(max, Point2F(10.0, 10.0)));
+    }
+}
@@ -0,0 +1,286 @@
+//! Geometric queries for hit testing(e.g. clickable rooms of a campus map or icon buttons drawn from SVG documents)
+
+use metrics::*;
+use super::{Path, Polyline, Mesh, FillRule, Transform2D, SvgImage, Shape, Group, Node, Paint, BakedImage};
+use super::sdf::segment_distance;
+
+/// Flattening tolerance of the queries, in the coordinate space of the queried point
+const TOLERANCE: f32 = 0.05;
+
+/// Vector images answering geometric queries in their own coordinate space
+pub trait VectorImage
+{
+    /// Bounding box(min, max) of the painted area including strokes. None if nothing is painted
+    fn bounds(&self) -> Option<(Point2F, Point2F)>;
+    /// Whether the point is in a filled area, with the fill rule of each shape
+    fn fill_contains(&self, p: Point2F) -> bool;
+    /// Distance from the point to the nearest stroked area(0 on a stroke). None if nothing is stroked
+    fn stroke_distance(&self, p: Point2F) -> Option<f32>;
+}
+
+/// Winding number of the figures around the point. Open figures are closed implicitly, as fills do
+pub fn winding_number(figures: &[Polyline], p: Point2F) -> i32
+{
+    figures.iter().map(|f| (0 .. f.points.len()).map(|i|
+    {
+        let (a, b) = (f.points[i], f.points[(i + 1) % f.points.len()]);
+        // crossing number with the ray to +x
+        if (a.1 <= p.1) == (b.1 <= p.1) { return 0; }
+        let x = a.0 + (p.1 - a.1) / (b.1 - a.1) * (b.0 - a.0);
+        if x > p.0 { if b.1 > a.1 { 1 } else { -1 } } else { 0 }
+    }).sum::<i32>()).sum()
+}
+
+impl Path
+{
+    /// Whether the point is inside the path filled with the rule
+    pub fn contains(&self, p: Point2F, rule: FillRule) -> bool { rule.is_inside(winding_number(&self.flatten(TOLERANCE), p)) }
+    /// Distance from the point to the outline(the closing segments of open figures are not included). None for an empty path
+    pub fn outline_distance(&self, p: Point2F) -> Option<f32>
+    {
+        self.flatten(TOLERANCE).iter().flat_map(|f|
+        {
+            let n = if f.closed { f.points.len() } else { f.points.len() - 1 };
+            (0 .. n).map(move |i| segment_distance(p, f.points[i], f.points[(i + 1) % f.points.len()]).0)
+        }).fold(None, |d, x| Some(d.map_or(x, |d: f32| d.min(x))))
+    }
+}
+/// A bare path: filled with the non-zero rule and stroked with a hairline
+impl VectorImage for Path
+{
+    fn bounds(&self) -> Option<(Point2F, Point2F)> { Path::bounds(self) }
+    fn fill_contains(&self, p: Point2F) -> bool { self.contains(p, FillRule::NonZero) }
+    fn stroke_distance(&self, p: Point2F) -> Option<f32> { self.outline_distance(p) }
+}
+
+impl Mesh
+{
+    /// Whether any triangle covers the point(edges included)
+    pub fn contains(&self, p: Point2F) -> bool
+    {
+        self.indices.chunks(3).any(|t|
+        {
+            let (a, b, c) = (self.vertices[t[0] as usize], self.vertices[t[1] as usize], self.vertices[t[2] as usize]);
+            let side = |a: [f32; 2], b: [f32; 2]| (b[0] - a[0]) * (p.1 - a[1]) - (b[1] - a[1]) * (p.0 - a[0]);
+            let (d1, d2, d3) = (side(a, b), side(b, c), side(c, a));
+            let area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+            area != 0.0 && !((d1 < 0.0 || d2 < 0.0 || d3 < 0.0) && (d1 > 0.0 || d2 > 0.0 || d3 > 0.0))
+        })
+    }
+    /// Distance from the point to the covered area(0 inside). None for an empty mesh
+    pub fn distance(&self, p: Point2F) -> Option<f32>
+    {
+        if self.indices.is_empty() { return None; }
+        if self.contains(p) { return Some(0.0); }
+        let point = |i: u32| { let v = self.vertices[i as usize]; Point2F(v[0], v[1]) };
+        self.indices.chunks(3).flat_map(|t| vec![(t[0], t[1]), (t[1], t[2]), (t[2], t[0])])
+            .map(|(a, b)| segment_distance(p, point(a), point(b)).0).fold(None, |d, x| Some(d.map_or(x, |d: f32| d.min(x))))
+    }
+    pub fn bounds(&self) -> Option<(Point2F, Point2F)>
+    {
+        self.vertices.iter().fold(None, |b, v| union(b, Some((Point2F(v[0], v[1]), Point2F(v[0], v[1])))))
+    }
+}
+
+impl Shape
+{
+    /// Whether the point is in the fill. Transparent paints are hit too(as `pointer-events: visiblePainted` does).
+    /// `t` maps the shape into the space of the point
+    pub fn fill_contains(&self, t: &Transform2D, p: Point2F) -> bool
+    {
+        self.style.fill != Paint::None && self.path.transformed(t).contains(p, self.style.fill_rule)
+    }
+    /// Distance from the point to the stroked area. None if the shape is not stroked
+    pub fn stroke_distance(&self, t: &Transform2D, p: Point2F) -> Option<f32>
+    {
+        if self.style.stroke == Paint::None { return None; }
+        self.stroke_mesh(t, TOLERANCE).distance(p)
+    }
+    /// Whether the point is in the fill or within `slop` from the stroke
+    pub fn hit(&self, t: &Transform2D, p: Point2F, slop: f32) -> bool
+    {
+        self.fill_contains(t, p) || self.stroke_distance(t, p).map_or(false, |d| d <= slop)
+    }
+    fn painted_bounds(&self, t: &Transform2D) -> Option<(Point2F, Point2F)>
+    {
+        let fill = if self.style.fill != Paint::None { self.path.transformed(t).bounds() } else { None };
+        let stroke = if self.style.stroke != Paint::None { self.stroke_mesh(t, TOLERANCE).bounds() } else { None };
+        union(fill, stroke)
+    }
+}
+
+/// Result of `SvgImage::hit_test`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit<'a>
+{
+    pub shape: &'a Shape,
+    /// `id`s of the shape and its ancestor groups, innermost first
+    pub ids: Vec<&'a str>
+}
+impl SvgImage
+{
+    /// The topmost shape hit by the point(in the image space), allowing `slop` around strokes
+    pub fn hit_test<'a>(&'a self, p: Point2F, slop: f32) -> Option<Hit<'a>>
+    {
+        fn rec<'a>(g: &'a Group, parent: &Transform2D, p: Point2F, slop: f32) -> Option<Hit<'a>>
+        {
+            let t = g.transform.then(parent);
+            let hit = g.children.iter().rev().filter_map(|c| match *c
+            {
+                Node::Group(ref g) => rec(g, &t, p, slop),
+                Node::Shape(ref s) => if s.hit(&t, p, slop) { Some(Hit { shape: s, ids: s.id.iter().map(|s| s as &str).collect() }) } else { None }
+            }).next();
+            hit.map(|mut h| { h.ids.extend(g.id.as_ref().map(|s| s as &str)); h })
+        }
+        rec(&self.root, &Transform2D::IDENTITY, p, slop)
+    }
+}
+impl VectorImage for SvgImage
+{
+    fn bounds(&self) -> Option<(Point2F, Point2F)>
+    {
+        let mut b = None;
+        self.visit(|s, t, _| b = union(b, s.painted_bounds(t)));
+        b
+    }
+    fn fill_contains(&self, p: Point2F) -> bool
+    {
+        let mut hit = false;
+        self.visit(|s, t, _| hit = hit || s.fill_contains(t, p));
+        hit
+    }
+    fn stroke_distance(&self, p: Point2F) -> Option<f32>
+    {
+        let mut d = None;
+        self.visit(|s, t, _| if let Some(x) = s.stroke_distance(t, p) { d = Some(d.map_or(x, |d: f32| d.min(x))); });
+        d
+    }
+}
+
+impl VectorImage for BakedImage
+{
+    fn bounds(&self) -> Option<(Point2F, Point2F)>
+    {
+        self.shapes.iter().fold(None, |b, s|
+        {
+            let fill = if s.has_fill { s.path.bounds() } else { None };
+            let stroke = if s.stroke != Paint::None { s.stroke_mesh(&Transform2D::IDENTITY, TOLERANCE).bounds() } else { None };
+            union(union(b, fill), stroke)
+        })
+    }
+    fn fill_contains(&self, p: Point2F) -> bool { self.shapes.iter().any(|s| s.has_fill && s.path.contains(p, s.fill_rule)) }
+    fn stroke_distance(&self, p: Point2F) -> Option<f32>
+    {
+        self.shapes.iter().filter(|s| s.stroke != Paint::None).filter_map(|s| s.stroke_mesh(&Transform2D::IDENTITY, TOLERANCE).distance(p))
+            .fold(None, |d, x| Some(d.map_or(x, |d: f32| d.min(x))))
+    }
+}
+
+fn union(a: Option<(Point2F, Point2F)>, b: Option<(Point2F, Point2F)>) -> Option<(Point2F, Point2F)>
+{
+    match (a, b)
+    {
+        (Some((amin, amax)), Some((bmin, bmax))) =>
+            Some((Point2F(amin.0.min(bmin.0), amin.1.min(bmin.1)), Point2F(amax.0.max(bmax.0), amax.1.max(bmax.1)))),
+        (a, None) => a, (None, b) => b
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn square(path: &mut Path, min: f32, max: f32) -> &mut Path
+    {
+        path.move_to(Point2F(min, min)).line_to(Point2F(max, min)).line_to(Point2F(max, max)).line_to(Point2F(min, max)).close()
+    }
+
+    #[test] fn path_queries()
+    {
+        let mut path = Path::new();
+        square(square(&mut path, 0.0, 10.0), 3.0, 7.0);
+        // the inner square winds the same way: a hole only with the even-odd rule
+        assert!(path.contains(Point2F(5.0, 5.0), FillRule::NonZero));
+        assert!(!path.contains(Point2F(5.0, 5.0), FillRule::EvenOdd));
+        assert!(path.contains(Point2F(1.0, 5.0), FillRule::EvenOdd));
+        assert!(!path.contains(Point2F(11.0, 5.0), FillRule::NonZero));
+        assert_eq!(path.outline_distance(Point2F(5.0, 5.0)), Some(2.0));
+        assert_eq!(path.outline_distance(Point2F(13.0, 14.0)), Some(5.0));
+        assert_eq!(Path::new().outline_distance(Point2F(0.0, 0.0)), None);
+
+        let mut open = Path::new();
+        open.move_to(Point2F(0.0, 0.0)).line_to(Point2F(10.0, 0.0)).line_to(Point2F(10.0, 10.0));
+        // filled as if closed, but the closing segment is not a part of the outline
+        assert!(open.contains(Point2F(8.0, 2.0), FillRule::NonZero));
+        assert!((open.outline_distance(Point2F(2.0, 8.0)).unwrap() - 8.0).abs() < 1.0e-4);
+    }
+
+    #[test] fn mesh_distance()
+    {
+        let mesh = Mesh { vertices: vec![[0.0, 0.0], [4.0, 0.0], [0.0, 4.0]], indices: vec![0, 1, 2] };
+        assert!(mesh.contains(Point2F(1.0, 1.0)) && mesh.contains(Point2F(2.0, 0.0)));
+        assert_eq!(mesh.distance(Point2F(1.0, 1.0)), Some(0.0));
+        assert_eq!(mesh.distance(Point2F(-3.0, 2.0)), Some(3.0));
+        assert_eq!(mesh.bounds(), Some((Point2F(0.0, 0.0), Point2F(4.0, 4.0))));
+        assert_eq!(Mesh::new().distance(Point2F(0.0, 0.0)), None);
+    }
+
+    #[test] fn svg_queries()
+    {
+        let svg = SvgImage::parse(r##"<svg width="100" height="100">
+            <g id="room-101" transform="translate(10 10)"><rect id="floor" width="20" height="20" fill="#ccc"/></g>
+            <g id="room-102"><rect x="15" y="15" width="20" height="20" fill="none" stroke="#000" stroke-width="2"/></g>
+            <circle id="hole" cx="70" cy="70" r="10" fill-opacity="0"/>
+            <line x1="60" y1="10" x2="90" y2="10" stroke="#000" stroke-width="4" transform="scale(1 2)"/></svg>"##).unwrap();
+
+        assert!(svg.fill_contains(Point2F(12.0, 12.0)));
+        assert!(svg.fill_contains(Point2F(70.0, 75.0)));
+        assert!(!svg.fill_contains(Point2F(25.0, 40.0)));
+        // the line is stroked 4 units wide in its own space: 8 in the image
+        assert_eq!(svg.stroke_distance(Point2F(75.0, 23.0)), Some(0.0));
+        assert!((svg.stroke_distance(Point2F(75.0, 28.0)).unwrap() - 4.0).abs() < 1.0e-3);
+        let (min, max) = svg.bounds().unwrap();
+        assert!((min.0 - 10.0).abs() < 1.0e-3 && (min.1 - 10.0).abs() < 1.0e-3);
+        assert!((max.0 - 90.0).abs() < 1.0e-3 && (max.1 - 80.0).abs() < 1.0e-3);
+
+        let ids = |p: Point2F, slop: f32| svg.hit_test(p, slop).map(|h| h.ids);
+        assert_eq!(ids(Point2F(12.0, 12.0), 0.0), Some(vec!["floor", "room-101"]));
+        // the outline drawn over the room wins, but its unfilled inside does not
+        assert_eq!(ids(Point2F(15.5, 25.0), 0.0), Some(vec!["room-102"]));
+        assert_eq!(ids(Point2F(25.0, 25.0), 0.0), Some(vec!["floor", "room-101"]));
+        assert_eq!(ids(Point2F(33.0, 25.0), 0.0), None);
+        assert_eq!(ids(Point2F(38.0, 25.0), 2.5), Some(vec!["room-102"]));
+        assert_eq!(ids(Point2F(70.0, 70.0), 0.0), Some(vec!["hole"]));
+    }
+
+    #[test] fn baked_queries()
+    {
+        let svg = SvgImage::parse(r##"<svg width="20" height="20"><path d="M0 0 H20 V20 H0 z M5 5 H15 V15 H5 z" fill-rule="evenodd"/>
+            <rect width="5" height="5" fill="none" stroke="#000"/></svg>"##).unwrap();
+        let baked = BakedImage::from_svg(&svg);
+        assert!(baked.fill_contains(Point2F(2.0, 10.0)));
+        assert!(!baked.fill_contains(Point2F(10.0, 10.0)));
+        // strokes are baked too
+        assert_eq!(baked.stroke_distance(Point2F(0.0, 0.0)), Some(0.0));
+        assert!((baked.stroke_distance(Point2F(2.5, 2.5)).unwrap() - 2.0).abs() < 1.0e-3);
+        assert_eq!(baked.bounds(), Some((Point2F(-0.5, -0.5), Point2F(20.0, 20.0))));
+    }
+    #[test] fn baked_transparent_fill()
+    {
+        let svg = SvgImage::parse(r##"<svg width="100" height="100"><circle id="hole" cx="70" cy="70" r="10" fill-opacity="0"/>
+            <rect width="10" height="10" fill="none"/></svg>"##).unwrap();
+        let baked = BakedImage::from_svg(&svg);
+        // nothing is painted, but the transparent fill is hit as the document does
+        assert_eq!(baked.shapes[0].fill, Paint::None);
+        assert!(svg.fill_contains(Point2F(70.0, 75.0)) && baked.fill_contains(Point2F(70.0, 75.0)));
+        assert!(!svg.fill_contains(Point2F(5.0, 5.0)) && !baked.fill_contains(Point2F(5.0, 5.0)));
+        assert!(baked.meshes(&Transform2D::IDENTITY, 0.1).is_empty());
+        // kept through the baked form
+        let mut bytes = Vec::new();
+        baked.write(&mut bytes).unwrap();
+        let read = BakedImage::read(&mut &bytes[..]).unwrap();
+        assert!(read.fill_contains(Point2F(70.0, 75.0)) && !read.fill_contains(Point2F(5.0, 5.0)));
+        assert_eq!(read.bounds(), svg.bounds());
+    }
+}
@@ -0,0 +1,187 @@
+//! CPU rasterization of tessellated meshes
+
+use metrics::*;
+use color::Color;
+use super::{Mesh, Paint, Polyline};
+
+/// Straight(non-premultiplied) RGBA8 pixels, in the same layout as `render::CapturedImage`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RasterImage { pub size: Size2U, pub pixels: Vec<u8> }
+impl RasterImage
+{
+    /// Samples per pixel along each axis
+    const SUBSAMPLES: usize = 4;
+
+    /// Fully transparent image
+    pub fn new(size: Size2U) -> Self { RasterImage { size, pixels: vec![0; size.x() as usize * size.y() as usize * 4] } }
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4]
+    {
+        let o = (y as usize * self.size.x() as usize + x as usize) * 4;
+        [self.pixels[o], self.pixels[o + 1], self.pixels[o + 2], self.pixels[o + 3]]
+    }
+
+    /// Composites the mesh(in pixel coordinates) with a solid color, antialiased by 4x4 supersampling.
+    /// Overlapping triangles of the same mesh cover each sample only once
+    pub fn fill_mesh(&mut self, mesh: &Mesh, color: Color) { self.composite(mesh, |_| color); }
+    /// Composites the mesh with the paint(resolved into pixel coordinates). Gradients are sampled at the pixel centers
+    pub fn paint_mesh(&mut self, mesh: &Mesh, paint: &Paint)
+    {
+        match *paint
+        {
+            Paint::None => (),
+            Paint::Color(c) => self.fill_mesh(mesh, c),
+            Paint::Gradient(ref g) => self.composite(mesh, |p| g.color_at(p))
+        }
+    }
+    fn composite<F: Fn(Point2F) -> Color>(&mut self, mesh: &Mesh, color_at: F)
+    {
+        let (w, h) = (self.size.x() as usize, self.size.y() as usize);
+        if w == 0 || h == 0 { return; }
+        // one bit per sample
+        let mut coverage = vec![0u16; w * h];
+        for tri in mesh.indices.chunks(3).filter(|t| t.len() == 3)
+        {
+            let (a, b, c) = (mesh.vertices[tri[0] as usize], mesh.vertices[tri[1] as usize], mesh.vertices[tri[2] as usize]);
+            let area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+            if area == 0.0 { continue; }
+            let clamp = |v: f32, max: usize| if v < 0.0 { 0 } else { (v as usize).min(max) };
+            let (x0, x1) = (clamp(a[0].min(b[0]).min(c[0]).floor(), w), clamp(a[0].max(b[0]).max(c[0]).ceil(), w));
+            let (y0, y1) = (clamp(a[1].min(b[1]).min(c[1]).floor(), h), clamp(a[1].max(b[1]).max(c[1]).ceil(), h));
+            let inside = |px: f32, py: f32|
+            {
+                let e0 = (b[0] - a[0]) * (py - a[1]) - (b[1] - a[1]) * (px - a[0]);
+                let e1 = (c[0] - b[0]) * (py - b[1]) - (c[1] - b[1]) * (px - b[0]);
+                let e2 = (a[0] - c[0]) * (py - c[1]) - (a[1] - c[1]) * (px - c[0]);
+                if area > 0.0 { e0 >= 0.0 && e1 >= 0.0 && e2 >= 0.0 } else { e0 <= 0.0 && e1 <= 0.0 && e2 <= 0.0 }
+            };
+            for y in y0 .. y1
+            {
+                for x in x0 .. x1
+                {
+                    let mut bits = 0u16;
+                    for s in 0 .. Self::SUBSAMPLES * Self::SUBSAMPLES
+                    {
+                        let sx = x as f32 + ((s % Self::SUBSAMPLES) as f32 + 0.5) / Self::SUBSAMPLES as f32;
+                        let sy = y as f32 + ((s / Self::SUBSAMPLES) as f32 + 0.5) / Self::SUBSAMPLES as f32;
+                        if inside(sx, sy) { bits |= 1 << s; }
+                    }
+                    coverage[y * w + x] |= bits;
+                }
+            }
+        }
+
+        for (i, &bits) in coverage.iter().enumerate().filter(|&(_, &b)| b != 0)
+        {
+            let color = color_at(Point2F((i % w) as f32 + 0.5, (i / w) as f32 + 0.5));
+            let alpha = color.3 * bits.count_ones() as f32 / (Self::SUBSAMPLES * Self::SUBSAMPLES) as f32;
+            let dst = &mut self.pixels[i * 4 .. i * 4 + 4];
+            // source-over
+            let dst_alpha = dst[3] as f32 / 255.0;
+            let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+            if out_alpha <= 0.0 { continue; }
+            for (d, &s) in dst[.. 3].iter_mut().zip(&[color.0, color.1, color.2])
+            {
+                let v = (s * alpha + *d as f32 / 255.0 * dst_alpha * (1.0 - alpha)) / out_alpha;
+                *d = (v * 255.0).round().max(0.0).min(255.0) as u8;
+            }
+            dst[3] = (out_alpha * 255.0).round().min(255.0) as u8;
+        }
+    }
+}
+
+/// Antialiased coverage(0-255, one byte per pixel) of the figures closed implicitly, by accumulating exact signed areas.
+/// Overlaps of the same direction saturate as with the non-zero rule; opposite directions cancel out(holes)
+pub fn coverage_mask(figures: &[Polyline], size: Size2U) -> Vec<u8>
+{
+    let (w, h) = (size.x() as usize, size.y() as usize);
+    // the last cells may receive the right halves of the edges on the right border
+    let mut acc = vec![0.0f32; w * h + 2];
+    for f in figures
+    {
+        let n = f.points.len();
+        for i in 0 .. n
+        {
+            let clamp = |p: Point2F| Point2F(p.0.max(0.0).min(w as f32), p.1);
+            accumulate_line(&mut acc, w, h, clamp(f.points[i]), clamp(f.points[(i + 1) % n]));
+        }
+    }
+    let mut sum = 0.0;
+    acc[.. w * h].iter().map(|&a| { sum += a; (sum.abs().min(1.0) * 255.0).round() as u8 }).collect()
+}
+/// Adds the signed area the edge covers in each cell on its left side to the cell, and the remainder to the next one
+fn accumulate_line(acc: &mut [f32], w: usize, h: usize, p0: Point2F, p1: Point2F)
+{
+    if p0.1 == p1.1 { return; }
+    let (dir, p0, p1) = if p0.1 < p1.1 { (1.0, p0, p1) } else { (-1.0, p1, p0) };
+    let dxdy = (p1.0 - p0.0) / (p1.1 - p0.1);
+    let mut x = if p0.1 < 0.0 { p0.0 - p0.1 * dxdy } else { p0.0 };
+    for y in (p0.1.max(0.0) as usize) .. (p1.1.ceil() as usize).min(h)
+    {
+        let line = y * w;
+        let dy = ((y + 1) as f32).min(p1.1) - (y as f32).max(p0.1);
+        let xnext = x + dxdy * dy;
+        let d = dy * dir;
+        let (x0, x1) = if x < xnext { (x, xnext) } else { (xnext, x) };
+        let (x0floor, x1ceil) = (x0.floor(), x1.ceil());
+        let (x0i, x1i) = (x0floor as usize, x1ceil as usize);
+        if x1i <= x0i + 1
+        {
+            // within a cell: split at the mean x
+            let xmf = 0.5 * (x + xnext) - x0floor;
+            acc[line + x0i] += d - d * xmf;
+            acc[line + x0i + 1] += d * xmf;
+        }
+        else
+        {
+            let s = (x1 - x0).recip();
+            let x0f = x0 - x0floor;
+            let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+            let x1f = x1 - x1ceil + 1.0;
+            let am = 0.5 * s * x1f * x1f;
+            acc[line + x0i] += d * a0;
+            if x1i == x0i + 2 { acc[line + x0i + 1] += d * (1.0 - a0 - am); }
+            else
+            {
+                let a1 = s * (1.5 - x0f);
+                acc[line + x0i + 1] += d * (a1 - a0);
+                for xi in x0i + 2 .. x1i - 1 { acc[line + xi] += d * s; }
+                let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+                acc[line + x1i - 1] += d * (1.0 - a2 - am);
+            }
+            acc[line + x1i] += d * am;
+        }
+        x = xnext;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Polyline
+    {
+        Polyline { points: vec![Point2F(x0, y0), Point2F(x1, y0), Point2F(x1, y1), Point2F(x0, y1)], closed: true }
+    }
+
+    #[test] fn coverage()
+    {
+        let mask = coverage_mask(&[rect(1.0, 1.0, 3.5, 3.0)], Size2U(4, 4));
+        assert_eq!(&mask[4 .. 8], &[0, 255, 255, 128]);
+        assert_eq!(&mask[12 .. 16], &[0, 0, 0, 0]);
+
+        // a diagonal halves the cells it passes through
+        let tri = Polyline { points: vec![Point2F(0.0, 0.0), Point2F(4.0, 4.0), Point2F(0.0, 4.0)], closed: true };
+        let mask = coverage_mask(&[tri], Size2U(4, 4));
+        assert_eq!(&mask[8 .. 12], &[255, 255, 128, 0]);
+
+        // a hole in the opposite direction
+        let mut hole = rect(1.0, 1.0, 3.0, 3.0);
+        hole.points.reverse();
+        let mask = coverage_mask(&[rect(0.0, 0.0, 4.0, 4.0), hole], Size2U(4, 4));
+        assert_eq!(&mask[4 .. 8], &[255, 0, 0, 255]);
+        // overlaps saturate
+        let mask = coverage_mask(&[rect(0.0, 0.0, 2.0, 2.0), rect(1.0, 0.0, 3.0, 2.0)], Size2U(4, 4));
+        assert_eq!(&mask[0 .. 4], &[255, 255, 255, 0]);
+    }
+}
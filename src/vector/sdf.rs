@@ -0,0 +1,372 @@
+//! Signed distance field generation
+//!
+//! Distances are encoded as `0.5 + d / (2 * spread)` in [0, 1] where d is the signed distance in output pixels(positive inside).
+//! Multi-channel fields follow the method of Chlumsky's msdfgen: the shape is reconstructed by the median of the channels.
+
+use metrics::*;
+use super::{Polyline, FillRule, Transform2D, SvgImage, Paint, winding_number};
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SdfParams
+{
+    /// Resolution of the field
+    pub size: Size2U,
+    /// Distance in output pixels mapped to the whole range on each side of the edge
+    pub spread: f32
+}
+impl SdfParams
+{
+    fn encode(&self, d: f32) -> u8 { ((0.5 + d / (2.0 * self.spread)).max(0.0).min(1.0) * 255.0).round() as u8 }
+}
+
+/// 1(single channel) or 3(RGB) channels per pixel, rows from the top
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistanceField { pub size: Size2U, pub channels: usize, pub pixels: Vec<u8> }
+impl DistanceField
+{
+    /// Decoded value of the median(multi-channel) or the only channel
+    pub fn value(&self, x: u32, y: u32) -> f32
+    {
+        let o = (y as usize * self.size.x() as usize + x as usize) * self.channels;
+        if self.channels == 3 { median(self.pixels[o] as f32, self.pixels[o + 1] as f32, self.pixels[o + 2] as f32) / 255.0 }
+        else { self.pixels[o] as f32 / 255.0 }
+    }
+}
+
+impl SvgImage
+{
+    /// Distance field of the filled shapes scaled to `params.size`
+    pub fn distance_field(&self, params: &SdfParams, multi_channel: bool) -> DistanceField
+    {
+        let scale = Transform2D::scale(params.size.x() as f32 / self.size.x(), params.size.y() as f32 / self.size.y());
+        let mut shapes = Vec::new();
+        self.visit(|s, t, _| if s.style.fill != Paint::None
+        {
+            shapes.push((s.path.transformed(&t.then(&scale)).flatten(0.05), s.style.fill_rule));
+        });
+        let shapes: Vec<_> = shapes.iter().map(|&(ref f, r)| (&f[..], r)).collect();
+        if multi_channel { msdf_from_shapes(&shapes, params) } else { sdf_from_shapes(&shapes, params) }
+    }
+}
+
+/// Single channel field of the union of the shapes(figures in output pixel coordinates, with their fill rules)
+pub fn sdf_from_shapes(shapes: &[(&[Polyline], FillRule)], params: &SdfParams) -> DistanceField
+{
+    let (w, h) = (params.size.x() as usize, params.size.y() as usize);
+    let segments: Vec<(Point2F, Point2F)> = shapes.iter().flat_map(|&(f, _)| f.iter()).flat_map(figure_segments).collect();
+    let mut pixels = Vec::with_capacity(w * h);
+    for y in 0 .. h
+    {
+        for x in 0 .. w
+        {
+            let p = Point2F(x as f32 + 0.5, y as f32 + 0.5);
+            let d = segments.iter().map(|&(a, b)| segment_distance(p, a, b).0).fold(::std::f32::INFINITY, f32::min);
+            pixels.push(params.encode(if is_inside(shapes, p) { d } else { -d }));
+        }
+    }
+    DistanceField { size: params.size, channels: 1, pixels }
+}
+
+/// Multi-channel field. Edges are split at corners and colored so that the corners stay sharp when magnified.
+/// Pixels whose median disagrees with the actual inside/outside test fall back to the single channel distance
+pub fn msdf_from_shapes(shapes: &[(&[Polyline], FillRule)], params: &SdfParams) -> DistanceField
+{
+    let (w, h) = (params.size.x() as usize, params.size.y() as usize);
+    // figures are expected to be oriented consistently(holes reversed); the sign is flipped if they are mostly negative
+    let orientation = if shapes.iter().flat_map(|&(f, _)| f.iter()).map(signed_area).sum::<f32>() < 0.0 { -1.0 } else { 1.0 };
+    let edges: Vec<ColoredEdge> = shapes.iter().flat_map(|&(f, _)| f.iter()).flat_map(color_edges).collect();
+    let mut pixels = Vec::with_capacity(w * h * 3);
+    for y in 0 .. h
+    {
+        for x in 0 .. w
+        {
+            let p = Point2F(x as f32 + 0.5, y as f32 + 0.5);
+            let inside = is_inside(shapes, p);
+            // nearest edge of each channel: (distance, orthogonality, edge)
+            let mut nearest: [Option<(f32, f32, &ColoredEdge)>; 3] = [None, None, None];
+            let mut true_distance = ::std::f32::INFINITY;
+            for e in &edges
+            {
+                let (d, ortho) = e.distance(p);
+                true_distance = true_distance.min(d);
+                for (_, n) in nearest.iter_mut().enumerate().filter(|&(ch, _)| e.channels & (1 << ch) != 0)
+                {
+                    let closer = match *n
+                    {
+                        None => true,
+                        Some((nd, no, _)) => d < nd - 1.0e-4 || ((d - nd).abs() <= 1.0e-4 && ortho > no)
+                    };
+                    if closer { *n = Some((d, ortho, e)); }
+                }
+            }
+            let fallback = if inside { true_distance } else { -true_distance };
+            let mut channels = [fallback; 3];
+            for (c, n) in channels.iter_mut().zip(&nearest)
+            {
+                if let Some((_, _, e)) = *n { *c = e.signed_pseudo_distance(p) * orientation; }
+            }
+            if (median(channels[0], channels[1], channels[2]) > 0.0) != inside { channels = [fallback; 3]; }
+            pixels.extend(channels.iter().map(|&d| params.encode(d)));
+        }
+    }
+    DistanceField { size: params.size, channels: 3, pixels }
+}
+
+/// Single channel field from a high resolution coverage bitmap(8bit, rows from the top; texels >= 128 are inside)
+pub fn sdf_from_bitmap(coverage: &[u8], size: Size2U, params: &SdfParams) -> DistanceField
+{
+    let (sw, sh) = (size.x() as usize, size.y() as usize);
+    if sw == 0 || sh == 0 { return DistanceField { size: params.size, channels: 1, pixels: vec![0; params.size.x() as usize * params.size.y() as usize] }; }
+    let inside: Vec<bool> = coverage.iter().map(|&c| c >= 128).collect();
+    let to_inside = squared_edt(&inside, sw, sh, true);
+    let to_outside = squared_edt(&inside, sw, sh, false);
+    // signed distance in source pixels: the edge lies halfway between the texel centers
+    let signed: Vec<f32> = inside.iter().zip(to_inside.iter().zip(&to_outside))
+        .map(|(&i, (&di, &dout))| if i { dout.sqrt() - 0.5 } else { 0.5 - di.sqrt() }).collect();
+
+    let (w, h) = (params.size.x() as usize, params.size.y() as usize);
+    let (rx, ry) = (sw as f32 / w as f32, sh as f32 / h as f32);
+    // source pixels to output pixels
+    let unit = 2.0 / (rx + ry);
+    let sample = |x: usize, y: usize| signed[y.min(sh - 1) * sw + x.min(sw - 1)];
+    let mut pixels = Vec::with_capacity(w * h);
+    for y in 0 .. h
+    {
+        for x in 0 .. w
+        {
+            // bilinear sampling at the center of the output pixel
+            let (sx, sy) = (((x as f32 + 0.5) * rx - 0.5).max(0.0), ((y as f32 + 0.5) * ry - 0.5).max(0.0));
+            let (x0, y0) = (sx.floor() as usize, sy.floor() as usize);
+            let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+            let top = sample(x0, y0) * (1.0 - fx) + sample(x0 + 1, y0) * fx;
+            let bottom = sample(x0, y0 + 1) * (1.0 - fx) + sample(x0 + 1, y0 + 1) * fx;
+            pixels.push(params.encode((top * (1.0 - fy) + bottom * fy) * unit));
+        }
+    }
+    DistanceField { size: params.size, channels: 1, pixels }
+}
+
+/// Squared euclidean distance to the nearest texel whose `inside` equals `target`(Felzenszwalb and Huttenlocher)
+fn squared_edt(inside: &[bool], w: usize, h: usize, target: bool) -> Vec<f32>
+{
+    const FAR: f32 = 1.0e20;
+    let mut grid: Vec<f32> = inside.iter().map(|&i| if i == target { 0.0 } else { FAR }).collect();
+    let mut buf = Vec::new();
+    for y in 0 .. h { edt_1d(&mut grid[y * w .. (y + 1) * w], &mut buf); }
+    let mut column = vec![0.0; h];
+    for x in 0 .. w
+    {
+        for y in 0 .. h { column[y] = grid[y * w + x]; }
+        edt_1d(&mut column, &mut buf);
+        for y in 0 .. h { grid[y * w + x] = column[y]; }
+    }
+    grid
+}
+/// Lower envelope of parabolas rooted at (q, f(q))
+fn edt_1d(f: &mut [f32], buf: &mut Vec<f32>)
+{
+    let n = f.len();
+    if n == 0 { return; }
+    let (mut v, mut z) = (vec![0usize; n], vec![0.0f32; n + 1]);
+    let intersection = |f: &[f32], q: usize, r: usize| ((f[q] + (q * q) as f32) - (f[r] + (r * r) as f32)) / (2.0 * (q as f32 - r as f32));
+    let mut k = 0;
+    z[0] = -::std::f32::INFINITY; z[1] = ::std::f32::INFINITY;
+    for q in 1 .. n
+    {
+        let mut s = intersection(f, q, v[k]);
+        while s <= z[k] { k -= 1; s = intersection(f, q, v[k]); }
+        k += 1; v[k] = q; z[k] = s; z[k + 1] = ::std::f32::INFINITY;
+    }
+    k = 0;
+    buf.clear();
+    for q in 0 .. n
+    {
+        while z[k + 1] < q as f32 { k += 1; }
+        let d = q as f32 - v[k] as f32;
+        buf.push(d * d + f[v[k]]);
+    }
+    f.copy_from_slice(buf);
+}
+
+/// A run of segments between corners, with the channels(bit 0 = R, 1 = G, 2 = B) it contributes to
+struct ColoredEdge { points: Vec<Point2F>, channels: u8 }
+impl ColoredEdge
+{
+    /// Unsigned distance and orthogonality(|sin| of the angle between the segment and the direction to the point) for tie-breaking
+    fn distance(&self, p: Point2F) -> (f32, f32)
+    {
+        self.points.windows(2).map(|s|
+        {
+            let (d, t) = segment_distance(p, s[0], s[1]);
+            let c = closest(s[0], s[1], t);
+            let (dx, dy) = (p.0 - c.0, p.1 - c.1);
+            let (sx, sy) = (s[1].0 - s[0].0, s[1].1 - s[0].1);
+            let (l1, l2) = ((dx * dx + dy * dy).sqrt(), (sx * sx + sy * sy).sqrt());
+            (d, if l1 * l2 > 0.0 { ((sx * dy - sy * dx) / (l1 * l2)).abs() } else { 0.0 })
+        }).fold((::std::f32::INFINITY, 0.0), |a, b| if b.0 < a.0 - 1.0e-4 || ((b.0 - a.0).abs() <= 1.0e-4 && b.1 > a.1) { b } else { a })
+    }
+    /// Signed distance where the ends are extended as rays.
+    /// Positive on the side of the positive cross product, which is the inside of figures with positive area
+    fn signed_pseudo_distance(&self, p: Point2F) -> f32
+    {
+        let last = self.points.len() - 2;
+        let (mut best, mut best_abs) = (0.0, ::std::f32::INFINITY);
+        for (i, s) in self.points.windows(2).enumerate()
+        {
+            let (a, b) = (s[0], s[1]);
+            let (_, t) = segment_distance(p, a, b);
+            let raw_t = project(p, a, b);
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            let len = (dx * dx + dy * dy).sqrt();
+            let perpendicular = (dx * (p.1 - a.1) - dy * (p.0 - a.0)) / len;
+            let extended = (i == 0 && raw_t < 0.0) || (i == last && raw_t > 1.0);
+            let d = if extended { perpendicular }
+            else
+            {
+                let c = closest(a, b, t);
+                let dist = ((p.0 - c.0) * (p.0 - c.0) + (p.1 - c.1) * (p.1 - c.1)).sqrt();
+                if perpendicular < 0.0 { -dist } else { dist }
+            };
+            if d.abs() < best_abs - 1.0e-6 { best = d; best_abs = d.abs(); }
+        }
+        best
+    }
+}
+
+/// Splits the figure at corners and assigns colors(two channels each) so that adjacent edges differ
+fn color_edges(f: &Polyline) -> Vec<ColoredEdge>
+{
+    const CYAN: u8 = 0b110; const MAGENTA: u8 = 0b101; const YELLOW: u8 = 0b011; const WHITE: u8 = 0b111;
+    // sin of the minimum angle between the directions to be a corner
+    let corner_sin = (PI - 3.0).sin();
+
+    let mut points = f.points.clone();
+    points.dedup();
+    if points.len() > 1 && points[0] == points[points.len() - 1] { points.pop(); }
+    if points.len() < 2 { return Vec::new(); }
+    // figures are always closed for filling
+    let n = points.len();
+    let dir = |i: usize| { let (a, b) = (points[i % n], points[(i + 1) % n]); Point2F(b.0 - a.0, b.1 - a.1) };
+    let corners: Vec<usize> = (0 .. n).filter(|&i|
+    {
+        let (d0, d1) = (dir(i + n - 1), dir(i));
+        let (dot, cross) = (d0.0 * d1.0 + d0.1 * d1.1, d0.0 * d1.1 - d0.1 * d1.0);
+        let (l0, l1) = ((d0.0 * d0.0 + d0.1 * d0.1).sqrt(), (d1.0 * d1.0 + d1.1 * d1.1).sqrt());
+        dot <= 0.0 || (cross / (l0 * l1)).abs() > corner_sin
+    }).collect();
+
+    let run = |from: usize, to: usize| (from .. to + 1).map(|i| points[i % n]).collect::<Vec<_>>();
+    match corners.len()
+    {
+        // smooth: any channel works
+        0 => vec![ColoredEdge { points: run(0, n), channels: WHITE }],
+        // teardrop: split into three parts
+        1 =>
+        {
+            let c = corners[0];
+            if n < 3 { return vec![ColoredEdge { points: run(c, c + n), channels: WHITE }]; }
+            let (s1, s2) = (c + n / 3, c + n * 2 / 3);
+            vec![
+                ColoredEdge { points: run(c, s1), channels: MAGENTA },
+                ColoredEdge { points: run(s1, s2), channels: WHITE },
+                ColoredEdge { points: run(s2, c + n), channels: YELLOW }
+            ]
+        },
+        m =>
+        {
+            let cycle = [CYAN, MAGENTA, YELLOW];
+            (0 .. m).map(|i|
+            {
+                let (from, to) = (corners[i], if i + 1 < m { corners[i + 1] } else { corners[0] + n });
+                // the last edge must also differ from the first one
+                let channels = if i == m - 1 && m % 3 == 1 { MAGENTA } else { cycle[i % 3] };
+                ColoredEdge { points: run(from, to), channels }
+            }).collect()
+        }
+    }
+}
+
+fn figure_segments(f: &Polyline) -> Vec<(Point2F, Point2F)>
+{
+    let n = f.points.len();
+    (0 .. n).map(|i| (f.points[i], f.points[(i + 1) % n])).collect()
+}
+fn is_inside(shapes: &[(&[Polyline], FillRule)], p: Point2F) -> bool
+{
+    shapes.iter().any(|&(figures, rule)| rule.is_inside(winding_number(figures, p)))
+}
+fn signed_area(f: &Polyline) -> f32 { figure_segments(f).iter().map(|&(a, b)| a.0 * b.1 - b.0 * a.1).sum::<f32>() * 0.5 }
+/// Parameter of the projection onto the line through a and b
+fn project(p: Point2F, a: Point2F, b: Point2F) -> f32
+{
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let l2 = dx * dx + dy * dy;
+    if l2 <= 0.0 { 0.0 } else { ((p.0 - a.0) * dx + (p.1 - a.1) * dy) / l2 }
+}
+fn closest(a: Point2F, b: Point2F, t: f32) -> Point2F { Point2F(a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t) }
+/// Distance to the segment and the parameter of the closest point
+pub(super) fn segment_distance(p: Point2F, a: Point2F, b: Point2F) -> (f32, f32)
+{
+    let t = project(p, a, b).max(0.0).min(1.0);
+    let c = closest(a, b, t);
+    (((p.0 - c.0) * (p.0 - c.0) + (p.1 - c.1) * (p.1 - c.1)).sqrt(), t)
+}
+fn median(a: f32, b: f32, c: f32) -> f32 { a.min(b).max(a.max(b).min(c)) }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn square() -> Vec<Polyline>
+    {
+        vec![Polyline { points: vec![Point2F(10.0, 10.0), Point2F(22.0, 10.0), Point2F(22.0, 22.0), Point2F(10.0, 22.0)], closed: true }]
+    }
+    const PARAMS: SdfParams = SdfParams { size: Size2U(32, 32), spread: 4.0 };
+    fn decoded(d: f32) -> f32 { (((0.5 + d / 8.0).max(0.0).min(1.0) * 255.0).round()) / 255.0 }
+
+    #[test] fn single_channel()
+    {
+        let f = square();
+        let field = sdf_from_shapes(&[(&f[..], FillRule::NonZero)], &PARAMS);
+        assert_eq!(field.value(16, 16), 1.0);
+        assert_eq!(field.value(10, 16), decoded(0.5));
+        assert_eq!(field.value(8, 16), decoded(-1.5));
+        assert_eq!(field.value(23, 23), decoded(-(1.5f32 * 1.5 * 2.0).sqrt()));
+        assert_eq!(field.value(0, 0), 0.0);
+    }
+    #[test] fn multi_channel()
+    {
+        for f in vec![square(), square().into_iter().map(|mut p| { p.points.reverse(); p }).collect()]
+        {
+            let field = msdf_from_shapes(&[(&f[..], FillRule::NonZero)], &PARAMS);
+            for y in 0 .. 32
+            {
+                for x in 0 .. 32
+                {
+                    let inside = 10 <= x && x < 22 && 10 <= y && y < 22;
+                    assert_eq!(field.value(x, y) > 0.5, inside, "({}, {})", x, y);
+                }
+            }
+            // the corner is kept sharp: the median follows the extended edges
+            assert!((field.value(23, 23) - decoded(-1.5)).abs() < 1.0e-3);
+            assert_eq!(field.value(16, 10), decoded(0.5));
+        }
+    }
+    #[test] fn from_bitmap()
+    {
+        let coverage: Vec<u8> = (0 .. 64 * 64).map(|i|
+        {
+            let (x, y) = ((i % 64) as f32 + 0.5 - 32.0, (i / 64) as f32 + 0.5 - 32.0);
+            if x * x + y * y <= 400.0 { 255 } else { 0 }
+        }).collect();
+        let field = sdf_from_bitmap(&coverage, Size2U(64, 64), &SdfParams { size: Size2U(16, 16), spread: 4.0 });
+        for &(x, y) in &[(8, 8), (12, 8), (13, 8), (3, 3), (5, 10)]
+        {
+            let (cx, cy) = ((x as f32 + 0.5) * 4.0 - 32.0, (y as f32 + 0.5) * 4.0 - 32.0);
+            let expected = (20.0 - (cx * cx + cy * cy).sqrt()) / 4.0;
+            assert!((field.value(x, y) - decoded(expected)).abs() < 0.03, "({}, {}): {} != {}", x, y, field.value(x, y), decoded(expected));
+        }
+    }
+}
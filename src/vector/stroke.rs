@@ -0,0 +1,263 @@
+//! Stroke geometry
+
+use metrics::*;
+use super::{Path, Polyline, Mesh, FillRule, tessellate_fill};
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin { Miter, Round, Bevel }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap { Butt, Round, Square }
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeStyle
+{
+    pub width: f32, pub join: LineJoin, pub cap: LineCap,
+    /// Limit of the ratio of the miter length to the width. Miter joins exceeding this are beveled
+    pub miter_limit: f32,
+    /// Lengths of alternating dashes and gaps(repeated twice if odd as SVG does). Empty for a solid line
+    pub dash_array: Vec<f32>, pub dash_offset: f32
+}
+impl Default for StrokeStyle
+{
+    fn default() -> Self
+    {
+        StrokeStyle { width: 1.0, join: LineJoin::Miter, cap: LineCap::Butt, miter_limit: 4.0, dash_array: Vec::new(), dash_offset: 0.0 }
+    }
+}
+impl StrokeStyle
+{
+    pub fn new(width: f32) -> Self { StrokeStyle { width, .. Default::default() } }
+    pub fn join(mut self, join: LineJoin) -> Self { self.join = join; self }
+    pub fn cap(mut self, cap: LineCap) -> Self { self.cap = cap; self }
+    pub fn miter_limit(mut self, limit: f32) -> Self { self.miter_limit = limit; self }
+    pub fn dashes(mut self, array: Vec<f32>, offset: f32) -> Self { self.dash_array = array; self.dash_offset = offset; self }
+}
+
+impl Path
+{
+    pub fn stroke(&self, style: &StrokeStyle, tolerance: f32) -> Mesh { stroke_polylines(&self.flatten(tolerance), style, tolerance) }
+}
+
+/// Generates the outline of the strokes as a triangle mesh.
+/// Outlines of segments, joins and caps are merged by the fill tessellator so that no area is covered twice
+/// (translucent strokes are blended once even at the joins)
+pub fn stroke_polylines(figures: &[Polyline], style: &StrokeStyle, tolerance: f32) -> Mesh
+{
+    if !(style.width > 0.0) { return Mesh::new(); }
+    let dashed;
+    let figures = if style.dash_array.is_empty() { figures } else { dashed = apply_dashes(figures, &style.dash_array, style.dash_offset); &dashed[..] };
+    let mut stroker = Stroker { style, half_width: style.width * 0.5, tolerance, pieces: Vec::new() };
+    for f in figures { stroker.figure(f); }
+    tessellate_fill(&stroker.pieces, FillRule::NonZero)
+}
+
+/// Splits the figures into dashes. Closed figures are opened at the first point
+pub fn apply_dashes(figures: &[Polyline], dash_array: &[f32], dash_offset: f32) -> Vec<Polyline>
+{
+    let mut pattern = dash_array.to_vec();
+    if pattern.len() % 2 == 1 { pattern.extend_from_slice(dash_array); }
+    let total: f32 = pattern.iter().sum();
+    if pattern.iter().any(|&d| d < 0.0) || !(total > 0.0) { return figures.to_vec(); }
+
+    let mut dashes = Vec::new();
+    for f in figures
+    {
+        // position in the pattern
+        let mut index = 0;
+        let mut offset = dash_offset % total;
+        if offset < 0.0 { offset += total; }
+        while offset >= pattern[index] { offset -= pattern[index]; index = (index + 1) % pattern.len(); }
+        let mut remaining = pattern[index] - offset;
+
+        let mut points = f.points.clone();
+        if f.closed && !points.is_empty() { let p = points[0]; points.push(p); }
+        let mut current = if index % 2 == 0 { points.first().map(|&p| vec![p]) } else { None };
+        for w in points.windows(2)
+        {
+            let (mut from, to) = (w[0], w[1]);
+            let mut len = distance(from, to);
+            while len > remaining
+            {
+                let split = lerp(from, to, remaining / len);
+                len -= remaining; from = split;
+                match current.take()
+                {
+                    Some(mut pts) => { pts.push(split); dashes.push(Polyline { points: pts, closed: false }); },
+                    None => current = Some(vec![split])
+                }
+                index = (index + 1) % pattern.len();
+                remaining = pattern[index];
+            }
+            remaining -= len;
+            if let Some(ref mut pts) = current { pts.push(to); }
+        }
+        if let Some(pts) = current { if pts.len() > 1 { dashes.push(Polyline { points: pts, closed: false }); } }
+    }
+    dashes
+}
+
+struct Stroker<'s> { style: &'s StrokeStyle, half_width: f32, tolerance: f32, pieces: Vec<Polyline> }
+impl<'s> Stroker<'s>
+{
+    fn figure(&mut self, f: &Polyline)
+    {
+        let mut points = f.points.clone();
+        points.dedup_by(|a, b| distance(*a, *b) <= ::std::f32::EPSILON);
+        if f.closed && points.len() > 1 && distance(points[0], points[points.len() - 1]) <= ::std::f32::EPSILON { points.pop(); }
+        if points.len() == 1
+        {
+            // zero-length subpath: only round and square caps produce a dot
+            self.cap(points[0], Point2F(1.0, 0.0)); self.cap(points[0], Point2F(-1.0, 0.0));
+            return;
+        }
+        if points.len() < 2 { return; }
+
+        let n = points.len();
+        let segments = if f.closed { n } else { n - 1 };
+        for i in 0 .. segments
+        {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            let nv = normal(a, b, self.half_width);
+            self.push(vec![a + &nv, b + &nv, b - &nv, a - &nv]);
+        }
+        let joints = if f.closed { 0 .. n } else { 1 .. n - 1 };
+        for i in joints
+        {
+            let (prev, p, next) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+            self.join(prev, p, next);
+        }
+        if !f.closed
+        {
+            self.cap(points[0], direction(points[1], points[0]));
+            self.cap(points[n - 1], direction(points[n - 2], points[n - 1]));
+        }
+    }
+
+    /// Fills the gap on the outer side of the corner at `p`
+    fn join(&mut self, prev: Point2F, p: Point2F, next: Point2F)
+    {
+        let (d0, d1) = (direction(prev, p), direction(p, next));
+        let cross = d0.0 * d1.1 - d0.1 * d1.0;
+        let dot = d0.0 * d1.0 + d0.1 * d1.1;
+        if cross.abs() <= 1.0e-6 && dot > 0.0 { return; }
+        // the outer side is opposite to the turning direction
+        let side = if cross > 0.0 { -1.0 } else { 1.0 };
+        let (n0, n1) = (Point2F(-d0.1 * side * self.half_width, d0.0 * side * self.half_width), Point2F(-d1.1 * side * self.half_width, d1.0 * side * self.half_width));
+        let (a, b) = (p + &n0, p + &n1);
+        match self.style.join
+        {
+            LineJoin::Round =>
+            {
+                let arc = self.arc(p, n0, (n0.0 * n1.1 - n0.1 * n1.0).atan2(n0.0 * n1.0 + n0.1 * n1.1));
+                self.push(arc);
+            },
+            LineJoin::Miter =>
+            {
+                // miter length / width = 1 / sin(theta / 2) where theta is the angle between the segments
+                let cos_theta = -dot;
+                let sin_half = ((1.0 - cos_theta) * 0.5).max(0.0).sqrt();
+                if sin_half > 1.0e-6 && 1.0 / sin_half <= self.style.miter_limit
+                {
+                    let bisector = Point2F(n0.0 + n1.0, n0.1 + n1.1);
+                    let bl = (bisector.0 * bisector.0 + bisector.1 * bisector.1).sqrt();
+                    let miter_len = self.half_width / sin_half;
+                    let tip = Point2F(p.0 + bisector.0 / bl * miter_len, p.1 + bisector.1 / bl * miter_len);
+                    self.push(vec![p, a, tip, b]);
+                }
+                else { self.push(vec![p, a, b]); }
+            },
+            LineJoin::Bevel => self.push(vec![p, a, b])
+        }
+    }
+    /// Cap at the end point `p` of a line heading to `dir`(unit vector)
+    fn cap(&mut self, p: Point2F, dir: Point2F)
+    {
+        let hw = self.half_width;
+        let (nv, ext) = (Point2F(-dir.1 * hw, dir.0 * hw), Point2F(dir.0 * hw, dir.1 * hw));
+        match self.style.cap
+        {
+            LineCap::Butt => (),
+            LineCap::Square => self.push(vec![p + &nv, p + &nv + &ext, p - &nv + &ext, p - &nv]),
+            LineCap::Round => { let arc = self.arc(p, nv, -PI); self.push(arc); }
+        }
+    }
+    /// Pie slice around `p` starting at `p + from`
+    fn arc(&self, p: Point2F, from: Point2F, sweep: f32) -> Vec<Point2F>
+    {
+        let a0 = from.1.atan2(from.0);
+        // the chord of each step deviates at most the tolerance from the circle
+        let step = if self.tolerance < self.half_width { 2.0 * (1.0 - self.tolerance / self.half_width).acos() } else { PI * 0.5 };
+        let n = (sweep.abs() / step.max(1.0e-3)).ceil().max(1.0) as usize;
+        let mut pts = vec![p];
+        pts.extend((0 .. n + 1).map(|i|
+        {
+            let (s, c) = (a0 + sweep * i as f32 / n as f32).sin_cos();
+            Point2F(p.0 + c * self.half_width, p.1 + s * self.half_width)
+        }));
+        pts
+    }
+    /// Adds an outline piece, oriented positively so that the pieces are merged by the nonzero rule
+    fn push(&mut self, mut points: Vec<Point2F>)
+    {
+        let area: f32 = (0 .. points.len()).map(|i| { let (a, b) = (points[i], points[(i + 1) % points.len()]); a.0 * b.1 - b.0 * a.1 }).sum();
+        if area.abs() <= 1.0e-9 { return; }
+        if area < 0.0 { points.reverse(); }
+        self.pieces.push(Polyline { points, closed: true });
+    }
+}
+
+fn distance(a: Point2F, b: Point2F) -> f32 { ((b.0 - a.0) * (b.0 - a.0) + (b.1 - a.1) * (b.1 - a.1)).sqrt() }
+fn lerp(a: Point2F, b: Point2F, t: f32) -> Point2F { Point2F(a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t) }
+fn direction(from: Point2F, to: Point2F) -> Point2F { let l = distance(from, to); Point2F((to.0 - from.0) / l, (to.1 - from.1) / l) }
+/// Left normal of the segment scaled to the length
+fn normal(a: Point2F, b: Point2F, length: f32) -> Point2F { let d = direction(a, b); Point2F(-d.1 * length, d.0 * length) }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn area(m: &Mesh) -> f32
+    {
+        m.indices.chunks(3).map(|t|
+        {
+            let (a, b, c) = (m.vertices[t[0] as usize], m.vertices[t[1] as usize], m.vertices[t[2] as usize]);
+            ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() * 0.5
+        }).sum()
+    }
+    fn polyline(points: &[(f32, f32)], closed: bool) -> Vec<Polyline>
+    {
+        vec![Polyline { points: points.iter().map(|&(x, y)| Point2F(x, y)).collect(), closed }]
+    }
+    fn assert_area(m: Mesh, expected: f32) { let a = area(&m); assert!((a - expected).abs() < 1.0e-2, "area {} != {}", a, expected); }
+
+    #[test] fn caps()
+    {
+        let line = polyline(&[(0.0, 0.0), (10.0, 0.0)], false);
+        assert_area(stroke_polylines(&line, &StrokeStyle::new(2.0), 0.001), 20.0);
+        assert_area(stroke_polylines(&line, &StrokeStyle::new(2.0).cap(LineCap::Square), 0.001), 24.0);
+        assert_area(stroke_polylines(&line, &StrokeStyle::new(2.0).cap(LineCap::Round), 0.0001), 20.0 + PI);
+        assert_area(stroke_polylines(&polyline(&[(3.0, 3.0)], false), &StrokeStyle::new(2.0).cap(LineCap::Square), 0.01), 4.0);
+    }
+    #[test] fn joins()
+    {
+        // overlapping parts of the segments are counted once
+        let corner = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)], false);
+        assert_area(stroke_polylines(&corner, &StrokeStyle::new(2.0), 0.001), 40.0);
+        assert_area(stroke_polylines(&corner, &StrokeStyle::new(2.0).join(LineJoin::Bevel), 0.001), 39.5);
+        assert_area(stroke_polylines(&corner, &StrokeStyle::new(2.0).join(LineJoin::Round), 0.0001), 39.0 + PI * 0.25);
+        assert_area(stroke_polylines(&corner, &StrokeStyle::new(2.0).miter_limit(1.2), 0.001), 39.5);
+        let square = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)], true);
+        assert_area(stroke_polylines(&square, &StrokeStyle::new(2.0), 0.001), 144.0 - 64.0);
+    }
+    #[test] fn dashes()
+    {
+        let line = polyline(&[(0.0, 0.0), (4.0, 0.0), (10.0, 0.0)], false);
+        let lengths = |d: Vec<Polyline>| d.iter().map(|p| p.points[p.points.len() - 1].0 - p.points[0].0).collect::<Vec<_>>();
+        assert_eq!(lengths(apply_dashes(&line, &[2.0, 3.0], 0.0)), vec![2.0, 2.0]);
+        assert_eq!(lengths(apply_dashes(&line, &[2.0, 3.0], 1.0)), vec![1.0, 2.0, 1.0]);
+        assert_eq!(lengths(apply_dashes(&line, &[2.0], -1.0)), vec![2.0, 2.0, 1.0]);
+        assert_area(stroke_polylines(&line, &StrokeStyle::new(2.0).dashes(vec![2.0, 3.0], 0.0), 0.001), 8.0);
+    }
+}
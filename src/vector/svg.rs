@@ -0,0 +1,815 @@
+//! SVG document loader
+//!
+//! Supported: `path`, `rect`, `circle`, `ellipse`, `line`, `polyline`, `polygon`, `g`(and `a`, nested `svg`),
+//! `transform`, fill/stroke/opacity properties as attributes or in the `style` attribute,
+//! and `linearGradient`/`radialGradient` paint servers referenced anywhere in the document.
+//! Other elements(e.g. `defs`, `text`) are not rendered
+
+use metrics::*;
+use color::Color;
+use svgparser::path::Tokenizer;
+use svgparser::FromSpan;
+use flate2::read::GzDecoder;
+use super::{Path, Transform2D, FillRule, Mesh, RasterImage, StrokeStyle, LineJoin, LineCap};
+use super::{Gradient, GradientStop, GradientUnits, SpreadMethod};
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::io::{Result as IOResult, Error as IOError, ErrorKind};
+use std::path::Path as FilePath;
+use std::fs::File;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paint { None, Color(Color), Gradient(Gradient) }
+impl Paint
+{
+    /// The paint in the space `t` maps the shape into, with the opacity applied.
+    /// Gradients in the bounding box units are resolved against the path. None if nothing is painted
+    pub fn resolve(&self, path: &Path, t: &Transform2D, opacity: f32) -> Option<Paint>
+    {
+        match *self
+        {
+            Paint::None => None,
+            Paint::Color(c) => if c.3 * opacity > 0.0 { Some(Paint::Color(Color(c.0, c.1, c.2, c.3 * opacity))) } else { None },
+            Paint::Gradient(ref g) =>
+            {
+                let bounds = if g.units == GradientUnits::ObjectBoundingBox { path.bounds() } else { None };
+                g.resolve(bounds, t).map(|g| g.with_opacity(opacity)).and_then(|g| if g.is_transparent() { None } else { Some(Paint::Gradient(g)) })
+            }
+        }
+    }
+}
+/// Painting properties. All but `opacity` are inherited by the children
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style
+{
+    pub fill: Paint, pub fill_rule: FillRule, pub fill_opacity: f32,
+    pub stroke: Paint, pub stroke_style: StrokeStyle, pub stroke_opacity: f32,
+    pub opacity: f32
+}
+impl Default for Style
+{
+    fn default() -> Self
+    {
+        Style
+        {
+            fill: Paint::Color(Color(0.0, 0.0, 0.0, 1.0)), fill_rule: FillRule::NonZero, fill_opacity: 1.0,
+            stroke: Paint::None, stroke_style: StrokeStyle::default(), stroke_opacity: 1.0, opacity: 1.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shape { pub id: Option<String>, pub path: Path, pub style: Style }
+impl Shape
+{
+    /// Stroke geometry mapped by `t`. Stroked in the local space so that non-uniform scaling applies to the width.
+    /// Tolerance is in the mapped coordinates
+    pub fn stroke_mesh(&self, t: &Transform2D, tolerance: f32) -> Mesh
+    {
+        self.path.stroke(&self.style.stroke_style, tolerance / t.mean_scale().max(::std::f32::EPSILON)).transformed(t)
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group { pub id: Option<String>, pub transform: Transform2D, pub opacity: f32, pub children: Vec<Node> }
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node { Group(Group), Shape(Shape) }
+
+/// Scene graph of an SVG document. The root group maps the viewBox onto `size`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgImage { pub size: Size2F, pub root: Group }
+impl SvgImage
+{
+    /// Loads a .svg or .svgz(gzip compressed) file
+    pub fn load<P: AsRef<FilePath>>(path: P) -> IOResult<Self>
+    {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+    pub fn from_bytes(bytes: &[u8]) -> IOResult<Self>
+    {
+        let mut text = String::new();
+        if bytes.starts_with(&[0x1f, 0x8b]) { GzDecoder::new(bytes)?.read_to_string(&mut text)?; }
+        else { text = String::from_utf8(bytes.to_vec()).map_err(|e| IOError::new(ErrorKind::InvalidData, e))?; }
+        Self::parse(&text)
+    }
+    pub fn parse(text: &str) -> IOResult<Self>
+    {
+        let root = XmlParser { rest: text }.document()?;
+        if root.name != "svg" { return Err(invalid_data(format!("The root element is <{}>, not <svg>", root.name))); }
+        let (transform, size) = viewport(&root, false);
+        let defs = Defs::new(&root, size);
+        let style = style_of(&root, &Style::default(), &defs);
+        let mut group = Group { id: root.attribute("id").map(str::to_owned), transform, opacity: style.opacity, children: Vec::new() };
+        collect_children(&root, &style, &defs, &mut group.children);
+        Ok(SvgImage { size, root: group })
+    }
+
+    /// Visits every shape in drawing order, with the transform into the image space and the opacity of the ancestor groups
+    pub fn visit<F: FnMut(&Shape, &Transform2D, f32)>(&self, mut f: F)
+    {
+        fn rec<F: FnMut(&Shape, &Transform2D, f32)>(g: &Group, parent: &Transform2D, opacity: f32, f: &mut F)
+        {
+            let (t, opacity) = (g.transform.then(parent), opacity * g.opacity);
+            for c in &g.children
+            {
+                match *c { Node::Group(ref g) => rec(g, &t, opacity, f), Node::Shape(ref s) => f(s, &t, opacity) }
+            }
+        }
+        rec(&self.root, &Transform2D::IDENTITY, 1.0, &mut f);
+    }
+
+    /// Tessellates fills and strokes of all shapes in drawing order, after applying `transform` to the image space.
+    /// Tolerance is in the final coordinates. Paints are resolved into the final coordinates(never `Paint::None`)
+    pub fn meshes(&self, transform: &Transform2D, tolerance: f32) -> Vec<(Mesh, Paint)>
+    {
+        let mut meshes = Vec::new();
+        self.visit(|s, t, opacity|
+        {
+            let (t, opacity) = (t.then(transform), s.style.opacity * opacity);
+            if let Some(paint) = s.style.fill.resolve(&s.path, &t, s.style.fill_opacity * opacity)
+            {
+                let mesh = s.path.transformed(&t).fill(tolerance, s.style.fill_rule);
+                if mesh.triangle_count() > 0 { meshes.push((mesh, paint)); }
+            }
+            if let Some(paint) = s.style.stroke.resolve(&s.path, &t, s.style.stroke_opacity * opacity)
+            {
+                let mesh = s.stroke_mesh(&t, tolerance);
+                if mesh.triangle_count() > 0 { meshes.push((mesh, paint)); }
+            }
+        });
+        meshes
+    }
+
+    /// Rasterizes the image scaled to the size
+    pub fn rasterize(&self, size: Size2U) -> RasterImage
+    {
+        let mut image = RasterImage::new(size);
+        let scale = Transform2D::scale(size.x() as f32 / self.size.x(), size.y() as f32 / self.size.y());
+        for (m, p) in self.meshes(&scale, 0.1) { image.paint_mesh(&m, &p); }
+        image
+    }
+}
+
+fn invalid_data(msg: String) -> IOError { IOError::new(ErrorKind::InvalidData, msg) }
+
+// Elements //
+
+#[derive(Debug)]
+struct Element { name: String, attributes: Vec<(String, String)>, children: Vec<Element> }
+impl Element
+{
+    fn attribute(&self, name: &str) -> Option<&str>
+    {
+        self.attributes.iter().find(|&&(ref n, _)| n == name).map(|&(_, ref v)| v as &str)
+    }
+    /// Presentation attribute: the `style` attribute takes precedence
+    fn property(&self, name: &str) -> Option<&str>
+    {
+        let from_style = self.attribute("style").and_then(|s| s.split(';').filter_map(|decl|
+        {
+            let mut kv = decl.splitn(2, ':');
+            match (kv.next(), kv.next()) { (Some(k), Some(v)) if k.trim() == name => Some(v.trim()), _ => None }
+        }).last());
+        from_style.or_else(|| self.attribute(name).map(str::trim))
+    }
+}
+
+fn style_of(e: &Element, parent: &Style, defs: &Defs) -> Style
+{
+    let mut s = Style { opacity: 1.0, .. parent.clone() };
+    if let Some(p) = e.property("fill").and_then(|v| parse_paint(v, &parent.fill, defs)) { s.fill = p; }
+    if let Some(p) = e.property("stroke").and_then(|v| parse_paint(v, &parent.stroke, defs)) { s.stroke = p; }
+    match e.property("fill-rule") { Some("evenodd") => s.fill_rule = FillRule::EvenOdd, Some("nonzero") => s.fill_rule = FillRule::NonZero, _ => () }
+    s.fill_opacity = number_attr(e, "fill-opacity", parent.fill_opacity);
+    s.stroke_opacity = number_attr(e, "stroke-opacity", parent.stroke_opacity);
+    if let Some(w) = e.property("stroke-width").and_then(parse_length) { s.stroke_style.width = w; }
+    match e.property("stroke-linejoin")
+    {
+        Some("miter") => s.stroke_style.join = LineJoin::Miter, Some("round") => s.stroke_style.join = LineJoin::Round,
+        Some("bevel") => s.stroke_style.join = LineJoin::Bevel, _ => ()
+    }
+    match e.property("stroke-linecap")
+    {
+        Some("butt") => s.stroke_style.cap = LineCap::Butt, Some("round") => s.stroke_style.cap = LineCap::Round,
+        Some("square") => s.stroke_style.cap = LineCap::Square, _ => ()
+    }
+    if let Some(l) = e.property("stroke-miterlimit").and_then(parse_length).and_then(|l| if l >= 1.0 { Some(l) } else { None })
+    {
+        s.stroke_style.miter_limit = l;
+    }
+    match e.property("stroke-dasharray")
+    {
+        Some("none") => s.stroke_style.dash_array.clear(),
+        Some(v) => s.stroke_style.dash_array = v.split(|c: char| c == ',' || c.is_whitespace()).filter(|v| !v.is_empty()).filter_map(parse_length).collect(),
+        None => ()
+    }
+    if let Some(o) = e.property("stroke-dashoffset").and_then(parse_length) { s.stroke_style.dash_offset = o; }
+    s.opacity = number_attr(e, "opacity", 1.0);
+    s
+}
+fn number_attr(e: &Element, name: &str, default: f32) -> f32
+{
+    e.property(name).and_then(parse_length).map(|v| v.max(0.0).min(1.0)).unwrap_or(default)
+}
+fn length_attr(e: &Element, name: &str) -> f32 { e.attribute(name).and_then(parse_length).unwrap_or(0.0) }
+
+/// The transform of the viewBox and the size of the viewport of an `svg` element.
+/// Percentages are resolved against the size of the viewBox since the size of the parent viewport is unknown
+fn viewport(e: &Element, nested: bool) -> (Transform2D, Size2F)
+{
+    let vb = e.attribute("viewBox").map(parse_numbers).and_then(|v| if v.len() == 4 && v[2] > 0.0 && v[3] > 0.0 { Some(v) } else { None });
+    let length = |name, fallback: f32| e.attribute(name).and_then(|v|
+        if v.trim().ends_with('%') { parse_length(v).map(|p| fallback * p / 100.0) } else { parse_length(v) }).unwrap_or(fallback);
+    let default = vb.as_ref().map(|v| Size2F(v[2], v[3])).unwrap_or(Size2F(100.0, 100.0));
+    let size = Size2F(length("width", default.x()), length("height", default.y()));
+    let offset = Transform2D::translate(length_attr(e, "x"), length_attr(e, "y"));
+    let t = match vb
+    {
+        None => Transform2D::IDENTITY,
+        Some(v) =>
+        {
+            let (sx, sy) = (size.x() / v[2], size.y() / v[3]);
+            if e.attribute("preserveAspectRatio").map(|a| a.trim().starts_with("none")).unwrap_or(false)
+            {
+                Transform2D::translate(-v[0], -v[1]).then(&Transform2D::scale(sx, sy))
+            }
+            else
+            {
+                // xMidYMid meet
+                let s = sx.min(sy);
+                Transform2D::translate(-v[0], -v[1]).then(&Transform2D::scale(s, s))
+                    .then(&Transform2D::translate((size.x() - v[2] * s) * 0.5, (size.y() - v[3] * s) * 0.5))
+            }
+        }
+    };
+    (if nested { t.then(&offset) } else { t }, size)
+}
+
+fn collect_children(e: &Element, style: &Style, defs: &Defs, sink: &mut Vec<Node>)
+{
+    for c in &e.children
+    {
+        if c.property("display") == Some("none") { continue; }
+        let transform = c.attribute("transform").map(parse_transform).unwrap_or(Transform2D::IDENTITY);
+        let cstyle = style_of(c, style, defs);
+        match &c.name as &str
+        {
+            "g" | "a" | "svg" =>
+            {
+                let viewbox = if c.name == "svg" { viewport(c, true).0 } else { Transform2D::IDENTITY };
+                let mut g = Group { id: c.attribute("id").map(str::to_owned), transform: viewbox.then(&transform), opacity: cstyle.opacity, children: Vec::new() };
+                collect_children(c, &cstyle, defs, &mut g.children);
+                if !g.children.is_empty() { sink.push(Node::Group(g)); }
+            },
+            _ => if let Some(path) = shape_path(c)
+            {
+                let shape = Shape { id: c.attribute("id").map(str::to_owned), path, style: cstyle };
+                if transform == Transform2D::IDENTITY { sink.push(Node::Shape(shape)); }
+                else { sink.push(Node::Group(Group { id: None, transform, opacity: 1.0, children: vec![Node::Shape(shape)] })); }
+            }
+        }
+    }
+}
+
+/// Geometry of a basic shape or a path. None for other elements and shapes which are not rendered(e.g. zero-sized)
+fn shape_path(e: &Element) -> Option<Path>
+{
+    let mut path = Path::new();
+    match &e.name as &str
+    {
+        "path" =>
+        {
+            let tokens: Vec<_> = Tokenizer::from_str(e.attribute("d").unwrap_or("")).collect();
+            path = Path::from_svg_tokens(&tokens);
+        },
+        "rect" =>
+        {
+            let (x, y, w, h) = (length_attr(e, "x"), length_attr(e, "y"), length_attr(e, "width"), length_attr(e, "height"));
+            if w <= 0.0 || h <= 0.0 { return None; }
+            let (rx, ry) = match (e.attribute("rx").and_then(parse_length), e.attribute("ry").and_then(parse_length))
+            {
+                (Some(rx), Some(ry)) => (rx, ry), (Some(r), None) | (None, Some(r)) => (r, r), (None, None) => (0.0, 0.0)
+            };
+            let (rx, ry) = (rx.max(0.0).min(w * 0.5), ry.max(0.0).min(h * 0.5));
+            if rx > 0.0 && ry > 0.0
+            {
+                let r = Point2F(rx, ry);
+                path.move_to(Point2F(x + rx, y)).line_to(Point2F(x + w - rx, y)).arc_to(r, 0.0, false, true, Point2F(x + w, y + ry))
+                    .line_to(Point2F(x + w, y + h - ry)).arc_to(r, 0.0, false, true, Point2F(x + w - rx, y + h))
+                    .line_to(Point2F(x + rx, y + h)).arc_to(r, 0.0, false, true, Point2F(x, y + h - ry))
+                    .line_to(Point2F(x, y + ry)).arc_to(r, 0.0, false, true, Point2F(x + rx, y)).close();
+            }
+            else
+            {
+                path.move_to(Point2F(x, y)).line_to(Point2F(x + w, y)).line_to(Point2F(x + w, y + h)).line_to(Point2F(x, y + h)).close();
+            }
+        },
+        "circle" | "ellipse" =>
+        {
+            let (cx, cy) = (length_attr(e, "cx"), length_attr(e, "cy"));
+            let (rx, ry) = if e.name == "circle" { let r = length_attr(e, "r"); (r, r) } else { (length_attr(e, "rx"), length_attr(e, "ry")) };
+            if rx <= 0.0 || ry <= 0.0 { return None; }
+            let r = Point2F(rx, ry);
+            path.move_to(Point2F(cx + rx, cy)).arc_to(r, 0.0, false, true, Point2F(cx - rx, cy)).arc_to(r, 0.0, false, true, Point2F(cx + rx, cy)).close();
+        },
+        "line" =>
+        {
+            path.move_to(Point2F(length_attr(e, "x1"), length_attr(e, "y1"))).line_to(Point2F(length_attr(e, "x2"), length_attr(e, "y2")));
+        },
+        "polyline" | "polygon" =>
+        {
+            let n = parse_numbers(e.attribute("points").unwrap_or(""));
+            let mut points = n.chunks(2).filter(|p| p.len() == 2).map(|p| Point2F(p[0], p[1]));
+            match points.next() { Some(p) => { path.move_to(p); }, None => return None }
+            for p in points { path.line_to(p); }
+            if e.name == "polygon" { path.close(); }
+        },
+        _ => return None
+    }
+    if path.commands.is_empty() { None } else { Some(path) }
+}
+
+// Paint servers //
+
+/// Elements with ids(the first one wins for duplicated ids)
+struct Defs<'e> { ids: HashMap<&'e str, &'e Element>, viewport: Size2F }
+impl<'e> Defs<'e>
+{
+    fn new(root: &'e Element, viewport: Size2F) -> Self
+    {
+        fn rec<'e>(e: &'e Element, ids: &mut HashMap<&'e str, &'e Element>)
+        {
+            if let Some(id) = e.attribute("id") { ids.entry(id).or_insert(e); }
+            for c in &e.children { rec(c, ids); }
+        }
+        let mut ids = HashMap::new();
+        rec(root, &mut ids);
+        Defs { ids, viewport }
+    }
+
+    /// Paint of the gradient element with the id. Attributes and stops which are not specified are taken from the `href` chain.
+    /// Gradients without stops paint nothing, and ones with a single stop paint its color
+    fn paint_server(&self, id: &str) -> Option<Paint>
+    {
+        let is_gradient = |e: &Element| e.name == "linearGradient" || e.name == "radialGradient";
+        let e = *self.ids.get(id)?;
+        if !is_gradient(e) { return None; }
+        let mut chain = vec![e];
+        while let Some(&next) = chain.last().unwrap().attribute("href").and_then(|h| if h.starts_with('#') { self.ids.get(&h[1 ..]) } else { None })
+        {
+            if !is_gradient(next) || chain.iter().any(|&c| c as *const _ == next as *const _) { break; }
+            chain.push(next);
+        }
+        let attr = |name: &str| chain.iter().filter_map(|c| c.attribute(name)).next();
+        let stops = chain.iter().map(|c| gradient_stops(c)).find(|s| !s.is_empty()).unwrap_or_else(Vec::new);
+        match stops.len() { 0 => return Some(Paint::None), 1 => return Some(Paint::Color(stops[0].color)), _ => () }
+
+        let units = if attr("gradientUnits") == Some("userSpaceOnUse") { GradientUnits::UserSpaceOnUse } else { GradientUnits::ObjectBoundingBox };
+        // percentages(and defaults) are fractions of the bounding box, or of the viewport in the user space
+        let (w, h) = (self.viewport.x(), self.viewport.y());
+        let coord = |name: &str, default: f32, extent: f32|
+        {
+            let extent = if units == GradientUnits::ObjectBoundingBox { 1.0 } else { extent };
+            match attr(name)
+            {
+                Some(v) if v.trim().ends_with('%') => parse_length(v).map(|p| p / 100.0 * extent),
+                Some(v) => parse_length(v),
+                None => None
+            }.unwrap_or(default * extent)
+        };
+        let mut g = if e.name == "linearGradient"
+        {
+            Gradient::linear(Point2F(coord("x1", 0.0, w), coord("y1", 0.0, h)), Point2F(coord("x2", 1.0, w), coord("y2", 0.0, h)))
+        }
+        else
+        {
+            let center = Point2F(coord("cx", 0.5, w), coord("cy", 0.5, h));
+            let r = coord("r", 0.5, ((w * w + h * h) * 0.5).sqrt());
+            // the focus defaults to the center
+            let fx = if attr("fx").is_some() { coord("fx", 0.0, w) } else { center.0 };
+            let fy = if attr("fy").is_some() { coord("fy", 0.0, h) } else { center.1 };
+            Gradient::radial(center, r).focus(Point2F(fx, fy))
+        };
+        g.stops = stops;
+        g.spread = match attr("spreadMethod") { Some("reflect") => SpreadMethod::Reflect, Some("repeat") => SpreadMethod::Repeat, _ => SpreadMethod::Pad };
+        g.units = units;
+        Some(Paint::Gradient(g.transform(attr("gradientTransform").map(parse_transform).unwrap_or(Transform2D::IDENTITY))))
+    }
+}
+/// `stop` children. Offsets are clamped into [0, 1] and to the offset of the previous stop
+fn gradient_stops(e: &Element) -> Vec<GradientStop>
+{
+    let mut g = Gradient::linear(Point2F::ZERO, Point2F::ZERO);
+    for s in e.children.iter().filter(|c| c.name == "stop")
+    {
+        let offset = s.attribute("offset").and_then(|v| parse_length(v).map(|o| if v.trim().ends_with('%') { o / 100.0 } else { o })).unwrap_or(0.0);
+        let c = match s.property("stop-color") { Some("currentColor") | None => None, Some(v) => reported_color(v) }.unwrap_or(Color(0.0, 0.0, 0.0, 1.0));
+        g = g.stop(offset.max(0.0), Color(c.0, c.1, c.2, c.3 * number_attr(s, "stop-opacity", 1.0)));
+    }
+    g.stops
+}
+
+// Attribute values //
+
+/// Splits a leading number off. Numbers can be concatenated without separators as in "10-5" or "0.5.5"
+fn take_number(s: &str) -> Option<(f32, &str)>
+{
+    let s = s.trim_left_matches(|c: char| c.is_whitespace() || c == ',');
+    let (b, digit) = (s.as_bytes(), |c: u8| b'0' <= c && c <= b'9');
+    let mut i = 0;
+    if i < b.len() && (b[i] == b'+' || b[i] == b'-') { i += 1; }
+    let digits_from = i;
+    while i < b.len() && digit(b[i]) { i += 1; }
+    if i < b.len() && b[i] == b'.' { i += 1; while i < b.len() && digit(b[i]) { i += 1; } }
+    if i == digits_from || &s[digits_from .. i] == "." { return None; }
+    if i < b.len() && (b[i] == b'e' || b[i] == b'E')
+    {
+        let mut j = i + 1;
+        if j < b.len() && (b[j] == b'+' || b[j] == b'-') { j += 1; }
+        if j < b.len() && digit(b[j]) { while j < b.len() && digit(b[j]) { j += 1; } i = j; }
+    }
+    s[.. i].parse().ok().map(|v| (v, &s[i ..]))
+}
+fn parse_numbers(mut s: &str) -> Vec<f32>
+{
+    let mut v = Vec::new();
+    while let Some((n, rest)) = take_number(s) { v.push(n); s = rest; }
+    v
+}
+/// Length in user units(px). Absolute units are converted at 90dpi as SVG 1.1 does. Percentages are returned as is
+fn parse_length(s: &str) -> Option<f32>
+{
+    let (v, unit) = take_number(s)?;
+    let scale = match unit.trim()
+    {
+        "" | "px" | "%" => 1.0, "pt" => 1.25, "pc" => 15.0, "mm" => 3.543307, "cm" => 35.43307, "in" => 90.0,
+        _ => return None
+    };
+    Some(v * scale)
+}
+
+/// Parses a transform list. The leftmost transform is applied last
+fn parse_transform(s: &str) -> Transform2D
+{
+    let mut t = Transform2D::IDENTITY;
+    for item in s.split(')')
+    {
+        let mut fa = item.splitn(2, '(');
+        let (name, args) = match (fa.next(), fa.next()) { (Some(n), Some(a)) => (n.trim_matches(|c: char| c.is_whitespace() || c == ','), parse_numbers(a)), _ => continue };
+        let arg = |i: usize, default: f32| args.get(i).cloned().unwrap_or(default);
+        let current = match (name, args.len())
+        {
+            ("matrix", 6) => Transform2D { a: args[0], b: args[1], c: args[2], d: args[3], e: args[4], f: args[5] },
+            ("translate", 1) | ("translate", 2) => Transform2D::translate(args[0], arg(1, 0.0)),
+            ("scale", 1) | ("scale", 2) => Transform2D::scale(args[0], arg(1, args[0])),
+            ("rotate", 1) => Transform2D::rotate(args[0]),
+            ("rotate", 3) => Transform2D::translate(-args[1], -args[2]).then(&Transform2D::rotate(args[0])).then(&Transform2D::translate(args[1], args[2])),
+            ("skewX", 1) => Transform2D::skew_x(args[0]),
+            ("skewY", 1) => Transform2D::skew_y(args[0]),
+            _ => { println!("!! Ignoring an invalid transform: {}", item.trim()); continue; }
+        };
+        t = current.then(&t);
+    }
+    t
+}
+
+/// None for unsupported paints(which leave the inherited value). Unknown colors are reported
+fn parse_paint(s: &str, inherited: &Paint, defs: &Defs) -> Option<Paint>
+{
+    match s
+    {
+        "none" => Some(Paint::None),
+        "inherit" => Some(inherited.clone()),
+        "currentColor" => Some(Paint::Color(Color(0.0, 0.0, 0.0, 1.0))),
+        _ if s.starts_with("url(") => Some(match s.find(')')
+        {
+            None => Paint::None,
+            Some(end) =>
+            {
+                let reference = s[4 .. end].trim().trim_matches(|c| c == '"' || c == '\'');
+                let server = if reference.starts_with('#') { defs.paint_server(&reference[1 ..]) } else { None };
+                // the fallback color is used if the reference is invalid
+                server.or_else(|| parse_paint(s[end + 1 ..].trim(), inherited, defs)).unwrap_or(Paint::None)
+            }
+        }),
+        "" => None,
+        _ => reported_color(s).map(Paint::Color)
+    }
+}
+fn reported_color(s: &str) -> Option<Color>
+{
+    let c = parse_color(s);
+    if c.is_none() { println!("!! Ignoring an unknown color: {}", s); }
+    c
+}
+fn parse_color(s: &str) -> Option<Color>
+{
+    let rgb8 = |r: u32, g: u32, b: u32| Color(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0);
+    if s.starts_with('#')
+    {
+        let hex = u32::from_str_radix(&s[1 ..], 16).ok()?;
+        return match s.len()
+        {
+            4 => Some(rgb8(((hex >> 8) & 0x0f) * 0x11, ((hex >> 4) & 0x0f) * 0x11, (hex & 0x0f) * 0x11)),
+            7 => Some(rgb8((hex >> 16) & 0xff, (hex >> 8) & 0xff, hex & 0xff)),
+            _ => None
+        };
+    }
+    if s.starts_with("rgb(") && s.ends_with(')')
+    {
+        let c: Vec<f32> = s[4 .. s.len() - 1].split(',').map(|v|
+        {
+            let v = v.trim();
+            if v.ends_with('%') { v[.. v.len() - 1].trim().parse::<f32>().map(|p| p / 100.0) } else { v.parse::<f32>().map(|v| v / 255.0) }
+        }).collect::<Result<_, _>>().ok()?;
+        if c.len() != 3 { return None; }
+        return Some(Color(c[0].max(0.0).min(1.0), c[1].max(0.0).min(1.0), c[2].max(0.0).min(1.0), 1.0));
+    }
+    let name = s.to_lowercase();
+    if name == "transparent" { return Some(Color(0.0, 0.0, 0.0, 0.0)); }
+    let rgb = NAMED_COLORS.binary_search_by(|&(n, _)| n.cmp(&name as &str)).ok().map(|i| NAMED_COLORS[i].1)?;
+    Some(rgb8(rgb >> 16, (rgb >> 8) & 0xff, rgb & 0xff))
+}
+/// The color keywords of SVG 1.1, sorted by the name
+const NAMED_COLORS: &'static [(&'static str, u32)] = &[
+    ("aliceblue", 0xf0f8ff), ("antiquewhite", 0xfaebd7), ("aqua", 0x00ffff), ("aquamarine", 0x7fffd4), ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc), ("bisque", 0xffe4c4), ("black", 0x000000), ("blanchedalmond", 0xffebcd), ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2), ("brown", 0xa52a2a), ("burlywood", 0xdeb887), ("cadetblue", 0x5f9ea0), ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e), ("coral", 0xff7f50), ("cornflowerblue", 0x6495ed), ("cornsilk", 0xfff8dc), ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff), ("darkblue", 0x00008b), ("darkcyan", 0x008b8b), ("darkgoldenrod", 0xb8860b), ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400), ("darkgrey", 0xa9a9a9), ("darkkhaki", 0xbdb76b), ("darkmagenta", 0x8b008b), ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00), ("darkorchid", 0x9932cc), ("darkred", 0x8b0000), ("darksalmon", 0xe9967a), ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b), ("darkslategray", 0x2f4f4f), ("darkslategrey", 0x2f4f4f), ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3), ("deeppink", 0xff1493), ("deepskyblue", 0x00bfff), ("dimgray", 0x696969), ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff), ("firebrick", 0xb22222), ("floralwhite", 0xfffaf0), ("forestgreen", 0x228b22), ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc), ("ghostwhite", 0xf8f8ff), ("gold", 0xffd700), ("goldenrod", 0xdaa520), ("gray", 0x808080),
+    ("green", 0x008000), ("greenyellow", 0xadff2f), ("grey", 0x808080), ("honeydew", 0xf0fff0), ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c), ("indigo", 0x4b0082), ("ivory", 0xfffff0), ("khaki", 0xf0e68c), ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5), ("lawngreen", 0x7cfc00), ("lemonchiffon", 0xfffacd), ("lightblue", 0xadd8e6), ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff), ("lightgoldenrodyellow", 0xfafad2), ("lightgray", 0xd3d3d3), ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3), ("lightpink", 0xffb6c1), ("lightsalmon", 0xffa07a), ("lightseagreen", 0x20b2aa), ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899), ("lightslategrey", 0x778899), ("lightsteelblue", 0xb0c4de), ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00), ("limegreen", 0x32cd32), ("linen", 0xfaf0e6), ("magenta", 0xff00ff), ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa), ("mediumblue", 0x0000cd), ("mediumorchid", 0xba55d3), ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371), ("mediumslateblue", 0x7b68ee), ("mediumspringgreen", 0x00fa9a), ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585), ("midnightblue", 0x191970), ("mintcream", 0xf5fffa), ("mistyrose", 0xffe4e1), ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead), ("navy", 0x000080), ("oldlace", 0xfdf5e6), ("olive", 0x808000), ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500), ("orangered", 0xff4500), ("orchid", 0xda70d6), ("palegoldenrod", 0xeee8aa), ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee), ("palevioletred", 0xdb7093), ("papayawhip", 0xffefd5), ("peachpuff", 0xffdab9), ("peru", 0xcd853f),
+    ("pink", 0xffc0cb), ("plum", 0xdda0dd), ("powderblue", 0xb0e0e6), ("purple", 0x800080), ("red", 0xff0000), ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1), ("saddlebrown", 0x8b4513), ("salmon", 0xfa8072), ("sandybrown", 0xf4a460), ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee), ("sienna", 0xa0522d), ("silver", 0xc0c0c0), ("skyblue", 0x87ceeb), ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090), ("slategrey", 0x708090), ("snow", 0xfffafa), ("springgreen", 0x00ff7f), ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c), ("teal", 0x008080), ("thistle", 0xd8bfd8), ("tomato", 0xff6347), ("turquoise", 0x40e0d0), ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3), ("white", 0xffffff), ("whitesmoke", 0xf5f5f5), ("yellow", 0xffff00), ("yellowgreen", 0x9acd32)
+];
+
+// Minimal XML parser: elements and attributes only //
+
+struct XmlParser<'s> { rest: &'s str }
+impl<'s> XmlParser<'s>
+{
+    /// Parses the root element, skipping the prolog and the misc parts
+    fn document(&mut self) -> IOResult<Element>
+    {
+        loop
+        {
+            self.skip_misc()?;
+            if self.rest.starts_with("<!DOCTYPE") { self.skip_doctype()?; continue; }
+            if self.rest.starts_with('<') { return self.element(); }
+            return Err(invalid_data("No root element".to_owned()));
+        }
+    }
+    /// Skips text, comments, processing instructions and CDATA sections until the next tag(or the end)
+    fn skip_misc(&mut self) -> IOResult<()>
+    {
+        loop
+        {
+            self.rest = &self.rest[self.rest.find('<').unwrap_or(self.rest.len()) ..];
+            let terminator = if self.rest.starts_with("<!--") { "-->" }
+                else if self.rest.starts_with("<?") { "?>" }
+                else if self.rest.starts_with("<![CDATA[") { "]]>" }
+                else { return Ok(()); };
+            let end = self.rest.find(terminator).ok_or_else(|| invalid_data(format!("Unterminated {}", &self.rest[.. 2])))?;
+            self.rest = &self.rest[end + terminator.len() ..];
+        }
+    }
+    fn skip_doctype(&mut self) -> IOResult<()>
+    {
+        let mut depth = 0;
+        for (i, c) in self.rest.char_indices()
+        {
+            match c
+            {
+                '[' => depth += 1, ']' => depth -= 1,
+                '>' if depth == 0 => { self.rest = &self.rest[i + 1 ..]; return Ok(()); },
+                _ => ()
+            }
+        }
+        Err(invalid_data("Unterminated DOCTYPE".to_owned()))
+    }
+    fn name(&mut self) -> &'s str
+    {
+        let end = self.rest.find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=').unwrap_or(self.rest.len());
+        let (name, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        // drops the namespace prefix(e.g. svg:path)
+        name.rsplit(':').next().unwrap_or(name)
+    }
+    fn element(&mut self) -> IOResult<Element>
+    {
+        self.rest = &self.rest[1 ..];
+        let name = self.name().to_owned();
+        let mut e = Element { name, attributes: Vec::new(), children: Vec::new() };
+        loop
+        {
+            self.rest = self.rest.trim_left();
+            if self.rest.starts_with("/>") { self.rest = &self.rest[2 ..]; return Ok(e); }
+            if self.rest.starts_with('>') { self.rest = &self.rest[1 ..]; break; }
+            if self.rest.is_empty() { return Err(invalid_data(format!("Unterminated <{}>", e.name))); }
+            let aname = self.name();
+            if aname.is_empty() { return Err(invalid_data(format!("Invalid character in <{}>", e.name))); }
+            self.rest = self.rest.trim_left();
+            if !self.rest.starts_with('=') { return Err(invalid_data(format!("Attribute {} without value", aname))); }
+            self.rest = self.rest[1 ..].trim_left();
+            let quote = match self.rest.chars().next()
+            {
+                Some(c) if c == '"' || c == '\'' => c, _ => return Err(invalid_data(format!("Unquoted value of {}", aname)))
+            };
+            let end = self.rest[1 ..].find(quote).ok_or_else(|| invalid_data(format!("Unterminated value of {}", aname)))? + 1;
+            e.attributes.push((aname.to_owned(), unescape(&self.rest[1 .. end])));
+            self.rest = &self.rest[end + 1 ..];
+        }
+        loop
+        {
+            self.skip_misc()?;
+            if self.rest.is_empty() { return Err(invalid_data(format!("<{}> is not closed", e.name))); }
+            if self.rest.starts_with("</")
+            {
+                self.rest = &self.rest[2 ..];
+                let closing = self.name();
+                if closing != e.name { return Err(invalid_data(format!("<{}> is closed by </{}>", e.name, closing))); }
+                self.rest = &self.rest[self.rest.find('>').map(|p| p + 1).unwrap_or(self.rest.len()) ..];
+                return Ok(e);
+            }
+            let child = self.element()?;
+            e.children.push(child);
+        }
+    }
+}
+fn unescape(s: &str) -> String
+{
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(p) = rest.find('&')
+    {
+        out.push_str(&rest[.. p]);
+        rest = &rest[p ..];
+        let end = match rest.find(';') { Some(e) => e, None => break };
+        let decoded = match &rest[1 .. end]
+        {
+            "amp" => Some('&'), "lt" => Some('<'), "gt" => Some('>'), "quot" => Some('"'), "apos" => Some('\''),
+            r if r.starts_with("#x") => u32::from_str_radix(&r[2 ..], 16).ok().and_then(::std::char::from_u32),
+            r if r.starts_with('#') => r[1 ..].parse().ok().and_then(::std::char::from_u32),
+            _ => None
+        };
+        match decoded
+        {
+            Some(c) => { out.push(c); rest = &rest[end + 1 ..]; },
+            None => { out.push('&'); rest = &rest[1 ..]; }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    const DOC: &'static str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" viewBox="0 0 100 50">
+    <!-- comment with <tags> -->
+    <defs><rect width="10" height="10"/></defs>
+    <g fill="#f00" transform="translate(10, 5)" opacity="0.5">
+        <rect x="0" y="0" width="10" height="20" style="fill: rgb(0, 0, 255); stroke:none"/>
+        <circle cx="5" cy="5" r="5" stroke="green" stroke-width="2mm"/>
+        <polygon points="0,0 10,0 10-10" display="none"/>
+    </g>
+</svg>"##;
+
+    fn shapes(image: &SvgImage) -> Vec<(Shape, Transform2D, f32)>
+    {
+        let mut v = Vec::new();
+        image.visit(|s, t, o| v.push((s.clone(), *t, o)));
+        v
+    }
+
+    #[test] fn structure_and_styles()
+    {
+        let image = SvgImage::parse(DOC).unwrap();
+        assert_eq!(image.size, Size2F(200.0, 100.0));
+        let s = shapes(&image);
+        assert_eq!(s.len(), 2);
+        assert_eq!(s[0].0.style.fill, Paint::Color(Color(0.0, 0.0, 1.0, 1.0)));
+        assert_eq!(s[0].0.style.stroke, Paint::None);
+        assert_eq!(s[1].0.style.fill, Paint::Color(Color(1.0, 0.0, 0.0, 1.0)));
+        assert_eq!(s[1].0.style.stroke, Paint::Color(Color(0.0, 128.0 / 255.0, 0.0, 1.0)));
+        assert!((s[1].0.style.stroke_style.width - 7.086614).abs() < 1.0e-4);
+        assert!(s.iter().all(|&(_, _, o)| o == 0.5));
+        // viewBox(x2) after translate(10, 5)
+        assert_eq!(s[0].1.apply(Point2F(1.0, 1.0)), Point2F(22.0, 12.0));
+    }
+    #[test] fn transforms()
+    {
+        let near = |a: Point2F, b: Point2F| (a.0 - b.0).abs() < 1.0e-4 && (a.1 - b.1).abs() < 1.0e-4;
+        assert_eq!(parse_transform("translate(10,20) scale(2)").apply(Point2F(1.0, 1.0)), Point2F(12.0, 22.0));
+        assert!(near(parse_transform("rotate(90 10 10)").apply(Point2F(20.0, 10.0)), Point2F(10.0, 20.0)));
+        assert_eq!(parse_transform("matrix(1,0,0,1,-5,5)").apply(Point2F::ZERO), Point2F(-5.0, 5.0));
+        let t = parse_transform("skewX(45) translate(3 4)");
+        assert!(near(t.inverse().unwrap().apply(t.apply(Point2F(7.0, -2.0))), Point2F(7.0, -2.0)));
+    }
+    #[test] fn values()
+    {
+        assert_eq!(parse_numbers("10-5.5.5e1,,3"), vec![10.0, -5.5, 5.0, 3.0]);
+        assert_eq!(parse_color("#0f8"), Some(Color(0.0, 1.0, 136.0 / 255.0, 1.0)));
+        assert_eq!(NAMED_COLORS.len(), 147);
+        assert!(NAMED_COLORS.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(parse_color("CornflowerBlue"), Some(Color(100.0 / 255.0, 149.0 / 255.0, 237.0 / 255.0, 1.0)));
+        assert_eq!(parse_color("yellowgreen"), Some(Color(154.0 / 255.0, 205.0 / 255.0, 50.0 / 255.0, 1.0)));
+        assert_eq!(parse_color("notacolor"), None);
+        let root = Element { name: "svg".to_owned(), attributes: Vec::new(), children: Vec::new() };
+        let defs = Defs::new(&root, Size2F(100.0, 100.0));
+        assert_eq!(parse_paint("url(#grad) #ffffff", &Paint::None, &defs), Some(Paint::Color(Color(1.0, 1.0, 1.0, 1.0))));
+        assert_eq!(parse_length("1in"), Some(90.0));
+        assert_eq!(unescape("a&amp;b&#x41;&#66;&unknown;"), "a&bAB&unknown;");
+    }
+    #[test] fn rasterize()
+    {
+        let image = SvgImage::parse(r##"<svg width="4" height="4"><rect width="2" height="4" fill="red"/>
+            <rect x="2" width="2" height="2" fill="#00f" opacity="0.5"/></svg>"##).unwrap();
+        let r = image.rasterize(Size2U(8, 8));
+        assert_eq!(r.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(r.pixel(3, 7), [255, 0, 0, 255]);
+        assert_eq!(r.pixel(6, 1), [0, 0, 255, 128]);
+        assert_eq!(r.pixel(6, 6), [0, 0, 0, 0]);
+    }
+    #[test] fn gradients()
+    {
+        let image = SvgImage::parse(r##"<svg width="100" height="50" xmlns:xlink="http://www.w3.org/1999/xlink">
+            <defs>
+                <linearGradient id="base" spreadMethod="reflect"><stop offset="0" stop-color="red"/><stop offset="100%" style="stop-color:#00f"/></linearGradient>
+                <linearGradient id="half" xlink:href="#base" x2="50%"/>
+                <radialGradient id="spot" gradientUnits="userSpaceOnUse" cx="50" cy="25" r="10" fx="55" gradientTransform="scale(1 0.5)">
+                    <stop offset="0.2" stop-color="white" stop-opacity="0.5"/><stop offset="0.1" stop-color="black"/>
+                </radialGradient>
+                <linearGradient id="solid"><stop stop-color="lime"/></linearGradient>
+                <linearGradient id="empty"/>
+            </defs>
+            <rect x="10" width="40" height="10" fill="url(#half)"/>
+            <g fill="url(#spot)"><circle cx="50" cy="25" r="5"/></g>
+            <rect width="1" height="1" fill="url(#solid)"/><rect width="1" height="1" fill="url(#empty) red"/>
+            <rect width="1" height="1" fill="url(#missing) red"/>
+        </svg>"##).unwrap();
+        let s = shapes(&image);
+        let g = match s[0].0.style.fill { Paint::Gradient(ref g) => g.clone(), ref p => panic!("not a gradient: {:?}", p) };
+        assert_eq!((g.stops.len(), g.spread, g.units), (2, SpreadMethod::Reflect, GradientUnits::ObjectBoundingBox));
+        assert_eq!(g.shape, super::super::GradientShape::Linear { from: Point2F(0.0, 0.0), to: Point2F(0.5, 0.0) });
+        match s[1].0.style.fill
+        {
+            Paint::Gradient(ref g) =>
+            {
+                assert_eq!(g.shape, super::super::GradientShape::Radial { center: Point2F(50.0, 25.0), radius: 10.0, focus: Point2F(55.0, 25.0) });
+                assert_eq!(g.stops[1], GradientStop { offset: 0.2, color: Color(0.0, 0.0, 0.0, 1.0) });
+                assert_eq!(g.stops[0].color, Color(1.0, 1.0, 1.0, 0.5));
+            },
+            ref p => panic!("not a gradient: {:?}", p)
+        }
+        assert_eq!(s[2].0.style.fill, Paint::Color(Color(0.0, 1.0, 0.0, 1.0)));
+        assert_eq!(s[3].0.style.fill, Paint::None);
+        assert_eq!(s[4].0.style.fill, Paint::Color(Color(1.0, 0.0, 0.0, 1.0)));
+
+        // the bounding box of the first rect: red at x = 10 and 50, blue at x = 30
+        let r = image.rasterize(Size2U(100, 50));
+        assert_eq!(r.pixel(10, 5), [249, 0, 6, 255]);
+        assert_eq!(r.pixel(29, 5), [6, 0, 249, 255]);
+        assert_eq!(r.pixel(49, 5), [249, 0, 6, 255]);
+    }
+    #[test] fn compressed()
+    {
+        let mut z = GzEncoder::new(Vec::new(), Compression::Default);
+        z.write_all(DOC.as_bytes()).unwrap();
+        assert_eq!(SvgImage::from_bytes(&z.finish().unwrap()).unwrap(), SvgImage::parse(DOC).unwrap());
+    }
+    #[test] fn malformed()
+    {
+        assert!(SvgImage::parse("<svg><g></svg>").is_err());
+        assert!(SvgImage::parse("<html/>").is_err());
+        assert!(SvgImage::parse("<svg width=10/>").is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! Fill tessellation
+
+use metrics::*;
+use super::{Path, Polyline};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillRule { NonZero, EvenOdd }
+impl FillRule
+{
+    pub fn is_inside(self, winding: i32) -> bool
+    {
+        match self { FillRule::NonZero => winding != 0, FillRule::EvenOdd => winding % 2 != 0 }
+    }
+}
+
+/// Triangle list: positions and 32-bit indices(`fe::IndexType::U32` in the Vulkan backend)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Mesh { pub vertices: Vec<[f32; 2]>, pub indices: Vec<u32> }
+impl Mesh
+{
+    pub fn new() -> Self { Mesh { vertices: Vec::new(), indices: Vec::new() } }
+    pub fn vertex_bytes(&self) -> &[u8]
+    {
+        unsafe { ::std::slice::from_raw_parts(self.vertices.as_ptr() as *const u8, self.vertices.len() * ::std::mem::size_of::<[f32; 2]>()) }
+    }
+    pub fn index_bytes(&self) -> &[u8]
+    {
+        unsafe { ::std::slice::from_raw_parts(self.indices.as_ptr() as *const u8, self.indices.len() * ::std::mem::size_of::<u32>()) }
+    }
+    pub fn triangle_count(&self) -> usize { self.indices.len() / 3 }
+    pub fn append(&mut self, other: &Mesh)
+    {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices.extend(other.indices.iter().map(|&i| base + i));
+    }
+    /// Splits the edges longer than `max_length` at their midpoints until none is left(up to 16 halvings).
+    /// Midpoints are shared between the triangles through the positions of the endpoints, so no T-junction is made
+    pub fn subdivided(&self, max_length: f32) -> Mesh
+    {
+        let mut mesh = self.clone();
+        if !(max_length > 0.0) || !max_length.is_finite() { return mesh; }
+        for _ in 0 .. 16
+        {
+            let (mut midpoints, mut indices) = (HashMap::new(), Vec::with_capacity(mesh.indices.len()));
+            for t in mesh.indices.chunks(3)
+            {
+                let mut ring = Vec::with_capacity(6);
+                for k in 0 .. 3
+                {
+                    let (a, b) = (t[k], t[(k + 1) % 3]);
+                    ring.push(a);
+                    let (pa, pb) = (mesh.vertices[a as usize], mesh.vertices[b as usize]);
+                    if (pb[0] - pa[0]).hypot(pb[1] - pa[1]) <= max_length { continue; }
+                    let (ka, kb) = ([pa[0].to_bits(), pa[1].to_bits()], [pb[0].to_bits(), pb[1].to_bits()]);
+                    let vertices = &mut mesh.vertices;
+                    ring.push(*midpoints.entry(if ka < kb { (ka, kb) } else { (kb, ka) }).or_insert_with(||
+                    {
+                        vertices.push([(pa[0] + pb[0]) * 0.5, (pa[1] + pb[1]) * 0.5]);
+                        vertices.len() as u32 - 1
+                    }));
+                }
+                if ring.len() == 3 { indices.extend_from_slice(t); continue; }
+                // fan from the first midpoint: no triangle is degenerate as the midpoint lies on the edge between its neighbors
+                let s = ring.iter().position(|i| !t.contains(i)).unwrap();
+                for k in 1 .. ring.len() - 1
+                {
+                    indices.extend_from_slice(&[ring[s], ring[(s + k) % ring.len()], ring[(s + k + 1) % ring.len()]]);
+                }
+            }
+            let split = !midpoints.is_empty();
+            mesh.indices = indices;
+            if !split { break; }
+        }
+        mesh
+    }
+    fn push_quad(&mut self, lt: [f32; 2], rt: [f32; 2], lb: [f32; 2], rb: [f32; 2])
+    {
+        let b = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&[lt, rt, lb, rb]);
+        self.indices.extend_from_slice(&[b, b + 1, b + 2, b + 2, b + 1, b + 3]);
+    }
+}
+
+impl Path
+{
+    pub fn fill(&self, tolerance: f32, rule: FillRule) -> Mesh { tessellate_fill(&self.flatten(tolerance), rule) }
+}
+
+/// Edge of a polygon directed downwards(+y). winding is +1 if the original edge was directed downwards
+#[derive(Debug, Clone, Copy)]
+struct Edge { top: Point2F, bottom: Point2F, winding: i32 }
+impl Edge
+{
+    fn x_at(&self, y: f32) -> f32
+    {
+        let t = (y - self.top.1) / (self.bottom.1 - self.top.1);
+        self.top.0 + (self.bottom.0 - self.top.0) * t
+    }
+    /// y coordinate of the intersection point of two edges, excluding their endpoints
+    fn intersection_y(&self, other: &Edge) -> Option<f32>
+    {
+        let (d1, d2) = (self.bottom - &self.top, other.bottom - &other.top);
+        let denom = d1.0 * d2.1 - d1.1 * d2.0;
+        if denom.abs() <= ::std::f32::EPSILON { return None; }
+        let o = other.top - &self.top;
+        let (t, u) = ((o.0 * d2.1 - o.1 * d2.0) / denom, (o.0 * d1.1 - o.1 * d1.0) / denom);
+        if 0.0 < t && t < 1.0 && 0.0 < u && u < 1.0 { Some(self.top.1 + d1.1 * t) } else { None }
+    }
+}
+
+/// Tessellates the interior of the figures(all treated as closed) by scanline trapezoidation.
+/// Self-intersecting figures and holes are resolved by the fill rule
+pub fn tessellate_fill(figures: &[Polyline], rule: FillRule) -> Mesh
+{
+    const EPS: f32 = 1.0e-5;
+
+    let mut edges = Vec::new();
+    for f in figures.iter().filter(|f| f.points.len() >= 3)
+    {
+        for (i, &a) in f.points.iter().enumerate()
+        {
+            let b = f.points[(i + 1) % f.points.len()];
+            if a.1 < b.1 { edges.push(Edge { top: a, bottom: b, winding: 1 }); }
+            else if a.1 > b.1 { edges.push(Edge { top: b, bottom: a, winding: -1 }); }
+        }
+    }
+    edges.sort_by(|a, b| a.top.1.partial_cmp(&b.top.1).unwrap_or(Ordering::Equal));
+
+    // boundaries of bands: vertices and intersections
+    let mut ys: Vec<f32> = edges.iter().flat_map(|e| vec![e.top.1, e.bottom.1]).collect();
+    for (i, e) in edges.iter().enumerate()
+    {
+        for o in edges[i + 1 ..].iter().take_while(|o| o.top.1 < e.bottom.1)
+        {
+            if let Some(y) = e.intersection_y(o) { ys.push(y); }
+        }
+    }
+    ys.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    ys.dedup_by(|a, b| (*a - *b).abs() <= EPS);
+
+    let mut mesh = Mesh::new();
+    let mut crossings = Vec::new();
+    for band in ys.windows(2)
+    {
+        let (y0, y1) = (band[0], band[1]);
+        let ym = (y0 + y1) * 0.5;
+        crossings.clear();
+        crossings.extend(edges.iter().take_while(|e| e.top.1 < ym).filter(|e| e.bottom.1 > ym)
+            .map(|e| (e.x_at(ym), e.x_at(y0), e.x_at(y1), e.winding)));
+        crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        let mut winding = 0;
+        let mut left = None;
+        for &(_, x0, x1, w) in &crossings
+        {
+            let was_inside = rule.is_inside(winding);
+            winding += w;
+            match (was_inside, rule.is_inside(winding))
+            {
+                (false, true) => left = Some((x0, x1)),
+                // spans without width(zero-area figures) are dropped
+                (true, false) => if let Some((lx0, lx1)) = left.take()
+                {
+                    if x0 - lx0 > EPS || x1 - lx1 > EPS { mesh.push_quad([lx0, y0], [x0, y0], [lx1, y1], [x1, y1]); }
+                },
+                _ => ()
+            }
+        }
+    }
+    mesh
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn area(m: &Mesh) -> f32
+    {
+        m.indices.chunks(3).map(|t|
+        {
+            let (a, b, c) = (m.vertices[t[0] as usize], m.vertices[t[1] as usize], m.vertices[t[2] as usize]);
+            ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() * 0.5
+        }).sum()
+    }
+    fn polygon(points: &[(f32, f32)]) -> Polyline
+    {
+        Polyline { points: points.iter().map(|&(x, y)| Point2F(x, y)).collect(), closed: true }
+    }
+    fn assert_area(m: Mesh, expected: f32) { let a = area(&m); assert!((a - expected).abs() < 1.0e-2, "area {} != {}", a, expected); }
+
+    #[test] fn holes()
+    {
+        let outer = polygon(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]);
+        let inner = polygon(&[(2.0, 2.0), (8.0, 2.0), (8.0, 8.0), (2.0, 8.0)]);
+        let inner_reversed = polygon(&[(2.0, 2.0), (2.0, 8.0), (8.0, 8.0), (8.0, 2.0)]);
+        assert_area(tessellate_fill(&[outer.clone()], FillRule::NonZero), 100.0);
+        // same direction: winding 2 inside the inner square
+        assert_area(tessellate_fill(&[outer.clone(), inner.clone()], FillRule::NonZero), 100.0);
+        assert_area(tessellate_fill(&[outer.clone(), inner], FillRule::EvenOdd), 64.0);
+        // opposite direction: winding 0 inside the inner square
+        assert_area(tessellate_fill(&[outer.clone(), inner_reversed.clone()], FillRule::NonZero), 64.0);
+        assert_area(tessellate_fill(&[outer, inner_reversed], FillRule::EvenOdd), 64.0);
+    }
+    #[test] fn self_intersections()
+    {
+        // bowtie: the halves have the opposite windings(+1 and -1)
+        let bowtie = polygon(&[(0.0, 0.0), (10.0, 10.0), (10.0, 0.0), (0.0, 10.0)]);
+        assert_area(tessellate_fill(&[bowtie.clone()], FillRule::NonZero), 50.0);
+        assert_area(tessellate_fill(&[bowtie], FillRule::EvenOdd), 50.0);
+
+        // pentagram: the inner pentagon has the winding 2
+        let (r_outer, r_inner) = (10.0f32, 10.0 * (0.4 * PI).cos() / (0.2 * PI).cos());
+        let star: Vec<_> = (0 .. 5).map(|i| { let a = (i * 2) as f32 * 0.4 * PI; (r_outer * a.cos(), r_outer * a.sin()) }).collect();
+        let pentagon_area = 2.5 * r_inner * r_inner * (0.4 * PI).sin();
+        let union_area = 5.0 * r_outer * r_inner * (0.2 * PI).sin();
+        assert_area(tessellate_fill(&[polygon(&star)], FillRule::NonZero), union_area);
+        assert_area(tessellate_fill(&[polygon(&star)], FillRule::EvenOdd), union_area - pentagon_area);
+    }
+    #[test] fn paths()
+    {
+        let mut p = Path::new();
+        p.move_to(Point2F(0.0, 0.0)).line_to(Point2F(10.0, 0.0)).line_to(Point2F(10.0, 10.0)).close()
+            .move_to(Point2F(20.0, 0.0)).quad_to(Point2F(30.0, 0.0), Point2F(30.0, 10.0)).line_to(Point2F(20.0, 10.0)).close();
+        // triangle + (half of the box + the parabolic segment(2/3 of the triangle of the control points))
+        assert_area(p.fill(0.001, FillRule::NonZero), 50.0 + 50.0 + 50.0 * 2.0 / 3.0);
+    }
+    #[test] fn subdivision()
+    {
+        let square = tessellate_fill(&[polygon(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)])], FillRule::NonZero);
+        let edges = |m: &Mesh| m.indices.chunks(3).flat_map(|t| (0 .. 3).map(move |k| (t[k], t[(k + 1) % 3])).collect::<Vec<_>>())
+            .map(|(a, b)| { let (a, b) = (m.vertices[a as usize], m.vertices[b as usize]); (b[0] - a[0]).hypot(b[1] - a[1]) })
+            .fold(0.0f32, f32::max);
+        let fine = square.subdivided(3.0);
+        assert!(edges(&fine) <= 3.0, "longest edge {}", edges(&fine));
+        assert_area(fine.clone(), 100.0);
+        // the shared edges are split at the same vertex: every interior edge has two triangles(no T-junction)
+        let mut counts = HashMap::new();
+        for t in fine.indices.chunks(3)
+        {
+            for k in 0 .. 3
+            {
+                let (a, b) = (fine.vertices[t[k] as usize], fine.vertices[t[(k + 1) % 3] as usize]);
+                let (a, b) = ([a[0].to_bits(), a[1].to_bits()], [b[0].to_bits(), b[1].to_bits()]);
+                *counts.entry(if a < b { (a, b) } else { (b, a) }).or_insert(0) += 1;
+            }
+        }
+        let boundary = counts.iter().filter(|&(&(a, b), _)|
+        {
+            let (a, b) = ([f32::from_bits(a[0]), f32::from_bits(a[1])], [f32::from_bits(b[0]), f32::from_bits(b[1])]);
+            (a[0] == b[0] && (a[0] == 0.0 || a[0] == 10.0)) || (a[1] == b[1] && (a[1] == 0.0 || a[1] == 10.0))
+        }).count();
+        assert!(counts.iter().all(|(_, &n)| n <= 2));
+        assert_eq!(counts.values().filter(|&&n| n == 1).count(), boundary);
+        // short enough already, or no limit
+        assert_eq!(square.subdivided(20.0), square);
+        assert_eq!(square.subdivided(0.0), square);
+    }
+    #[test] fn degenerate()
+    {
+        assert_eq!(Path::new().fill(0.01, FillRule::NonZero).triangle_count(), 0);
+        assert_eq!(Path::new().move_to(Point2F(1.0, 1.0)).close().fill(0.01, FillRule::NonZero).triangle_count(), 0);
+        assert_eq!(tessellate_fill(&[], FillRule::EvenOdd), Mesh::new());
+        // zero-area figures: lines(open or not) and collinear points
+        let line = Polyline { points: vec![Point2F(0.0, 0.0), Point2F(5.0, 5.0)], closed: false };
+        let collinear = polygon(&[(0.0, 0.0), (5.0, 5.0), (10.0, 10.0)]);
+        let horizontal = polygon(&[(0.0, 3.0), (5.0, 3.0), (10.0, 3.0)]);
+        for rule in &[FillRule::NonZero, FillRule::EvenOdd]
+        {
+            assert_eq!(tessellate_fill(&[line.clone(), collinear.clone(), horizontal.clone()], *rule).triangle_count(), 0);
+        }
+        // a zero-area figure does not affect the others
+        let square = polygon(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]);
+        assert_area(tessellate_fill(&[square, collinear], FillRule::EvenOdd), 100.0);
+    }
+}
@@ -0,0 +1,84 @@
+//! Affine transforms
+
+use metrics::*;
+use super::{Path, PathCommand, Mesh};
+
+/// 2D affine transform in the SVG matrix form:
+/// x' = a * x + c * y + e, y' = b * x + d * y + f
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform2D { pub a: f32, pub b: f32, pub c: f32, pub d: f32, pub e: f32, pub f: f32 }
+impl Default for Transform2D { fn default() -> Self { Self::IDENTITY } }
+impl Transform2D
+{
+    pub const IDENTITY: Transform2D = Transform2D { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+
+    pub fn translate(x: f32, y: f32) -> Self { Transform2D { e: x, f: y, .. Self::IDENTITY } }
+    pub fn scale(x: f32, y: f32) -> Self { Transform2D { a: x, d: y, .. Self::IDENTITY } }
+    /// rotation in degrees(clockwise in the y-down coordinate system)
+    pub fn rotate(degrees: f32) -> Self
+    {
+        let (s, c) = degrees.to_radians().sin_cos();
+        Transform2D { a: c, b: s, c: -s, d: c, e: 0.0, f: 0.0 }
+    }
+    pub fn skew_x(degrees: f32) -> Self { Transform2D { c: degrees.to_radians().tan(), .. Self::IDENTITY } }
+    pub fn skew_y(degrees: f32) -> Self { Transform2D { b: degrees.to_radians().tan(), .. Self::IDENTITY } }
+
+    /// The transform applying `self` first, then `next`
+    pub fn then(&self, next: &Transform2D) -> Transform2D
+    {
+        Transform2D
+        {
+            a: next.a * self.a + next.c * self.b, b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d, d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e, f: next.b * self.e + next.d * self.f + next.f
+        }
+    }
+    pub fn apply(&self, p: Point2F) -> Point2F
+    {
+        Point2F(self.a * p.0 + self.c * p.1 + self.e, self.b * p.0 + self.d * p.1 + self.f)
+    }
+    /// Applies only the linear part(for directions and extents)
+    pub fn apply_vector(&self, v: Point2F) -> Point2F { Point2F(self.a * v.0 + self.c * v.1, self.b * v.0 + self.d * v.1) }
+    pub fn determinant(&self) -> f32 { self.a * self.d - self.b * self.c }
+    pub fn inverse(&self) -> Option<Transform2D>
+    {
+        let det = self.determinant();
+        if det.abs() <= ::std::f32::EPSILON { return None; }
+        let (a, b, c, d) = (self.d / det, -self.b / det, -self.c / det, self.a / det);
+        Some(Transform2D { a, b, c, d, e: -(a * self.e + c * self.f), f: -(b * self.e + d * self.f) })
+    }
+    /// Geometric mean of the scaling factors(e.g. for scaling stroke widths and tolerances)
+    pub fn mean_scale(&self) -> f32 { self.determinant().abs().sqrt() }
+}
+
+impl Path
+{
+    /// Transformed copy of the path. Arcs are converted into cubic curves since they are not closed under affine transforms
+    pub fn transformed(&self, t: &Transform2D) -> Path
+    {
+        let src = if self.commands.iter().any(|c| match *c { PathCommand::ArcTo { .. } => true, _ => false })
+        {
+            ::std::borrow::Cow::Owned(self.arcs_to_cubics())
+        }
+        else { ::std::borrow::Cow::Borrowed(self) };
+        let commands = src.commands.iter().map(|c| match *c
+        {
+            PathCommand::MoveTo(p) => PathCommand::MoveTo(t.apply(p)),
+            PathCommand::LineTo(p) => PathCommand::LineTo(t.apply(p)),
+            PathCommand::QuadTo(c, p) => PathCommand::QuadTo(t.apply(c), t.apply(p)),
+            PathCommand::CubicTo(c1, c2, p) => PathCommand::CubicTo(t.apply(c1), t.apply(c2), t.apply(p)),
+            PathCommand::ArcTo { .. } => unreachable!("arcs are converted before transforming"),
+            PathCommand::Close => PathCommand::Close
+        }).collect();
+        Path { commands }
+    }
+}
+
+impl Mesh
+{
+    pub fn transformed(&self, t: &Transform2D) -> Mesh
+    {
+        let vertices = self.vertices.iter().map(|v| { let p = t.apply(Point2F(v[0], v[1])); [p.0, p.1] }).collect();
+        Mesh { vertices, indices: self.indices.clone() }
+    }
+}